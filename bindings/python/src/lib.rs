@@ -0,0 +1,278 @@
+// Python bindings for the secret ballot.
+//
+// These mirror the phases of `src/main.rs`: build the parameters, run the key ceremony,
+// encrypt the votes, sum them into a tally and threshold-decrypt it. The ceremonies are the
+// crate's own, `KeyCeremony` with its commitment round and share proofs and
+// `DecryptionCeremony` over the key slots of a `TrusteeSet`, so Python drives the same checks as
+// the demo. The FHE objects are opaque handles on the Python side, only the final tally comes
+// back as plain integers.
+
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, PublicKey},
+    mbfv::{CommonRandomPoly, PublicKeyShare},
+};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rand::thread_rng;
+use std::{fmt, sync::Arc};
+use workshop::{
+    decryption_ceremony::{DecryptionCeremony, DecryptionShareMessage, Progress},
+    key_ceremony::{CrpMessage, KeyCeremony, KeyShareCommitment, KeyShareMessage},
+    params, party,
+    share_proof::BindingProof,
+    tally,
+    threshold::TrusteeSet,
+    voter::{self, Choice},
+};
+
+fn to_py_err(e: impl fmt::Display) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+#[pyclass(name = "Params", frozen)]
+struct PyParams(Arc<BfvParameters>);
+
+#[pymethods]
+impl PyParams {
+    #[new]
+    #[pyo3(signature = (num_votes, degree = params::DEGREE))]
+    fn new(num_votes: usize, degree: usize) -> PyResult<Self> {
+        let plaintext_modulus: u64 = params::plaintext_modulus(num_votes);
+        params::check_capacity(num_votes, 1, plaintext_modulus).map_err(to_py_err)?;
+        params::build(degree, plaintext_modulus, &params::MODULI)
+            .map(PyParams)
+            .map_err(to_py_err)
+    }
+
+    #[getter]
+    fn degree(&self) -> usize {
+        self.0.degree()
+    }
+
+    #[getter]
+    fn plaintext_modulus(&self) -> u64 {
+        self.0.plaintext()
+    }
+}
+
+// Published by `KeyCeremony.publish_crp`, every party generates its key share from it.
+#[pyclass(name = "Crp", frozen)]
+struct PyCrp(CommonRandomPoly);
+
+#[pyclass(name = "TrusteeSet", frozen)]
+struct PyTrusteeSet(TrusteeSet);
+
+#[pymethods]
+impl PyTrusteeSet {
+    // Any `threshold` of the `num_trustees` trustees can decrypt, see `threshold.rs`.
+    #[new]
+    fn new(num_trustees: usize, threshold: usize) -> PyResult<Self> {
+        TrusteeSet::new(num_trustees, threshold)
+            .map(PyTrusteeSet)
+            .map_err(to_py_err)
+    }
+
+    #[getter]
+    fn num_trustees(&self) -> usize {
+        self.0.num_trustees()
+    }
+
+    #[getter]
+    fn threshold(&self) -> usize {
+        self.0.threshold()
+    }
+
+    // The key slots, each a party of the key ceremony.
+    #[getter]
+    fn num_slots(&self) -> usize {
+        self.0.num_slots()
+    }
+
+    fn slots_of(&self, trustee: usize) -> Vec<usize> {
+        self.0.slots_of(trustee).collect()
+    }
+
+    fn holders(&self, slot: usize) -> Vec<usize> {
+        self.0.holders(slot).to_vec()
+    }
+}
+
+// The party's secret key share, and the public key share made with it.
+#[pyclass(name = "Party", frozen)]
+struct PyParty(party::Party, PublicKeyShare);
+
+#[pymethods]
+impl PyParty {
+    #[new]
+    fn new(params: &PyParams, crp: &PyCrp) -> PyResult<Self> {
+        let (key, share): (party::Party, PublicKeyShare) =
+            party::Party::new(&params.0, &crp.0, &mut thread_rng()).map_err(to_py_err)?;
+        Ok(PyParty(key, share))
+    }
+
+    // The party's public key share for key slot `slot`, with its proof. Python may ask more than
+    // once, so the message gets a copy of the share.
+    fn key_share(&self, slot: usize, crp: &PyCrp) -> PyResult<PyKeyShareMessage> {
+        KeyShareMessage::new(slot, &self.0, self.1.clone(), &crp.0, &BindingProof)
+            .map(PyKeyShareMessage)
+            .map_err(to_py_err)
+    }
+
+    // The decryption share of the tally for key slot `slot`, sent by `trustee`.
+    fn decryption_share(
+        &self,
+        slot: usize,
+        trustee: usize,
+        tally: &PyCiphertext,
+    ) -> PyResult<PyDecryptionShareMessage> {
+        DecryptionShareMessage::new(slot, trustee, &self.0, &tally.0, &mut thread_rng())
+            .map(PyDecryptionShareMessage)
+            .map_err(to_py_err)
+    }
+}
+
+#[pyclass(name = "KeyShareCommitment", frozen)]
+struct PyKeyShareCommitment(KeyShareCommitment);
+
+#[pyclass(name = "KeyShareMessage", frozen)]
+struct PyKeyShareMessage(KeyShareMessage);
+
+#[pymethods]
+impl PyKeyShareMessage {
+    // What the party sends before revealing the share.
+    fn commitment(&self) -> PyKeyShareCommitment {
+        PyKeyShareCommitment(self.0.commitment())
+    }
+}
+
+#[pyclass(name = "KeyCeremony")]
+struct PyKeyCeremony(KeyCeremony);
+
+#[pymethods]
+impl PyKeyCeremony {
+    // A ceremony between the key slots `0..num_parties`, e.g. `TrusteeSet.num_slots`.
+    #[new]
+    fn new(params: &PyParams, num_parties: usize) -> Self {
+        PyKeyCeremony(KeyCeremony::new(&params.0, num_parties))
+    }
+
+    #[getter]
+    fn state(&self) -> String {
+        format!("{:?}", self.0.state())
+    }
+
+    fn publish_crp(&mut self) -> PyResult<PyCrp> {
+        let CrpMessage { crp } = self.0.publish_crp(&mut thread_rng()).map_err(to_py_err)?;
+        Ok(PyCrp(crp))
+    }
+
+    fn commit(&mut self, commitment: &PyKeyShareCommitment) -> PyResult<()> {
+        self.0.commit(commitment.0).map_err(to_py_err)
+    }
+
+    fn receive(&mut self, message: &PyKeyShareMessage) -> PyResult<()> {
+        self.0.receive(message.0.clone()).map_err(to_py_err)
+    }
+
+    // Checks the proofs and sums the shares into the public key.
+    fn aggregate(&mut self) -> PyResult<PyPublicKey> {
+        self.0
+            .aggregate()
+            .map(|pk| PyPublicKey(pk.clone()))
+            .map_err(to_py_err)
+    }
+}
+
+#[pyclass(name = "PublicKey", frozen)]
+struct PyPublicKey(PublicKey);
+
+#[pyclass(name = "Ciphertext", frozen)]
+struct PyCiphertext(Arc<Ciphertext>);
+
+#[pyclass(name = "DecryptionShareMessage", frozen)]
+struct PyDecryptionShareMessage(DecryptionShareMessage);
+
+#[pyclass(name = "DecryptionCeremony")]
+struct PyDecryptionCeremony(DecryptionCeremony);
+
+#[pymethods]
+impl PyDecryptionCeremony {
+    #[new]
+    fn new(tally: &PyCiphertext, trustees: &PyTrusteeSet) -> Self {
+        PyDecryptionCeremony(DecryptionCeremony::new(&tally.0, &trustees.0))
+    }
+
+    // Returns how many key slots are covered and how many are needed.
+    fn receive(&mut self, message: &PyDecryptionShareMessage) -> PyResult<(usize, usize)> {
+        let progress: Progress = self.0.receive(message.0.clone()).map_err(to_py_err)?;
+        Ok((progress.received, progress.required))
+    }
+
+    // Returns `[against, for, abstain]`, in the same order as the command line demo prints
+    // them, once every key slot is covered.
+    fn decrypt(&mut self) -> PyResult<Vec<u64>> {
+        self.0
+            .decrypt(Choice::ALL.len())
+            .map(<[u64]>::to_vec)
+            .map_err(to_py_err)
+    }
+
+    // The trustees left out for sending bad shares.
+    #[getter]
+    fn excluded(&self) -> Vec<usize> {
+        self.0.excluded().iter().copied().collect()
+    }
+}
+
+// Encrypts a vote: 0 against, 1 for or 2 to abstain.
+#[pyfunction]
+fn encrypt_vote(vote: u64, pk: &PyPublicKey, params: &PyParams) -> PyResult<PyCiphertext> {
+    Choice::try_from(vote)
+        .and_then(|vote| voter::encrypt_vote(vote, &pk.0, &params.0, &mut thread_rng()))
+        .map(|ct| PyCiphertext(Arc::new(ct)))
+        .map_err(to_py_err)
+}
+
+// Encrypts a list of votes at once, which is much faster than one at a time for many votes.
+#[pyfunction]
+fn encrypt_votes(
+    votes: Vec<u64>,
+    pk: &PyPublicKey,
+    params: &PyParams,
+) -> PyResult<Vec<PyCiphertext>> {
+    let slots: Vec<[u64; 3]> = votes
+        .into_iter()
+        .map(|vote| Choice::try_from(vote).map(Choice::slots))
+        .collect::<Result<_, _>>()
+        .map_err(to_py_err)?;
+    let ballots: Vec<Ciphertext> =
+        voter::encrypt_votes(&slots, &pk.0, &params.0, &mut thread_rng()).map_err(to_py_err)?;
+    Ok(ballots
+        .into_iter()
+        .map(|ct| PyCiphertext(Arc::new(ct)))
+        .collect())
+}
+
+#[pyfunction(name = "tally")]
+fn sum_ballots(ballots: Vec<PyRef<'_, PyCiphertext>>, params: &PyParams) -> PyCiphertext {
+    let sum: Ciphertext = tally::sum(ballots.iter().map(|b| b.0.as_ref()), &params.0);
+    PyCiphertext(Arc::new(sum))
+}
+
+#[pymodule]
+fn fhe_workshop(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyParams>()?;
+    m.add_class::<PyCrp>()?;
+    m.add_class::<PyTrusteeSet>()?;
+    m.add_class::<PyParty>()?;
+    m.add_class::<PyKeyShareCommitment>()?;
+    m.add_class::<PyKeyShareMessage>()?;
+    m.add_class::<PyKeyCeremony>()?;
+    m.add_class::<PyPublicKey>()?;
+    m.add_class::<PyCiphertext>()?;
+    m.add_class::<PyDecryptionShareMessage>()?;
+    m.add_class::<PyDecryptionCeremony>()?;
+    m.add_function(wrap_pyfunction!(encrypt_vote, m)?)?;
+    m.add_function(wrap_pyfunction!(encrypt_votes, m)?)?;
+    m.add_function(wrap_pyfunction!(sum_ballots, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,10 @@
+// Generates the Rust types of the protocol's protobuf messages with the `proto` feature, see
+// `src/proto.rs`. prost-build runs `protoc`, which must be installed, or pointed to with the
+// `PROTOC` environment variable.
+
+fn main() -> std::io::Result<()> {
+    println!("cargo:rerun-if-changed=proto/workshop.proto");
+    #[cfg(feature = "proto")]
+    prost_build::compile_protos(&["proto/workshop.proto"], &["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+pragma solidity ^0.8.20;
+
+/// Public commitments of elections run with fhe-workshop, keyed by the Merkle root of their
+/// ballots. `publish-onchain` writes them and `verify-onchain` checks the published artifacts
+/// against them, see `src/eth.rs`.
+contract ElectionRegistry {
+    struct Election {
+        address publisher;
+        bytes32 tallyHash;
+        bytes32 paramsHash;
+        uint64 numBallots;
+        uint64[] result;
+    }
+
+    mapping(bytes32 => Election) private elections;
+
+    event ElectionPublished(bytes32 indexed ballotsRoot, address indexed publisher, bytes32 tallyHash);
+
+    /// Records an election. Each ballots root can only be published once.
+    function publish(
+        bytes32 ballotsRoot,
+        bytes32 tallyHash,
+        bytes32 paramsHash,
+        uint64 numBallots,
+        uint64[] calldata result
+    ) external {
+        require(elections[ballotsRoot].publisher == address(0), "already published");
+        elections[ballotsRoot] = Election(msg.sender, tallyHash, paramsHash, numBallots, result);
+        emit ElectionPublished(ballotsRoot, msg.sender, tallyHash);
+    }
+
+    /// The election published under the ballots root, with a zero publisher if there is none.
+    function get(bytes32 ballotsRoot)
+        external
+        view
+        returns (
+            address publisher,
+            bytes32 tallyHash,
+            bytes32 paramsHash,
+            uint64 numBallots,
+            uint64[] memory result
+        )
+    {
+        Election storage election = elections[ballotsRoot];
+        return (
+            election.publisher,
+            election.tallyHash,
+            election.paramsHash,
+            election.numBallots,
+            election.result
+        );
+    }
+}
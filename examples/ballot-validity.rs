@@ -0,0 +1,132 @@
+// Turning away ballots that don't hold a vote of bits, without decrypting a single vote.
+//
+// A ballot here is a vote for one of `NUM_CHOICES` options, one slot each, encoded with
+// `Encoding::simd()`: a 1 in the slot of the choice and 0 everywhere else. A voter running a
+// modified client could put a 5 in a slot instead, and be counted five times. The signature on
+// the ballot doesn't show it, and neither does the ciphertext.
+//
+// The coordinator checks the ballots under encryption instead. `b·(1−b)` is 0 when `b` is 0 or
+// 1, and not otherwise, and multiplying a ciphertext by itself computes it in every slot at
+// once, see `validity::bit_indicators`. The trustees only ever decrypt those indicators, after
+// the coordinator multiplies them by random non-zero masks so that the result tells which slots
+// are zero and nothing more.
+//
+// To keep the decryptions down, the masked indicators of every ballot are summed and decrypted
+// together first: that comes out all zero when every ballot is valid, which is the usual case.
+// Only when it doesn't are the ballots decrypted one at a time, masked, to find the ones to
+// reject. The rest are summed into the tally as in the election.
+//
+// The check only covers the bits. A ballot of 1 in every slot passes it and votes for every
+// option. Checking that the bits add up to 1 takes the sum of the slots, which needs rotations
+// and Galois keys the trustees can't generate together with fhe.rs yet.
+//
+// Run with `cargo run --release --example ballot-validity`.
+
+mod common;
+
+use common::Trustees;
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, RelinearizationKey};
+use fhe_traits::{FheEncoder, FheEncrypter};
+use fhe_workshop::{
+    params::{self, ModuliChain},
+    tally, validity,
+};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::{error::Error, sync::Arc};
+
+const NUM_VOTERS: usize = 12;
+const NUM_TRUSTEES: usize = 3;
+const NUM_CHOICES: usize = 4;
+// The voters who cheat, putting `STUFFED` votes in the slot of their choice.
+const CHEATERS: [usize; 2] = [3, 8];
+const STUFFED: u64 = 5;
+
+// A degree of 4096 leaves room for the multiplication at 128 bits of security, see
+// `params::check_security`.
+const DEGREE: usize = 4096;
+// A prime of the form `2·DEGREE·k + 1`, as SIMD encoding needs.
+const PLAINTEXT_MODULUS: u64 = 40_961;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng: ThreadRng = thread_rng();
+    let params: Arc<BfvParameters> = ModuliChain::Depth(1).build(DEGREE, PLAINTEXT_MODULUS)?;
+    params::check_security(DEGREE, params::modulus_bits(params.moduli()))?;
+
+    // The trustees run the same key ceremony as in the election, and generate a
+    // relinearization key for the products.
+    let trustees: Trustees = Trustees::new(&params, NUM_TRUSTEES, &mut rng)?;
+    let rlk: RelinearizationKey = trustees.relinearization_key(&params, &mut rng)?;
+
+    // Each voter encrypts their choice on their own machine.
+    let choices: Vec<usize> = (0..NUM_VOTERS)
+        .map(|_| rng.gen_range(0..NUM_CHOICES))
+        .collect();
+    let ballots: Vec<Ciphertext> = choices
+        .iter()
+        .enumerate()
+        .map(|(voter, &choice)| {
+            let mut slots: Vec<u64> = vec![0; NUM_CHOICES];
+            slots[choice] = if CHEATERS.contains(&voter) {
+                STUFFED
+            } else {
+                1
+            };
+            let pt: Plaintext = Plaintext::try_encode(&slots, Encoding::simd(), &params)?;
+            trustees.public_key().try_encrypt(&pt, &mut rng)
+        })
+        .collect::<Result<_, _>>()?;
+
+    // The coordinator computes the indicators, and the trustees check all of them at once.
+    let indicators: Vec<Ciphertext> = ballots
+        .iter()
+        .map(|ballot| validity::bit_indicators(ballot, &rlk))
+        .collect::<Result<_, _>>()?;
+    let combined: Arc<Ciphertext> = Arc::new(validity::combine_indicators(
+        &indicators,
+        &params,
+        &mut rng,
+    )?);
+    let slots: Vec<u64> =
+        trustees.decrypt_encoded(&combined, NUM_CHOICES, Encoding::simd(), &mut rng)?;
+    let mut rejected: Vec<usize> = Vec::new();
+    if slots.iter().any(|&slot| slot != 0) {
+        // Some ballot is invalid: find which, one decryption per ballot.
+        for (voter, indicators) in indicators.iter().enumerate() {
+            let masked: Arc<Ciphertext> =
+                Arc::new(validity::mask_indicators(indicators, &params, &mut rng)?);
+            let slots: Vec<u64> =
+                trustees.decrypt_encoded(&masked, NUM_CHOICES, Encoding::simd(), &mut rng)?;
+            if slots.iter().any(|&slot| slot != 0) {
+                rejected.push(voter);
+            }
+        }
+    }
+    println!("rejected the ballots of voters {rejected:?}");
+
+    // Only the valid ballots are tallied.
+    let tally: Arc<Ciphertext> = Arc::new(tally::sum(
+        ballots
+            .iter()
+            .enumerate()
+            .filter(|(voter, _)| !rejected.contains(voter))
+            .map(|(_, ballot)| ballot),
+        &params,
+    ));
+    let counts: Vec<u64> =
+        trustees.decrypt_encoded(&tally, NUM_CHOICES, Encoding::simd(), &mut rng)?;
+    println!("counts: {counts:?}");
+
+    assert_eq!(
+        rejected,
+        CHEATERS.to_vec(),
+        "the wrong ballots were rejected"
+    );
+    let mut expected: Vec<u64> = vec![0; NUM_CHOICES];
+    for (voter, &choice) in choices.iter().enumerate() {
+        if !CHEATERS.contains(&voter) {
+            expected[choice] += 1;
+        }
+    }
+    assert_eq!(counts, expected, "the tally is wrong");
+    Ok(())
+}
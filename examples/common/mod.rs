@@ -0,0 +1,167 @@
+// The trustees the examples share: the same key ceremony and decryption ceremony as the
+// election, with every trustee in one process.
+//
+// All trustees are needed to decrypt, each holding a single key slot of an n-of-n `TrusteeSet`.
+// Only the results an example asks for are ever decrypted, never a single input.
+
+// Every example compiles its own copy of this module, and not every one uses all of it.
+#![allow(dead_code)]
+
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, Encoding, PublicKey, RelinearizationKey},
+    mbfv::{
+        AggregateIter, CommonRandomPoly, PublicKeyShare, R1Aggregated, RelinKeyGenerator,
+        RelinKeyShare,
+    },
+};
+use fhe_workshop::{
+    decryption_ceremony::{DecryptionCeremony, DecryptionShareMessage},
+    key_ceremony::{CrpMessage, KeyCeremony, KeyShareMessage},
+    party::Party,
+    share_proof::BindingProof,
+    threshold::TrusteeSet,
+};
+use rand::{CryptoRng, RngCore};
+use std::{error::Error, sync::Arc};
+
+pub struct Trustees {
+    trustees: TrusteeSet,
+    // The key share of each slot.
+    keys: Vec<Party>,
+    crp: CommonRandomPoly,
+    public_key: PublicKey,
+}
+
+impl Trustees {
+    // Runs the key ceremony between `num_trustees` trustees.
+    pub fn new<R: RngCore + CryptoRng>(
+        params: &Arc<BfvParameters>,
+        num_trustees: usize,
+        rng: &mut R,
+    ) -> Result<Self, Box<dyn Error>> {
+        let trustees: TrusteeSet = TrusteeSet::new(num_trustees, num_trustees)?;
+        let mut ceremony: KeyCeremony = KeyCeremony::new(params, trustees.num_slots());
+        let CrpMessage { crp } = ceremony.publish_crp(rng)?;
+        let (keys, shares): (Vec<Party>, Vec<PublicKeyShare>) = (0..trustees.num_slots())
+            .map(|_| Party::new(params, &crp, rng))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        // Every trustee commits to its share before any share is revealed, as in the election.
+        let messages: Vec<KeyShareMessage> = keys
+            .iter()
+            .zip(shares)
+            .enumerate()
+            .map(|(slot, (key, share))| KeyShareMessage::new(slot, key, share, &crp, &BindingProof))
+            .collect::<Result<_, _>>()?;
+        for message in &messages {
+            ceremony.commit(message.commitment())?;
+        }
+        for message in messages {
+            ceremony.receive(message)?;
+        }
+        let public_key: PublicKey = ceremony.aggregate()?.clone();
+        Ok(Trustees {
+            trustees,
+            keys,
+            crp,
+            public_key,
+        })
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    // Trustees holding the given key slots, e.g. handed over from another set, see `reshare`.
+    pub fn from_keys(
+        trustees: TrusteeSet,
+        keys: Vec<Party>,
+        crp: CommonRandomPoly,
+        public_key: PublicKey,
+    ) -> Self {
+        Trustees {
+            trustees,
+            keys,
+            crp,
+            public_key,
+        }
+    }
+
+    pub fn crp(&self) -> &CommonRandomPoly {
+        &self.crp
+    }
+
+    pub fn trustee_set(&self) -> &TrusteeSet {
+        &self.trustees
+    }
+
+    // The key share of each slot.
+    pub fn keys(&self) -> &[Party] {
+        &self.keys
+    }
+
+    // Runs the two rounds of the relinearization key generation between the trustees. Like the
+    // public key, the relinearization key is the sum of a share from every trustee, and it
+    // turns the product of two ciphertexts back into a ciphertext they can decrypt.
+    pub fn relinearization_key<R: RngCore + CryptoRng>(
+        &self,
+        params: &Arc<BfvParameters>,
+        rng: &mut R,
+    ) -> Result<RelinearizationKey, Box<dyn Error>> {
+        let crps: Vec<CommonRandomPoly> = CommonRandomPoly::new_vec(params, rng)?;
+        let generators: Vec<RelinKeyGenerator> = self
+            .keys
+            .iter()
+            .map(|key| RelinKeyGenerator::new(&key.sk_share, &crps, rng))
+            .collect::<Result<_, _>>()?;
+        let round_1: RelinKeyShare<R1Aggregated> = generators
+            .iter()
+            .map(|generator| generator.round_1(rng))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .aggregate()?;
+        let rlk: RelinearizationKey = generators
+            .iter()
+            .map(|generator| generator.round_2(&round_1, rng))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .aggregate()?;
+        Ok(rlk)
+    }
+
+    // Every trustee sends a decryption share of the ciphertext for the slot it holds, and the
+    // first `len` slots of the plaintext are decoded.
+    pub fn decrypt<R: RngCore + CryptoRng>(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+        len: usize,
+        rng: &mut R,
+    ) -> Result<Vec<u64>, Box<dyn Error>> {
+        self.decrypt_encoded(ciphertext, len, Encoding::poly(), rng)
+    }
+
+    // The same, for a plaintext encoded with `encoding`.
+    pub fn decrypt_encoded<R: RngCore + CryptoRng>(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+        len: usize,
+        encoding: Encoding,
+        rng: &mut R,
+    ) -> Result<Vec<u64>, Box<dyn Error>> {
+        let mut ceremony: DecryptionCeremony =
+            DecryptionCeremony::new(ciphertext, &self.trustees).with_encoding(encoding);
+        for trustee in 0..self.trustees.num_trustees() {
+            for slot in self.trustees.slots_of(trustee) {
+                ceremony.receive(DecryptionShareMessage::new(
+                    slot,
+                    trustee,
+                    &self.keys[slot],
+                    ciphertext,
+                    rng,
+                )?)?;
+            }
+        }
+        Ok(ceremony.decrypt(len)?.to_vec())
+    }
+}
@@ -0,0 +1,160 @@
+// An encrypted survey: several questions answered on a scale of 1 to 5, with the average answer
+// to each question as the only result.
+//
+// A plaintext has thousands of slots, and a survey only needs a few of them per question, so
+// every answer of a respondent goes into a single ciphertext. Each question takes two slots: the
+// answer, and a 1 saying the question was answered. A question left unanswered is 0 in both. The
+// sum of the ciphertexts then holds, for each question, the total of its answers and how many
+// respondents answered it, and the trustees decrypt that sum with a single decryption ceremony.
+//
+//     slot:    0        1          2        3          ...
+//     holds:   total 0  answered 0 total 1  answered 1 ...
+//
+// Decoding the result walks the slots two at a time, and the average of each question is its
+// total divided by its count, after decryption, as BFV can't divide.
+//
+// With only a few respondents, the exact totals can give an answer away, e.g. by comparing them
+// before and after someone answers. With `--epsilon`, each trustee adds discrete Laplace noise
+// of its own to the sum before it is decrypted, so the results are differentially private and
+// only approximately right, see `privacy`. A respondent changes the total of each question by at
+// most `SCALE` and its count by 1, which sets the scale of the noise. The plaintext modulus is
+// picked large enough that the noise can't wrap a total around it.
+//
+// Run with `cargo run --release --example likert-survey`, or with
+// `cargo run --release --example likert-survey -- --epsilon 1` for noisy results.
+
+mod common;
+
+use common::Trustees;
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext};
+use fhe_traits::{FheEncoder, FheEncrypter};
+use fhe_workshop::{
+    params,
+    privacy::{self, DiscreteLaplace},
+    tally,
+};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::{error::Error, sync::Arc};
+
+const QUESTIONS: [&str; 4] = [
+    "The workshop was easy to follow",
+    "The exercises were useful",
+    "I would use FHE in a project",
+    "The venue was comfortable",
+];
+const NUM_RESPONDENTS: usize = 40;
+const NUM_TRUSTEES: usize = 3;
+const SCALE: u64 = 5;
+const SLOTS_PER_QUESTION: usize = 2;
+// What the noise may be truncated at, see `privacy`.
+const DELTA: f64 = 1e-9;
+// The most a single respondent changes the slots by, in total.
+const SENSITIVITY: u64 = QUESTIONS.len() as u64 * (SCALE + 1);
+
+// The privacy budget given with `--epsilon`, if any.
+fn epsilon() -> Result<Option<f64>, Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => Ok(None),
+        [flag, epsilon] if flag == "--epsilon" => Ok(Some(epsilon.parse()?)),
+        _ => Err("usage: likert-survey [--epsilon <epsilon>]".into()),
+    }
+}
+
+// One respondent's answers, `None` for a question they skipped, laid out two slots a question.
+fn encode_answers(answers: &[Option<u64>]) -> Vec<u64> {
+    answers
+        .iter()
+        .flat_map(|answer| match answer {
+            Some(answer) => [*answer, 1],
+            None => [0, 0],
+        })
+        .collect()
+}
+
+// The total of the answers to each question and how many respondents answered it. With noise,
+// either can come out negative.
+fn decode_results(slots: &[i64]) -> Vec<(i64, i64)> {
+    slots
+        .chunks(SLOTS_PER_QUESTION)
+        .map(|question| (question[0], question[1]))
+        .collect()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng: ThreadRng = thread_rng();
+    let noise: Option<DiscreteLaplace> = epsilon()?
+        .map(|epsilon| DiscreteLaplace::new(epsilon, DELTA, SENSITIVITY))
+        .transpose()?;
+    // Room for the largest total plus the noise on either side of zero.
+    let headroom: usize = noise.map_or(0, |noise| noise.headroom(NUM_TRUSTEES) as usize);
+    let plaintext_modulus: u64 =
+        params::plaintext_modulus(2 * (NUM_RESPONDENTS * SCALE as usize + headroom));
+    params::check_capacity(NUM_RESPONDENTS, SCALE, plaintext_modulus)?;
+    let params: Arc<BfvParameters> =
+        params::build(params::DEGREE, plaintext_modulus, &params::MODULI)?;
+    let trustees: Trustees = Trustees::new(&params, NUM_TRUSTEES, &mut rng)?;
+
+    // Each respondent answers, skipping a question now and then, and encrypts their answers.
+    let responses: Vec<Vec<Option<u64>>> = (0..NUM_RESPONDENTS)
+        .map(|_| {
+            QUESTIONS
+                .iter()
+                .map(|_| rng.gen_bool(0.9).then(|| rng.gen_range(1..=SCALE)))
+                .collect()
+        })
+        .collect();
+    let ciphertexts: Vec<Ciphertext> = responses
+        .iter()
+        .map(|answers| {
+            let pt: Plaintext =
+                Plaintext::try_encode(&encode_answers(answers), Encoding::poly(), &params)?;
+            trustees.public_key().try_encrypt(&pt, &mut rng)
+        })
+        .collect::<Result<_, _>>()?;
+
+    let len: usize = QUESTIONS.len() * SLOTS_PER_QUESTION;
+    let mut sum: Ciphertext = tally::sum(&ciphertexts, &params);
+    // Each trustee adds its noise. It is only summed up here to check the results below, no one
+    // would know the total in a real survey.
+    let mut total_noise: Vec<i64> = vec![0; len];
+    if let Some(noise) = &noise {
+        for _ in 0..NUM_TRUSTEES {
+            let slots: Vec<i64> = noise.sample_slots(len, &mut rng);
+            sum += &privacy::encrypt_noise(&slots, trustees.public_key(), &params, &mut rng)?;
+            for (total, slot) in total_noise.iter_mut().zip(slots) {
+                *total += slot;
+            }
+        }
+        println!(
+            "with (ε = {}, δ = {})-differential privacy",
+            noise.epsilon(),
+            noise.delta()
+        );
+    }
+    let sum: Arc<Ciphertext> = Arc::new(sum);
+    let slots: Vec<i64> = trustees
+        .decrypt(&sum, len, &mut rng)?
+        .into_iter()
+        .map(|slot| privacy::centered(slot, plaintext_modulus))
+        .collect();
+    let results: Vec<(i64, i64)> = decode_results(&slots);
+    let noise_results: Vec<(i64, i64)> = decode_results(&total_noise);
+
+    for (index, (question, &(total, answered))) in QUESTIONS.iter().zip(&results).enumerate() {
+        let average: f64 = total as f64 / answered as f64;
+        println!("{question}: {average:.2} out of {SCALE}, from {answered} answers");
+
+        let answers: Vec<u64> = responses
+            .iter()
+            .filter_map(|answers| answers[index])
+            .collect();
+        let (total_noise, answered_noise): (i64, i64) = noise_results[index];
+        assert_eq!(
+            (total - total_noise, answered - answered_noise),
+            (answers.iter().sum::<u64>() as i64, answers.len() as i64),
+            "the results of a question are wrong"
+        );
+    }
+    Ok(())
+}
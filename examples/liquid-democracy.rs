@@ -0,0 +1,95 @@
+// Liquid democracy: voters who would rather not vote on a question hand their vote to someone
+// they trust, who may hand it on in turn.
+//
+// Every voter either casts a ballot, delegates to another voter, or abstains. The delegations
+// are public and resolved before the tally, see `delegation::resolve`, which gives every ballot
+// a weight: one for the voter who cast it, plus one for each voter whose chain of delegations
+// ends with them. The tally multiplies each encrypted ballot by its weight and adds them up,
+// see `delegation::weighted_sum`, so the votes themselves stay as secret as in the election.
+//
+// Some of the delegations go round in a circle or end with a voter who abstained. Those voters'
+// weight goes nowhere, and the example prints who they are.
+//
+// Run with `cargo run --release --example liquid-democracy`.
+
+mod common;
+
+use common::Trustees;
+use fhe::bfv::{BfvParameters, Ciphertext};
+use fhe_workshop::{
+    delegation::{self, Intent, Resolution},
+    params,
+    voter::{self, Choice},
+};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::{error::Error, sync::Arc};
+
+const NUM_VOTERS: usize = 30;
+const NUM_TRUSTEES: usize = 3;
+// The share of voters who delegate, and of those who abstain.
+const DELEGATE: f64 = 0.4;
+const ABSTAIN: f64 = 0.1;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng: ThreadRng = thread_rng();
+    // The weights add up to at most the number of voters, so the usual modulus holds the tally.
+    let plaintext_modulus: u64 = params::plaintext_modulus(NUM_VOTERS);
+    let params: Arc<BfvParameters> =
+        params::build(params::DEGREE, plaintext_modulus, &params::MODULI)?;
+    let trustees: Trustees = Trustees::new(&params, NUM_TRUSTEES, &mut rng)?;
+
+    let intents: Vec<Intent> = (0..NUM_VOTERS)
+        .map(|voter| {
+            let draw: f64 = rng.gen();
+            if draw < DELEGATE {
+                // Anyone but themselves.
+                let to: usize = (voter + rng.gen_range(1..NUM_VOTERS)) % NUM_VOTERS;
+                Intent::Delegate(to)
+            } else if draw < DELEGATE + ABSTAIN {
+                Intent::Abstain
+            } else {
+                Intent::Vote
+            }
+        })
+        .collect();
+    let resolution: Resolution = delegation::resolve(&intents)?;
+
+    let mut ballots: Vec<(Ciphertext, u64)> = Vec::new();
+    let mut expected: Vec<u64> = vec![0; 3];
+    for (voter, intent) in intents.iter().enumerate() {
+        if *intent != Intent::Vote {
+            continue;
+        }
+        let vote: Choice = Choice::ALL[rng.gen_range(0..Choice::ALL.len())];
+        let weight: u64 = resolution.weights[voter];
+        expected[vote as usize] += weight;
+        ballots.push((
+            voter::encrypt_vote(vote, trustees.public_key(), &params, &mut rng)?,
+            weight,
+        ));
+    }
+    let tally: Arc<Ciphertext> = Arc::new(delegation::weighted_sum(
+        ballots.iter().map(|(ballot, weight)| (ballot, *weight)),
+        &params,
+    )?);
+    let counts: Vec<u64> = trustees.decrypt(&tally, 3, &mut rng)?;
+
+    let delegated: usize = intents
+        .iter()
+        .filter(|intent| matches!(intent, Intent::Delegate(_)))
+        .count();
+    println!(
+        "{} ballots cast, {delegated} voters delegated, {} of the {NUM_VOTERS} voters counted",
+        ballots.len(),
+        resolution.total()
+    );
+    if !resolution.lost.is_empty() {
+        println!("delegations that went nowhere: {:?}", resolution.lost);
+    }
+    println!(
+        "against {}, for {}, abstain {}",
+        counts[0], counts[1], counts[2]
+    );
+    assert_eq!(counts, expected, "the weighted tally is wrong");
+    Ok(())
+}
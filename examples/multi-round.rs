@@ -0,0 +1,102 @@
+// Several votes, one after the other, under the key of a single key ceremony.
+//
+// The trustees run the key ceremony once. Each question is then a round of its own, see
+// `rounds`: the ballots are submitted to the round of their question, and the round is closed
+// and decrypted on its own, with decryption shares bound to it. A share the trustees sent for
+// one round is refused by the ceremony of another, which the example checks on the way.
+//
+// Run with `cargo run --release --example multi-round`.
+
+mod common;
+
+use common::Trustees;
+use fhe::bfv::{BfvParameters, Ciphertext};
+use fhe_workshop::{
+    decryption_ceremony::{DecryptionCeremony, DecryptionShareMessage},
+    params,
+    rounds::{RoundId, Rounds},
+    voter::{self, Choice},
+};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::{error::Error, sync::Arc};
+
+const NUM_TRUSTEES: usize = 3;
+const VOTES_PER_ROUND: usize = 15;
+const QUESTIONS: [&str; 3] = [
+    "adopt the budget",
+    "elect the treasurer",
+    "move the meeting",
+];
+
+// Every trustee sends a share of the round's tally for the slots they hold.
+fn shares(
+    trustees: &Trustees,
+    round: RoundId,
+    tally: &Arc<Ciphertext>,
+    rng: &mut ThreadRng,
+) -> Result<Vec<DecryptionShareMessage>, Box<dyn Error>> {
+    let mut shares: Vec<DecryptionShareMessage> = Vec::new();
+    for trustee in 0..trustees.trustee_set().num_trustees() {
+        for slot in trustees.trustee_set().slots_of(trustee) {
+            shares.push(
+                DecryptionShareMessage::new(slot, trustee, &trustees.keys()[slot], tally, rng)?
+                    .with_round(round),
+            );
+        }
+    }
+    Ok(shares)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng: ThreadRng = thread_rng();
+    let plaintext_modulus: u64 = params::plaintext_modulus(VOTES_PER_ROUND);
+    let params: Arc<BfvParameters> =
+        params::build(params::DEGREE, plaintext_modulus, &params::MODULI)?;
+
+    // The only key ceremony.
+    let trustees: Trustees = Trustees::new(&params, NUM_TRUSTEES, &mut rng)?;
+    let mut rounds: Rounds = Rounds::new(&params);
+
+    let mut previous: Option<(RoundId, DecryptionShareMessage)> = None;
+    for question in QUESTIONS {
+        let round: RoundId = rounds.open(question);
+        let cast: Vec<Choice> = (0..VOTES_PER_ROUND)
+            .map(|_| Choice::ALL[rng.gen_range(0..Choice::ALL.len())])
+            .collect();
+        for &vote in &cast {
+            let ballot: Ciphertext =
+                voter::encrypt_vote(vote, trustees.public_key(), &params, &mut rng)?;
+            rounds.submit(round, ballot)?;
+        }
+        let tally: Arc<Ciphertext> = rounds.close(round)?;
+
+        let mut ceremony: DecryptionCeremony = rounds.decryption(round, trustees.trustee_set())?;
+        // A share from the round before doesn't count towards this one.
+        if let Some((earlier, share)) = previous.take() {
+            assert!(
+                ceremony.receive(share).is_err(),
+                "a share of round {earlier} was taken for round {round}"
+            );
+        }
+        let shares: Vec<DecryptionShareMessage> = shares(&trustees, round, &tally, &mut rng)?;
+        previous = Some((round, shares[0].clone()));
+        for share in shares {
+            ceremony.receive(share)?;
+        }
+        let counts: Vec<u64> = ceremony.decrypt(3)?.to_vec();
+        println!(
+            "round {round}, {}: against {}, for {}, abstain {}",
+            rounds.label(round)?,
+            counts[0],
+            counts[1],
+            counts[2]
+        );
+
+        let mut expected: Vec<u64> = vec![0; 3];
+        for vote in &cast {
+            expected[*vote as usize] += 1;
+        }
+        assert_eq!(counts, expected, "the tally of round {round} is wrong");
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+// The average salary of a group, and how far the salaries spread around it, without anyone
+// learning a single salary.
+//
+// The election counts votes by adding up encrypted ballots. Nothing in it is specific to votes:
+// any number can be encrypted and added up the same way. Here every employee encrypts their
+// salary to the trustees' public key, next to a 1 in the second slot, so the sum of the
+// ciphertexts holds the total of the salaries and the number of employees who took part.
+//
+// BFV can add and multiply, but not divide, so the average is worked out after the decryption:
+// the trustees decrypt the total and the count, and the division happens in the clear. That
+// reveals the total and the count along with the average, which is fine here, and nothing about
+// any one salary.
+//
+// The variance takes the sum of the squares as well, `Σx²/n - (Σx/n)²`, and that one is
+// computed under encryption: each ciphertext is multiplied by itself. Squaring `x + 1·X`, the
+// polynomial the ciphertext encrypts, gives `x² + 2x·X + X²`, so the sum of the squares lands in
+// the first slot. A product of two ciphertexts has three parts instead of two, and the trustees
+// can only decrypt it once it is relinearized back to two, with a relinearization key they
+// generate together in two more rounds. The product also adds far more noise than a sum, and
+// the squares of the salaries need a much larger plaintext modulus than votes do, so the
+// example uses larger parameters than the election: a degree of 8192 and three 60 bit moduli.
+//
+// Run with `cargo run --release --example salary-average`.
+
+mod common;
+
+use common::Trustees;
+use fhe::bfv::{
+    BfvParameters, BfvParametersBuilder, Ciphertext, Encoding, Plaintext, RelinearizationKey,
+};
+use fhe_traits::{FheEncoder, FheEncrypter};
+use fhe_workshop::{params, tally};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::{error::Error, sync::Arc};
+
+const NUM_EMPLOYEES: usize = 25;
+const NUM_TRUSTEES: usize = 3;
+const MIN_SALARY: u64 = 30_000;
+const MAX_SALARY: u64 = 200_000;
+
+const DEGREE: usize = 8192;
+const MODULI_SIZES: [usize; 3] = [60, 60, 60];
+// A prime large enough to hold the sum of the squared salaries, see `check_capacity` below.
+const PLAINTEXT_MODULUS: u64 = 1_099_511_922_689;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng: ThreadRng = thread_rng();
+    params::check_capacity(NUM_EMPLOYEES, MAX_SALARY * MAX_SALARY, PLAINTEXT_MODULUS)?;
+    let params: Arc<BfvParameters> = BfvParametersBuilder::new()
+        .set_degree(DEGREE)
+        .set_plaintext_modulus(PLAINTEXT_MODULUS)
+        .set_moduli_sizes(&MODULI_SIZES)
+        .build_arc()?;
+
+    // The trustees run the same key ceremony as in the election, and generate a
+    // relinearization key for the squares.
+    let trustees: Trustees = Trustees::new(&params, NUM_TRUSTEES, &mut rng)?;
+    let rlk: RelinearizationKey = trustees.relinearization_key(&params, &mut rng)?;
+
+    // Each employee encrypts `[salary, 1]` on their own machine.
+    let salaries: Vec<u64> = (0..NUM_EMPLOYEES)
+        .map(|_| rng.gen_range(MIN_SALARY..=MAX_SALARY))
+        .collect();
+    let ciphertexts: Vec<Ciphertext> = salaries
+        .iter()
+        .map(|&salary| {
+            let pt: Plaintext = Plaintext::try_encode(&[salary, 1], Encoding::poly(), &params)?;
+            trustees.public_key().try_encrypt(&pt, &mut rng)
+        })
+        .collect::<Result<_, _>>()?;
+
+    // Anyone can add the ciphertexts up, or square them with the relinearization key, and only
+    // the sums are decrypted.
+    let sum: Arc<Ciphertext> = Arc::new(tally::sum(&ciphertexts, &params));
+    let squares: Vec<Ciphertext> = ciphertexts
+        .iter()
+        .map(|ct| {
+            let mut square: Ciphertext = ct * ct;
+            rlk.relinearizes(&mut square)?;
+            Ok(square)
+        })
+        .collect::<Result<_, fhe::Error>>()?;
+    let sum_of_squares: Arc<Ciphertext> = Arc::new(tally::sum(&squares, &params));
+
+    let decrypted: Vec<u64> = trustees.decrypt(&sum, 2, &mut rng)?;
+    let (total, count) = (decrypted[0], decrypted[1]);
+    // `Σx²`, `2·Σx` and `n`, of which only the first is new.
+    let total_of_squares: u64 = trustees.decrypt(&sum_of_squares, 3, &mut rng)?[0];
+
+    let average: f64 = total as f64 / count as f64;
+    let variance: f64 = total_of_squares as f64 / count as f64 - average * average;
+    println!(
+        "{count} employees, total {total}, average salary {average:.2}, standard deviation {:.2}",
+        variance.sqrt()
+    );
+
+    assert_eq!(
+        (count, total, total_of_squares),
+        (
+            NUM_EMPLOYEES as u64,
+            salaries.iter().sum(),
+            salaries.iter().map(|salary| salary * salary).sum()
+        ),
+        "the decrypted sums are wrong"
+    );
+    Ok(())
+}
@@ -0,0 +1,107 @@
+// A sealed-bid auction: the highest bid wins, and no other bid is ever revealed.
+//
+// BFV has no comparison. What it has is addition and multiplication, slot by slot with SIMD
+// encoding, and that is enough once the bids are encoded the right way. Each bidder encrypts
+// their bid `b` as a thermometer over the possible prices: slot `j` holds 1 if `b > j` and 0
+// otherwise. Summed over the bidders, slot `j` counts the bids above `j`, so the highest bid is
+// the number of slots that aren't zero.
+//
+// Decrypting the sum would give those counts away, and with them how many bidders bid above
+// every price. So the auctioneer first multiplies the sum, slot by slot, by a plaintext of
+// random non-zero masks. The plaintext modulus is prime, so a masked count is zero exactly when
+// the count is, and otherwise a random number telling nothing about the count. The trustees
+// decrypt the masked sum and publish only which slots are zero: that is the winning bid, and
+// nothing else. Whoever drew the masks must not see the decrypted values, or they can unmask
+// the counts; a real deployment would have the trustees publish the zeros and nothing more.
+//
+// A second round of decryption finds the winner: every bid is multiplied by a plaintext with a
+// 1 in the slot of the winning bid and 0 everywhere else, which decrypts to 1 for the bids equal
+// to the winning one and to 0 for the others. Each of those is decrypted on its own, telling
+// the losers apart from the winners and no more.
+//
+// This is a hybrid: the comparison with zero is done in the clear, by the trustees, after the
+// masks hid everything but the answer. A comparison fully under encryption takes many levels of
+// multiplication, which the workshop's parameters, with a single ciphertext modulus, don't
+// leave room for. Each multiplication by a plaintext grows the noise, so only one is done per
+// ciphertext.
+//
+// Run with `cargo run --release --example sealed-bid-auction`.
+
+mod common;
+
+use common::Trustees;
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext};
+use fhe_traits::{FheEncoder, FheEncrypter};
+use fhe_workshop::{params, tally};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::{error::Error, sync::Arc};
+
+const NUM_BIDDERS: usize = 5;
+const NUM_TRUSTEES: usize = 3;
+// Bids are whole numbers from 1 to `MAX_BID`, one slot for each.
+const MAX_BID: usize = 100;
+
+// A prime of the form `2·DEGREE·k + 1`, as SIMD encoding needs. It is well above the number of
+// bidders, so a count never wraps around to zero.
+const PLAINTEXT_MODULUS: u64 = 12_289;
+
+// Slot `j` is 1 for every `j` below the bid.
+fn thermometer(bid: usize) -> Vec<u64> {
+    (0..MAX_BID).map(|j| u64::from(j < bid)).collect()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng: ThreadRng = thread_rng();
+    let params: Arc<BfvParameters> =
+        params::build(params::DEGREE, PLAINTEXT_MODULUS, &params::MODULI)?;
+    let trustees: Trustees = Trustees::new(&params, NUM_TRUSTEES, &mut rng)?;
+
+    // Each bidder encrypts their bid on their own machine.
+    let bids: Vec<usize> = (0..NUM_BIDDERS)
+        .map(|_| rng.gen_range(1..=MAX_BID))
+        .collect();
+    let sealed: Vec<Ciphertext> = bids
+        .iter()
+        .map(|&bid| {
+            let pt: Plaintext =
+                Plaintext::try_encode(&thermometer(bid), Encoding::simd(), &params)?;
+            trustees.public_key().try_encrypt(&pt, &mut rng)
+        })
+        .collect::<Result<_, _>>()?;
+
+    // Round one: the auctioneer sums the bids and masks the counts, and the trustees decrypt.
+    let masks: Vec<u64> = (0..MAX_BID)
+        .map(|_| rng.gen_range(1..PLAINTEXT_MODULUS))
+        .collect();
+    let masks: Plaintext = Plaintext::try_encode(&masks, Encoding::simd(), &params)?;
+    let masked: Arc<Ciphertext> = Arc::new(&tally::sum(&sealed, &params) * &masks);
+    let slots: Vec<u64> = trustees.decrypt_encoded(&masked, MAX_BID, Encoding::simd(), &mut rng)?;
+    let winning_bid: usize = slots.iter().filter(|&&slot| slot != 0).count();
+    println!("winning bid: {winning_bid}");
+
+    // Round two: which bids are equal to the winning one.
+    let mut selector: Vec<u64> = vec![0; MAX_BID];
+    selector[winning_bid - 1] = 1;
+    let selector: Plaintext = Plaintext::try_encode(&selector, Encoding::simd(), &params)?;
+    let mut winners: Vec<usize> = Vec::new();
+    for (bidder, bid) in sealed.iter().enumerate() {
+        let selected: Arc<Ciphertext> = Arc::new(bid * &selector);
+        let slots: Vec<u64> =
+            trustees.decrypt_encoded(&selected, MAX_BID, Encoding::simd(), &mut rng)?;
+        if slots[winning_bid - 1] == 1 {
+            winners.push(bidder);
+        }
+    }
+    println!("won by bidder {winners:?}");
+
+    assert_eq!(
+        Some(&winning_bid),
+        bids.iter().max(),
+        "the winning bid is wrong"
+    );
+    let expected: Vec<usize> = (0..NUM_BIDDERS)
+        .filter(|&bidder| bids[bidder] == winning_bid)
+        .collect();
+    assert_eq!(winners, expected, "the winners are wrong");
+    Ok(())
+}
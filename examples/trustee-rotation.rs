@@ -0,0 +1,123 @@
+// Handing an election key over to a new set of trustees between two rounds of voting, without
+// changing the public key or ever putting the secret key together.
+//
+// Three trustees hold the key, all three needed to decrypt. Voters encrypt to it, and then the
+// trustees change: five new ones take over, any three of whom can decrypt. The old trustees
+// split each of their shares into random parts, one for each key slot of the new set, and every
+// new slot adds up the parts it was sent, see `reshare`. The new shares add up to the same secret
+// key, so the ballots cast before the handover and those cast after it, to the same public key,
+// are tallied together and decrypted by the new trustees, with two of them absent.
+//
+// Once the parts are sent, the old trustees delete their shares: here they are simply dropped.
+//
+// Run with `cargo run --release --example trustee-rotation`.
+
+mod common;
+
+use common::Trustees;
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, PublicKey},
+    mbfv::CommonRandomPoly,
+};
+use fhe_workshop::{
+    decryption_ceremony::{DecryptionCeremony, DecryptionShareMessage},
+    params,
+    party::Party,
+    reshare::{self, Reshare, ReshareMessage},
+    tally,
+    threshold::TrusteeSet,
+    voter::{self, Choice},
+};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use std::{collections::BTreeSet, error::Error, sync::Arc};
+
+const OLD_TRUSTEES: usize = 3;
+const NEW_TRUSTEES: usize = 5;
+const NEW_THRESHOLD: usize = 3;
+const VOTES_PER_ROUND: usize = 20;
+
+fn votes(rng: &mut ThreadRng) -> Vec<Choice> {
+    (0..VOTES_PER_ROUND)
+        .map(|_| Choice::ALL[rng.gen_range(0..Choice::ALL.len())])
+        .collect()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut rng: ThreadRng = thread_rng();
+    let plaintext_modulus: u64 = params::plaintext_modulus(2 * VOTES_PER_ROUND);
+    let params: Arc<BfvParameters> =
+        params::build(params::DEGREE, plaintext_modulus, &params::MODULI)?;
+    let old: Trustees = Trustees::new(&params, OLD_TRUSTEES, &mut rng)?;
+    let pk: PublicKey = old.public_key().clone();
+
+    // The first round of voting, under the old trustees.
+    let mut cast: Vec<Choice> = votes(&mut rng);
+    let mut ballots: Vec<Ciphertext> = cast
+        .iter()
+        .map(|&vote| voter::encrypt_vote(vote, &pk, &params, &mut rng))
+        .collect::<Result<_, _>>()?;
+
+    // The handover: every old slot sends a part of its share to every new slot.
+    let new_set: TrusteeSet = TrusteeSet::new(NEW_TRUSTEES, NEW_THRESHOLD)?;
+    let mut handover: Reshare = Reshare::new(
+        &params,
+        old.crp(),
+        old.trustee_set().num_slots(),
+        new_set.num_slots(),
+    )?;
+    for (from, key) in old.keys().iter().enumerate() {
+        let parts: Vec<ReshareMessage> =
+            reshare::split_share(from, key, new_set.num_slots(), &params, &mut rng)?;
+        for part in parts {
+            handover.receive(part)?;
+        }
+    }
+    let crp: CommonRandomPoly = old.crp().clone();
+    drop(old);
+    let keys: Vec<Party> = (0..new_set.num_slots())
+        .map(|slot| handover.new_share(slot, &mut rng).map(|(key, _)| key))
+        .collect::<Result<_, _>>()?;
+    assert!(
+        reshare::check_same_key(&pk, &keys, &params, &mut rng)?,
+        "the new shares hold another key"
+    );
+    println!(
+        "handed over from {OLD_TRUSTEES} trustees to {NEW_TRUSTEES}, \
+         any {NEW_THRESHOLD} of whom can decrypt"
+    );
+    let new: Trustees = Trustees::from_keys(new_set, keys, crp, pk.clone());
+
+    // The second round of voting, to the same public key.
+    let second: Vec<Choice> = votes(&mut rng);
+    for &vote in &second {
+        ballots.push(voter::encrypt_vote(vote, &pk, &params, &mut rng)?);
+    }
+    cast.extend(second);
+
+    // Only a quorum of the new trustees decrypts.
+    let sum: Arc<Ciphertext> = Arc::new(tally::sum(&ballots, &params));
+    let present: BTreeSet<usize> = (0..NEW_THRESHOLD).collect();
+    let assignment: Vec<usize> = new.trustee_set().assign(&present)?;
+    let mut ceremony: DecryptionCeremony = DecryptionCeremony::new(&sum, new.trustee_set());
+    for (slot, &trustee) in assignment.iter().enumerate() {
+        ceremony.receive(DecryptionShareMessage::new(
+            slot,
+            trustee,
+            &new.keys()[slot],
+            &sum,
+            &mut rng,
+        )?)?;
+    }
+    let counts: Vec<u64> = ceremony.decrypt(3)?.to_vec();
+    println!(
+        "against: {}, for: {}, abstain: {}",
+        counts[0], counts[1], counts[2]
+    );
+
+    let mut expected: Vec<u64> = vec![0; 3];
+    for vote in &cast {
+        expected[*vote as usize] += 1;
+    }
+    assert_eq!(counts, expected, "the tally is wrong");
+    Ok(())
+}
@@ -0,0 +1,15 @@
+// Arbitrary bytes read as the decryption request and share response files of air-gapped
+// trustees, and checked against a fixed coordinator key.
+
+#![no_main]
+
+use ed25519_dalek::SigningKey;
+use fhe_workshop::airgap::{DecryptionRequest, ShareResponse};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(request) = DecryptionRequest::from_json(data) {
+        let _ = request.open(&SigningKey::from_bytes(&[7; 32]).verifying_key());
+    }
+    let _ = ShareResponse::from_json(data);
+});
@@ -0,0 +1,26 @@
+// Arbitrary bytes read as a ballot and, if they parse, submitted to a ballot box on whose voter
+// roll the ballot's voter is, so the signature and the proof are checked too. Everything must
+// be rejected with an error rather than a panic.
+
+#![no_main]
+
+use fhe::bfv::BfvParameters;
+use fhe_workshop::{
+    ballot::{Ballot, BallotBox, DuplicatePolicy},
+    params,
+    validity::{ProofCheck, TrustedProof},
+};
+use libfuzzer_sys::fuzz_target;
+use std::sync::{Arc, LazyLock};
+
+static PARAMS: LazyLock<Arc<BfvParameters>> =
+    LazyLock::new(|| params::build(params::DEGREE, 1009, &params::MODULI).unwrap());
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(ballot) = Ballot::from_bytes(data, &PARAMS) else {
+        return;
+    };
+    let mut ballot_box: BallotBox = BallotBox::new([ballot.voter], DuplicatePolicy::KeepLatest)
+        .with_validity(ProofCheck(TrustedProof));
+    let _ = ballot_box.submit(ballot);
+});
@@ -0,0 +1,16 @@
+// Arbitrary bytes read as a ciphertext under the workshop's parameters.
+
+#![no_main]
+
+use fhe::bfv::{BfvParameters, Ciphertext};
+use fhe_traits::DeserializeParametrized;
+use fhe_workshop::params;
+use libfuzzer_sys::fuzz_target;
+use std::sync::{Arc, LazyLock};
+
+static PARAMS: LazyLock<Arc<BfvParameters>> =
+    LazyLock::new(|| params::build(params::DEGREE, 1009, &params::MODULI).unwrap());
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Ciphertext::from_bytes(data, &PARAMS);
+});
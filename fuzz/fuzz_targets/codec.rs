@@ -0,0 +1,10 @@
+// Arbitrary bytes read as a codec frame.
+
+#![no_main]
+
+use fhe_workshop::codec;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = codec::decode(data);
+});
@@ -0,0 +1,10 @@
+// Arbitrary bytes read as a tally journal.
+
+#![no_main]
+
+use fhe_workshop::journal::TallyJournal;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = TallyJournal::from_json(data);
+});
@@ -0,0 +1,11 @@
+// Arbitrary bytes read as BFV parameters.
+
+#![no_main]
+
+use fhe::bfv::BfvParameters;
+use fhe_traits::Deserialize;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = BfvParameters::try_deserialize(data);
+});
@@ -0,0 +1,365 @@
+// Decryption by trustees who never connect to anything: requests and responses as files.
+//
+// The coordinator exports a decryption request holding the parameters, the ciphertext to
+// decrypt and a context hash saying which election it belongs to (e.g. the ballots root), and
+// signs it. The file is carried to each trustee's air-gapped machine, where the trustee checks
+// the coordinator's signature, produces a decryption share for each key slot they hold and
+// signs a share response, which is carried back and imported by the coordinator.
+//
+// Both files are JSON, so a trustee can read what they are being asked to decrypt before doing
+// it. Each carries its kind and a format version. The ciphertext and the decryption shares are
+// hex encoded messages in the wire format, see `wire`, the same as on the network, and the
+// parameters a hex encoded `codec` frame. A response names the request it answers by hash, so
+// it can't be replayed against another ciphertext. Either file can also be wrapped in ASCII
+// armor to be pasted into an email or a chat, see `armor.rs`.
+
+use crate::{
+    armor::{self, ArmorError, Label},
+    codec::{self, CodecError, Compression},
+    decryption_ceremony::{self, DecryptionShareMessage, TallyHash},
+    key_ceremony::PartyId,
+    merkle::Hash,
+    party::Party,
+    wire::{self, WireError},
+};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use fhe::bfv::{BfvParameters, Ciphertext};
+use fhe_traits::{Deserialize as FheDeserialize, Serialize as FheSerialize};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{error::Error, fmt, sync::Arc};
+
+// 1 had the ciphertext and the shares in fhe.rs's own serialization.
+pub const EXCHANGE_VERSION: u32 = 2;
+
+const REQUEST_KIND: &str = "decryption_request";
+const RESPONSE_KIND: &str = "share_response";
+const SIGNATURE_DOMAIN: &[u8] = b"fhe-workshop/airgap";
+
+#[derive(Debug)]
+pub enum ExchangeError {
+    Json(serde_json::Error),
+    Codec(CodecError),
+    Armor(ArmorError),
+    Fhe(fhe::Error),
+    Wire(WireError),
+    UnsupportedVersion(u32),
+    WrongKind(String),
+    BadSignature,
+    UnexpectedSigner,
+    WrongRequest,
+    Malformed,
+}
+
+impl fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExchangeError::Json(e) => write!(f, "{e}"),
+            ExchangeError::Codec(e) => write!(f, "{e}"),
+            ExchangeError::Armor(e) => write!(f, "{e}"),
+            ExchangeError::Fhe(e) => write!(f, "{e}"),
+            ExchangeError::Wire(e) => write!(f, "{e}"),
+            ExchangeError::UnsupportedVersion(v) => write!(
+                f,
+                "exchange format version {v} is not supported, expected {EXCHANGE_VERSION}"
+            ),
+            ExchangeError::WrongKind(kind) => write!(f, "unexpected file kind {kind}"),
+            ExchangeError::BadSignature => write!(f, "signature is invalid"),
+            ExchangeError::UnexpectedSigner => write!(f, "signed by an unexpected key"),
+            ExchangeError::WrongRequest => write!(f, "response answers a different request"),
+            ExchangeError::Malformed => write!(f, "file could not be parsed"),
+        }
+    }
+}
+
+impl Error for ExchangeError {}
+
+impl From<serde_json::Error> for ExchangeError {
+    fn from(e: serde_json::Error) -> Self {
+        ExchangeError::Json(e)
+    }
+}
+
+impl From<CodecError> for ExchangeError {
+    fn from(e: CodecError) -> Self {
+        ExchangeError::Codec(e)
+    }
+}
+
+impl From<ArmorError> for ExchangeError {
+    fn from(e: ArmorError) -> Self {
+        ExchangeError::Armor(e)
+    }
+}
+
+impl From<fhe::Error> for ExchangeError {
+    fn from(e: fhe::Error) -> Self {
+        ExchangeError::Fhe(e)
+    }
+}
+
+impl From<WireError> for ExchangeError {
+    fn from(e: WireError) -> Self {
+        ExchangeError::Wire(e)
+    }
+}
+
+// Hashes the fields of a file in order, each prefixed with its length, for signing.
+fn digest<'a>(kind: &str, fields: impl IntoIterator<Item = &'a [u8]>) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(SIGNATURE_DOMAIN);
+    hasher.update(kind.as_bytes());
+    for field in fields {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field);
+    }
+    hasher.finalize().into()
+}
+
+fn check_header(kind: &str, version: u32, expected: &str) -> Result<(), ExchangeError> {
+    if kind != expected {
+        return Err(ExchangeError::WrongKind(kind.to_string()));
+    }
+    if version != EXCHANGE_VERSION {
+        return Err(ExchangeError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+fn check_signature(
+    signer: &[u8; 32],
+    expected: &VerifyingKey,
+    message: &Hash,
+    signature: &[u8],
+) -> Result<(), ExchangeError> {
+    if signer != expected.as_bytes() {
+        return Err(ExchangeError::UnexpectedSigner);
+    }
+    let signature: Signature =
+        Signature::from_slice(signature).map_err(|_| ExchangeError::Malformed)?;
+    expected
+        .verify(message, &signature)
+        .map_err(|_| ExchangeError::BadSignature)
+}
+
+// Exported by the coordinator for the trustees.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecryptionRequest {
+    pub kind: String,
+    pub version: u32,
+    #[serde(with = "hex::serde")]
+    pub params: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub ciphertext: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub context: Hash,
+    #[serde(with = "hex::serde")]
+    pub coordinator: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl DecryptionRequest {
+    pub fn new(
+        params: &BfvParameters,
+        ciphertext: &Ciphertext,
+        context: Hash,
+        coordinator: &SigningKey,
+    ) -> Result<Self, ExchangeError> {
+        let mut request = DecryptionRequest {
+            kind: REQUEST_KIND.to_string(),
+            version: EXCHANGE_VERSION,
+            params: codec::encode(&params.to_bytes(), Compression::None)?,
+            ciphertext: wire::encode_tally(ciphertext),
+            context,
+            coordinator: coordinator.verifying_key().to_bytes(),
+            signature: Vec::new(),
+        };
+        request.signature = coordinator.sign(&request.hash()).to_vec();
+        Ok(request)
+    }
+
+    // The hash the coordinator signs, and responses refer to the request by.
+    pub fn hash(&self) -> Hash {
+        digest(
+            REQUEST_KIND,
+            [
+                self.params.as_slice(),
+                self.ciphertext.as_slice(),
+                self.context.as_slice(),
+                self.coordinator.as_slice(),
+            ],
+        )
+    }
+
+    // Checks the request is signed by the coordinator, then decodes the parameters and the
+    // ciphertext to decrypt.
+    pub fn open(
+        &self,
+        coordinator: &VerifyingKey,
+    ) -> Result<(Arc<BfvParameters>, Arc<Ciphertext>), ExchangeError> {
+        check_signature(
+            &self.coordinator,
+            coordinator,
+            &self.hash(),
+            &self.signature,
+        )?;
+        let params: Arc<BfvParameters> = Arc::new(BfvParameters::try_deserialize(&codec::decode(
+            &self.params,
+        )?)?);
+        let ciphertext: Ciphertext = wire::decode_tally(&self.ciphertext, &params)?;
+        Ok((params, Arc::new(ciphertext)))
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<Self, ExchangeError> {
+        let request: DecryptionRequest = serde_json::from_slice(bytes)?;
+        check_header(&request.kind, request.version, REQUEST_KIND)?;
+        Ok(request)
+    }
+
+    // The JSON in ASCII armor, to paste into a message, see `armor.rs`.
+    pub fn to_armored(&self) -> String {
+        armor::armor(Label::DecryptionRequest, &self.to_json())
+    }
+
+    pub fn from_armored(text: &str) -> Result<Self, ExchangeError> {
+        Self::from_json(&armor::dearmor_as(Label::DecryptionRequest, text)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotShare {
+    pub slot: usize,
+    #[serde(with = "hex::serde")]
+    pub share: Vec<u8>,
+}
+
+// Produced by a trustee on their air-gapped machine, answering a request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareResponse {
+    pub kind: String,
+    pub version: u32,
+    #[serde(with = "hex::serde")]
+    pub request: Hash,
+    pub trustee: PartyId,
+    pub shares: Vec<SlotShare>,
+    #[serde(with = "hex::serde")]
+    pub signer: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl ShareResponse {
+    // Checks the request came from the coordinator, then decrypts it with the key of each of
+    // the trustee's slots and signs the shares.
+    pub fn new<'a, R: RngCore + CryptoRng>(
+        request: &DecryptionRequest,
+        coordinator: &VerifyingKey,
+        trustee: PartyId,
+        keys: impl IntoIterator<Item = (usize, &'a Party)>,
+        signing_key: &SigningKey,
+        rng: &mut R,
+    ) -> Result<Self, ExchangeError> {
+        let (_, ciphertext) = request.open(coordinator)?;
+        let tally_hash: TallyHash = decryption_ceremony::tally_hash(&ciphertext);
+        let shares: Vec<SlotShare> = keys
+            .into_iter()
+            .map(|(slot, key)| {
+                Ok(SlotShare {
+                    slot,
+                    share: wire::encode_decryption_share(&DecryptionShareMessage {
+                        slot,
+                        trustee,
+                        tally_hash,
+                        share: key.decryption_share(&ciphertext, rng)?,
+                    }),
+                })
+            })
+            .collect::<Result<_, ExchangeError>>()?;
+        let mut response = ShareResponse {
+            kind: RESPONSE_KIND.to_string(),
+            version: EXCHANGE_VERSION,
+            request: request.hash(),
+            trustee,
+            shares,
+            signer: signing_key.verifying_key().to_bytes(),
+            signature: Vec::new(),
+        };
+        response.signature = signing_key.sign(&response.hash()).to_vec();
+        Ok(response)
+    }
+
+    fn hash(&self) -> Hash {
+        let trustee: [u8; 8] = (self.trustee as u64).to_le_bytes();
+        let slots: Vec<[u8; 8]> = self
+            .shares
+            .iter()
+            .map(|share| (share.slot as u64).to_le_bytes())
+            .collect();
+        digest(
+            RESPONSE_KIND,
+            [
+                self.request.as_slice(),
+                trustee.as_slice(),
+                self.signer.as_slice(),
+            ]
+            .into_iter()
+            .chain(
+                slots
+                    .iter()
+                    .zip(&self.shares)
+                    .flat_map(|(slot, share)| [slot.as_slice(), share.share.as_slice()]),
+            ),
+        )
+    }
+
+    // Checks the response answers the request and is signed by the trustee, then turns it into
+    // the decryption share messages the ceremony takes.
+    pub fn open(
+        &self,
+        request: &DecryptionRequest,
+        params: &Arc<BfvParameters>,
+        ciphertext: &Arc<Ciphertext>,
+        trustee_key: &VerifyingKey,
+    ) -> Result<Vec<DecryptionShareMessage>, ExchangeError> {
+        if self.request != request.hash() {
+            return Err(ExchangeError::WrongRequest);
+        }
+        check_signature(&self.signer, trustee_key, &self.hash(), &self.signature)?;
+        self.shares
+            .iter()
+            .map(|share| {
+                let message: DecryptionShareMessage =
+                    wire::decode_decryption_share(&share.share, params, ciphertext.clone())?;
+                // The signed slot and trustee have to be the ones in the message.
+                if message.slot != share.slot || message.trustee != self.trustee {
+                    return Err(ExchangeError::Malformed);
+                }
+                Ok(message)
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<Self, ExchangeError> {
+        let response: ShareResponse = serde_json::from_slice(bytes)?;
+        check_header(&response.kind, response.version, RESPONSE_KIND)?;
+        Ok(response)
+    }
+
+    pub fn to_armored(&self) -> String {
+        armor::armor(Label::ShareResponse, &self.to_json())
+    }
+
+    pub fn from_armored(text: &str) -> Result<Self, ExchangeError> {
+        Self::from_json(&armor::dearmor_as(Label::ShareResponse, text)?)
+    }
+}
@@ -0,0 +1,240 @@
+// ASCII armor for the artifacts people carry by hand: public keys, ballots and decryption
+// shares, pasted into a chat or an email during the workshop, and the files of the air-gapped
+// trustee workflow, see `airgap.rs`.
+//
+// The format follows OpenPGP's: a line naming what is armored, a version header, a blank line,
+// the bytes in base64 over lines of 64 characters, a checksum line starting with `=`, and a
+// closing line:
+//
+//     -----BEGIN FHE-WORKSHOP BALLOT-----
+//     Version: 1
+//
+//     omd2ZXJzaW9uAWdtZXNzYWdlp2RraW5kZmJhbGxvdGV2b3RlclggO2bWqOh8...
+//     =q5bZ8w==
+//     -----END FHE-WORKSHOP BALLOT-----
+//
+// The checksum is the first four bytes of the SHA-256 hash of the bytes rather than OpenPGP's
+// CRC-24. It catches a line lost or mangled on the way, not tampering: ballots and the files of
+// the air-gapped workflow are signed for that. Reading skips whatever surrounds the armor, e.g.
+// the rest of an email, and whitespace at the ends of lines.
+
+use crate::{
+    ballot::{Ballot, BallotError},
+    decryption_ceremony::DecryptionShareMessage,
+    wire::{self, WireError},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fhe::bfv::{BfvParameters, Ciphertext, PublicKey};
+use fhe_traits::{DeserializeParametrized, Serialize};
+use sha2::{Digest, Sha256};
+use std::{error::Error, fmt, sync::Arc};
+
+const VERSION: &str = "1";
+const LINE_LEN: usize = 64;
+const CHECKSUM_LEN: usize = 4;
+
+// What is armored, named on the first and last lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    PublicKey,
+    Ballot,
+    DecryptionShare,
+    DecryptionRequest,
+    ShareResponse,
+}
+
+impl Label {
+    const ALL: [Label; 5] = [
+        Label::PublicKey,
+        Label::Ballot,
+        Label::DecryptionShare,
+        Label::DecryptionRequest,
+        Label::ShareResponse,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Label::PublicKey => "FHE-WORKSHOP PUBLIC KEY",
+            Label::Ballot => "FHE-WORKSHOP BALLOT",
+            Label::DecryptionShare => "FHE-WORKSHOP DECRYPTION SHARE",
+            Label::DecryptionRequest => "FHE-WORKSHOP DECRYPTION REQUEST",
+            Label::ShareResponse => "FHE-WORKSHOP SHARE RESPONSE",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|label| label.name() == name)
+    }
+}
+
+#[derive(Debug)]
+pub enum ArmorError {
+    // No armor in the text, or armor missing a line.
+    Malformed,
+    UnknownLabel(String),
+    WrongLabel { expected: Label, found: Label },
+    UnsupportedVersion(String),
+    Base64,
+    Checksum,
+    Fhe(fhe::Error),
+    Wire(WireError),
+    Ballot(BallotError),
+}
+
+impl fmt::Display for ArmorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArmorError::Malformed => write!(f, "no armor found, or the armor is cut short"),
+            ArmorError::UnknownLabel(label) => write!(f, "unknown armor label `{label}`"),
+            ArmorError::WrongLabel { expected, found } => {
+                write!(f, "expected a {}, got a {}", expected.name(), found.name())
+            }
+            ArmorError::UnsupportedVersion(v) => write!(f, "unsupported armor version {v}"),
+            ArmorError::Base64 => write!(f, "the armored text is not valid base64"),
+            ArmorError::Checksum => {
+                write!(
+                    f,
+                    "the armor checksum doesn't match, a line may be mangled or missing"
+                )
+            }
+            ArmorError::Fhe(e) => write!(f, "{e}"),
+            ArmorError::Wire(e) => write!(f, "{e}"),
+            ArmorError::Ballot(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ArmorError {}
+
+impl From<fhe::Error> for ArmorError {
+    fn from(e: fhe::Error) -> Self {
+        ArmorError::Fhe(e)
+    }
+}
+
+impl From<WireError> for ArmorError {
+    fn from(e: WireError) -> Self {
+        ArmorError::Wire(e)
+    }
+}
+
+impl From<BallotError> for ArmorError {
+    fn from(e: BallotError) -> Self {
+        ArmorError::Ballot(e)
+    }
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+    Sha256::digest(bytes)[..CHECKSUM_LEN].try_into().unwrap()
+}
+
+pub fn armor(label: Label, bytes: &[u8]) -> String {
+    let encoded: String = STANDARD.encode(bytes);
+    let mut text: String = format!("-----BEGIN {}-----\nVersion: {VERSION}\n\n", label.name());
+    // Base64 is ASCII, so the lines can be cut anywhere.
+    for line in encoded.as_bytes().chunks(LINE_LEN) {
+        text.push_str(std::str::from_utf8(line).unwrap());
+        text.push('\n');
+    }
+    text.push_str(&format!(
+        "={}\n-----END {}-----\n",
+        STANDARD.encode(checksum(bytes)),
+        label.name()
+    ));
+    text
+}
+
+// Finds the first armor in the text and returns what it holds, once its checksum is checked.
+pub fn dearmor(text: &str) -> Result<(Label, Vec<u8>), ArmorError> {
+    let mut lines = text.lines().map(str::trim_end);
+    let name: &str = lines
+        .by_ref()
+        .find_map(|line| line.strip_prefix("-----BEGIN ")?.strip_suffix("-----"))
+        .ok_or(ArmorError::Malformed)?;
+    let label: Label =
+        Label::from_name(name).ok_or_else(|| ArmorError::UnknownLabel(name.to_string()))?;
+
+    let mut version: Option<&str> = None;
+    for header in lines.by_ref().take_while(|line| !line.is_empty()) {
+        if let Some(value) = header.strip_prefix("Version: ") {
+            version = Some(value);
+        }
+    }
+    match version {
+        Some(VERSION) => {}
+        Some(other) => return Err(ArmorError::UnsupportedVersion(other.to_string())),
+        None => return Err(ArmorError::Malformed),
+    }
+
+    let mut encoded: String = String::new();
+    let expected: &str = loop {
+        let line: &str = lines.next().ok_or(ArmorError::Malformed)?;
+        match line.strip_prefix('=') {
+            Some(checksum) => break checksum,
+            None => encoded.push_str(line.trim_start()),
+        }
+    };
+    if lines.next() != Some(format!("-----END {name}-----").as_str()) {
+        return Err(ArmorError::Malformed);
+    }
+
+    let bytes: Vec<u8> = STANDARD.decode(encoded).map_err(|_| ArmorError::Base64)?;
+    let expected: Vec<u8> = STANDARD.decode(expected).map_err(|_| ArmorError::Base64)?;
+    if expected != checksum(&bytes) {
+        return Err(ArmorError::Checksum);
+    }
+    Ok((label, bytes))
+}
+
+// The same, refusing armor of anything but `expected`.
+pub fn dearmor_as(expected: Label, text: &str) -> Result<Vec<u8>, ArmorError> {
+    let (found, bytes) = dearmor(text)?;
+    if found != expected {
+        return Err(ArmorError::WrongLabel { expected, found });
+    }
+    Ok(bytes)
+}
+
+pub fn armor_public_key(public_key: &PublicKey) -> String {
+    armor(Label::PublicKey, &public_key.to_bytes())
+}
+
+pub fn read_public_key(text: &str, params: &Arc<BfvParameters>) -> Result<PublicKey, ArmorError> {
+    Ok(PublicKey::from_bytes(
+        &dearmor_as(Label::PublicKey, text)?,
+        params,
+    )?)
+}
+
+// In the wire format, see `wire`.
+pub fn armor_ballot(ballot: &Ballot) -> String {
+    armor(Label::Ballot, &ballot.to_bytes())
+}
+
+pub fn read_ballot(text: &str, params: &Arc<BfvParameters>) -> Result<Ballot, ArmorError> {
+    Ok(Ballot::from_bytes(
+        &dearmor_as(Label::Ballot, text)?,
+        params,
+    )?)
+}
+
+// In the wire format, see `wire`.
+pub fn armor_decryption_share(message: &DecryptionShareMessage) -> String {
+    armor(
+        Label::DecryptionShare,
+        &wire::encode_decryption_share(message),
+    )
+}
+
+// The share is read against the tally it decrypts.
+pub fn read_decryption_share(
+    text: &str,
+    params: &Arc<BfvParameters>,
+    tally: Arc<Ciphertext>,
+) -> Result<DecryptionShareMessage, ArmorError> {
+    Ok(wire::decode_decryption_share(
+        &dearmor_as(Label::DecryptionShare, text)?,
+        params,
+        tally,
+    )?)
+}
@@ -0,0 +1,252 @@
+// The public artifacts of an election, written to a directory so anyone can check the result.
+//
+// Everything here is already public by the end of an election: the parameters, the public key,
+// the accepted ballots, the tally ciphertext, the decryption shares that opened it and the
+// result, along with the tally journal binding them together and the coordinator's manifest of
+// the ballots, see `manifest`, and the tracking codes of the ballots, see `tracking`. Auditors
+// only need this directory and the published ballots root to re-check the tally, see the
+// `verify` subcommand.
+//
+// The certification bundle, see `certification`, goes in the same directory, next to them.
+//
+// Each binary file is framed by `codec`. Files holding several items (ballots, shares) prefix
+// each framed item with its length as a little endian `u32`. The ballots, the tally and the
+// decryption shares are in the wire format, see `wire`, the same as on the network and in a
+// checkpoint, so each share says which trustee and slot it came from. `result.json` holds the
+// version of the format, and readers refuse versions they don't know.
+
+use crate::{
+    ballot::{Ballot, BallotError},
+    codec::{self, CodecError, Compression},
+    decryption_ceremony::DecryptionShareMessage,
+    journal::{JournalError, TallyJournal},
+    manifest::{BallotManifest, ManifestError},
+    tracking,
+    wire::{self, WireError},
+};
+use fhe::bfv::{BfvParameters, Ciphertext, PublicKey};
+use fhe_traits::{Deserialize, DeserializeParametrized, Serialize};
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use std::{error::Error, fmt, fs, io, path::Path, sync::Arc};
+
+pub const PARAMS_FILE: &str = "params.bin";
+pub const PUBLIC_KEY_FILE: &str = "public_key.bin";
+pub const BALLOTS_FILE: &str = "ballots.bin";
+pub const TALLY_FILE: &str = "tally.bin";
+pub const SHARES_FILE: &str = "shares.bin";
+pub const RESULT_FILE: &str = "result.json";
+pub const JOURNAL_FILE: &str = "journal.json";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const TRACKING_FILE: &str = "tracking_codes.json";
+// 1 had the tally and the decryption shares in fhe.rs's own serialization.
+pub const ARTIFACTS_VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum ArtifactError {
+    Io(io::Error),
+    Codec(CodecError),
+    Fhe(fhe::Error),
+    Ballot(BallotError),
+    Json(serde_json::Error),
+    Journal(JournalError),
+    Manifest(ManifestError),
+    Wire(WireError),
+    UnsupportedVersion(u32),
+    Truncated,
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::Io(e) => write!(f, "{e}"),
+            ArtifactError::Codec(e) => write!(f, "{e}"),
+            ArtifactError::Fhe(e) => write!(f, "{e}"),
+            ArtifactError::Ballot(e) => write!(f, "{e}"),
+            ArtifactError::Json(e) => write!(f, "{e}"),
+            ArtifactError::Journal(e) => write!(f, "{e}"),
+            ArtifactError::Manifest(e) => write!(f, "{e}"),
+            ArtifactError::Wire(e) => write!(f, "{e}"),
+            ArtifactError::UnsupportedVersion(v) => write!(
+                f,
+                "artifacts version {v} is not supported, expected {ARTIFACTS_VERSION}"
+            ),
+            ArtifactError::Truncated => write!(f, "artifact file is truncated"),
+        }
+    }
+}
+
+impl Error for ArtifactError {}
+
+impl From<io::Error> for ArtifactError {
+    fn from(e: io::Error) -> Self {
+        ArtifactError::Io(e)
+    }
+}
+
+impl From<CodecError> for ArtifactError {
+    fn from(e: CodecError) -> Self {
+        ArtifactError::Codec(e)
+    }
+}
+
+impl From<fhe::Error> for ArtifactError {
+    fn from(e: fhe::Error) -> Self {
+        ArtifactError::Fhe(e)
+    }
+}
+
+impl From<BallotError> for ArtifactError {
+    fn from(e: BallotError) -> Self {
+        ArtifactError::Ballot(e)
+    }
+}
+
+impl From<JournalError> for ArtifactError {
+    fn from(e: JournalError) -> Self {
+        ArtifactError::Journal(e)
+    }
+}
+
+impl From<ManifestError> for ArtifactError {
+    fn from(e: ManifestError) -> Self {
+        ArtifactError::Manifest(e)
+    }
+}
+
+impl From<WireError> for ArtifactError {
+    fn from(e: WireError) -> Self {
+        ArtifactError::Wire(e)
+    }
+}
+
+impl From<serde_json::Error> for ArtifactError {
+    fn from(e: serde_json::Error) -> Self {
+        ArtifactError::Json(e)
+    }
+}
+
+// The decrypted result.
+#[derive(Debug, Clone, PartialEq, Eq, SerdeSerialize, SerdeDeserialize)]
+pub struct PublishedResult {
+    // The version of the artifacts format, missing before there was one.
+    #[serde(default)]
+    pub version: u32,
+    pub tally: Vec<u64>,
+    // How many trustees it takes to decrypt.
+    #[serde(default)]
+    pub threshold: usize,
+}
+
+pub struct Artifacts {
+    pub params: Arc<BfvParameters>,
+    pub public_key: PublicKey,
+    pub ballots: Vec<Ballot>,
+    pub tally: Arc<Ciphertext>,
+    pub shares: Vec<DecryptionShareMessage>,
+    pub result: PublishedResult,
+    pub journal: TallyJournal,
+    pub manifest: BallotManifest,
+}
+
+impl Artifacts {
+    pub fn write(&self, dir: &Path, compression: Compression) -> Result<(), ArtifactError> {
+        fs::create_dir_all(dir)?;
+        let frame = |bytes: Vec<u8>| codec::encode(&bytes, compression);
+        fs::write(dir.join(PARAMS_FILE), frame(self.params.to_bytes())?)?;
+        fs::write(
+            dir.join(PUBLIC_KEY_FILE),
+            frame(self.public_key.to_bytes())?,
+        )?;
+        fs::write(
+            dir.join(BALLOTS_FILE),
+            write_list(self.ballots.iter().map(Ballot::to_bytes), compression)?,
+        )?;
+        fs::write(
+            dir.join(TALLY_FILE),
+            frame(wire::encode_tally(&self.tally))?,
+        )?;
+        fs::write(
+            dir.join(SHARES_FILE),
+            write_list(
+                self.shares.iter().map(wire::encode_decryption_share),
+                compression,
+            )?,
+        )?;
+        fs::write(
+            dir.join(RESULT_FILE),
+            serde_json::to_vec_pretty(&self.result)?,
+        )?;
+        fs::write(dir.join(JOURNAL_FILE), self.journal.to_json())?;
+        fs::write(dir.join(MANIFEST_FILE), self.manifest.to_json())?;
+        fs::write(
+            dir.join(TRACKING_FILE),
+            serde_json::to_vec_pretty(&tracking::published_codes(&self.ballots))?,
+        )?;
+        Ok(())
+    }
+
+    pub fn read(dir: &Path) -> Result<Self, ArtifactError> {
+        let read = |name: &str| -> Result<Vec<u8>, ArtifactError> {
+            Ok(codec::decode(&fs::read(dir.join(name))?)?)
+        };
+        let result: PublishedResult = serde_json::from_slice(&fs::read(dir.join(RESULT_FILE))?)?;
+        if result.version != ARTIFACTS_VERSION {
+            return Err(ArtifactError::UnsupportedVersion(result.version));
+        }
+        let params: Arc<BfvParameters> =
+            Arc::new(BfvParameters::try_deserialize(&read(PARAMS_FILE)?)?);
+        let public_key: PublicKey = PublicKey::from_bytes(&read(PUBLIC_KEY_FILE)?, &params)?;
+        let ballots: Vec<Ballot> = read_list(&fs::read(dir.join(BALLOTS_FILE))?)?
+            .iter()
+            .map(|bytes| Ballot::from_bytes(bytes, &params))
+            .collect::<Result<_, _>>()?;
+        let tally: Arc<Ciphertext> = Arc::new(wire::decode_tally(&read(TALLY_FILE)?, &params)?);
+        let shares: Vec<DecryptionShareMessage> = read_list(&fs::read(dir.join(SHARES_FILE))?)?
+            .iter()
+            .map(|bytes| wire::decode_decryption_share(bytes, &params, tally.clone()))
+            .collect::<Result<_, _>>()?;
+        let journal: TallyJournal = TallyJournal::from_json(&fs::read(dir.join(JOURNAL_FILE))?)?;
+        let manifest: BallotManifest =
+            BallotManifest::from_json(&fs::read(dir.join(MANIFEST_FILE))?)?;
+        Ok(Artifacts {
+            params,
+            public_key,
+            ballots,
+            tally,
+            shares,
+            result,
+            journal,
+            manifest,
+        })
+    }
+}
+
+fn write_list(
+    items: impl Iterator<Item = Vec<u8>>,
+    compression: Compression,
+) -> Result<Vec<u8>, CodecError> {
+    let mut out: Vec<u8> = Vec::new();
+    for item in items {
+        let framed: Vec<u8> = codec::encode(&item, compression)?;
+        out.extend_from_slice(&(framed.len() as u32).to_le_bytes());
+        out.extend_from_slice(&framed);
+    }
+    Ok(out)
+}
+
+fn read_list(mut bytes: &[u8]) -> Result<Vec<Vec<u8>>, ArtifactError> {
+    let mut items: Vec<Vec<u8>> = Vec::new();
+    while !bytes.is_empty() {
+        let (len, rest) = bytes
+            .split_first_chunk::<4>()
+            .ok_or(ArtifactError::Truncated)?;
+        let len: usize = u32::from_le_bytes(*len) as usize;
+        if rest.len() < len {
+            return Err(ArtifactError::Truncated);
+        }
+        let (item, rest) = rest.split_at(len);
+        items.push(codec::decode(item)?);
+        bytes = rest;
+    }
+    Ok(items)
+}
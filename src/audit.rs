@@ -0,0 +1,216 @@
+// An audit of an election from its public artifacts alone, see `artifacts`.
+//
+// Unlike the `verify` subcommand, which checks the artifacts against a ballots root the auditor
+// got from elsewhere, the audit takes nothing but the artifacts directory and re-derives every
+// step it can from it: the ballot signatures and proofs, the nullifiers, the Merkle root of the
+// ballots, the coordinator's manifest, the tally as the sum of the ballots and the result as
+// the aggregation of the decryption shares. Every check is run even once one has failed, and
+// the report lists them all, each with what was found.
+//
+// What the audit can't check is whether the ballots are the ones voters cast: that is up to
+// each voter, with an inclusion proof against the published root.
+
+use crate::{
+    artifacts::Artifacts,
+    ballot::Nullifier,
+    journal::{self, TallyJournal},
+    manifest::BallotManifest,
+    merkle::{self, Hash, MerkleTree},
+    tally,
+    validity::{ProofCheck, TrustedProof, ValidityCheck},
+};
+use fhe_traits::Serialize as FheSerialize;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, mem};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    // What was found, e.g. the recomputed root, or why the check failed.
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditReport {
+    pub checks: Vec<Check>,
+}
+
+impl AuditReport {
+    fn check(&mut self, name: &str, passed: bool, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            passed,
+            detail: detail.into(),
+        });
+    }
+
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+}
+
+// One line per check, with its outcome first.
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width: usize = self
+            .checks
+            .iter()
+            .map(|check| check.name.len())
+            .max()
+            .unwrap_or(0);
+        for check in &self.checks {
+            let outcome: &str = if check.passed { "PASS" } else { "FAIL" };
+            writeln!(f, "{outcome}  {:width$}  {}", check.name, check.detail)?;
+        }
+        let failed: usize = self.failed().count();
+        write!(
+            f,
+            "{} checks, {} passed, {failed} failed",
+            self.checks.len(),
+            self.checks.len() - failed
+        )
+    }
+}
+
+// Runs every check on the artifacts. The decryption shares are consumed by the aggregation.
+pub fn audit(mut artifacts: Artifacts) -> AuditReport {
+    let mut report: AuditReport = AuditReport::default();
+    let journal: &TallyJournal = &artifacts.journal;
+    let num_ballots: usize = artifacts.ballots.len();
+
+    let params_hash: Hash = journal::params_hash(&artifacts.params);
+    report.check(
+        "parameters",
+        params_hash == journal.params_hash,
+        format!("hash {}", hex::encode(params_hash)),
+    );
+
+    let unsigned: Vec<usize> = positions(&artifacts.ballots, |ballot| ballot.verify().is_ok());
+    report.check(
+        "ballot signatures",
+        unsigned.is_empty(),
+        outcome(num_ballots, &unsigned, "signed by their voter"),
+    );
+    // The only proof system so far is a stub, so this checks the proofs are there rather than
+    // that the votes are valid, see `validity`.
+    let unproven: Vec<usize> = positions(&artifacts.ballots, |ballot| {
+        ProofCheck(TrustedProof).check(ballot).is_ok()
+    });
+    report.check(
+        "ballot proofs",
+        unproven.is_empty(),
+        outcome(num_ballots, &unproven, "carrying a valid proof"),
+    );
+    let mut nullifiers: HashSet<Nullifier> = HashSet::new();
+    let reused: Vec<usize> = positions(&artifacts.ballots, |ballot| {
+        nullifiers.insert(ballot.nullifier)
+    });
+    report.check(
+        "nullifiers",
+        reused.is_empty(),
+        outcome(num_ballots, &reused, "with a nullifier of their own"),
+    );
+
+    report.check(
+        "ballot count",
+        num_ballots == journal.num_ballots,
+        format!(
+            "{num_ballots} published, {} in the journal",
+            journal.num_ballots
+        ),
+    );
+    let leaves: Vec<Hash> = artifacts
+        .ballots
+        .iter()
+        .map(|ballot| merkle::hash_leaf(&ballot.ciphertext.to_bytes()))
+        .collect();
+    let root: Hash = MerkleTree::new(leaves).root();
+    report.check(
+        "ballots root",
+        root == journal.ballots_root,
+        format!("recomputed {}", hex::encode(root)),
+    );
+
+    let manifest: &BallotManifest = &artifacts.manifest;
+    report.check(
+        "manifest signature",
+        manifest.verify_signature().is_ok(),
+        format!("signed by {}", hex::encode(manifest.signer)),
+    );
+    match manifest.check_ballots(&artifacts.ballots) {
+        Ok(()) => report.check(
+            "manifest hashes",
+            true,
+            format!("{} entries match the ballots", manifest.entries.len()),
+        ),
+        Err(e) => report.check("manifest hashes", false, e.to_string()),
+    }
+    report.check(
+        "manifest root",
+        manifest.ballots_root == root,
+        format!("lists {}", hex::encode(manifest.ballots_root)),
+    );
+
+    let published: Vec<u8> = artifacts.tally.to_bytes();
+    // A re-randomized tally is no longer the sum of the ballots, so it can only be checked
+    // against the journal.
+    if journal.rerandomized {
+        report.check(
+            "tally",
+            published == journal.tally_bytes,
+            "re-randomized, checked against the journal only",
+        );
+    } else {
+        let sum: Vec<u8> = tally::sum(
+            artifacts.ballots.iter().map(|ballot| &ballot.ciphertext),
+            &artifacts.params,
+        )
+        .to_bytes();
+        report.check(
+            "tally",
+            sum == published && sum == journal.tally_bytes,
+            format!("sum of {num_ballots} ballots"),
+        );
+    }
+
+    let num_shares: usize = artifacts.shares.len();
+    match tally::decrypt(
+        mem::take(&mut artifacts.shares)
+            .into_iter()
+            .map(|message| message.share),
+        artifacts.result.tally.len(),
+    ) {
+        Ok(decrypted) => report.check(
+            "result",
+            decrypted == artifacts.result.tally,
+            format!("aggregated {num_shares} decryption shares into {decrypted:?}"),
+        ),
+        Err(e) => report.check("result", false, format!("aggregation failed: {e}")),
+    }
+
+    report
+}
+
+// The positions of the ballots failing the check.
+fn positions<T>(items: &[T], mut check: impl FnMut(&T) -> bool) -> Vec<usize> {
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(position, item)| (!check(item)).then_some(position))
+        .collect()
+}
+
+fn outcome(total: usize, failing: &[usize], what: &str) -> String {
+    match failing.first() {
+        None => format!("{total} of {total} ballots {what}"),
+        Some(first) => format!(
+            "{} of {total} ballots {what}, first failing at {first}",
+            total - failing.len()
+        ),
+    }
+}
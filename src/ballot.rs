@@ -0,0 +1,707 @@
+// Signed ballots and the ballot box that accepts them.
+//
+// Every voter holds an Ed25519 keypair and signs the serialized bytes of their encrypted vote.
+// The ballot box only accepts a ballot if the signer is on the voter roll, the signature is
+// valid for the ciphertext, and the signer has not already cast a ballot. This is the
+// eligibility layer: it doesn't tell us anything about the vote itself, only that it came from
+// someone who was allowed to vote.
+//
+// Each ballot also carries a nullifier, a value derived from the voter's secret key that is the
+// same for every ballot that voter casts. The ballot box uses it to decide what happens when a
+// voter votes more than once: either the new ballot replaces the old one (re-voting, which helps
+// against coercion since a coerced vote can later be overridden) or it is rejected outright.
+//
+// Sending the same ballot again is safe, so a voter who never heard back can retry. The
+// signature is the ballot's idempotency key: Ed25519 signatures are deterministic, so the same
+// ballot always carries the same one, and any change to it makes another. A ballot the box
+// already took is acknowledged without being counted again, and without replacing a later
+// ballot of the same voter if the retry was held up on the way.
+//
+// The precinct the ballot was cast in is signed along with it, see `precinct`.
+//
+// A ballot can also carry the voter's encryption of `[1]`, signed along with the rest, which a
+// ballot box counting turnout sums and requires, see `turnout`.
+//
+// Whether the vote inside is valid is a separate question, left to a `ValidityCheck`, see
+// `validity`. A ballot can carry a proof of validity for the check to verify, which is signed
+// along with the rest of the ballot.
+//
+// A serialized ballot can't be larger than two ciphertexts under the election's parameters, a
+// proof and a few fixed fields, see `Ballot::max_len`. Anything larger is turned away by
+// `Ballot::from_bytes` on its length alone, before any of it is parsed, so a flood of oversized
+// payloads costs the ballot box nothing but reading them.
+//
+// The ballot box holds every surviving ballot in memory, unless it is given stores, see
+// `BallotBox::with_store`. It then appends the ciphertexts of each ballot it accepts to them
+// and keeps only the rest of the ballot, the hash of its ciphertext and where it went, so a
+// large election doesn't need its ciphertexts in memory. The ballots are read back out of the
+// stores when they are needed whole, e.g. to be published.
+
+use crate::{
+    lifecycle,
+    merkle::{self, Hash},
+    params,
+    precinct::{PrecinctId, PrecinctStores, RunningTally},
+    store::{CiphertextStore, StoreError, StoreReader},
+    tracking::TrackingCode,
+    turnout::TurnoutCounter,
+    validity::{TrustClient, ValidityCheck},
+    wire,
+};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use fhe::bfv::{BfvParameters, Ciphertext};
+use fhe_traits::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fmt,
+    sync::Arc,
+};
+
+pub type Nullifier = [u8; 32];
+
+// What tells two submissions of the same ballot apart from two ballots, see `Ballot::key`.
+pub type IdempotencyKey = [u8; 64];
+
+const NULLIFIER_DOMAIN: &[u8] = b"fhe-workshop/nullifier";
+
+// The largest proof of validity a ballot may carry. The stand-in proofs are a few bytes, see
+// `validity`, this leaves room for a real proof system.
+pub const MAX_PROOF_LEN: usize = 64 * 1024;
+
+// fhe.rs's framing of a serialized ciphertext around its coefficients, with room to spare.
+const CIPHERTEXT_FRAMING: usize = 1024;
+
+// What a serialized ballot holds besides its ciphertext and proof: the voter's key, the
+// nullifier, the precinct, the signature and the CBOR envelope, with room to spare.
+const BALLOT_FRAMING: usize = 1024;
+
+// What the ballot box does with a second ballot carrying the same nullifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    KeepLatest,
+    Reject,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BallotError {
+    IneligibleVoter,
+    InvalidSignature,
+    DuplicateVoter,
+    NullifierMismatch,
+    Malformed,
+    InvalidVote,
+    VotingClosed,
+    // The ballot was spoiled, see `challenge`, and can't be cast.
+    Spoiled,
+    // The ballot was cast and can't be spoiled.
+    AlreadyCast,
+    // The serialized ballot is longer than any ballot under the parameters can be.
+    TooLarge { len: usize, max: usize },
+    // The ballot box counts turnout and the ballot carries no turnout ciphertext.
+    NoTurnout,
+    // The ballot box keeps its ballots in stores and the ballot couldn't be written to them.
+    Store(String),
+}
+
+impl fmt::Display for BallotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BallotError::IneligibleVoter => write!(f, "voter is not on the voter roll"),
+            BallotError::InvalidSignature => write!(f, "ballot signature is invalid"),
+            BallotError::DuplicateVoter => write!(f, "voter has already cast a ballot"),
+            BallotError::NullifierMismatch => {
+                write!(f, "nullifier differs from the voter's previous ballot")
+            }
+            BallotError::Malformed => write!(f, "ballot could not be deserialized"),
+            BallotError::InvalidVote => write!(f, "ballot does not hold a valid vote"),
+            BallotError::VotingClosed => write!(f, "voting is closed"),
+            BallotError::Spoiled => write!(f, "ballot was spoiled and can't be cast"),
+            BallotError::AlreadyCast => write!(f, "ballot was cast and can't be spoiled"),
+            BallotError::TooLarge { len, max } => {
+                write!(f, "ballot is {len} bytes, over the limit of {max}")
+            }
+            BallotError::NoTurnout => write!(f, "ballot carries no turnout ciphertext"),
+            BallotError::Store(e) => write!(f, "ballot could not be stored: {e}"),
+        }
+    }
+}
+
+impl Error for BallotError {}
+
+impl From<StoreError> for BallotError {
+    fn from(e: StoreError) -> Self {
+        BallotError::Store(e.to_string())
+    }
+}
+
+impl BallotError {
+    // A short name for the error, e.g. the reason a ballot was rejected in the feed's metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BallotError::IneligibleVoter => "ineligible_voter",
+            BallotError::InvalidSignature => "invalid_signature",
+            BallotError::DuplicateVoter => "duplicate_voter",
+            BallotError::NullifierMismatch => "nullifier_mismatch",
+            BallotError::Malformed => "malformed",
+            BallotError::InvalidVote => "invalid_vote",
+            BallotError::VotingClosed => "voting_closed",
+            BallotError::Spoiled => "spoiled",
+            BallotError::AlreadyCast => "already_cast",
+            BallotError::TooLarge { .. } => "too_large",
+            BallotError::NoTurnout => "no_turnout",
+            BallotError::Store(_) => "store",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Ballot {
+    pub voter: VerifyingKey,
+    pub nullifier: Nullifier,
+    pub precinct: PrecinctId,
+    pub ciphertext: Ciphertext,
+    // A proof that the ciphertext holds a valid vote, empty if there is none, see `validity`.
+    pub proof: Vec<u8>,
+    // The voter's encryption of `[1]`, if the ballot is to be counted in the turnout.
+    pub turnout: Option<Ciphertext>,
+    pub signature: Signature,
+}
+
+impl Ballot {
+    // Signs the nullifier, the precinct and the serialized ciphertext with the voter's key.
+    pub fn new(ciphertext: Ciphertext, precinct: PrecinctId, signing_key: &SigningKey) -> Self {
+        Self::with_proof(ciphertext, precinct, Vec::new(), signing_key)
+    }
+
+    // Like `new`, attaching a proof that the ciphertext holds a valid vote.
+    pub fn with_proof(
+        ciphertext: Ciphertext,
+        precinct: PrecinctId,
+        proof: Vec<u8>,
+        signing_key: &SigningKey,
+    ) -> Self {
+        Self::sign(ciphertext, None, precinct, proof, signing_key)
+    }
+
+    // Like `with_proof`, attaching the voter's encryption of `[1]` to be counted in the turnout,
+    // see `turnout::encrypt_one`.
+    pub fn with_turnout(
+        ciphertext: Ciphertext,
+        turnout: Ciphertext,
+        precinct: PrecinctId,
+        proof: Vec<u8>,
+        signing_key: &SigningKey,
+    ) -> Self {
+        Self::sign(ciphertext, Some(turnout), precinct, proof, signing_key)
+    }
+
+    fn sign(
+        ciphertext: Ciphertext,
+        turnout: Option<Ciphertext>,
+        precinct: PrecinctId,
+        proof: Vec<u8>,
+        signing_key: &SigningKey,
+    ) -> Self {
+        let nullifier: Nullifier = Self::nullifier(signing_key);
+        let signature: Signature = signing_key.sign(&Self::message(
+            &nullifier,
+            precinct,
+            &proof,
+            turnout.as_ref(),
+            &ciphertext,
+        ));
+        Ballot {
+            voter: signing_key.verifying_key(),
+            nullifier,
+            precinct,
+            ciphertext,
+            proof,
+            turnout,
+            signature,
+        }
+    }
+
+    // The nullifier is derived from the secret key, so only the voter can produce it, and it
+    // is the same for every ballot they cast.
+    pub fn nullifier(signing_key: &SigningKey) -> Nullifier {
+        let mut hasher = Sha256::new();
+        hasher.update(NULLIFIER_DOMAIN);
+        hasher.update(signing_key.to_bytes());
+        hasher.finalize().into()
+    }
+
+    // Serializes the ballot in the wire format, see `wire`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::encode_ballot(self)
+    }
+
+    // Parses a serialized ballot. The signature is not checked here, that's up to the ballot box.
+    pub fn from_bytes(bytes: &[u8], params: &Arc<BfvParameters>) -> Result<Self, BallotError> {
+        let max: usize = Self::max_len(params);
+        if bytes.len() > max {
+            return Err(BallotError::TooLarge {
+                len: bytes.len(),
+                max,
+            });
+        }
+        wire::decode_ballot(bytes, params).map_err(|_| BallotError::Malformed)
+    }
+
+    // The most bytes a serialized ballot can take under the parameters: a ciphertext and a
+    // turnout ciphertext, a proof of up to `MAX_PROOF_LEN` bytes, and their framing.
+    pub fn max_len(params: &BfvParameters) -> usize {
+        2 * Self::max_ciphertext_len(params) + MAX_PROOF_LEN + BALLOT_FRAMING
+    }
+
+    // The most bytes a serialized ciphertext can take: two polynomials with every coefficient
+    // taking a full u64.
+    pub fn max_ciphertext_len(params: &BfvParameters) -> usize {
+        params::ciphertext_memory(params.degree(), params.moduli().len()) as usize
+            + CIPHERTEXT_FRAMING
+    }
+
+    fn message(
+        nullifier: &Nullifier,
+        precinct: PrecinctId,
+        proof: &[u8],
+        turnout: Option<&Ciphertext>,
+        ciphertext: &Ciphertext,
+    ) -> Vec<u8> {
+        // Empty without a turnout ciphertext, which no serialized ciphertext is.
+        let turnout: Vec<u8> = turnout.map(Ciphertext::to_bytes).unwrap_or_default();
+        [
+            nullifier.as_slice(),
+            &precinct.to_le_bytes(),
+            &(proof.len() as u32).to_le_bytes(),
+            proof,
+            &(turnout.len() as u32).to_le_bytes(),
+            &turnout,
+            &ciphertext.to_bytes(),
+        ]
+        .concat()
+    }
+
+    // The ballot's signature, the same every time the same ballot is sent.
+    pub fn key(&self) -> IdempotencyKey {
+        self.signature.to_bytes()
+    }
+
+    // The hash of the ciphertext alone, its leaf in the ballot tree, see `merkle`. Unlike `key`
+    // it stays the same when the ciphertext is signed again with another precinct or proof.
+    pub fn ciphertext_hash(&self) -> Hash {
+        merkle::hash_leaf(&self.ciphertext.to_bytes())
+    }
+
+    pub fn verify(&self) -> Result<(), BallotError> {
+        self.voter
+            .verify(
+                &Self::message(
+                    &self.nullifier,
+                    self.precinct,
+                    &self.proof,
+                    self.turnout.as_ref(),
+                    &self.ciphertext,
+                ),
+                &self.signature,
+            )
+            .map_err(|_| BallotError::InvalidSignature)
+    }
+}
+
+// A ballot whose ciphertexts the ballot box appended to its stores, see `BallotBox::with_store`:
+// the rest of the ballot, the hash of its ciphertext, and where the ciphertexts went.
+#[derive(Clone)]
+pub struct StoredBallot {
+    pub voter: VerifyingKey,
+    pub nullifier: Nullifier,
+    pub precinct: PrecinctId,
+    pub proof: Vec<u8>,
+    pub signature: Signature,
+    pub ciphertext_hash: Hash,
+    // The position of the vote in its precinct's store, and of the turnout in the turnout store.
+    vote: usize,
+    turnout: Option<usize>,
+}
+
+// The stores of a ballot box, and what it keeps of each surviving ballot.
+//
+// A replaced ballot's vote is taken back out of its precinct's sum, see `PrecinctStores`. Its
+// turnout is left in the turnout store, which is never summed, only read back.
+struct BallotStore {
+    votes: PrecinctStores,
+    turnouts: Box<dyn CiphertextStore>,
+    ballots: Vec<StoredBallot>,
+}
+
+impl BallotStore {
+    // Appends the ciphertexts of the ballot to the stores.
+    fn put(&mut self, ballot: &Ballot) -> Result<StoredBallot, StoreError> {
+        let vote: usize = self.votes.add(ballot.precinct, &ballot.ciphertext)?;
+        let turnout: Option<usize> = ballot
+            .turnout
+            .as_ref()
+            .map(|one| self.turnouts.append(one))
+            .transpose()?;
+        Ok(StoredBallot {
+            voter: ballot.voter,
+            nullifier: ballot.nullifier,
+            precinct: ballot.precinct,
+            proof: ballot.proof.clone(),
+            signature: ballot.signature,
+            ciphertext_hash: ballot.ciphertext_hash(),
+            vote,
+            turnout,
+        })
+    }
+
+    // Puts the ballot in place of the one at `position`, whose vote is taken back out of its
+    // precinct's sum, and hands that one back.
+    fn replace(&mut self, position: usize, ballot: &Ballot) -> Result<Ballot, StoreError> {
+        let old: Ballot = self.get(position)?;
+        let stored: StoredBallot = self.put(ballot)?;
+        self.votes
+            .remove(old.precinct, self.ballots[position].vote)?;
+        self.ballots[position] = stored;
+        Ok(old)
+    }
+
+    fn get(&self, position: usize) -> Result<Ballot, StoreError> {
+        self.read(
+            &self.ballots[position],
+            &mut BTreeMap::new(),
+            &mut StoreReader::new(self.turnouts.as_ref()),
+        )
+    }
+
+    // Reads a ballot back out of the stores, with a reader for each precinct and one for the
+    // turnouts, so that reading the ballots in order walks each store about once.
+    fn read<'a>(
+        &'a self,
+        stored: &StoredBallot,
+        votes: &mut BTreeMap<PrecinctId, StoreReader<'a>>,
+        turnouts: &mut StoreReader<'a>,
+    ) -> Result<Ballot, StoreError> {
+        let ciphertext: Ciphertext = votes
+            .entry(stored.precinct)
+            .or_insert_with(|| StoreReader::new(self.votes.store(stored.precinct)))
+            .read(stored.vote)?;
+        let turnout: Option<Ciphertext> = stored
+            .turnout
+            .map(|position| turnouts.read(position))
+            .transpose()?;
+        Ok(Ballot {
+            voter: stored.voter,
+            nullifier: stored.nullifier,
+            precinct: stored.precinct,
+            ciphertext,
+            proof: stored.proof.clone(),
+            turnout,
+            signature: stored.signature,
+        })
+    }
+}
+
+pub struct BallotBox {
+    policy: DuplicatePolicy,
+    validity: Box<dyn ValidityCheck>,
+    // Each registered voter, and the nullifier they used on their first ballot (if any).
+    voter_roll: HashMap<VerifyingKey, Option<Nullifier>>,
+    // The position of the surviving ballot for each nullifier.
+    index: HashMap<Nullifier, usize>,
+    // The surviving ballots, unless they are kept in `store`.
+    ballots: Vec<Ballot>,
+    store: Option<BallotStore>,
+    // When each surviving ballot was submitted, in the same order.
+    submitted_at: Vec<u64>,
+    replaced: usize,
+    // Every ballot accepted so far, surviving or replaced, so that a retry is only taken once.
+    accepted: HashSet<IdempotencyKey>,
+    // The ciphertext of every ballot accepted so far, which can't be spoiled any more.
+    cast: HashSet<Hash>,
+    resubmitted: usize,
+    // The ballots voters spoiled to challenge their client, see `challenge`. A spoiled
+    // ciphertext is refused however it is signed, its randomness is public.
+    spoiled: Vec<Ballot>,
+    spoiled_ciphertexts: HashSet<Hash>,
+    turnout: Option<TurnoutCounter>,
+    tally: Option<RunningTally>,
+    closed: bool,
+}
+
+impl BallotBox {
+    pub fn new(
+        voter_roll: impl IntoIterator<Item = VerifyingKey>,
+        policy: DuplicatePolicy,
+    ) -> Self {
+        BallotBox {
+            policy,
+            validity: Box::new(TrustClient),
+            voter_roll: voter_roll.into_iter().map(|voter| (voter, None)).collect(),
+            index: HashMap::new(),
+            ballots: Vec::new(),
+            store: None,
+            submitted_at: Vec::new(),
+            replaced: 0,
+            accepted: HashSet::new(),
+            cast: HashSet::new(),
+            resubmitted: 0,
+            spoiled: Vec::new(),
+            spoiled_ciphertexts: HashSet::new(),
+            turnout: None,
+            tally: None,
+            closed: false,
+        }
+    }
+
+    // Replaces the check run on the vote inside each ballot, `TrustClient` by default.
+    pub fn with_validity(mut self, validity: impl ValidityCheck + 'static) -> Self {
+        self.validity = Box::new(validity);
+        self
+    }
+
+    // Sums the turnout ciphertext of every voter's latest ballot on an encrypted counter, and
+    // turns away ballots that carry none.
+    pub fn with_turnout(mut self, turnout: TurnoutCounter) -> Self {
+        self.turnout = Some(turnout);
+        self
+    }
+
+    // Adds every accepted ballot to a running tally of its precinct, taking a replaced ballot
+    // back out, so the tally is ready as soon as voting closes.
+    pub fn with_tally(mut self, tally: RunningTally) -> Self {
+        self.tally = Some(tally);
+        self
+    }
+
+    // Appends the vote of every accepted ballot to the store of its precinct, and its turnout to
+    // `turnouts`, keeping only the rest of the ballot in memory. A replaced ballot's vote is
+    // taken back out of the sum of its precinct's store, so the stores sum to the tally.
+    pub fn with_store(mut self, votes: PrecinctStores, turnouts: Box<dyn CiphertextStore>) -> Self {
+        self.store = Some(BallotStore {
+            votes,
+            turnouts,
+            ballots: Vec::new(),
+        });
+        self
+    }
+
+    // Stops accepting ballots, e.g. once the election is closed, see `lifecycle`.
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.closed
+    }
+
+    // Checks the ballot box is open, then eligibility, the signature and the vote, then applies
+    // the duplicate policy. A ballot that was already accepted is acknowledged again and left
+    // alone, whatever the policy.
+    //
+    // A voter is bound to the nullifier of their first ballot, so they can't sidestep the
+    // policy by making up a fresh nullifier for every ballot, and a nullifier belongs to the
+    // voter who first used it, so nobody can replace someone else's ballot with their own.
+    //
+    // The voter gets back the tracking code of the ballot, see `tracking`, the same code if it
+    // is sent again.
+    //
+    // With a store, the ballot's ciphertexts are appended to it before anything else changes, so
+    // a ballot that couldn't be stored is turned away as if it had never been sent.
+    pub fn submit(&mut self, ballot: Ballot) -> Result<TrackingCode, BallotError> {
+        if self.closed {
+            return Err(BallotError::VotingClosed);
+        }
+        let bound: Option<Nullifier> = *self
+            .voter_roll
+            .get(&ballot.voter)
+            .ok_or(BallotError::IneligibleVoter)?;
+        ballot.verify()?;
+        let code: TrackingCode = TrackingCode::of(&ballot);
+        if self.accepted.contains(&ballot.key()) {
+            self.resubmitted += 1;
+            return Ok(code);
+        }
+        if self.spoiled_ciphertexts.contains(&ballot.ciphertext_hash()) {
+            return Err(BallotError::Spoiled);
+        }
+        if self.turnout.is_some() && ballot.turnout.is_none() {
+            return Err(BallotError::NoTurnout);
+        }
+        self.validity.check(&ballot)?;
+        let existing: Option<usize> = self.index.get(&ballot.nullifier).copied();
+        let owned: bool = existing.is_none_or(|position| self.voter_at(position) == &ballot.voter);
+        if bound.is_some_and(|nullifier| nullifier != ballot.nullifier) || !owned {
+            return Err(BallotError::NullifierMismatch);
+        }
+        match (existing, self.policy) {
+            (Some(_), DuplicatePolicy::Reject) => Err(BallotError::DuplicateVoter),
+            (Some(position), DuplicatePolicy::KeepLatest) => {
+                let old: Cow<Ballot> = match &mut self.store {
+                    Some(store) => Cow::Owned(store.replace(position, &ballot)?),
+                    None => Cow::Borrowed(&self.ballots[position]),
+                };
+                if let Some(tally) = &mut self.tally {
+                    tally.remove(&old);
+                    tally.add(&ballot);
+                }
+                if let (Some(turnout), Some(old), Some(new)) =
+                    (&mut self.turnout, &old.turnout, &ballot.turnout)
+                {
+                    turnout.remove(old);
+                    turnout.add(new);
+                }
+                self.accepted.insert(ballot.key());
+                self.cast.insert(ballot.ciphertext_hash());
+                if self.store.is_none() {
+                    self.ballots[position] = ballot;
+                }
+                self.submitted_at[position] = lifecycle::now();
+                self.replaced += 1;
+                Ok(code)
+            }
+            (None, _) => {
+                let position: usize = self.len();
+                if let Some(store) = &mut self.store {
+                    let stored: StoredBallot = store.put(&ballot)?;
+                    store.ballots.push(stored);
+                }
+                self.voter_roll.insert(ballot.voter, Some(ballot.nullifier));
+                self.index.insert(ballot.nullifier, position);
+                if let Some(tally) = &mut self.tally {
+                    tally.add(&ballot);
+                }
+                if let (Some(turnout), Some(one)) = (&mut self.turnout, &ballot.turnout) {
+                    turnout.add(one);
+                }
+                self.accepted.insert(ballot.key());
+                self.cast.insert(ballot.ciphertext_hash());
+                if self.store.is_none() {
+                    self.ballots.push(ballot);
+                }
+                self.submitted_at.push(lifecycle::now());
+                Ok(code)
+            }
+        }
+    }
+
+    // Takes a ballot the voter spoiled rather than cast, to be published with its opening, see
+    // `challenge`. It must be signed by a voter on the roll, and it is never counted: casting its
+    // ciphertext afterwards is refused, whatever it is signed with. Spoiling it again is
+    // acknowledged and left alone.
+    pub fn spoil(&mut self, ballot: Ballot) -> Result<(), BallotError> {
+        if self.closed {
+            return Err(BallotError::VotingClosed);
+        }
+        if !self.voter_roll.contains_key(&ballot.voter) {
+            return Err(BallotError::IneligibleVoter);
+        }
+        ballot.verify()?;
+        let ciphertext: Hash = ballot.ciphertext_hash();
+        if self.cast.contains(&ciphertext) {
+            return Err(BallotError::AlreadyCast);
+        }
+        if self.spoiled_ciphertexts.insert(ciphertext) {
+            self.spoiled.push(ballot);
+        }
+        Ok(())
+    }
+
+    // The spoiled ballots, in the order they were spoiled.
+    pub fn spoiled(&self) -> &[Ballot] {
+        &self.spoiled
+    }
+
+    // The surviving ballots, at most one per nullifier. With a store they are kept in it and
+    // this is empty, see `iter`.
+    pub fn ballots(&self) -> &[Ballot] {
+        &self.ballots
+    }
+
+    // How many ballots survive, at most one per nullifier.
+    pub fn len(&self) -> usize {
+        match &self.store {
+            Some(store) => store.ballots.len(),
+            None => self.ballots.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The surviving ballots, read back out of the store if there is one.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Ballot>, StoreError>> + '_> {
+        match &self.store {
+            Some(store) => {
+                let mut votes: BTreeMap<PrecinctId, StoreReader> = BTreeMap::new();
+                let mut turnouts: StoreReader = StoreReader::new(store.turnouts.as_ref());
+                Box::new(store.ballots.iter().map(move |stored| {
+                    Ok(Cow::Owned(store.read(stored, &mut votes, &mut turnouts)?))
+                }))
+            }
+            None => Box::new(self.ballots.iter().map(|ballot| Ok(Cow::Borrowed(ballot)))),
+        }
+    }
+
+    // The surviving ballot at `position`, in the order of `iter`.
+    pub fn ballot_at(&self, position: usize) -> Result<Cow<'_, Ballot>, StoreError> {
+        Ok(match &self.store {
+            Some(store) => Cow::Owned(store.get(position)?),
+            None => Cow::Borrowed(&self.ballots[position]),
+        })
+    }
+
+    // What the store keeps of each surviving ballot, in the order of `iter`, if there is one.
+    pub fn stored(&self) -> Option<&[StoredBallot]> {
+        self.store.as_ref().map(|store| store.ballots.as_slice())
+    }
+
+    // The stores the votes were appended to, if any, to be tallied once voting is over, see
+    // `precinct::tally_stores`.
+    pub fn precinct_stores(&self) -> Option<&PrecinctStores> {
+        self.store.as_ref().map(|store| &store.votes)
+    }
+
+    fn voter_at(&self, position: usize) -> &VerifyingKey {
+        match &self.store {
+            Some(store) => &store.ballots[position].voter,
+            None => &self.ballots[position].voter,
+        }
+    }
+
+    // When each surviving ballot was submitted, in seconds since the Unix epoch, in the order of
+    // `iter`.
+    pub fn submitted_at(&self) -> &[u64] {
+        &self.submitted_at
+    }
+
+    // The ballot counted for the nullifier, if any.
+    pub fn ballot(&self, nullifier: &Nullifier) -> Result<Option<Cow<'_, Ballot>>, StoreError> {
+        self.index
+            .get(nullifier)
+            .map(|&position| self.ballot_at(position))
+            .transpose()
+    }
+
+    pub fn replaced(&self) -> usize {
+        self.replaced
+    }
+
+    // How many submissions were retries of a ballot already accepted.
+    pub fn resubmitted(&self) -> usize {
+        self.resubmitted
+    }
+
+    // Hands over the running tally, if the ballot box is keeping one.
+    pub fn take_tally(&mut self) -> Option<RunningTally> {
+        self.tally.take()
+    }
+
+    // The encrypted turnout so far, if the ballot box is counting it.
+    pub fn turnout(&self) -> Option<&Ciphertext> {
+        self.turnout.as_ref().map(TurnoutCounter::ciphertext)
+    }
+}
@@ -0,0 +1,264 @@
+// A registry of the candidates of an election: who they are, which slot of the ballot is
+// theirs, and the order they are listed in.
+//
+// The registry is loaded from the election's config, a JSON list of candidates:
+//
+//     [
+//         { "id": "ada", "name": "Ada Lovelace" },
+//         { "id": "alan", "name": "Alan Turing", "order": 0 }
+//     ]
+//
+// Each candidate takes the slot at its position in the list, and a ballot is one-hot over the
+// slots of the candidates: a 1 in the slot of the candidate voted for, see `Candidates::slots`.
+// Summing the ballots counts the votes of every candidate at once.
+//
+// The list fixes the layout of the ballots, so it can't be reordered once voting has opened.
+// The order the candidates are shown in is kept apart, in the optional `order` of each candidate,
+// lowest first, with those without one after them in the order of the list. The results, see
+// `Candidates::standings`, come in that order with the names, counts and percentages of the
+// candidates, and the leaders, more than one of them being a tie.
+//
+// One of the candidates can be a write-in bucket, `{ "id": "other", "name": "Other",
+// "write_in": { "reveal_at": 10 } }`, counted like any other candidate. The names written in
+// never go on the ballot: they are handed to a collector of their own and only revealed if the
+// bucket gets at least `reveal_at` percent of the votes, see `write_in`.
+
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, ops::Range};
+
+#[derive(Debug)]
+pub enum CandidatesError {
+    Empty,
+    DuplicateId(String),
+    SeveralWriteIns,
+    // A write-in revealed above 100 percent of the votes.
+    RevealAt(u32),
+    UnknownCandidate(String),
+    // A tally with this many slots rather than one per candidate.
+    TallyLength { expected: usize, got: usize },
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for CandidatesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CandidatesError::Empty => write!(f, "an election needs at least one candidate"),
+            CandidatesError::DuplicateId(id) => write!(f, "two candidates have the id {id:?}"),
+            CandidatesError::SeveralWriteIns => {
+                write!(f, "an election has at most one write-in candidate")
+            }
+            CandidatesError::RevealAt(percent) => {
+                write!(
+                    f,
+                    "write-ins can't be revealed at {percent} percent of the votes"
+                )
+            }
+            CandidatesError::UnknownCandidate(id) => write!(f, "no candidate has the id {id:?}"),
+            CandidatesError::TallyLength { expected, got } => {
+                write!(
+                    f,
+                    "expected a tally of {expected} candidates, got {got} slots"
+                )
+            }
+            CandidatesError::Json(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for CandidatesError {}
+
+impl From<serde_json::Error> for CandidatesError {
+    fn from(e: serde_json::Error) -> Self {
+        CandidatesError::Json(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub id: String,
+    pub name: String,
+    // Where the candidate is listed, lowest first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<u32>,
+    // Makes the candidate the write-in bucket.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_in: Option<WriteInRule>,
+}
+
+// When the names written in are revealed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteInRule {
+    // The percentage of the votes the bucket needs.
+    pub reveal_at: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Candidate>", into = "Vec<Candidate>")]
+pub struct Candidates {
+    // In the order of their slots.
+    candidates: Vec<Candidate>,
+}
+
+impl TryFrom<Vec<Candidate>> for Candidates {
+    type Error = CandidatesError;
+
+    fn try_from(candidates: Vec<Candidate>) -> Result<Self, Self::Error> {
+        Candidates::new(candidates)
+    }
+}
+
+impl From<Candidates> for Vec<Candidate> {
+    fn from(candidates: Candidates) -> Self {
+        candidates.candidates
+    }
+}
+
+// A candidate's share of the result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing<'a> {
+    pub candidate: &'a Candidate,
+    pub votes: u64,
+    // Of all the votes for a candidate, 0 when there are none.
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standings<'a> {
+    // In display order.
+    pub standings: Vec<Standing<'a>>,
+    // The candidates with the most votes, in display order.
+    pub leaders: Vec<&'a Candidate>,
+}
+
+impl Standings<'_> {
+    pub fn total(&self) -> u64 {
+        self.standings.iter().map(|standing| standing.votes).sum()
+    }
+
+    pub fn is_tie(&self) -> bool {
+        self.leaders.len() > 1
+    }
+}
+
+impl Candidates {
+    pub fn new(candidates: Vec<Candidate>) -> Result<Self, CandidatesError> {
+        if candidates.is_empty() {
+            return Err(CandidatesError::Empty);
+        }
+        for (slot, candidate) in candidates.iter().enumerate() {
+            if candidates[..slot]
+                .iter()
+                .any(|other| other.id == candidate.id)
+            {
+                return Err(CandidatesError::DuplicateId(candidate.id.clone()));
+            }
+            match candidate.write_in {
+                Some(rule) if rule.reveal_at > 100 => {
+                    return Err(CandidatesError::RevealAt(rule.reveal_at));
+                }
+                _ => {}
+            }
+        }
+        if candidates.iter().filter(|c| c.write_in.is_some()).count() > 1 {
+            return Err(CandidatesError::SeveralWriteIns);
+        }
+        Ok(Candidates { candidates })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, CandidatesError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    // The slots of the ballot taken up by the candidates.
+    pub fn range(&self) -> Range<usize> {
+        0..self.len()
+    }
+
+    // The candidate of the slot.
+    pub fn get(&self, slot: usize) -> Option<&Candidate> {
+        self.candidates.get(slot)
+    }
+
+    pub fn slot_of(&self, id: &str) -> Option<usize> {
+        self.candidates
+            .iter()
+            .position(|candidate| candidate.id == id)
+    }
+
+    // The slot of the write-in bucket and when it is revealed, if there is one.
+    pub fn write_in(&self) -> Option<(usize, WriteInRule)> {
+        self.candidates
+            .iter()
+            .enumerate()
+            .find_map(|(slot, candidate)| Some((slot, candidate.write_in?)))
+    }
+
+    // The slots of a ballot for the candidate with the id.
+    pub fn slots(&self, id: &str) -> Result<Vec<u64>, CandidatesError> {
+        let slot: usize = self
+            .slot_of(id)
+            .ok_or_else(|| CandidatesError::UnknownCandidate(id.to_string()))?;
+        let mut slots: Vec<u64> = vec![0; self.len()];
+        slots[slot] = 1;
+        Ok(slots)
+    }
+
+    // Reads the candidate back from the slots of a single ballot, if it holds exactly one vote.
+    pub fn choice(&self, slots: &[u64]) -> Option<&Candidate> {
+        if slots.len() != self.len() || slots.iter().sum::<u64>() != 1 {
+            return None;
+        }
+        self.get(slots.iter().position(|&slot| slot == 1)?)
+    }
+
+    // The slots of the candidates, in the order they are listed.
+    pub fn display_order(&self) -> Vec<usize> {
+        let mut slots: Vec<usize> = self.range().collect();
+        slots.sort_by_key(|&slot| {
+            (
+                self.candidates[slot].order.is_none(),
+                self.candidates[slot].order,
+                slot,
+            )
+        });
+        slots
+    }
+
+    // The decrypted tally, candidate by candidate in display order.
+    pub fn standings(&self, tally: &[u64]) -> Result<Standings<'_>, CandidatesError> {
+        if tally.len() != self.len() {
+            return Err(CandidatesError::TallyLength {
+                expected: self.len(),
+                got: tally.len(),
+            });
+        }
+        let total: u64 = tally.iter().sum();
+        let most: u64 = tally.iter().copied().max().unwrap_or_default();
+        let order: Vec<usize> = self.display_order();
+        let standings: Vec<Standing> = order
+            .iter()
+            .map(|&slot| Standing {
+                candidate: &self.candidates[slot],
+                votes: tally[slot],
+                percent: match total {
+                    0 => 0.0,
+                    total => 100.0 * tally[slot] as f64 / total as f64,
+                },
+            })
+            .collect();
+        let leaders: Vec<&Candidate> = order
+            .iter()
+            .filter(|&&slot| tally[slot] == most)
+            .map(|&slot| &self.candidates[slot])
+            .collect();
+        Ok(Standings { standings, leaders })
+    }
+}
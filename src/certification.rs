@@ -0,0 +1,448 @@
+// The certification bundle: the result of an election and everything it stands on, in one
+// signed file.
+//
+// The published artifacts, see `artifacts`, are what an auditor needs to re-check the result,
+// but a system downstream only wants to know what the result is and that the trustees stand by
+// it. `certification.json` gives it that in one place: the result, the hashes of the parameters
+// and the public key, the ballots root and number of ballots, the hash of the tally, which
+// trustees contributed decryption shares, and the signature of each of those trustees over all
+// of it, see `Certification::hash`. If the tally was computed in a zkVM, the digest of its
+// receipt goes in as well.
+//
+// Each signature names the Ed25519 key that made it. Whether those keys are the trustees' is up
+// to the reader, as with the manifest, see `manifest`: `Certification::certify` checks them
+// against a roster of the trustees' keys, which has to reach the reader some other way than
+// with the bundle, see `roster_from_json`. Without one, anyone can sign a bundle.
+//
+// The result only counts as certified once a quorum of the trustees has signed it, the same
+// number as it takes to decrypt. The bundle records the quorum and the trustees sign it, but
+// the reader passes the quorum it requires itself, see `Certification::certify`: a bundle can't
+// lower the bar it is checked against, and a quorum of zero certifies nothing.
+// Trustees running the election among themselves, see `gossip`, have no ballots root or
+// coordinator to bundle things for them, and sign what they came to instead: the hashes of
+// their public key and tally, the quorum and the result, see `result_digest`. Their
+// signatures, once a quorum of them agree, make a `ResultCertificate`.
+//
+// Both are JSON, with a format version that readers check.
+
+use crate::{
+    decryption_ceremony::{self, TallyHash},
+    journal,
+    key_ceremony::PartyId,
+    merkle::Hash,
+    transparency,
+};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use fhe::bfv::{BfvParameters, Ciphertext, PublicKey};
+use fhe_traits::Serialize as FheSerialize;
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeSet, error::Error, fmt};
+
+pub const CERTIFICATION_FILE: &str = "certification.json";
+pub const CERTIFICATION_VERSION: u32 = 2;
+
+const SIGNATURE_DOMAIN: &[u8] = b"fhe-workshop/certification";
+const RESULT_DOMAIN: &[u8] = b"fhe-workshop/result";
+
+#[derive(Debug)]
+pub enum CertificationError {
+    UnsupportedVersion(u32),
+    Json(serde_json::Error),
+    // A signature from a trustee that didn't contribute a decryption share.
+    NotContributor(PartyId),
+    DuplicateSignature(PartyId),
+    BadSignature(PartyId),
+    // The trustee signed with a key other than theirs on the roster.
+    UnexpectedSigner(PartyId),
+    Quorum { signed: usize, required: usize },
+    // A quorum of zero, which any bundle would meet without a single signature.
+    NoQuorum,
+    // The trustees signed for a lower quorum than the reader requires.
+    LowQuorum { quorum: usize, required: usize },
+    // The key of this trustee on the roster isn't an Ed25519 key.
+    BadRosterKey(PartyId),
+}
+
+impl fmt::Display for CertificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificationError::UnsupportedVersion(v) => write!(
+                f,
+                "certification version {v} is not supported, expected {CERTIFICATION_VERSION}"
+            ),
+            CertificationError::Json(e) => write!(f, "{e}"),
+            CertificationError::NotContributor(trustee) => write!(
+                f,
+                "trustee {trustee} signed the result without contributing a decryption share"
+            ),
+            CertificationError::DuplicateSignature(trustee) => {
+                write!(f, "trustee {trustee} signed the result twice")
+            }
+            CertificationError::BadSignature(trustee) => {
+                write!(f, "the signature of trustee {trustee} is invalid")
+            }
+            CertificationError::UnexpectedSigner(trustee) => {
+                write!(f, "trustee {trustee} signed with a key that isn't theirs")
+            }
+            CertificationError::Quorum { signed, required } => write!(
+                f,
+                "{signed} trustees signed the result, {required} are needed to certify it"
+            ),
+            CertificationError::NoQuorum => {
+                write!(f, "a quorum of zero trustees can't certify a result")
+            }
+            CertificationError::LowQuorum { quorum, required } => write!(
+                f,
+                "the result was signed for a quorum of {quorum} trustees, {required} are needed"
+            ),
+            CertificationError::BadRosterKey(trustee) => {
+                write!(f, "the key of trustee {trustee} on the roster is invalid")
+            }
+        }
+    }
+}
+
+impl Error for CertificationError {}
+
+impl From<serde_json::Error> for CertificationError {
+    fn from(e: serde_json::Error) -> Self {
+        CertificationError::Json(e)
+    }
+}
+
+// A trustee's signature over a certification or a result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultSignature {
+    pub trustee: PartyId,
+    #[serde(with = "hex::serde")]
+    pub signer: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Certification {
+    pub version: u32,
+    pub result: Vec<u64>,
+    #[serde(with = "hex::serde")]
+    pub params_hash: Hash,
+    #[serde(with = "hex::serde")]
+    pub public_key_hash: Hash,
+    #[serde(with = "hex::serde")]
+    pub ballots_root: Hash,
+    pub num_ballots: usize,
+    #[serde(with = "hex::serde")]
+    pub tally_hash: TallyHash,
+    pub rerandomized: bool,
+    // The trustees who contributed decryption shares, sorted.
+    pub contributors: Vec<PartyId>,
+    // How many of them have to sign for the result to be certified, as the trustees signed it.
+    // The reader checks against the quorum it requires itself, see `certify`.
+    pub quorum: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_option")]
+    pub receipt_digest: Option<Hash>,
+    pub signatures: Vec<ResultSignature>,
+}
+
+impl Certification {
+    // A certification nobody has signed yet.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        result: &[u64],
+        params: &BfvParameters,
+        public_key: &PublicKey,
+        ballots_root: Hash,
+        num_ballots: usize,
+        tally: &Ciphertext,
+        rerandomized: bool,
+        contributors: impl IntoIterator<Item = PartyId>,
+        quorum: usize,
+    ) -> Self {
+        let contributors: BTreeSet<PartyId> = contributors.into_iter().collect();
+        Certification {
+            version: CERTIFICATION_VERSION,
+            result: result.to_vec(),
+            params_hash: journal::params_hash(params),
+            public_key_hash: transparency::artifact_hash(&public_key.to_bytes()),
+            ballots_root,
+            num_ballots,
+            tally_hash: decryption_ceremony::tally_hash(tally),
+            rerandomized,
+            contributors: contributors.into_iter().collect(),
+            quorum,
+            receipt_digest: None,
+            signatures: Vec::new(),
+        }
+    }
+
+    // Adds the digest of the zkVM receipt of the tally. It has to be added before anyone signs.
+    pub fn with_receipt(mut self, digest: Hash) -> Self {
+        self.receipt_digest = Some(digest);
+        self
+    }
+
+    // The hash the trustees sign: every field but the signatures, in order.
+    pub fn hash(&self) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update(SIGNATURE_DOMAIN);
+        hasher.update(self.version.to_le_bytes());
+        hasher.update((self.result.len() as u64).to_le_bytes());
+        for count in &self.result {
+            hasher.update(count.to_le_bytes());
+        }
+        hasher.update(self.params_hash);
+        hasher.update(self.public_key_hash);
+        hasher.update(self.ballots_root);
+        hasher.update((self.num_ballots as u64).to_le_bytes());
+        hasher.update(self.tally_hash);
+        hasher.update([self.rerandomized as u8]);
+        hasher.update((self.contributors.len() as u64).to_le_bytes());
+        for trustee in &self.contributors {
+            hasher.update((*trustee as u64).to_le_bytes());
+        }
+        hasher.update((self.quorum as u64).to_le_bytes());
+        match &self.receipt_digest {
+            Some(digest) => {
+                hasher.update([1]);
+                hasher.update(digest);
+            }
+            None => hasher.update([0]),
+        }
+        hasher.finalize().into()
+    }
+
+    // Adds the trustee's signature.
+    pub fn sign(&mut self, trustee: PartyId, key: &SigningKey) {
+        let hash: Hash = self.hash();
+        self.signatures
+            .push(ResultSignature::new(trustee, &hash, key));
+    }
+
+    // Checks every signature is by a contributor, only one per trustee, and valid for the key
+    // it names.
+    pub fn verify_signatures(&self) -> Result<(), CertificationError> {
+        for signature in &self.signatures {
+            if self.contributors.binary_search(&signature.trustee).is_err() {
+                return Err(CertificationError::NotContributor(signature.trustee));
+            }
+        }
+        verify_signatures(&self.hash(), &self.signatures)
+    }
+
+    // Checks the signatures, each against the key of its trustee on the roster, and that at
+    // least `quorum` of the trustees signed, as the reader requires rather than as the bundle
+    // says.
+    pub fn certify(
+        &self,
+        roster: &[VerifyingKey],
+        quorum: usize,
+    ) -> Result<(), CertificationError> {
+        self.verify_signatures()?;
+        check_roster(&self.signatures, roster)?;
+        if self.quorum < quorum {
+            return Err(CertificationError::LowQuorum {
+                quorum: self.quorum,
+                required: quorum,
+            });
+        }
+        check_quorum(&self.signatures, quorum)
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<Self, CertificationError> {
+        let certification: Certification = serde_json::from_slice(bytes)?;
+        if certification.version != CERTIFICATION_VERSION {
+            return Err(CertificationError::UnsupportedVersion(
+                certification.version,
+            ));
+        }
+        Ok(certification)
+    }
+}
+
+impl ResultSignature {
+    pub fn new(trustee: PartyId, hash: &Hash, key: &SigningKey) -> Self {
+        ResultSignature {
+            trustee,
+            signer: key.verifying_key().to_bytes(),
+            signature: key.sign(hash).to_vec(),
+        }
+    }
+
+    // Checks the signature is valid for the key it names.
+    pub fn verify(&self, hash: &Hash) -> Result<(), CertificationError> {
+        let signer: VerifyingKey = VerifyingKey::from_bytes(&self.signer)
+            .map_err(|_| CertificationError::BadSignature(self.trustee))?;
+        let signature: Signature = Signature::from_slice(&self.signature)
+            .map_err(|_| CertificationError::BadSignature(self.trustee))?;
+        signer
+            .verify(hash, &signature)
+            .map_err(|_| CertificationError::BadSignature(self.trustee))
+    }
+}
+
+// Checks every signature is valid for the key it names, and that no trustee signed twice.
+fn verify_signatures(
+    hash: &Hash,
+    signatures: &[ResultSignature],
+) -> Result<(), CertificationError> {
+    let mut signed: BTreeSet<PartyId> = BTreeSet::new();
+    for signature in signatures {
+        if !signed.insert(signature.trustee) {
+            return Err(CertificationError::DuplicateSignature(signature.trustee));
+        }
+        signature.verify(hash)?;
+    }
+    Ok(())
+}
+
+fn check_roster(
+    signatures: &[ResultSignature],
+    roster: &[VerifyingKey],
+) -> Result<(), CertificationError> {
+    match signatures.iter().find(|signature| {
+        roster
+            .get(signature.trustee)
+            .is_none_or(|key| key.as_bytes() != &signature.signer)
+    }) {
+        Some(signature) => Err(CertificationError::UnexpectedSigner(signature.trustee)),
+        None => Ok(()),
+    }
+}
+
+fn check_quorum(signatures: &[ResultSignature], quorum: usize) -> Result<(), CertificationError> {
+    if quorum == 0 {
+        return Err(CertificationError::NoQuorum);
+    }
+    if signatures.len() < quorum {
+        return Err(CertificationError::Quorum {
+            signed: signatures.len(),
+            required: quorum,
+        });
+    }
+    Ok(())
+}
+
+// The hash trustees without a coordinator sign: the hashes of the public key and tally they
+// came to, the quorum they certify it with, and the result.
+pub fn result_digest(
+    public_key_hash: &Hash,
+    tally_hash: &TallyHash,
+    quorum: usize,
+    result: &[u64],
+) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(RESULT_DOMAIN);
+    hasher.update(public_key_hash);
+    hasher.update(tally_hash);
+    hasher.update((quorum as u64).to_le_bytes());
+    hasher.update((result.len() as u64).to_le_bytes());
+    for count in result {
+        hasher.update(count.to_le_bytes());
+    }
+    hasher.finalize().into()
+}
+
+// A result signed by a quorum of the trustees who came to it, see `result_digest`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultCertificate {
+    pub version: u32,
+    pub result: Vec<u64>,
+    #[serde(with = "hex::serde")]
+    pub public_key_hash: Hash,
+    #[serde(with = "hex::serde")]
+    pub tally_hash: TallyHash,
+    // The quorum the trustees signed for. The reader checks against its own, see `certify`.
+    pub quorum: usize,
+    pub signatures: Vec<ResultSignature>,
+}
+
+impl ResultCertificate {
+    pub fn digest(&self) -> Hash {
+        result_digest(
+            &self.public_key_hash,
+            &self.tally_hash,
+            self.quorum,
+            &self.result,
+        )
+    }
+
+    // Checks the signatures, against the trustees' keys on the roster if there is one, and
+    // that at least `quorum` of the trustees signed, as the reader requires, e.g. the threshold
+    // of the election. A certificate signed for a lower quorum is refused.
+    pub fn certify(
+        &self,
+        roster: Option<&[VerifyingKey]>,
+        quorum: usize,
+    ) -> Result<(), CertificationError> {
+        verify_signatures(&self.digest(), &self.signatures)?;
+        if let Some(roster) = roster {
+            check_roster(&self.signatures, roster)?;
+        }
+        if self.quorum < quorum {
+            return Err(CertificationError::LowQuorum {
+                quorum: self.quorum,
+                required: quorum,
+            });
+        }
+        check_quorum(&self.signatures, quorum)
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<Self, CertificationError> {
+        let certificate: ResultCertificate = serde_json::from_slice(bytes)?;
+        if certificate.version != CERTIFICATION_VERSION {
+            return Err(CertificationError::UnsupportedVersion(certificate.version));
+        }
+        Ok(certificate)
+    }
+}
+
+// The roster of the trustees' keys as JSON, a list of hex keys with trustee `i`'s at `i`.
+pub fn roster_to_json(roster: &[VerifyingKey]) -> Vec<u8> {
+    let keys: Vec<String> = roster
+        .iter()
+        .map(|key| hex::encode(key.as_bytes()))
+        .collect();
+    serde_json::to_vec_pretty(&keys).unwrap()
+}
+
+pub fn roster_from_json(bytes: &[u8]) -> Result<Vec<VerifyingKey>, CertificationError> {
+    let keys: Vec<String> = serde_json::from_slice(bytes)?;
+    keys.iter()
+        .enumerate()
+        .map(|(trustee, key)| {
+            <[u8; 32]>::from_hex(key)
+                .ok()
+                .and_then(|key| VerifyingKey::from_bytes(&key).ok())
+                .ok_or(CertificationError::BadRosterKey(trustee))
+        })
+        .collect()
+}
+
+// An optional hash as hex, like the others.
+mod hex_option {
+    use crate::merkle::Hash;
+    use hex::FromHex;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &Option<Hash>, serializer: S) -> Result<S::Ok, S::Error> {
+        hash.map(hex::encode).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Hash>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|hash| <Hash as FromHex>::from_hex(hash).map_err(de::Error::custom))
+            .transpose()
+    }
+}
@@ -0,0 +1,85 @@
+// Benaloh challenges: checking a voter's client encrypted what the voter asked it to.
+//
+// A voter can't look inside their own ballot, so a client that encrypts a different vote goes
+// unnoticed. The challenge turns this around: after the client has encrypted the ballot, and
+// before it is cast, the voter chooses to either cast it or spoil it. A spoiled ballot is opened:
+// the client reveals the vote and the randomness it encrypted it with, and anyone can encrypt
+// the vote again with the same randomness and check they get the same ciphertext, see
+// `Opening::verify`. The voter then has the client encrypt afresh and chooses again. A client
+// that cheats doesn't know which ballots will be opened, so it is caught with every challenge
+// it faces.
+//
+// fhe.rs draws all the randomness of an encryption from the generator it is given, so the
+// randomness is a 32-byte seed for a `StdRng`. The seed must stay secret unless the ballot is
+// spoiled: together with the ciphertext it gives the vote away.
+//
+// The ballot box takes the spoiled ballot, signed like any other, so that it is published and
+// can never be cast afterwards, see `BallotBox::spoil`. The opening is published next to it.
+
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, PublicKey};
+use fhe_traits::{FheEncoder, FheEncrypter, Serialize as FheSerialize};
+use rand::{rngs::StdRng, CryptoRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub type EncryptionSeed = [u8; 32];
+
+// What a spoiled ballot encrypted, and the randomness it was encrypted with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Opening {
+    // The slots of the vote, encoded as polynomials like every ballot's.
+    pub slots: Vec<u64>,
+    #[serde(with = "hex::serde")]
+    pub seed: EncryptionSeed,
+}
+
+impl Opening {
+    // Encrypts the vote again with the revealed randomness.
+    pub fn reencrypt(
+        &self,
+        pk: &PublicKey,
+        params: &Arc<BfvParameters>,
+    ) -> Result<Ciphertext, fhe::Error> {
+        encrypt_with_seed(&self.slots, self.seed, pk, params)
+    }
+
+    // Whether the ciphertext is the encryption of the vote with the revealed randomness.
+    pub fn verify(
+        &self,
+        ciphertext: &Ciphertext,
+        pk: &PublicKey,
+        params: &Arc<BfvParameters>,
+    ) -> Result<bool, fhe::Error> {
+        Ok(self.reencrypt(pk, params)?.to_bytes() == ciphertext.to_bytes())
+    }
+}
+
+fn encrypt_with_seed(
+    slots: &[u64],
+    seed: EncryptionSeed,
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+) -> Result<Ciphertext, fhe::Error> {
+    let pt: Plaintext = Plaintext::try_encode(slots, Encoding::poly(), params)?;
+    pk.try_encrypt(&pt, &mut StdRng::from_seed(seed))
+}
+
+// Encrypts a vote, given as its slots, keeping the randomness so the ballot can be opened if
+// the voter spoils it. If it is cast instead, the opening must be thrown away.
+pub fn encrypt_challengeable<R: RngCore + CryptoRng>(
+    slots: &[u64],
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<(Ciphertext, Opening), fhe::Error> {
+    let mut seed: EncryptionSeed = [0; 32];
+    rng.fill_bytes(&mut seed);
+    let ciphertext: Ciphertext = encrypt_with_seed(slots, seed, pk, params)?;
+    Ok((
+        ciphertext,
+        Opening {
+            slots: slots.to_vec(),
+            seed,
+        },
+    ))
+}
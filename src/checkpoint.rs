@@ -0,0 +1,394 @@
+// Crash recovery for the coordinator.
+//
+// With a checkpoint directory, the coordinator writes down its protocol state as it goes: the
+// phase it has reached, every ballot it accepted, the tally once it is published and every
+// decryption share that arrived. Each is written before the coordinator moves on, so wherever
+// the process is stopped, a restart with the same directory replays what was recorded and picks
+// up from there instead of starting the election over.
+//
+// The coordinator of the workshop simulates the parties and the voters, so their keys are not
+// received but generated. They are drawn from random generators derived from a seed kept in
+// the checkpoint, see `derive_rng`, so a restart generates the same keys, and the recorded
+// ballots and shares still fit them.
+//
+// The state is a small JSON file, replaced as a whole through a rename so that it is never half
+// written. Ballots and decryption shares are appended to files of records, each a
+// little-endian u64 length followed by the bytes, as in the segments of a `FileStore`. Like the
+// tally, they are written in the wire format, see `wire`. Records are written straight to the
+// file, so they survive the process being killed, but they are not synced to disk, so a power
+// cut can still lose the last few. A record cut short by the crash is dropped on restart, as if
+// it never arrived.
+//
+// With `--tally-chunk`, the tally of each precinct's store is summed a chunk at a time, and the
+// sum of each chunk is recorded as it is done, so a restart during the tally only sums the
+// chunks that weren't recorded yet. The store is refilled from the recorded ballots, in the same
+// order, so the chunks line up.
+//
+// Note: the seed is as secret as the keys derived from it. A checkpoint directory is only for a
+// coordinator that holds the keys of the parties anyway, as the workshop's does.
+
+use crate::{
+    ballot::{Ballot, BallotError},
+    decryption_ceremony::{self, DecryptionShareMessage},
+    journal,
+    merkle::Hash,
+    precinct::PrecinctId,
+    wire::{self, WireError},
+};
+use fhe::bfv::{BfvParameters, Ciphertext};
+use rand::{rngs::StdRng, CryptoRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// 2 since ballots, the tally and shares are recorded in the wire format, see `wire`.
+const VERSION: u32 = 2;
+const STATE_FILE: &str = "state.json";
+const BALLOTS_FILE: &str = "ballots.bin";
+const TALLY_FILE: &str = "tally.bin";
+const SHARES_FILE: &str = "shares.bin";
+const PARTIAL_SUMS_FILE: &str = "partial_sums.bin";
+// A partial sum record starts with its precinct, the chunk size and the chunk's index.
+const PARTIAL_SUM_HEADER: usize = 4 + 8 + 8;
+const RNG_DOMAIN: &[u8] = b"fhe-workshop/checkpoint-rng";
+
+pub type Seed = [u8; 32];
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Fhe(fhe::Error),
+    Ballot(BallotError),
+    Wire(WireError),
+    // The checkpoint was written by another version of the workshop.
+    Version(u32),
+    // The checkpoint is of an election with other parameters.
+    ParamsMismatch,
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "{e}"),
+            CheckpointError::Json(e) => write!(f, "{e}"),
+            CheckpointError::Fhe(e) => write!(f, "{e}"),
+            CheckpointError::Ballot(e) => write!(f, "{e}"),
+            CheckpointError::Wire(e) => write!(f, "{e}"),
+            CheckpointError::Version(version) => {
+                write!(f, "checkpoint version {version} is not supported")
+            }
+            CheckpointError::ParamsMismatch => {
+                write!(f, "the checkpoint is of an election with other parameters")
+            }
+        }
+    }
+}
+
+impl Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(e: serde_json::Error) -> Self {
+        CheckpointError::Json(e)
+    }
+}
+
+impl From<fhe::Error> for CheckpointError {
+    fn from(e: fhe::Error) -> Self {
+        CheckpointError::Fhe(e)
+    }
+}
+
+impl From<BallotError> for CheckpointError {
+    fn from(e: BallotError) -> Self {
+        CheckpointError::Ballot(e)
+    }
+}
+
+impl From<WireError> for CheckpointError {
+    fn from(e: WireError) -> Self {
+        CheckpointError::Wire(e)
+    }
+}
+
+// How far the coordinator got. Phases only ever move forward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    KeyGeneration,
+    Voting,
+    Tallying,
+    Decryption,
+    Done,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct State {
+    version: u32,
+    #[serde(with = "hex::serde")]
+    seed: Seed,
+    #[serde(with = "hex::serde")]
+    params_hash: Hash,
+    phase: Phase,
+}
+
+pub struct Checkpoint {
+    dir: PathBuf,
+    params: Arc<BfvParameters>,
+    state: State,
+    ballots: File,
+    shares: File,
+    partial_sums: File,
+    resumed: bool,
+}
+
+impl Checkpoint {
+    // Resumes from the checkpoint in the directory, or starts a new one with a fresh seed if
+    // there is none yet.
+    pub fn open<R: RngCore + CryptoRng>(
+        dir: impl Into<PathBuf>,
+        params: &Arc<BfvParameters>,
+        rng: &mut R,
+    ) -> Result<Self, CheckpointError> {
+        let dir: PathBuf = dir.into();
+        fs::create_dir_all(&dir)?;
+        let params_hash: Hash = journal::params_hash(params);
+        let resumed: bool = dir.join(STATE_FILE).exists();
+        let state: State = if resumed {
+            let state: State = serde_json::from_slice(&fs::read(dir.join(STATE_FILE))?)?;
+            if state.version != VERSION {
+                return Err(CheckpointError::Version(state.version));
+            }
+            if state.params_hash != params_hash {
+                return Err(CheckpointError::ParamsMismatch);
+            }
+            state
+        } else {
+            let mut seed: Seed = [0; 32];
+            rng.fill_bytes(&mut seed);
+            let state: State = State {
+                version: VERSION,
+                seed,
+                params_hash,
+                phase: Phase::KeyGeneration,
+            };
+            write_state(&dir, &state)?;
+            state
+        };
+        Ok(Checkpoint {
+            ballots: open_records(&dir.join(BALLOTS_FILE))?,
+            shares: open_records(&dir.join(SHARES_FILE))?,
+            partial_sums: open_records(&dir.join(PARTIAL_SUMS_FILE))?,
+            dir,
+            params: params.clone(),
+            state,
+            resumed,
+        })
+    }
+
+    // Whether the checkpoint was left by an earlier run.
+    pub fn resumed(&self) -> bool {
+        self.resumed
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.state.phase
+    }
+
+    pub fn seed(&self) -> &Seed {
+        &self.state.seed
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Moves on to the phase, unless the checkpoint is already there or further.
+    pub fn advance(&mut self, phase: Phase) -> Result<(), CheckpointError> {
+        if phase > self.state.phase {
+            self.state.phase = phase;
+            write_state(&self.dir, &self.state)?;
+        }
+        Ok(())
+    }
+
+    // The accepted ballots, in the order they were accepted.
+    pub fn ballots(&self) -> Result<Vec<Ballot>, CheckpointError> {
+        read_records(&self.dir.join(BALLOTS_FILE))?
+            .iter()
+            .map(|bytes| Ok(Ballot::from_bytes(bytes, &self.params)?))
+            .collect()
+    }
+
+    pub fn record_ballot(&mut self, ballot: &Ballot) -> Result<(), CheckpointError> {
+        append_record(&mut self.ballots, &ballot.to_bytes())
+    }
+
+    // The published tally, if the coordinator got that far.
+    pub fn tally(&self) -> Result<Option<Ciphertext>, CheckpointError> {
+        match fs::read(self.dir.join(TALLY_FILE)) {
+            Ok(bytes) => Ok(Some(wire::decode_tally(&bytes, &self.params)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn record_tally(&mut self, tally: &Ciphertext) -> Result<(), CheckpointError> {
+        replace_file(&self.dir.join(TALLY_FILE), &wire::encode_tally(tally))
+    }
+
+    // The chunk sums of the precinct's tally recorded so far, in order, see
+    // `tally::sum_store_chunked`. Only sums over chunks of `chunk_size` count, and only up to the
+    // first chunk missing.
+    pub fn partial_sums(
+        &self,
+        precinct: PrecinctId,
+        chunk_size: usize,
+    ) -> Result<Vec<Ciphertext>, CheckpointError> {
+        let mut sums: Vec<Ciphertext> = Vec::new();
+        for record in read_records(&self.dir.join(PARTIAL_SUMS_FILE))? {
+            if record.len() < PARTIAL_SUM_HEADER {
+                continue;
+            }
+            let (header, sum): (&[u8], &[u8]) = record.split_at(PARTIAL_SUM_HEADER);
+            let (id, rest): (&[u8], &[u8]) = header.split_at(4);
+            let (size, chunk): (&[u8], &[u8]) = rest.split_at(8);
+            if u32::from_le_bytes(id.try_into().unwrap()) == precinct
+                && u64::from_le_bytes(size.try_into().unwrap()) == chunk_size as u64
+                && u64::from_le_bytes(chunk.try_into().unwrap()) == sums.len() as u64
+            {
+                sums.push(wire::decode_tally(sum, &self.params)?);
+            }
+        }
+        Ok(sums)
+    }
+
+    pub fn record_partial_sum(
+        &mut self,
+        precinct: PrecinctId,
+        chunk_size: usize,
+        chunk: usize,
+        sum: &Ciphertext,
+    ) -> Result<(), CheckpointError> {
+        let mut record: Vec<u8> = Vec::with_capacity(PARTIAL_SUM_HEADER);
+        record.extend_from_slice(&precinct.to_le_bytes());
+        record.extend_from_slice(&(chunk_size as u64).to_le_bytes());
+        record.extend_from_slice(&(chunk as u64).to_le_bytes());
+        record.extend_from_slice(&wire::encode_tally(sum));
+        append_record(&mut self.partial_sums, &record)
+    }
+
+    // The decryption shares of the ciphertext that arrived, in the order they did. Shares of
+    // other ciphertexts, e.g. the turnout counter, are left out.
+    pub fn shares(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+    ) -> Result<Vec<DecryptionShareMessage>, CheckpointError> {
+        let tally_hash: Hash = decryption_ceremony::tally_hash(ciphertext);
+        let mut shares: Vec<DecryptionShareMessage> = Vec::new();
+        for record in read_records(&self.dir.join(SHARES_FILE))? {
+            let message: DecryptionShareMessage =
+                wire::decode_decryption_share(&record, &self.params, ciphertext.clone())?;
+            if message.tally_hash == tally_hash {
+                shares.push(message);
+            }
+        }
+        Ok(shares)
+    }
+
+    pub fn record_share(
+        &mut self,
+        message: &DecryptionShareMessage,
+    ) -> Result<(), CheckpointError> {
+        append_record(&mut self.shares, &wire::encode_decryption_share(message))
+    }
+}
+
+// A random generator of its own for each `label` and `index`, e.g. `("voter", 12)` for the
+// signing key of the twelfth voter, all derived from the seed.
+pub fn derive_rng(seed: &Seed, label: &str, index: u64) -> StdRng {
+    let mut hasher = Sha256::new();
+    hasher.update(RNG_DOMAIN);
+    hasher.update(seed);
+    hasher.update((label.len() as u64).to_le_bytes());
+    hasher.update(label.as_bytes());
+    hasher.update(index.to_le_bytes());
+    StdRng::from_seed(hasher.finalize().into())
+}
+
+fn write_state(dir: &Path, state: &State) -> Result<(), CheckpointError> {
+    replace_file(&dir.join(STATE_FILE), &serde_json::to_vec_pretty(state)?)
+}
+
+// Writes the file next to its place and renames it over, so it is either all there or not.
+fn replace_file(path: &Path, bytes: &[u8]) -> Result<(), CheckpointError> {
+    let partial: PathBuf = path.with_extension("partial");
+    fs::write(&partial, bytes)?;
+    fs::rename(&partial, path)?;
+    Ok(())
+}
+
+// Opens a file of records for appending, first cutting off a record left half written.
+fn open_records(path: &Path) -> Result<File, CheckpointError> {
+    let file: File = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .read(true)
+        .open(path)?;
+    let whole: u64 = whole_records(&fs::read(path)?).1 as u64;
+    if whole < file.metadata()?.len() {
+        file.set_len(whole)?;
+    }
+    Ok(file)
+}
+
+fn append_record(file: &mut File, bytes: &[u8]) -> Result<(), CheckpointError> {
+    // The length and the bytes go out in a single write.
+    let mut record: Vec<u8> = Vec::with_capacity(8 + bytes.len());
+    record.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    record.extend_from_slice(bytes);
+    file.write_all(&record)?;
+    Ok(())
+}
+
+fn read_records(path: &Path) -> Result<Vec<Vec<u8>>, CheckpointError> {
+    let bytes: Vec<u8> = fs::read(path)?;
+    Ok(whole_records(&bytes)
+        .0
+        .into_iter()
+        .map(<[u8]>::to_vec)
+        .collect())
+}
+
+// The whole records at the start of the bytes, and where they end.
+fn whole_records(bytes: &[u8]) -> (Vec<&[u8]>, usize) {
+    let mut records: Vec<&[u8]> = Vec::new();
+    let mut offset: usize = 0;
+    while let Some(len) = bytes.get(offset..offset + 8) {
+        let start: usize = offset + 8;
+        let end: Option<usize> = usize::try_from(u64::from_le_bytes(len.try_into().unwrap()))
+            .ok()
+            .and_then(|len| start.checked_add(len));
+        match end.and_then(|end| bytes.get(start..end)) {
+            Some(record) => {
+                records.push(record);
+                offset = start + record.len();
+            }
+            None => break,
+        }
+    }
+    (records, offset)
+}
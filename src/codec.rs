@@ -0,0 +1,95 @@
+// Framing for the serialized ciphertexts and keys that are written to disk or sent over the wire.
+//
+// Every blob starts with a four byte header: the magic bytes `FW`, a format version and the
+// compression applied to the payload. Readers go by the header, so decompression is automatic
+// and compressed and uncompressed blobs can be mixed freely. Compression needs the `zstd`
+// feature, reading an uncompressed blob doesn't.
+
+use std::{error::Error, fmt};
+
+const MAGIC: [u8; 2] = *b"FW";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 4;
+
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+}
+
+impl Compression {
+    fn tag(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zstd => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self, CodecError> {
+        match tag {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Zstd),
+            _ => Err(CodecError::UnknownCompression(tag)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    MissingHeader,
+    UnsupportedVersion(u8),
+    UnknownCompression(u8),
+    Unsupported(Compression),
+    Corrupt,
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::MissingHeader => write!(f, "blob doesn't start with a workshop header"),
+            CodecError::UnsupportedVersion(v) => write!(f, "unsupported format version {v}"),
+            CodecError::UnknownCompression(tag) => write!(f, "unknown compression {tag}"),
+            CodecError::Unsupported(c) => write!(f, "{c:?} compression is not enabled"),
+            CodecError::Corrupt => write!(f, "compressed payload is corrupt"),
+        }
+    }
+}
+
+impl Error for CodecError {}
+
+// Prefixes the payload with a header and compresses it as asked.
+pub fn encode(payload: &[u8], compression: Compression) -> Result<Vec<u8>, CodecError> {
+    let header: [u8; HEADER_LEN] = [MAGIC[0], MAGIC[1], VERSION, compression.tag()];
+    let body: Vec<u8> = match compression {
+        Compression::None => payload.to_vec(),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            zstd::encode_all(payload, ZSTD_LEVEL).map_err(|_| CodecError::Corrupt)?
+        }
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => return Err(CodecError::Unsupported(compression)),
+    };
+    Ok([header.as_slice(), &body].concat())
+}
+
+// Reads the header and returns the decompressed payload.
+pub fn decode(blob: &[u8]) -> Result<Vec<u8>, CodecError> {
+    if blob.len() < HEADER_LEN || blob[..2] != MAGIC {
+        return Err(CodecError::MissingHeader);
+    }
+    if blob[2] != VERSION {
+        return Err(CodecError::UnsupportedVersion(blob[2]));
+    }
+    let body: &[u8] = &blob[HEADER_LEN..];
+    match Compression::from_tag(blob[3])? {
+        Compression::None => Ok(body.to_vec()),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd::decode_all(body).map_err(|_| CodecError::Corrupt),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => Err(CodecError::Unsupported(Compression::Zstd)),
+    }
+}
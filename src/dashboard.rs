@@ -0,0 +1,234 @@
+// A terminal dashboard for the demo, shown with `--tui` in place of the log lines and progress
+// bars, e.g. on a projector during the workshop.
+//
+// It is drawn from a thread of its own while the election runs: a gauge for each phase with its
+// throughput, the elapsed time and peak memory use, the latest log lines and, once decrypted,
+// the result. The gauges follow the progress bars handed out by `progress_bar`, which are
+// hidden and tracked here instead, and the log lines come in through `log_writer`.
+
+use indicatif::ProgressBar;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    text::Line,
+    widgets::{BarChart, Block, Gauge, Paragraph},
+    DefaultTerminal, Frame,
+};
+use std::{
+    collections::VecDeque,
+    io, process,
+    sync::{Mutex, OnceLock},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+// How many of the latest log lines are kept.
+const LOG_LINES: usize = 64;
+const REFRESH: Duration = Duration::from_millis(100);
+
+static DASHBOARD: OnceLock<Dashboard> = OnceLock::new();
+
+struct Dashboard {
+    started: Instant,
+    // Set once the run is over, stopping the clock.
+    finished: OnceLock<Duration>,
+    phases: Mutex<Vec<ProgressBar>>,
+    logs: Mutex<VecDeque<String>>,
+    result: Mutex<Option<Vec<(String, u64)>>>,
+}
+
+// Takes over the terminal and draws the dashboard until the run is finished and a key pressed.
+pub fn start() -> JoinHandle<io::Result<()>> {
+    let dashboard: &Dashboard = DASHBOARD.get_or_init(|| Dashboard {
+        started: Instant::now(),
+        finished: OnceLock::new(),
+        phases: Mutex::new(Vec::new()),
+        logs: Mutex::new(VecDeque::new()),
+        result: Mutex::new(None),
+    });
+    thread::spawn(move || {
+        let mut terminal: DefaultTerminal = ratatui::init();
+        let result: io::Result<()> = dashboard.run(&mut terminal);
+        ratatui::restore();
+        result
+    })
+}
+
+pub fn is_active() -> bool {
+    DASHBOARD.get().is_some()
+}
+
+// Shows a gauge for the phase the progress bar follows.
+pub fn track(pb: &ProgressBar) {
+    if let Some(dashboard) = DASHBOARD.get() {
+        dashboard.phases.lock().unwrap().push(pb.clone());
+    }
+}
+
+// Shows the decrypted result, as a count for each label.
+pub fn show_result(result: Vec<(String, u64)>) {
+    if let Some(dashboard) = DASHBOARD.get() {
+        *dashboard.result.lock().unwrap() = Some(result);
+    }
+}
+
+// Stops the clock and waits for a key press to give the terminal back.
+pub fn finish(handle: JoinHandle<io::Result<()>>) -> io::Result<()> {
+    if let Some(dashboard) = DASHBOARD.get() {
+        let _ = dashboard.finished.set(dashboard.started.elapsed());
+    }
+    handle.join().unwrap()
+}
+
+// Hands log lines to the dashboard, for `tracing_subscriber`.
+pub fn log_writer() -> LogWriter {
+    LogWriter(Vec::new())
+}
+
+pub struct LogWriter(Vec<u8>);
+
+impl io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// A log event is written in one go, and handed over once the writer is dropped.
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        let Some(dashboard) = DASHBOARD.get() else {
+            return;
+        };
+        let mut logs = dashboard.logs.lock().unwrap();
+        for line in String::from_utf8_lossy(&self.0).lines() {
+            if logs.len() == LOG_LINES {
+                logs.pop_front();
+            }
+            logs.push_back(line.to_string());
+        }
+    }
+}
+
+impl Dashboard {
+    fn run(&self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(REFRESH)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            // The terminal is in raw mode, so Ctrl-C arrives as a key rather than a signal.
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                ratatui::restore();
+                process::exit(130);
+            }
+            if self.finished.get().is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let phases = self.phases.lock().unwrap();
+        let [header, gauges, bottom, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(phases.len().min(12) as u16 + 2),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [result, logs] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(bottom);
+
+        let elapsed: Duration = self
+            .finished
+            .get()
+            .copied()
+            .unwrap_or_else(|| self.started.elapsed());
+        let memory: String = match crate::peak_rss() {
+            Some(bytes) => format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+            None => "n/a".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(format!(
+                "elapsed {:.1}s   peak memory {memory}",
+                elapsed.as_secs_f64()
+            ))
+            .block(Block::bordered().title(" Practical FHE Workshop: Secret Ballot ")),
+            header,
+        );
+
+        self.draw_phases(frame, &phases, gauges);
+        drop(phases);
+        self.draw_result(frame, result);
+
+        let logs_block: Block = Block::bordered().title(" log ");
+        let height: usize = logs_block.inner(logs).height as usize;
+        let lines: Vec<Line> = {
+            let logs = self.logs.lock().unwrap();
+            logs.iter()
+                .skip(logs.len().saturating_sub(height))
+                .map(|line| Line::raw(line.clone()))
+                .collect()
+        };
+        frame.render_widget(Paragraph::new(lines).block(logs_block), logs);
+
+        let hint: &str = if self.finished.get().is_some() {
+            "done, press any key to exit"
+        } else {
+            "running, Ctrl-C to abort"
+        };
+        frame.render_widget(Paragraph::new(hint), footer);
+    }
+
+    // A gauge per phase, the latest at the bottom, with as many as fit.
+    fn draw_phases(&self, frame: &mut Frame, phases: &[ProgressBar], area: Rect) {
+        let block: Block = Block::bordered().title(" phases ");
+        let inner: Rect = block.inner(area);
+        frame.render_widget(block, area);
+        let shown: &[ProgressBar] = &phases[phases.len().saturating_sub(inner.height as usize)..];
+        let rows = Layout::vertical(vec![Constraint::Length(1); shown.len()]).split(inner);
+        for (pb, row) in shown.iter().zip(rows.iter()) {
+            let len: u64 = pb.length().unwrap_or(0).max(1);
+            let ratio: f64 = (pb.position() as f64 / len as f64).min(1.0);
+            let label: String = format!(
+                "{:>12} {}/{} {:.0}/s {:.1}s",
+                pb.message(),
+                pb.position(),
+                len,
+                pb.per_sec(),
+                pb.elapsed().as_secs_f64()
+            );
+            frame.render_widget(Gauge::default().ratio(ratio).label(label), *row);
+        }
+    }
+
+    fn draw_result(&self, frame: &mut Frame, area: Rect) {
+        let block: Block = Block::bordered().title(" result ");
+        match &*self.result.lock().unwrap() {
+            Some(result) => {
+                let data: Vec<(&str, u64)> = result
+                    .iter()
+                    .map(|(label, count)| (label.as_str(), *count))
+                    .collect();
+                frame.render_widget(
+                    BarChart::default()
+                        .block(block)
+                        .data(&data)
+                        .bar_width(9)
+                        .bar_gap(2),
+                    area,
+                );
+            }
+            None => frame.render_widget(Paragraph::new("not decrypted yet").block(block), area),
+        }
+    }
+}
@@ -0,0 +1,497 @@
+// The threshold decryption of the tally, collecting decryption shares one at a time.
+//
+// Each trustee sends a decryption share of the published tally for every key slot they hold
+// (see `threshold`). The ceremony keeps track of who has contributed and only decrypts once
+// every slot is covered by at least one share.
+//
+// A trustee can also send garbage. Since every ballot only fills the first few slots of the
+// plaintext, the rest of the decrypted tally must be zero, and a bad share turns it into noise.
+// When that happens and slots are held by more than one trustee, the ceremony looks for the
+// smallest set of trustees whose exclusion gives a clean result, then checks the shares of
+// every trustee against it and leaves out those that break it. With the
+// plain additive scheme every share is needed, so all it can say is that some share is bad.
+//
+// Note: only noise in the padding gives a bad share away. A trustee who shifts its share by a
+// multiple of the plaintext scale in the first `len` slots alone moves the counts and leaves the
+// padding at zero, so the result looks clean and is simply wrong, and nobody is excluded. Only
+// a proof that each share was computed with the trustee's key share, which this crate doesn't
+// have, would catch that. Shares made with the wrong key, as `--byzantine` simulates, are
+// caught.
+//
+// The padding is only zero if every ballot's is. With a validity check that doesn't look at it,
+// `TrustClient` or the `TrustedProof` stub, one ballot with nonzero padding makes the tally
+// garbage whatever the shares. Nobody is blamed for that: when every slot is held by more than
+// one trustee and their good shares agree on the garbage, the ceremony fails with `InvalidTally`,
+// and otherwise, e.g. with every trustee needed, with `InvalidResult`, which can mean either.
+//
+// When the same key decrypts the results of several rounds, see `rounds`, the ceremony and its
+// shares can be bound to a round with `with_round`: the tally hash they carry is then hashed
+// with the round number, so a share sent for one round is refused by the ceremony of another,
+// even for the same ciphertext.
+
+use crate::{
+    key_ceremony::{CeremonyError, PartyId},
+    party::Party,
+    rounds::RoundId,
+    signer::{SignerError, TrusteeSigner},
+    tally,
+    threshold::{self, TrusteeSet},
+};
+use fhe::{
+    bfv::{Ciphertext, Encoding},
+    mbfv::DecryptionShare,
+};
+use fhe_traits::Serialize;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::Arc,
+};
+
+pub type TallyHash = [u8; 32];
+
+const ROUND_DOMAIN: &[u8] = b"fhe-workshop/round";
+
+// Give up looking for faulty trustees after trying this many combinations.
+const MAX_EXCLUSION_ATTEMPTS: usize = 10_000;
+
+pub fn tally_hash(tally: &Ciphertext) -> TallyHash {
+    Sha256::digest(tally.to_bytes()).into()
+}
+
+// The tally hash of a ceremony bound to a round.
+pub fn round_hash(round: RoundId, tally_hash: &TallyHash) -> TallyHash {
+    let mut hasher = Sha256::new();
+    hasher.update(ROUND_DOMAIN);
+    hasher.update(round.to_le_bytes());
+    hasher.update(tally_hash);
+    hasher.finalize().into()
+}
+
+// Sent by a trustee for each key slot they hold, in reply to the published tally.
+#[derive(Clone)]
+pub struct DecryptionShareMessage {
+    pub slot: usize,
+    pub trustee: PartyId,
+    pub tally_hash: TallyHash,
+    pub share: DecryptionShare,
+}
+
+impl DecryptionShareMessage {
+    pub fn new<R: RngCore + CryptoRng>(
+        slot: usize,
+        trustee: PartyId,
+        key: &Party,
+        tally: &Arc<Ciphertext>,
+        rng: &mut R,
+    ) -> Result<Self, fhe::Error> {
+        Ok(DecryptionShareMessage {
+            slot,
+            trustee,
+            tally_hash: tally_hash(tally),
+            share: key.decryption_share(tally, rng)?,
+        })
+    }
+
+    // The same, with the share computed by the trustee's signer, see `signer.rs`.
+    pub fn from_signer(
+        slot: usize,
+        trustee: PartyId,
+        signer: &dyn TrusteeSigner,
+        tally: &Arc<Ciphertext>,
+    ) -> Result<Self, SignerError> {
+        Ok(DecryptionShareMessage {
+            slot,
+            trustee,
+            tally_hash: tally_hash(tally),
+            share: signer.decryption_share(tally)?,
+        })
+    }
+
+    // Binds the share to a round, see `DecryptionCeremony::with_round`.
+    pub fn with_round(mut self, round: RoundId) -> Self {
+        self.tally_hash = round_hash(round, &self.tally_hash);
+        self
+    }
+}
+
+// How many key slots are covered out of how many are needed, e.g. for a progress bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub received: usize,
+    pub required: usize,
+}
+
+impl Progress {
+    pub fn is_complete(&self) -> bool {
+        self.received >= self.required
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.received, self.required)
+    }
+}
+
+pub struct DecryptionCeremony {
+    tally_hash: TallyHash,
+    holders: Vec<Vec<PartyId>>,
+    shares: BTreeMap<(usize, PartyId), DecryptionShare>,
+    // How the plaintext is decoded, see `with_encoding`.
+    encoding: Encoding,
+    excluded: BTreeSet<PartyId>,
+    // The trustee whose share was used for each slot, once decrypted.
+    assignment: Option<Vec<PartyId>>,
+    result: Option<Vec<u64>>,
+}
+
+impl DecryptionCeremony {
+    // A ceremony decrypting `tally` between the trustees of the key ceremony.
+    pub fn new(tally: &Ciphertext, trustees: &TrusteeSet) -> Self {
+        DecryptionCeremony {
+            tally_hash: tally_hash(tally),
+            holders: (0..trustees.num_slots())
+                .map(|slot| trustees.holders(slot).to_vec())
+                .collect(),
+            shares: BTreeMap::new(),
+            encoding: Encoding::poly(),
+            excluded: BTreeSet::new(),
+            assignment: None,
+            result: None,
+        }
+    }
+
+    // Decodes the plaintext with `encoding` rather than as a polynomial. With `Encoding::simd()`
+    // the slots past `len` that must be zero are the SIMD slots.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    // Only takes shares bound to the round, see `DecryptionShareMessage::with_round`.
+    pub fn with_round(mut self, round: RoundId) -> Self {
+        self.tally_hash = round_hash(round, &self.tally_hash);
+        self
+    }
+
+    // Checks that the share is for this tally, from a trustee holding the slot, and that they
+    // haven't already sent one for it.
+    pub fn receive(&mut self, message: DecryptionShareMessage) -> Result<Progress, CeremonyError> {
+        let holders: &[PartyId] =
+            self.holders
+                .get(message.slot)
+                .ok_or(CeremonyError::UnknownSlot {
+                    trustee: message.trustee,
+                    slot: message.slot,
+                })?;
+        if !holders.contains(&message.trustee) {
+            return Err(CeremonyError::NotHolder {
+                trustee: message.trustee,
+                slot: message.slot,
+            });
+        }
+        if self.shares.contains_key(&(message.slot, message.trustee)) {
+            return Err(CeremonyError::DuplicateShare(message.trustee));
+        }
+        if message.tally_hash != self.tally_hash {
+            return Err(CeremonyError::TallyMismatch(message.trustee));
+        }
+        self.shares
+            .insert((message.slot, message.trustee), message.share);
+        Ok(self.progress())
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            received: self.assign(&self.excluded).iter().flatten().count(),
+            required: self.holders.len(),
+        }
+    }
+
+    // The trustees whose shares have been accepted, in order.
+    pub fn contributors(&self) -> BTreeSet<PartyId> {
+        self.shares.keys().map(|&(_, trustee)| trustee).collect()
+    }
+
+    // The trustees left out for sending bad shares.
+    pub fn excluded(&self) -> &BTreeSet<PartyId> {
+        &self.excluded
+    }
+
+    // The shares that went into the decrypted result, one per slot, to be published with it.
+    pub fn shares_used(&self) -> Vec<&DecryptionShare> {
+        self.assignment
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(slot, &trustee)| &self.shares[&(slot, trustee)])
+            .collect()
+    }
+
+    // The same, handing the shares over rather than cloning them once the ceremony is done, as
+    // the messages they came in.
+    pub fn into_shares_used(mut self) -> Vec<DecryptionShareMessage> {
+        let tally_hash: TallyHash = self.tally_hash;
+        self.assignment
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter_map(|(slot, &trustee)| {
+                let share: DecryptionShare = self.shares.remove(&(slot, trustee))?;
+                Some(DecryptionShareMessage {
+                    slot,
+                    trustee,
+                    tally_hash,
+                    share,
+                })
+            })
+            .collect()
+    }
+
+    // Aggregates a share for every slot and decodes the first `len` slots of the tally, once
+    // every slot is covered, singling out the trustees that sent bad shares along the way.
+    // Later calls return the same result.
+    pub fn decrypt(&mut self, len: usize) -> Result<&[u64], CeremonyError> {
+        let progress: Progress = self.progress();
+        if !progress.is_complete() {
+            return Err(CeremonyError::MissingShares {
+                received: progress.received,
+                required: progress.required,
+            });
+        }
+        if self.result.is_none() {
+            self.result = Some(self.decrypt_excluding(len)?);
+        }
+        Ok(self.result.as_deref().unwrap())
+    }
+
+    fn decrypt_excluding(&mut self, len: usize) -> Result<Vec<u64>, CeremonyError> {
+        let (assignment, result) = self.find_clean_assignment(len)?;
+        // Every share used in a clean decryption is good. Check the unused shares of each
+        // trustee by swapping them in, one trustee at a time.
+        for trustee in self.contributors() {
+            let mut swapped: Vec<PartyId> = assignment.clone();
+            for (slot, holder) in swapped.iter_mut().enumerate() {
+                if self.shares.contains_key(&(slot, trustee)) {
+                    *holder = trustee;
+                }
+            }
+            if swapped != assignment && self.try_decrypt(&swapped, len)?.is_none() {
+                self.excluded.insert(trustee);
+            }
+        }
+        self.assignment = Some(assignment);
+        Ok(result)
+    }
+
+    // Leaves out ever larger sets of trustees until the shares of the others decrypt cleanly.
+    //
+    // If none do, either the bad shares couldn't be singled out or the tally itself has nonzero
+    // padding, e.g. from a ballot that wasn't checked. The two can only be told apart when every
+    // slot was decrypted with the shares of at least two trustees: good shares of a bad tally
+    // all decrypt to the same garbage, while a bad share changes it.
+    fn find_clean_assignment(&self, len: usize) -> Result<(Vec<PartyId>, Vec<u64>), CeremonyError> {
+        let contributors: Vec<PartyId> = self.contributors().into_iter().collect();
+        let mut attempts: usize = 0;
+        // The first garbage decrypted, whether every decryption since gave the same, and the
+        // trustees whose shares were used for each slot.
+        let mut garbage: Option<Vec<u64>> = None;
+        let mut consistent: bool = true;
+        let mut used: Vec<BTreeSet<PartyId>> = vec![BTreeSet::new(); self.holders.len()];
+        for size in 0..contributors.len() {
+            for group in threshold::combinations(contributors.len(), size) {
+                attempts += 1;
+                if attempts > MAX_EXCLUSION_ATTEMPTS {
+                    return Err(CeremonyError::InvalidResult);
+                }
+                let excluded: BTreeSet<PartyId> = group.iter().map(|&i| contributors[i]).collect();
+                let Some(assignment) = self
+                    .assign(&excluded)
+                    .into_iter()
+                    .collect::<Option<Vec<PartyId>>>()
+                else {
+                    continue;
+                };
+                let mut slots: Vec<u64> = self.decrypt_with(&assignment)?;
+                if is_clean(&slots, len) {
+                    slots.truncate(len);
+                    return Ok((assignment, slots));
+                }
+                for (trustees, &trustee) in used.iter_mut().zip(&assignment) {
+                    trustees.insert(trustee);
+                }
+                match &garbage {
+                    Some(first) => consistent &= *first == slots,
+                    None => garbage = Some(slots),
+                }
+            }
+        }
+        if consistent && used.iter().all(|trustees| trustees.len() > 1) {
+            return Err(CeremonyError::InvalidTally);
+        }
+        Err(CeremonyError::InvalidResult)
+    }
+
+    // Decrypts with the share of the given trustee for each slot, or `None` if the padding
+    // past `len` isn't zero.
+    fn try_decrypt(
+        &self,
+        assignment: &[PartyId],
+        len: usize,
+    ) -> Result<Option<Vec<u64>>, CeremonyError> {
+        let mut slots: Vec<u64> = self.decrypt_with(assignment)?;
+        if !is_clean(&slots, len) {
+            return Ok(None);
+        }
+        slots.truncate(len);
+        Ok(Some(slots))
+    }
+
+    // Decrypts every slot of the plaintext, padding included.
+    fn decrypt_with(&self, assignment: &[PartyId]) -> Result<Vec<u64>, CeremonyError> {
+        let shares = assignment
+            .iter()
+            .enumerate()
+            .map(|(slot, &trustee)| self.shares[&(slot, trustee)].clone());
+        Ok(tally::decrypt_encoded(shares, self.encoding.clone())?)
+    }
+
+    // For every slot, the first trustee outside `excluded` who sent a share for it.
+    fn assign(&self, excluded: &BTreeSet<PartyId>) -> Vec<Option<PartyId>> {
+        self.holders
+            .iter()
+            .enumerate()
+            .map(|(slot, holders)| {
+                holders.iter().copied().find(|trustee| {
+                    !excluded.contains(trustee) && self.shares.contains_key(&(slot, *trustee))
+                })
+            })
+            .collect()
+    }
+}
+
+// Whether the padding past `len` is zero.
+fn is_clean(slots: &[u64], len: usize) -> bool {
+    slots.iter().skip(len).all(|&coefficient| coefficient == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{params, party};
+    use fhe::{
+        bfv::{BfvParameters, Plaintext, PublicKey},
+        mbfv::{CommonRandomPoly, PublicKeyShare},
+    };
+    use fhe_traits::{FheEncoder, FheEncrypter};
+    use rand::thread_rng;
+
+    // A ceremony decrypting an encryption of `slots` under a `threshold`-of-`num_trustees` key,
+    // with a share from every trustee for each slot it holds. The `byzantine` trustees compute
+    // theirs with a key of their own.
+    fn decrypting(
+        num_trustees: usize,
+        threshold: usize,
+        slots: &[u64],
+        byzantine: &[PartyId],
+    ) -> DecryptionCeremony {
+        let params: Arc<BfvParameters> = params::build(
+            params::DEGREE,
+            params::plaintext_modulus(1),
+            &params::MODULI,
+        )
+        .unwrap();
+        let trustees: TrusteeSet = TrusteeSet::new(num_trustees, threshold).unwrap();
+        let crp: CommonRandomPoly = CommonRandomPoly::new(&params, &mut thread_rng()).unwrap();
+        let (keys, shares): (Vec<Party>, Vec<PublicKeyShare>) = (0..trustees.num_slots())
+            .map(|_| Party::new(&params, &crp, &mut thread_rng()).unwrap())
+            .unzip();
+        let (impostor, _): (Party, PublicKeyShare) =
+            Party::new(&params, &crp, &mut thread_rng()).unwrap();
+        let pk: PublicKey = party::aggregate_public_key(shares).unwrap();
+        let pt: Plaintext = Plaintext::try_encode(slots, Encoding::poly(), &params).unwrap();
+        let tally: Arc<Ciphertext> = Arc::new(pk.try_encrypt(&pt, &mut thread_rng()).unwrap());
+        let mut ceremony: DecryptionCeremony = DecryptionCeremony::new(&tally, &trustees);
+        for trustee in 0..num_trustees {
+            for slot in trustees.slots_of(trustee) {
+                let key: &Party = if byzantine.contains(&trustee) {
+                    &impostor
+                } else {
+                    &keys[slot]
+                };
+                let message: DecryptionShareMessage =
+                    DecryptionShareMessage::new(slot, trustee, key, &tally, &mut thread_rng())
+                        .unwrap();
+                ceremony.receive(message).unwrap();
+            }
+        }
+        ceremony
+    }
+
+    #[test]
+    fn good_shares_decrypt_the_tally() {
+        let mut ceremony: DecryptionCeremony = decrypting(3, 3, &[2, 0, 1], &[]);
+        assert_eq!(ceremony.decrypt(3).unwrap(), &[2, 0, 1]);
+        assert!(ceremony.excluded().is_empty());
+        assert_eq!(ceremony.shares_used().len(), 3);
+    }
+
+    #[test]
+    fn bad_shares_are_excluded_with_a_threshold() {
+        let mut ceremony: DecryptionCeremony = decrypting(4, 2, &[1, 3], &[1, 3]);
+        assert_eq!(ceremony.decrypt(2).unwrap(), &[1, 3]);
+        assert_eq!(ceremony.excluded(), &BTreeSet::from([1, 3]));
+    }
+
+    #[test]
+    fn a_bad_share_is_not_attributed_when_every_trustee_is_needed() {
+        let mut ceremony: DecryptionCeremony = decrypting(3, 3, &[1, 1], &[2]);
+        assert!(matches!(
+            ceremony.decrypt(2),
+            Err(CeremonyError::InvalidResult)
+        ));
+        assert!(ceremony.excluded().is_empty());
+    }
+
+    #[test]
+    fn a_tally_with_nonzero_padding_blames_nobody() {
+        // One slot past `len` is set, as a ballot with nonzero padding would.
+        let mut ceremony: DecryptionCeremony = decrypting(4, 2, &[1, 0, 0, 5], &[]);
+        assert!(matches!(
+            ceremony.decrypt(2),
+            Err(CeremonyError::InvalidTally)
+        ));
+        assert!(ceremony.excluded().is_empty());
+    }
+
+    #[test]
+    fn shares_for_unknown_or_foreign_slots_are_refused() {
+        // Slot 0 is held by trustees 1 and 2, every trustee but 0.
+        let mut ceremony: DecryptionCeremony = decrypting(3, 2, &[1], &[]);
+        let message = |slot: usize, trustee: PartyId| DecryptionShareMessage {
+            slot,
+            trustee,
+            tally_hash: ceremony.tally_hash,
+            share: ceremony.shares[&(0, 1)].clone(),
+        };
+        let (unknown, foreign): (DecryptionShareMessage, DecryptionShareMessage) =
+            (message(3, 1), message(0, 0));
+        assert!(matches!(
+            ceremony.receive(unknown),
+            Err(CeremonyError::UnknownSlot {
+                trustee: 1,
+                slot: 3
+            })
+        ));
+        assert!(matches!(
+            ceremony.receive(foreign),
+            Err(CeremonyError::NotHolder {
+                trustee: 0,
+                slot: 0
+            })
+        ));
+    }
+}
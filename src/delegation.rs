@@ -0,0 +1,142 @@
+// Liquid democracy: voters who don't vote themselves hand their weight to another voter.
+//
+// Each voter either casts a ballot or delegates to another voter, who may delegate in turn.
+// The delegations are public and resolved before the tally, see `resolve`: following each
+// voter's chain of delegations to the voter at its end gives every ballot its weight, one for
+// the voter who cast it plus one for everyone whose chain ends with them. The tally then sums
+// each ballot multiplied by its weight, see `weighted_sum`, so the votes stay encrypted and
+// only who delegated to whom is public.
+//
+// A chain that goes round in a circle, or ends with a voter who neither voted nor delegated,
+// has nowhere to put its weight, and those voters are left out, see `Resolution::lost`.
+//
+// Multiplying by a weight multiplies the noise of the ballot by as much, which the election's
+// parameters have room for as long as the weights stay below the number of voters. The total
+// weight is never more than the number of voters, so the plaintext modulus that holds the
+// tally without delegations still holds it.
+//
+// Note: publishing the delegations shows who trusts whom, and the weight of every ballot. A
+// delegation kept secret would need the weights computed under encryption, which isn't done
+// here.
+
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext};
+use fhe_traits::FheEncoder;
+use std::{error::Error, fmt, sync::Arc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelegationError {
+    // A delegation to a voter who isn't on the list.
+    UnknownVoter { from: usize, to: usize },
+    SelfDelegation(usize),
+}
+
+impl fmt::Display for DelegationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DelegationError::UnknownVoter { from, to } => {
+                write!(f, "voter {from} delegates to voter {to}, who isn't a voter")
+            }
+            DelegationError::SelfDelegation(voter) => {
+                write!(f, "voter {voter} delegates to themselves")
+            }
+        }
+    }
+}
+
+impl Error for DelegationError {}
+
+// What a voter does with their vote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intent {
+    // Casts a ballot.
+    Vote,
+    // Hands their weight to the voter with this index.
+    Delegate(usize),
+    // Neither votes nor delegates.
+    Abstain,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    // The weight of each voter's ballot, 0 for those who didn't vote.
+    pub weights: Vec<u64>,
+    // The voters whose delegation chain goes round in a circle or ends with a voter who
+    // abstained.
+    pub lost: Vec<usize>,
+}
+
+impl Resolution {
+    // The weight of every ballot together, the number of voters whose weight counts.
+    pub fn total(&self) -> u64 {
+        self.weights.iter().sum()
+    }
+}
+
+// Where each voter's weight ends up: the voter at the end of their chain, if any.
+pub fn resolve(intents: &[Intent]) -> Result<Resolution, DelegationError> {
+    for (from, intent) in intents.iter().enumerate() {
+        match *intent {
+            Intent::Delegate(to) if to == from => {
+                return Err(DelegationError::SelfDelegation(from));
+            }
+            Intent::Delegate(to) if to >= intents.len() => {
+                return Err(DelegationError::UnknownVoter { from, to });
+            }
+            _ => {}
+        }
+    }
+    // The end of each voter's chain once known, `None` for a chain that loses its weight.
+    let mut ends: Vec<Option<Option<usize>>> = vec![None; intents.len()];
+    for start in 0..intents.len() {
+        let mut path: Vec<usize> = Vec::new();
+        let mut voter: usize = start;
+        let end: Option<usize> = loop {
+            if let Some(end) = ends[voter] {
+                break end;
+            }
+            if path.contains(&voter) {
+                break None;
+            }
+            path.push(voter);
+            match intents[voter] {
+                Intent::Vote => break Some(voter),
+                Intent::Delegate(to) => voter = to,
+                Intent::Abstain => break None,
+            }
+        };
+        for voter in path {
+            ends[voter] = Some(end);
+        }
+    }
+    let mut weights: Vec<u64> = vec![0; intents.len()];
+    let mut lost: Vec<usize> = Vec::new();
+    for (voter, end) in ends.into_iter().enumerate() {
+        match end.flatten() {
+            Some(end) => weights[end] += 1,
+            None if intents[voter] != Intent::Abstain => lost.push(voter),
+            None => {}
+        }
+    }
+    Ok(Resolution { weights, lost })
+}
+
+// Sums the ballots, each multiplied by its weight. The weights are public, so each is a
+// plaintext constant: with ballots encoded as polynomials, multiplying by it multiplies every
+// slot.
+pub fn weighted_sum<'a>(
+    ballots: impl IntoIterator<Item = (&'a Ciphertext, u64)>,
+    params: &Arc<BfvParameters>,
+) -> Result<Ciphertext, fhe::Error> {
+    let mut sum: Ciphertext = Ciphertext::zero(params);
+    for (ballot, weight) in ballots {
+        match weight {
+            0 => {}
+            1 => sum += ballot,
+            weight => {
+                let weight: Plaintext = Plaintext::try_encode(&[weight], Encoding::poly(), params)?;
+                sum += &(ballot * &weight);
+            }
+        }
+    }
+    Ok(sum)
+}
@@ -0,0 +1,152 @@
+// How the simulated voters vote, and which of them turn out.
+//
+// Drawing every vote uniformly gives tallies that all look alike, with every option close to
+// an even share. Real elections rarely do, and the interesting cases for testing are the ones
+// that don't: a landslide, an electorate split between two extremes, a race decided by a single
+// vote. A `Distribution` picks the option a voter goes for among those of a question:
+//
+// - `uniform`, every option equally likely,
+// - `skewed:p`, the first option with probability `p`, otherwise one of the others uniformly,
+// - `polarized`, the first or the last option evenly, never one in between,
+// - `weights:w1,w2,...`, each option in proportion to its weight, e.g. the popularity of each
+//   candidate. Options past the last weight are never picked,
+// - `near-tie`, voters alternate between the first two options, so they end up level or one
+//   vote apart.
+//
+// A `Turnout` gives the fraction of the registered voters of each precinct who cast a ballot,
+// so precincts can be compared at different levels of turnout.
+
+use crate::precinct::PrecinctId;
+use rand::{
+    distributions::{Distribution as _, WeightedIndex},
+    Rng,
+};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Distribution {
+    #[default]
+    Uniform,
+    // The probability of the first option.
+    Skewed(f64),
+    Polarized,
+    Weighted(Vec<f64>),
+    NearTie,
+}
+
+impl Distribution {
+    // The option the voter goes for, out of `options`. Only `NearTie` depends on who the voter
+    // is, to alternate between voters.
+    pub fn pick<R: Rng>(&self, voter: usize, options: usize, rng: &mut R) -> usize {
+        match self {
+            _ if options <= 1 => 0,
+            Distribution::Uniform => rng.gen_range(0..options),
+            Distribution::Skewed(p) => {
+                if rng.gen_bool(*p) {
+                    0
+                } else {
+                    rng.gen_range(1..options)
+                }
+            }
+            Distribution::Polarized => {
+                if rng.gen_bool(0.5) {
+                    0
+                } else {
+                    options - 1
+                }
+            }
+            Distribution::Weighted(weights) => {
+                match WeightedIndex::new(weights.iter().take(options)) {
+                    Ok(index) => index.sample(rng),
+                    // Every option in reach has a weight of zero.
+                    Err(_) => rng.gen_range(0..options),
+                }
+            }
+            Distribution::NearTie => voter % 2,
+        }
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg): (&str, Option<&str>) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        match (name, arg) {
+            ("uniform", None) => Ok(Distribution::Uniform),
+            ("polarized", None) => Ok(Distribution::Polarized),
+            ("near-tie", None) => Ok(Distribution::NearTie),
+            ("skewed", Some(p)) => match p.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => Ok(Distribution::Skewed(p)),
+                _ => Err(format!("{p} is not a probability between 0 and 1")),
+            },
+            ("weights", Some(weights)) => {
+                let weights: Vec<f64> = weights
+                    .split(',')
+                    .map(|w| match w.parse::<f64>() {
+                        Ok(w) if w >= 0.0 && w.is_finite() => Ok(w),
+                        _ => Err(format!("{w} is not a weight, e.g. 3 or 0.5")),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Distribution::Weighted(weights))
+            }
+            _ => Err(format!(
+                "unknown distribution `{s}`, expected uniform, skewed:<p>, polarized, \
+                 weights:<w1,w2,...> or near-tie"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Distribution::Uniform => write!(f, "uniform"),
+            Distribution::Skewed(p) => write!(f, "skewed:{p}"),
+            Distribution::Polarized => write!(f, "polarized"),
+            Distribution::Weighted(weights) => {
+                let weights: Vec<String> = weights.iter().map(f64::to_string).collect();
+                write!(f, "weights:{}", weights.join(","))
+            }
+            Distribution::NearTie => write!(f, "near-tie"),
+        }
+    }
+}
+
+// The fraction of the registered voters of each precinct who cast a ballot. A single rate
+// applies to every precinct, and with none everyone votes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Turnout {
+    rates: Vec<f64>,
+}
+
+impl Turnout {
+    pub fn new(rates: Vec<f64>, precincts: PrecinctId) -> Result<Self, String> {
+        if rates.len() > 1 && rates.len() != precincts as usize {
+            return Err(format!(
+                "got {} turnout rates for {precincts} precincts, give one or one per precinct",
+                rates.len()
+            ));
+        }
+        if let Some(rate) = rates.iter().find(|rate| !(0.0..=1.0).contains(*rate)) {
+            return Err(format!("turnout {rate} is not between 0 and 1"));
+        }
+        Ok(Turnout { rates })
+    }
+
+    pub fn rate(&self, precinct: PrecinctId) -> f64 {
+        match self.rates.as_slice() {
+            [] => 1.0,
+            [rate] => *rate,
+            rates => rates[precinct as usize],
+        }
+    }
+
+    // Whether a voter of the precinct turns out.
+    pub fn turns_out<R: Rng>(&self, precinct: PrecinctId, rng: &mut R) -> bool {
+        rng.gen_bool(self.rate(precinct))
+    }
+}
@@ -0,0 +1,247 @@
+// An adapter to the request and response shapes of Enclave's encrypted execution environments
+// (E3s), so the workshop's election can stand in as the computation of an E3.
+//
+// An E3 goes through the same steps as the workshop's election, under other names:
+//
+// - the requester asks for a computation by a committee of `threshold` out of `committee`
+//   ciphernodes, with the BFV parameters of the program (`E3Request`),
+// - the committee runs the key ceremony and publishes the aggregated public key
+//   (`CommitteePublicKey`),
+// - data providers publish inputs encrypted to that key, here the ballots (`FheInputs`),
+// - the compute provider runs the program over the inputs (`fhe_processor`, summing them) and
+//   commits to what it computed over (`ComputeResult`),
+// - the committee decrypts the output (`E3Output`).
+//
+// Binary fields are hex encoded fhe.rs serializations, without the `codec` frame, as E3
+// programs read them with fhe.rs directly. The hashes are SHA-256 and the inputs root is the
+// workshop's Merkle root of the ballots, so they match the tally journal rather than anything
+// computed on chain.
+
+use crate::{
+    artifacts::Artifacts,
+    journal,
+    merkle::{self, Hash, MerkleTree},
+    tally,
+};
+use fhe::bfv::{BfvParameters, Ciphertext};
+use fhe_traits::{
+    Deserialize as FheDeserialize, DeserializeParametrized, Serialize as FheSerialize,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{error::Error, fmt, sync::Arc};
+
+pub type E3Id = u64;
+
+#[derive(Debug)]
+pub enum E3Error {
+    Json(serde_json::Error),
+    Fhe(fhe::Error),
+}
+
+impl fmt::Display for E3Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            E3Error::Json(e) => write!(f, "{e}"),
+            E3Error::Fhe(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for E3Error {}
+
+impl From<serde_json::Error> for E3Error {
+    fn from(e: serde_json::Error) -> Self {
+        E3Error::Json(e)
+    }
+}
+
+impl From<fhe::Error> for E3Error {
+    fn from(e: fhe::Error) -> Self {
+        E3Error::Fhe(e)
+    }
+}
+
+// Asks for a computation by `threshold` of the `committee` ciphernodes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct E3Request {
+    pub e3_id: E3Id,
+    pub threshold: [usize; 2],
+    #[serde(with = "hex::serde")]
+    pub e3_program_params: Vec<u8>,
+}
+
+impl E3Request {
+    pub fn new(e3_id: E3Id, threshold: usize, committee: usize, params: &BfvParameters) -> Self {
+        E3Request {
+            e3_id,
+            threshold: [threshold, committee],
+            e3_program_params: params.to_bytes(),
+        }
+    }
+
+    pub fn params(&self) -> Result<Arc<BfvParameters>, E3Error> {
+        Ok(Arc::new(BfvParameters::try_deserialize(
+            &self.e3_program_params,
+        )?))
+    }
+}
+
+// The public key the committee aggregated in the key ceremony.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitteePublicKey {
+    pub e3_id: E3Id,
+    #[serde(with = "hex::serde")]
+    pub public_key: Vec<u8>,
+}
+
+// The encrypted inputs of the computation, each with its position, and the parameters they
+// were encrypted under.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FheInputs {
+    #[serde(with = "hex_inputs")]
+    pub ciphertexts: Vec<(Vec<u8>, u64)>,
+    #[serde(with = "hex::serde")]
+    pub params: Vec<u8>,
+}
+
+impl FheInputs {
+    pub fn new<'a>(
+        ciphertexts: impl IntoIterator<Item = &'a Ciphertext>,
+        params: &BfvParameters,
+    ) -> Self {
+        FheInputs {
+            ciphertexts: ciphertexts
+                .into_iter()
+                .zip(0..)
+                .map(|(ct, index)| (ct.to_bytes(), index))
+                .collect(),
+            params: params.to_bytes(),
+        }
+    }
+}
+
+// The program the compute provider runs: the sum of the inputs, serialized.
+pub fn fhe_processor(inputs: &FheInputs) -> Result<Vec<u8>, E3Error> {
+    let params: Arc<BfvParameters> = Arc::new(BfvParameters::try_deserialize(&inputs.params)?);
+    let ciphertexts: Vec<Ciphertext> = inputs
+        .ciphertexts
+        .iter()
+        .map(|(bytes, _)| Ciphertext::from_bytes(bytes, &params))
+        .collect::<Result<_, _>>()?;
+    Ok(tally::sum(&ciphertexts, &params).to_bytes())
+}
+
+// What the compute provider commits to alongside its output: the output, the parameters and
+// the inputs it computed over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComputeResult {
+    #[serde(with = "hex::serde")]
+    pub ciphertext_hash: Hash,
+    #[serde(with = "hex::serde")]
+    pub params_hash: Hash,
+    #[serde(with = "hex::serde")]
+    pub merkle_root: Hash,
+}
+
+impl ComputeResult {
+    pub fn new(inputs: &FheInputs, output: &[u8]) -> Self {
+        let leaves: Vec<Hash> = inputs
+            .ciphertexts
+            .iter()
+            .map(|(bytes, _)| merkle::hash_leaf(bytes))
+            .collect();
+        ComputeResult {
+            ciphertext_hash: Sha256::digest(output).into(),
+            params_hash: Sha256::digest(&inputs.params).into(),
+            merkle_root: MerkleTree::new(leaves).root(),
+        }
+    }
+}
+
+// The output of the computation, encrypted and decrypted by the committee.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct E3Output {
+    pub e3_id: E3Id,
+    #[serde(with = "hex::serde")]
+    pub ciphertext_output: Vec<u8>,
+    pub plaintext_output: Vec<u64>,
+}
+
+// Every step of an election as an E3, from its published artifacts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct E3Transcript {
+    pub request: E3Request,
+    pub committee_public_key: CommitteePublicKey,
+    pub inputs: FheInputs,
+    pub compute_result: ComputeResult,
+    pub output: E3Output,
+}
+
+impl E3Transcript {
+    pub fn new(e3_id: E3Id, threshold: usize, committee: usize, artifacts: &Artifacts) -> Self {
+        let inputs: FheInputs = FheInputs::new(
+            artifacts.ballots.iter().map(|ballot| &ballot.ciphertext),
+            &artifacts.params,
+        );
+        let ciphertext_output: Vec<u8> = artifacts.tally.to_bytes();
+        E3Transcript {
+            request: E3Request::new(e3_id, threshold, committee, &artifacts.params),
+            committee_public_key: CommitteePublicKey {
+                e3_id,
+                public_key: artifacts.public_key.to_bytes(),
+            },
+            compute_result: ComputeResult::new(&inputs, &ciphertext_output),
+            inputs,
+            output: E3Output {
+                e3_id,
+                ciphertext_output,
+                plaintext_output: artifacts.result.tally.clone(),
+            },
+        }
+    }
+
+    // Whether the commitments agree with the tally journal of the same election. A
+    // re-randomized tally still matches, as the journal holds the published tally.
+    pub fn matches(&self, journal: &journal::TallyJournal) -> bool {
+        self.compute_result.merkle_root == journal.ballots_root
+            && self.compute_result.params_hash == journal.params_hash
+            && self.output.ciphertext_output == journal.tally_bytes
+            && self.inputs.ciphertexts.len() == journal.num_ballots
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).expect("a transcript always serializes")
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<Self, E3Error> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+// The inputs as `[hex, index]` pairs.
+mod hex_inputs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        inputs: &[(Vec<u8>, u64)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        inputs
+            .iter()
+            .map(|(bytes, index)| (hex::encode(bytes), *index))
+            .collect::<Vec<(String, u64)>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(Vec<u8>, u64)>, D::Error> {
+        Vec::<(String, u64)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(bytes, index)| {
+                Ok((hex::decode(bytes).map_err(serde::de::Error::custom)?, index))
+            })
+            .collect()
+    }
+}
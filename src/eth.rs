@@ -0,0 +1,155 @@
+// Publishing the commitments of an election on Ethereum.
+//
+// The `ElectionRegistry` contract in `contracts/` keeps, for each ballots root, the hash of the
+// tally ciphertext, the hash of the parameters, the number of ballots and the decrypted result.
+// Once they are on chain, anyone holding the published artifacts can check they are the ones
+// the election was run with, without trusting whoever serves the files.
+
+use crate::{artifacts::Artifacts, decryption_ceremony, merkle::Hash};
+use alloy::{
+    network::EthereumWallet,
+    primitives::{Address, TxHash, B256},
+    providers::{PendingTransactionError, ProviderBuilder},
+    signers::local::PrivateKeySigner,
+    sol,
+    transports::http::reqwest::Url,
+};
+use std::{error::Error, fmt};
+
+sol! {
+    #[sol(rpc)]
+    interface IElectionRegistry {
+        function publish(
+            bytes32 ballotsRoot,
+            bytes32 tallyHash,
+            bytes32 paramsHash,
+            uint64 numBallots,
+            uint64[] calldata result
+        ) external;
+
+        function get(bytes32 ballotsRoot)
+            external
+            view
+            returns (
+                address publisher,
+                bytes32 tallyHash,
+                bytes32 paramsHash,
+                uint64 numBallots,
+                uint64[] memory result
+            );
+    }
+}
+
+#[derive(Debug)]
+pub enum EthError {
+    InvalidUrl(String),
+    InvalidKey(String),
+    Contract(alloy::contract::Error),
+    Transaction(PendingTransactionError),
+}
+
+impl fmt::Display for EthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EthError::InvalidUrl(e) => write!(f, "invalid RPC URL: {e}"),
+            EthError::InvalidKey(e) => write!(f, "invalid private key: {e}"),
+            EthError::Contract(e) => write!(f, "{e}"),
+            EthError::Transaction(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for EthError {}
+
+impl From<alloy::contract::Error> for EthError {
+    fn from(e: alloy::contract::Error) -> Self {
+        EthError::Contract(e)
+    }
+}
+
+impl From<PendingTransactionError> for EthError {
+    fn from(e: PendingTransactionError) -> Self {
+        EthError::Transaction(e)
+    }
+}
+
+// What the registry holds for one election.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commitment {
+    pub ballots_root: Hash,
+    pub tally_hash: Hash,
+    pub params_hash: Hash,
+    pub num_ballots: u64,
+    pub result: Vec<u64>,
+}
+
+impl Commitment {
+    // The commitment to published artifacts, as the journal states it.
+    pub fn of(artifacts: &Artifacts) -> Self {
+        Commitment {
+            ballots_root: artifacts.journal.ballots_root,
+            tally_hash: decryption_ceremony::tally_hash(&artifacts.tally),
+            params_hash: artifacts.journal.params_hash,
+            num_ballots: artifacts.journal.num_ballots as u64,
+            result: artifacts.result.tally.clone(),
+        }
+    }
+}
+
+fn parse_url(rpc_url: &str) -> Result<Url, EthError> {
+    rpc_url
+        .parse::<Url>()
+        .map_err(|e| EthError::InvalidUrl(e.to_string()))
+}
+
+// Sends the commitment to the registry at `contract`, signed with the hex encoded private key,
+// and waits for the transaction to be mined.
+pub async fn publish(
+    rpc_url: &str,
+    contract: Address,
+    private_key: &str,
+    commitment: &Commitment,
+) -> Result<TxHash, EthError> {
+    let signer: PrivateKeySigner = private_key
+        .parse::<PrivateKeySigner>()
+        .map_err(|e| EthError::InvalidKey(e.to_string()))?;
+    let provider = ProviderBuilder::new()
+        .with_recommended_fillers()
+        .wallet(EthereumWallet::from(signer))
+        .on_http(parse_url(rpc_url)?);
+    let registry = IElectionRegistry::new(contract, provider);
+    let receipt = registry
+        .publish(
+            B256::from(commitment.ballots_root),
+            B256::from(commitment.tally_hash),
+            B256::from(commitment.params_hash),
+            commitment.num_ballots,
+            commitment.result.clone(),
+        )
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    Ok(receipt.transaction_hash)
+}
+
+// Reads the commitment published under the ballots root, if any.
+pub async fn fetch(
+    rpc_url: &str,
+    contract: Address,
+    ballots_root: Hash,
+) -> Result<Option<Commitment>, EthError> {
+    let provider = ProviderBuilder::new().on_http(parse_url(rpc_url)?);
+    let registry = IElectionRegistry::new(contract, provider);
+    let election = registry.get(B256::from(ballots_root)).call().await?;
+    if election.publisher == Address::ZERO {
+        return Ok(None);
+    }
+    Ok(Some(Commitment {
+        ballots_root,
+        tally_hash: election.tallyHash.0,
+        params_hash: election.paramsHash.0,
+        num_ballots: election.numBallots,
+        result: election.result,
+    }))
+}
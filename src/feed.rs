@@ -0,0 +1,421 @@
+// A live feed of the run's progress over WebSocket, for a dashboard in a browser, e.g. on a
+// projector during the workshop. Started with `--feed <addr>`.
+//
+// `/dashboard` serves a small page that connects to `/feed` and draws what it receives: twice a
+// second, a JSON snapshot of how far each phase got, the ballots accepted so far and how long
+// the run has been going. The phases follow the progress bars handed out by `progress_bar`, the
+// same as the gauges of `dashboard`. Only counts go out, never a vote, a ciphertext or a log
+// line, so the feed can be put in front of the room without giving anything away. The result
+// isn't sent either: it is printed as usual once decrypted.
+//
+// `/metrics` serves the run's counters and latency histograms in Prometheus' text format, for a
+// scraper to graph next to the dashboard: the ballots accepted and rejected, by reason, the
+// decryption shares collected, the size of the tally, and how long encrypting a batch of votes,
+// submitting a ballot and computing a decryption share took. These are counts and timings too.
+//
+// `/events` streams the events of the transparency log as Server-Sent Events, each the event's
+// JSON on a `data:` line, for a dashboard to show the ballots accepted, the tally published, the
+// decryption shares received and the result as they happen. A client gets the events from the
+// moment it connects; the whole history is in the log itself, see `--log`. Events refer to
+// ballots and shares by hash only, but `result_published` carries the result, so unlike `/feed`
+// the stream gives the result away as soon as it is decrypted. A client that falls
+// `EVENT_BUFFER` events behind is dropped rather than held in memory.
+//
+// The WebSocket side is the bare minimum of RFC 6455 over std's TCP: the opening handshake, then
+// unmasked text frames from the server and a close frame once the run is over. Whatever the
+// browser sends is ignored.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fhe_workshop::transparency::Event;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+        Mutex, MutexGuard, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const INTERVAL: Duration = Duration::from_millis(500);
+// Appended to the client's key to prove the server speaks WebSocket, see RFC 6455.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const PAGE: &str = include_str!("feed.html");
+// How many events a client of `/events` may fall behind before it is dropped.
+const EVENT_BUFFER: usize = 4096;
+// The upper bounds of the buckets of the latency histograms, in seconds.
+const BUCKETS: [f64; 12] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0,
+];
+
+static FEED: OnceLock<Feed> = OnceLock::new();
+
+struct Feed {
+    started: Instant,
+    // Set once the run is over, stopping the clock.
+    finished: OnceLock<Duration>,
+    phases: Mutex<Vec<ProgressBar>>,
+    ballots: AtomicUsize,
+    accepted: AtomicU64,
+    rejected: Mutex<BTreeMap<&'static str, u64>>,
+    shares: AtomicU64,
+    tally_bytes: AtomicUsize,
+    latencies: Mutex<BTreeMap<&'static str, Histogram>>,
+    // One per client of `/events`, each taking the events as JSON.
+    subscribers: Mutex<Vec<SyncSender<String>>>,
+}
+
+// The latencies of one kind of item, counted in `BUCKETS`, each bucket counting the items no
+// slower than its bound.
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Serialize)]
+struct Snapshot {
+    elapsed_ms: u128,
+    finished: bool,
+    ballots: usize,
+    phases: Vec<PhaseProgress>,
+}
+
+#[derive(Serialize)]
+struct PhaseProgress {
+    name: String,
+    done: u64,
+    total: Option<u64>,
+}
+
+// Listens on the address and serves the feed, each connection on a thread of its own, until the
+// process exits. Returns the address listened on, for a port of 0.
+pub fn start(addr: SocketAddr) -> io::Result<SocketAddr> {
+    let listener: TcpListener = TcpListener::bind(addr)?;
+    let local: SocketAddr = listener.local_addr()?;
+    let feed: &'static Feed = FEED.get_or_init(|| Feed {
+        started: Instant::now(),
+        finished: OnceLock::new(),
+        phases: Mutex::new(Vec::new()),
+        ballots: AtomicUsize::new(0),
+        accepted: AtomicU64::new(0),
+        rejected: Mutex::new(BTreeMap::new()),
+        shares: AtomicU64::new(0),
+        tally_bytes: AtomicUsize::new(0),
+        latencies: Mutex::new(BTreeMap::new()),
+        subscribers: Mutex::new(Vec::new()),
+    });
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A browser going away mid-stream is nothing to report.
+            thread::spawn(move || feed.serve(stream).ok());
+        }
+    });
+    Ok(local)
+}
+
+// Follows the phase of the progress bar.
+pub fn track(pb: &ProgressBar) {
+    if let Some(feed) = FEED.get() {
+        feed.phases.lock().unwrap().push(pb.clone());
+    }
+}
+
+pub fn set_ballots(count: usize) {
+    if let Some(feed) = FEED.get() {
+        feed.ballots.store(count, Ordering::Relaxed);
+    }
+}
+
+pub fn ballot_accepted() {
+    if let Some(feed) = FEED.get() {
+        feed.accepted.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Counts a ballot the ballot box turned down, under the reason given, e.g. `BallotError::kind`.
+pub fn ballot_rejected(reason: &'static str) {
+    if let Some(feed) = FEED.get() {
+        *feed.rejected.lock().unwrap().entry(reason).or_default() += 1;
+    }
+}
+
+pub fn share_received() {
+    if let Some(feed) = FEED.get() {
+        feed.shares.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn set_tally_bytes(len: usize) {
+    if let Some(feed) = FEED.get() {
+        feed.tally_bytes.store(len, Ordering::Relaxed);
+    }
+}
+
+// Runs `f` as one item of the histogram, recording how long it took.
+pub fn time<T>(histogram: &'static str, f: impl FnOnce() -> T) -> T {
+    let Some(feed) = FEED.get() else {
+        return f();
+    };
+    let start: Instant = Instant::now();
+    let result: T = f();
+    let seconds: f64 = start.elapsed().as_secs_f64();
+    let mut latencies: MutexGuard<BTreeMap<&'static str, Histogram>> =
+        feed.latencies.lock().unwrap();
+    let entry: &mut Histogram = latencies.entry(histogram).or_default();
+    for (bucket, bound) in entry.buckets.iter_mut().zip(BUCKETS) {
+        if seconds <= bound {
+            *bucket += 1;
+        }
+    }
+    entry.count += 1;
+    entry.sum += seconds;
+    result
+}
+
+// Sends the event to every client of `/events`, dropping the ones that went away or fell behind.
+pub fn publish(event: &Event) {
+    if let Some(feed) = FEED.get() {
+        let mut subscribers: MutexGuard<Vec<SyncSender<String>>> = feed.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return;
+        }
+        let json: String = serde_json::to_string(event).expect("events serialize to JSON");
+        subscribers.retain(|subscriber| subscriber.try_send(json.clone()).is_ok());
+    }
+}
+
+// Stops the clock, and gives the dashboards connected one last snapshot before the process exits.
+pub fn finish() {
+    if let Some(feed) = FEED.get() {
+        let _ = feed.finished.set(feed.started.elapsed());
+        thread::sleep(INTERVAL);
+    }
+}
+
+impl Feed {
+    fn snapshot(&self) -> Snapshot {
+        let phases: Vec<PhaseProgress> = self
+            .phases
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|pb| PhaseProgress {
+                name: pb.message(),
+                done: pb.position(),
+                total: pb.length(),
+            })
+            .collect();
+        let finished: Option<&Duration> = self.finished.get();
+        Snapshot {
+            elapsed_ms: finished
+                .copied()
+                .unwrap_or_else(|| self.started.elapsed())
+                .as_millis(),
+            finished: finished.is_some(),
+            ballots: self.ballots.load(Ordering::Relaxed),
+            phases,
+        }
+    }
+
+    // The metrics in Prometheus' text exposition format, each with its help and type lines.
+    fn metrics(&self) -> String {
+        let mut out: String = String::new();
+        help(
+            &mut out,
+            "ballots_accepted_total",
+            "counter",
+            "Ballots accepted by the ballot box, replacements included.",
+        );
+        let _ = writeln!(
+            out,
+            "fhe_workshop_ballots_accepted_total {}",
+            self.accepted.load(Ordering::Relaxed)
+        );
+        help(
+            &mut out,
+            "ballots_rejected_total",
+            "counter",
+            "Ballots turned down by the ballot box, by reason.",
+        );
+        for (reason, count) in self.rejected.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "fhe_workshop_ballots_rejected_total{{reason=\"{reason}\"}} {count}"
+            );
+        }
+        help(&mut out, "ballots", "gauge", "Ballots in the ballot box.");
+        let _ = writeln!(
+            out,
+            "fhe_workshop_ballots {}",
+            self.ballots.load(Ordering::Relaxed)
+        );
+        help(
+            &mut out,
+            "decryption_shares_total",
+            "counter",
+            "Decryption shares collected by the decryption ceremony.",
+        );
+        let _ = writeln!(
+            out,
+            "fhe_workshop_decryption_shares_total {}",
+            self.shares.load(Ordering::Relaxed)
+        );
+        help(
+            &mut out,
+            "tally_bytes",
+            "gauge",
+            "Size of the serialized tally, once published.",
+        );
+        let _ = writeln!(
+            out,
+            "fhe_workshop_tally_bytes {}",
+            self.tally_bytes.load(Ordering::Relaxed)
+        );
+        for (name, histogram) in self.latencies.lock().unwrap().iter() {
+            let name: String = format!("{name}_seconds");
+            help(
+                &mut out,
+                &name,
+                "histogram",
+                "How long each item took, in seconds.",
+            );
+            for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(out, "fhe_workshop_{name}_bucket{{le=\"{bound}\"}} {count}");
+            }
+            let _ = writeln!(
+                out,
+                "fhe_workshop_{name}_bucket{{le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(out, "fhe_workshop_{name}_sum {}", histogram.sum);
+            let _ = writeln!(out, "fhe_workshop_{name}_count {}", histogram.count);
+        }
+        out
+    }
+
+    // Reads the request head and answers it: the page, the feed, the metrics, the events, or
+    // nothing.
+    fn serve(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut reader: BufReader<TcpStream> = BufReader::new(stream.try_clone()?);
+        let mut request: String = String::new();
+        reader.read_line(&mut request)?;
+        let path: String = request.split_whitespace().nth(1).unwrap_or("").to_string();
+        let mut key: Option<String> = None;
+        loop {
+            let mut line: String = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                    key = Some(value.trim().to_string());
+                }
+            }
+        }
+        match (path.as_str(), key) {
+            ("/feed", Some(key)) => self.stream(stream, &key),
+            ("/" | "/dashboard", _) => respond(&mut stream, "200 OK", "text/html", PAGE),
+            ("/metrics", _) => respond(
+                &mut stream,
+                "200 OK",
+                "text/plain; version=0.0.4",
+                &self.metrics(),
+            ),
+            ("/events", _) => self.events(stream),
+            _ => respond(&mut stream, "404 Not Found", "text/plain", "not found\n"),
+        }
+    }
+
+    // Completes the handshake, then sends a snapshot every `INTERVAL` until the run is over.
+    fn stream(&self, mut stream: TcpStream, key: &str) -> io::Result<()> {
+        let accept: String = STANDARD.encode(Sha1::digest(format!("{key}{WEBSOCKET_GUID}")));
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {accept}\r\n\r\n"
+        )?;
+        loop {
+            let snapshot: Snapshot = self.snapshot();
+            write_frame(
+                &mut stream,
+                TEXT,
+                serde_json::to_string(&snapshot)?.as_bytes(),
+            )?;
+            if snapshot.finished {
+                return write_frame(&mut stream, CLOSE, &[]);
+            }
+            thread::sleep(INTERVAL);
+        }
+    }
+
+    // Sends the events published from now on, until the run is over and every one was sent.
+    fn events(&self, mut stream: TcpStream) -> io::Result<()> {
+        let (sender, receiver): (SyncSender<String>, Receiver<String>) =
+            mpsc::sync_channel(EVENT_BUFFER);
+        self.subscribers.lock().unwrap().push(sender);
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+             Connection: close\r\n\r\n"
+        )?;
+        loop {
+            match receiver.recv_timeout(INTERVAL) {
+                Ok(event) => write!(stream, "data: {event}\n\n")?,
+                // A comment keeps the connection open and finds out if the browser went away.
+                Err(RecvTimeoutError::Timeout) if self.finished.get().is_none() => {
+                    stream.write_all(b":\n\n")?
+                }
+                // The run is over, or the client was dropped for falling behind.
+                Err(_) => return Ok(()),
+            }
+        }
+    }
+}
+
+// The help and type lines that come before a metric.
+fn help(out: &mut String, name: &str, kind: &str, text: &str) {
+    let _ = writeln!(out, "# HELP fhe_workshop_{name} {text}");
+    let _ = writeln!(out, "# TYPE fhe_workshop_{name} {kind}");
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+// The opcodes of the frames sent, with the FIN bit set: every message fits in one frame.
+const TEXT: u8 = 0x81;
+const CLOSE: u8 = 0x88;
+
+// A frame from the server, which isn't masked. The length takes one byte up to 125, then two
+// or eight more.
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame: Vec<u8> = Vec::with_capacity(payload.len() + 10);
+    frame.push(opcode);
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
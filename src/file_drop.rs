@@ -0,0 +1,157 @@
+// The `file-drop` subcommand: one trustee of an election held over a shared directory, without
+// a coordinator.
+//
+// The same protocol as `p2p`, see `gossip`, with files instead of a network: a network share,
+// a synced folder, or a USB stick passed around the room. Each trustee writes every message it
+// sends to its own file in the directory, named after the trustee and the message's number, and
+// reads every file the others have written, checking the directory again every `POLL`. Files
+// are written under a temporary name and renamed, so nobody reads one half written.
+//
+// Every trustee computes the public key, tally and result itself, and the result is accepted
+// once every trustee's outcome matches, see `GossipTrustee::accepted`, and certified once a
+// quorum of the trustees have signed it, see `GossipTrustee::certificate`. A trustee that
+// hasn't seen every matching, signed outcome by `--timeout` gives up. `--certificate` writes
+// the signed result to a file.
+
+use clap::Args;
+use fhe_workshop::{
+    gossip::{GossipMessage, GossipTrustee},
+    voter::Choice,
+};
+use rand::thread_rng;
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+const POLL: Duration = Duration::from_millis(500);
+const EXTENSION: &str = "msg";
+
+#[derive(Args)]
+pub struct FileDropArgs {
+    /// This trustee's number, from 0.
+    #[arg(long)]
+    trustee: usize,
+
+    /// Number of trustees in the election.
+    #[arg(long)]
+    trustees: usize,
+
+    /// Name of the election, the same for every trustee. The CRP is derived from it.
+    #[arg(long, default_value = "fhe-workshop")]
+    session: String,
+
+    /// This trustee's vote: 0 (against), 1 (for) or 2 (abstain).
+    #[arg(long, default_value_t = 1)]
+    vote: u64,
+
+    /// Directory every trustee reads and writes the messages in.
+    #[arg(long)]
+    dir: PathBuf,
+
+    /// Seconds to wait for the other trustees before giving up.
+    #[arg(long, default_value_t = 600)]
+    timeout: u64,
+
+    /// Write the result, with the signatures of a quorum of the trustees, to this file.
+    #[arg(long)]
+    certificate: Option<PathBuf>,
+}
+
+// Writes every message the trustee has sent that isn't in the directory yet, each under a
+// temporary name first and then renamed into place.
+fn write_sent(dir: &Path, node: &GossipTrustee) -> Result<(), Box<dyn Error>> {
+    for (number, message) in node.sent().iter().enumerate() {
+        let name: String = format!("{}-{number}", node.trustee());
+        let path: PathBuf = dir.join(format!("{name}.{EXTENSION}"));
+        if path.exists() {
+            continue;
+        }
+        let partial: PathBuf = dir.join(format!(".{name}.partial"));
+        fs::write(&partial, message.to_bytes())?;
+        fs::rename(&partial, &path)?;
+    }
+    Ok(())
+}
+
+pub fn run(args: &FileDropArgs) -> Result<(), Box<dyn Error>> {
+    let vote: Choice = Choice::try_from(args.vote)?;
+    let mut node: GossipTrustee = GossipTrustee::new(
+        args.trustee,
+        args.trustees,
+        &args.session,
+        vote,
+        &mut thread_rng(),
+    )?;
+    fs::create_dir_all(&args.dir)?;
+    info!(
+        trustee = args.trustee,
+        trustees = args.trustees,
+        dir = %args.dir.display(),
+        key_share = %hex::encode(node.key_share_hash()),
+        "trustee started"
+    );
+
+    let deadline: Instant = Instant::now() + Duration::from_secs(args.timeout);
+    let mut read: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut decrypted: bool = false;
+    loop {
+        write_sent(&args.dir, &node)?;
+        for entry in fs::read_dir(&args.dir)? {
+            let path: PathBuf = entry?.path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != EXTENSION)
+                || read.contains(&path)
+            {
+                continue;
+            }
+            // A bad message is pinned on its file and skipped, the rest of the election goes
+            // on.
+            let received = GossipMessage::from_bytes(&fs::read(&path)?)
+                .and_then(|message| node.receive(message));
+            if let Err(e) = received {
+                warn!(file = %path.display(), "message dropped: {e}");
+            }
+            read.insert(path);
+        }
+        if !decrypted {
+            if let Some(result) = node.result() {
+                info!(
+                    public_key = hex::encode(node.public_key_hash().unwrap_or_default()),
+                    tally = hex::encode(node.tally_hash().unwrap_or_default()),
+                    ?result,
+                    "election decrypted"
+                );
+                decrypted = true;
+            }
+        }
+        if let (Some(result), Some(certificate)) = (node.accepted(), node.certificate()) {
+            info!(?result, "result accepted by every trustee");
+            info!(
+                signatures = certificate.signatures.len(),
+                quorum = certificate.quorum,
+                "result certified"
+            );
+            if let Some(path) = &args.certificate {
+                fs::write(path, certificate.to_json())?;
+            }
+            // The outcome is written before leaving, for the trustees still waiting on it.
+            write_sent(&args.dir, &node)?;
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "gave up after {} seconds without every trustee's matching, signed outcome",
+                args.timeout
+            )
+            .into());
+        }
+        thread::sleep(POLL);
+    }
+}
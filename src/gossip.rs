@@ -0,0 +1,865 @@
+// Trustees running an election among themselves, without a coordinator.
+//
+// Every trustee broadcasts a commitment to its public key share, then the share once every
+// trustee has committed, see `KeyCeremony::commit`, then its ballot, then its decryption share of
+// the tally, and picks up everyone else's in whatever order the network delivers them. Each
+// one aggregates the key, sums the ballots and combines the decryption shares itself, and they
+// all end up with the same public key, tally and result: aggregation only adds things up, so
+// the order the pieces arrive in doesn't matter and nobody has to be trusted to do it for the
+// others.
+//
+// This is the protocol of one trustee, whatever carries the messages. The demo gossips them
+// over libp2p with the `p2p` subcommand. All trustees are needed to decrypt, as every one of
+// them holds a single key slot of an n-of-n `TrusteeSet`.
+//
+// The CRP is derived from a session name the trustees agree on beforehand, since there is no
+// coordinator to sample and publish it.
+//
+// Once it has a result, each trustee broadcasts what it came to: the hashes of its public key
+// and tally, and the counts. The result is only accepted once every trustee's outcome is in and
+// matches its own, see `accepted`. A trustee that aggregated anything differently, or was sent
+// something different from the others, shows up as a mismatch rather than a quiet disagreement.
+//
+// Each trustee signs its outcome with an Ed25519 key of its own, announced along with its key
+// share, see `certification::result_digest`. Once a quorum of the trustees, as many as it takes
+// to decrypt, have signed the same outcome with the keys they announced, the result is
+// certified, see `certificate`, and the signatures make a certificate anyone can check.
+//
+// Note: nothing ties the announced keys to the trustees but the key share they came with. A
+// roster of the trustees' keys agreed on beforehand would, see `ResultCertificate::certify`.
+//
+// A trustee also signs its ballot with the key it announced, see `ballot_digest`, and a ballot
+// only counts once the signature checks out, so nobody else can cast a trustee's ballot first.
+//
+// Every message comes from a peer, so a ballot or decryption share is turned away on its length
+// alone if it is larger than a ciphertext under the parameters can be, before fhe.rs parses it,
+// see `Ballot::max_ciphertext_len`. Key shares that arrive before every commitment is in, ballots
+// that arrive before their trustee's key share and decryption shares that arrive before the tally
+// is summed are held on to, at most one per slot, trustee, or trustee and slot it holds. A held
+// ballot whose signature doesn't check out is dropped, and the trustee's next copy of it taken.
+// Nothing limits how often a peer sends, which is up to the transport.
+
+use crate::{
+    ballot::Ballot,
+    certification::{self, CertificationError, ResultCertificate, ResultSignature},
+    decryption_ceremony::{self, DecryptionCeremony, DecryptionShareMessage, TallyHash},
+    key_ceremony::{
+        CeremonyError, CrpHash, KeyCeremony, KeyCeremonyState, KeyShareCommitment, KeyShareMessage,
+        PartyId, ShareCommitment,
+    },
+    merkle::Hash,
+    mnemonic, params,
+    party::Party,
+    signer::{LocalSigner, SignerError, TrusteeSigner},
+    threshold::{ThresholdError, TrusteeSet},
+    transparency,
+    voter::{self, Choice},
+    wire::{self, WireError},
+};
+use bip39::Mnemonic;
+use ed25519_dalek::{Signer, SigningKey};
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, PublicKey},
+    mbfv::{CommonRandomPoly, DecryptionShare, PublicKeyShare},
+};
+use fhe_traits::{DeserializeParametrized, Serialize as FheSerialize};
+use rand::{rngs::StdRng, CryptoRng, Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, error::Error, fmt, mem, sync::Arc};
+
+const BALLOT_DOMAIN: &[u8] = b"fhe-workshop/gossip-ballot";
+
+#[derive(Debug)]
+pub enum GossipError {
+    Wire(WireError),
+    Fhe(fhe::Error),
+    Ceremony(CeremonyError),
+    Threshold(ThresholdError),
+    Signer(SignerError),
+    Certification(CertificationError),
+    UnknownTrustee(PartyId),
+    // A share for a slot the trustee doesn't hold.
+    UnknownSlot {
+        trustee: PartyId,
+        slot: usize,
+    },
+    // A payload larger than any ciphertext under the parameters.
+    TooLarge {
+        trustee: PartyId,
+        len: usize,
+        max: usize,
+    },
+    // The trustee came to another public key, tally or result.
+    OutcomeMismatch(PartyId),
+}
+
+impl fmt::Display for GossipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GossipError::Wire(e) => write!(f, "{e}"),
+            GossipError::Fhe(e) => write!(f, "{e}"),
+            GossipError::Ceremony(e) => write!(f, "{e}"),
+            GossipError::Threshold(e) => write!(f, "{e}"),
+            GossipError::Signer(e) => write!(f, "{e}"),
+            GossipError::Certification(e) => write!(f, "{e}"),
+            GossipError::UnknownTrustee(trustee) => {
+                write!(f, "trustee {trustee} is not in the election")
+            }
+            GossipError::UnknownSlot { trustee, slot } => {
+                write!(
+                    f,
+                    "trustee {trustee} sent a message for slot {slot}, which it doesn't hold"
+                )
+            }
+            GossipError::TooLarge { trustee, len, max } => write!(
+                f,
+                "trustee {trustee} sent {len} bytes, more than the {max} a ciphertext can take"
+            ),
+            GossipError::OutcomeMismatch(trustee) => {
+                write!(f, "trustee {trustee} came to another outcome")
+            }
+        }
+    }
+}
+
+impl Error for GossipError {}
+
+impl From<WireError> for GossipError {
+    fn from(e: WireError) -> Self {
+        GossipError::Wire(e)
+    }
+}
+
+impl From<fhe::Error> for GossipError {
+    fn from(e: fhe::Error) -> Self {
+        GossipError::Fhe(e)
+    }
+}
+
+impl From<CeremonyError> for GossipError {
+    fn from(e: CeremonyError) -> Self {
+        GossipError::Ceremony(e)
+    }
+}
+
+impl From<ThresholdError> for GossipError {
+    fn from(e: ThresholdError) -> Self {
+        GossipError::Threshold(e)
+    }
+}
+
+impl From<SignerError> for GossipError {
+    fn from(e: SignerError) -> Self {
+        GossipError::Signer(e)
+    }
+}
+
+impl From<CertificationError> for GossipError {
+    fn from(e: CertificationError) -> Self {
+        GossipError::Certification(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GossipMessage {
+    KeyShareCommitment {
+        trustee: PartyId,
+        slot: usize,
+        #[serde(with = "serde_bytes")]
+        crp_hash: CrpHash,
+        #[serde(with = "serde_bytes")]
+        commitment: ShareCommitment,
+    },
+    KeyShare {
+        trustee: PartyId,
+        slot: usize,
+        #[serde(with = "serde_bytes")]
+        crp_hash: CrpHash,
+        #[serde(with = "serde_bytes")]
+        share: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        proof: Vec<u8>,
+        // The key the trustee signs its outcome with.
+        #[serde(with = "serde_bytes")]
+        signer: [u8; 32],
+    },
+    Ballot {
+        trustee: PartyId,
+        #[serde(with = "serde_bytes")]
+        ciphertext: Vec<u8>,
+        // With the key announced along with the trustee's key share, see `ballot_digest`.
+        #[serde(with = "serde_bytes")]
+        signature: Vec<u8>,
+    },
+    DecryptionShare {
+        trustee: PartyId,
+        slot: usize,
+        #[serde(with = "serde_bytes")]
+        tally_hash: TallyHash,
+        #[serde(with = "serde_bytes")]
+        share: Vec<u8>,
+    },
+    // What the trustee came to, for the others to check against theirs.
+    Outcome {
+        trustee: PartyId,
+        #[serde(with = "serde_bytes")]
+        public_key_hash: [u8; 32],
+        #[serde(with = "serde_bytes")]
+        tally_hash: TallyHash,
+        result: Vec<u64>,
+        #[serde(with = "serde_bytes")]
+        signer: [u8; 32],
+        // Over the outcome, see `certification::result_digest`.
+        #[serde(with = "serde_bytes")]
+        signature: Vec<u8>,
+    },
+}
+
+impl GossipMessage {
+    // In the versioned envelope of the wire format, see `wire`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        wire::encode(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GossipError> {
+        Ok(wire::decode(bytes)?)
+    }
+
+    pub fn trustee(&self) -> PartyId {
+        match self {
+            GossipMessage::KeyShareCommitment { trustee, .. }
+            | GossipMessage::KeyShare { trustee, .. }
+            | GossipMessage::Ballot { trustee, .. }
+            | GossipMessage::DecryptionShare { trustee, .. }
+            | GossipMessage::Outcome { trustee, .. } => *trustee,
+        }
+    }
+}
+
+// Derives the CRP from the session name, the same for every trustee of the session.
+pub fn session_crp(
+    params: &Arc<BfvParameters>,
+    session: &str,
+) -> Result<CommonRandomPoly, fhe::Error> {
+    let seed: [u8; 32] = Sha256::digest(session.as_bytes()).into();
+    CommonRandomPoly::new(params, &mut StdRng::from_seed(seed))
+}
+
+// What a trustee signs its ballot over.
+pub fn ballot_digest(trustee: PartyId, ciphertext: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(BALLOT_DOMAIN);
+    hasher.update((trustee as u64).to_le_bytes());
+    hasher.update(ciphertext);
+    hasher.finalize().into()
+}
+
+// The hashes of the public key and tally a trustee came to, and its result.
+type Outcome = ([u8; 32], TallyHash, Vec<u64>);
+
+pub struct GossipTrustee {
+    trustee: PartyId,
+    vote: Choice,
+    params: Arc<BfvParameters>,
+    trustees: TrusteeSet,
+    crp: CommonRandomPoly,
+    // Holds the trustee's key share, see `signer.rs`.
+    signer: Box<dyn TrusteeSigner>,
+    key_share_hash: Hash,
+    // Draws the randomness of the ballot.
+    rng: StdRng,
+    key_ceremony: KeyCeremony,
+    // This trustee's key share, sent once every trustee has committed to theirs.
+    reveal: Option<GossipMessage>,
+    // Key shares that arrived before every trustee had committed, the first for each slot.
+    early_key_shares: BTreeMap<usize, GossipMessage>,
+    // Ballots that arrived before their trustee's key share, the first for each trustee.
+    early_ballots: BTreeMap<PartyId, GossipMessage>,
+    public_key: Option<Arc<PublicKey>>,
+    // The ballot of each trustee, numbered in the order they arrived in.
+    ballots: BTreeMap<PartyId, (usize, Ciphertext)>,
+    tally: Option<Arc<Ciphertext>>,
+    decryption: Option<DecryptionCeremony>,
+    // Decryption shares that arrived before this trustee had summed the ballots, the first for
+    // each trustee and slot.
+    early_shares: BTreeMap<(PartyId, usize), GossipMessage>,
+    result: Option<Vec<u64>>,
+    // The public key hash, tally hash and result every trustee broadcast, this one's included.
+    outcomes: BTreeMap<PartyId, Outcome>,
+    // Signs this trustee's outcome.
+    signing_key: SigningKey,
+    // The key each trustee announced with its key share.
+    signers: BTreeMap<PartyId, [u8; 32]>,
+    // Each trustee's valid signature over its outcome.
+    signatures: BTreeMap<PartyId, ResultSignature>,
+    // Everything this trustee has sent so far.
+    sent: Vec<GossipMessage>,
+}
+
+// What every trustee of a session agrees on before any message is sent: who holds which key
+// slot, the parameters and the CRP. Along with the trustee it is for.
+pub struct Session {
+    trustee: PartyId,
+    trustees: TrusteeSet,
+    params: Arc<BfvParameters>,
+    crp: CommonRandomPoly,
+}
+
+impl Session {
+    pub fn new(trustee: PartyId, num_trustees: usize, session: &str) -> Result<Self, GossipError> {
+        if trustee >= num_trustees {
+            return Err(GossipError::UnknownTrustee(trustee));
+        }
+        let trustees: TrusteeSet = TrusteeSet::new(num_trustees, num_trustees)?;
+        let params: Arc<BfvParameters> = params::build(
+            params::DEGREE,
+            params::plaintext_modulus(num_trustees),
+            &params::MODULI,
+        )?;
+        let crp: CommonRandomPoly = session_crp(&params, session)?;
+        Ok(Session {
+            trustee,
+            trustees,
+            params,
+            crp,
+        })
+    }
+
+    pub fn params(&self) -> &Arc<BfvParameters> {
+        &self.params
+    }
+
+    pub fn crp(&self) -> &CommonRandomPoly {
+        &self.crp
+    }
+
+    // With `t = n`, every trustee holds exactly one slot.
+    fn slot(&self) -> usize {
+        self.trustees
+            .slots_of(self.trustee)
+            .next()
+            .expect("every trustee holds a slot")
+    }
+
+    // A fresh key share for the trustee, and its public key share.
+    pub fn random_key<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> Result<(Party, PublicKeyShare), GossipError> {
+        Ok(Party::new(&self.params, &self.crp, rng)?)
+    }
+
+    // The trustee's key share and its public key share, derived from a mnemonic, see
+    // `mnemonic.rs`.
+    pub fn mnemonic_key(
+        &self,
+        mnemonic: &Mnemonic,
+    ) -> Result<(Party, PublicKeyShare), GossipError> {
+        Ok(mnemonic::derive_party(
+            &self.params,
+            &self.crp,
+            mnemonic,
+            self.slot(),
+        )?)
+    }
+}
+
+// The key share `GossipTrustee::from_mnemonic` gives the trustee, e.g. to check that a mnemonic
+// regenerates the share the trustee sent.
+pub fn mnemonic_key(
+    trustee: PartyId,
+    num_trustees: usize,
+    session: &str,
+    mnemonic: &Mnemonic,
+) -> Result<(Party, PublicKeyShare), GossipError> {
+    Session::new(trustee, num_trustees, session)?.mnemonic_key(mnemonic)
+}
+
+impl GossipTrustee {
+    // Trustee `trustee` of `num_trustees`, casting `vote`. Its public key share is ready to be
+    // sent straight away. The key share and all the randomness the trustee needs later on are
+    // drawn from `rng`.
+    pub fn new<R: RngCore + CryptoRng>(
+        trustee: PartyId,
+        num_trustees: usize,
+        session: &str,
+        vote: Choice,
+        rng: &mut R,
+    ) -> Result<Self, GossipError> {
+        let session: Session = Session::new(trustee, num_trustees, session)?;
+        let (key, share): (Party, PublicKeyShare) = session.random_key(rng)?;
+        let signer: LocalSigner = LocalSigner::new(key, share, session.crp.clone(), rng);
+        GossipTrustee::with_signer(session, vote, Box::new(signer), rng)
+    }
+
+    // The same, with the key share derived from a mnemonic, so that a replacement machine can
+    // take the trustee's place, see `mnemonic.rs`. Only the key share comes from the mnemonic:
+    // the randomness of the ballot and the decryption share is still drawn from `rng`.
+    pub fn from_mnemonic<R: RngCore + CryptoRng>(
+        trustee: PartyId,
+        num_trustees: usize,
+        session: &str,
+        vote: Choice,
+        mnemonic: &Mnemonic,
+        rng: &mut R,
+    ) -> Result<Self, GossipError> {
+        let session: Session = Session::new(trustee, num_trustees, session)?;
+        let (key, share): (Party, PublicKeyShare) = session.mnemonic_key(mnemonic)?;
+        let signer: LocalSigner = LocalSigner::new(key, share, session.crp.clone(), rng);
+        GossipTrustee::with_signer(session, vote, Box::new(signer), rng)
+    }
+
+    // The trustee of the session, with its key share held by `signer`, e.g. a `RemoteSigner` so
+    // that the share never enters this process. The randomness of the ballot is drawn from
+    // `rng`, that of the decryption share is the signer's.
+    pub fn with_signer<R: RngCore + CryptoRng>(
+        session: Session,
+        vote: Choice,
+        signer: Box<dyn TrusteeSigner>,
+        rng: &mut R,
+    ) -> Result<Self, GossipError> {
+        let slot: usize = session.slot();
+        let message: KeyShareMessage = signer.key_share(slot)?;
+        let Session {
+            trustee,
+            trustees,
+            params,
+            crp,
+        } = session;
+        let mut key_ceremony: KeyCeremony = KeyCeremony::new(&params, trustees.num_slots());
+        key_ceremony.open_with_crp(crp.clone())?;
+        let mut node: GossipTrustee = GossipTrustee {
+            trustee,
+            vote,
+            params,
+            trustees,
+            crp,
+            signer,
+            key_share_hash: transparency::artifact_hash(&message.share.to_bytes()),
+            rng: StdRng::from_seed(rng.gen()),
+            key_ceremony,
+            reveal: None,
+            early_key_shares: BTreeMap::new(),
+            early_ballots: BTreeMap::new(),
+            public_key: None,
+            ballots: BTreeMap::new(),
+            tally: None,
+            decryption: None,
+            early_shares: BTreeMap::new(),
+            result: None,
+            outcomes: BTreeMap::new(),
+            signing_key: SigningKey::generate(rng),
+            signers: BTreeMap::new(),
+            signatures: BTreeMap::new(),
+            sent: Vec::new(),
+        };
+        let signer: [u8; 32] = node.signing_key.verifying_key().to_bytes();
+        let commitment: KeyShareCommitment = message.commitment();
+        node.reveal = Some(GossipMessage::KeyShare {
+            trustee,
+            slot: message.party,
+            crp_hash: message.crp_hash,
+            share: message.share.to_bytes(),
+            proof: message.proof,
+            signer,
+        });
+        node.send(GossipMessage::KeyShareCommitment {
+            trustee,
+            slot: commitment.party,
+            crp_hash: commitment.crp_hash,
+            commitment: commitment.commitment,
+        })?;
+        Ok(node)
+    }
+
+    pub fn trustee(&self) -> PartyId {
+        self.trustee
+    }
+
+    // With `t = n`, every trustee holds exactly one slot.
+    fn slot(&self) -> usize {
+        self.trustees
+            .slots_of(self.trustee)
+            .next()
+            .expect("every trustee holds a slot")
+    }
+
+    // The hash of this trustee's public key share, as the transparency log records it.
+    pub fn key_share_hash(&self) -> Hash {
+        self.key_share_hash
+    }
+
+    // The messages this trustee has sent. Gossip makes no promise of delivery to peers that
+    // joined late, so they should be sent again every so often until every trustee is done.
+    pub fn sent(&self) -> &[GossipMessage] {
+        &self.sent
+    }
+
+    // Hands one of its own messages to this trustee, as if it had come back from the network.
+    fn send(&mut self, message: GossipMessage) -> Result<(), GossipError> {
+        self.sent.push(message.clone());
+        self.receive(message)
+    }
+
+    // Takes in a message from the network. Copies of messages already taken in are ignored, so
+    // it's safe to call with every message gossiped, however often it was sent.
+    pub fn receive(&mut self, message: GossipMessage) -> Result<(), GossipError> {
+        if message.trustee() >= self.trustees.num_trustees() {
+            return Err(GossipError::UnknownTrustee(message.trustee()));
+        }
+        match message {
+            GossipMessage::KeyShareCommitment {
+                trustee,
+                slot,
+                crp_hash,
+                commitment,
+            } => {
+                self.check_slot(trustee, slot)?;
+                if self.key_ceremony.has_commitment(slot) {
+                    return Ok(());
+                }
+                self.key_ceremony.commit(KeyShareCommitment {
+                    party: slot,
+                    crp_hash,
+                    commitment,
+                })?;
+            }
+            GossipMessage::KeyShare {
+                trustee,
+                slot,
+                crp_hash,
+                share,
+                proof,
+                signer,
+            } => {
+                self.check_slot(trustee, slot)?;
+                if self.public_key.is_some() || self.key_ceremony.has_share(slot) {
+                    return Ok(());
+                }
+                self.check_len(trustee, &share)?;
+                if self.key_ceremony.state() == KeyCeremonyState::CollectingCommitments {
+                    self.early_key_shares
+                        .entry(slot)
+                        .or_insert(GossipMessage::KeyShare {
+                            trustee,
+                            slot,
+                            crp_hash,
+                            share,
+                            proof,
+                            signer,
+                        });
+                    return Ok(());
+                }
+                let share: PublicKeyShare =
+                    PublicKeyShare::deserialize(&share, &self.params, self.crp.clone())?;
+                self.key_ceremony.receive(KeyShareMessage {
+                    party: slot,
+                    crp_hash,
+                    share,
+                    proof,
+                })?;
+                self.signers.insert(trustee, signer);
+                if let Some(GossipMessage::Ballot {
+                    ciphertext,
+                    signature,
+                    ..
+                }) = self.early_ballots.remove(&trustee)
+                {
+                    // A held ballot that doesn't check out is dropped rather than failing the
+                    // key share it waited for. The trustee sends its own again.
+                    let _ = self.take_ballot(trustee, signer, &ciphertext, signature);
+                }
+            }
+            GossipMessage::Ballot {
+                trustee,
+                ciphertext,
+                signature,
+            } => {
+                if self.ballots.contains_key(&trustee) {
+                    return Ok(());
+                }
+                self.check_len(trustee, &ciphertext)?;
+                let Some(&signer) = self.signers.get(&trustee) else {
+                    self.early_ballots
+                        .entry(trustee)
+                        .or_insert(GossipMessage::Ballot {
+                            trustee,
+                            ciphertext,
+                            signature,
+                        });
+                    return Ok(());
+                };
+                self.take_ballot(trustee, signer, &ciphertext, signature)?;
+            }
+            GossipMessage::DecryptionShare {
+                trustee,
+                slot,
+                tally_hash,
+                share,
+            } => {
+                self.check_slot(trustee, slot)?;
+                self.check_len(trustee, &share)?;
+                let Some(tally) = self.tally.clone() else {
+                    self.early_shares.entry((trustee, slot)).or_insert(
+                        GossipMessage::DecryptionShare {
+                            trustee,
+                            slot,
+                            tally_hash,
+                            share,
+                        },
+                    );
+                    return Ok(());
+                };
+                let decryption: &mut DecryptionCeremony = self
+                    .decryption
+                    .as_mut()
+                    .expect("the decryption starts along with the tally");
+                if self.result.is_some() || decryption.contributors().contains(&trustee) {
+                    return Ok(());
+                }
+                let share: DecryptionShare =
+                    DecryptionShare::deserialize(&share, &self.params, tally)?;
+                decryption.receive(DecryptionShareMessage {
+                    slot,
+                    trustee,
+                    tally_hash,
+                    share,
+                })?;
+            }
+            GossipMessage::Outcome {
+                trustee,
+                public_key_hash,
+                tally_hash,
+                result,
+                signer,
+                signature,
+            } => {
+                let signature: ResultSignature = ResultSignature {
+                    trustee,
+                    signer,
+                    signature,
+                };
+                signature.verify(&certification::result_digest(
+                    &public_key_hash,
+                    &tally_hash,
+                    self.trustees.threshold(),
+                    &result,
+                ))?;
+                let outcome: Outcome = (public_key_hash, tally_hash, result);
+                if let Some(known) = self.outcomes.get(&trustee) {
+                    if *known != outcome {
+                        return Err(GossipError::OutcomeMismatch(trustee));
+                    }
+                    return Ok(());
+                }
+                self.outcomes.insert(trustee, outcome);
+                self.signatures.insert(trustee, signature);
+                self.check_outcomes()?;
+            }
+        }
+        self.advance()
+    }
+
+    // Moves on to the next step once everything for it has come in.
+    fn advance(&mut self) -> Result<(), GossipError> {
+        if self.key_ceremony.state() == KeyCeremonyState::CollectingShares {
+            if let Some(reveal) = self.reveal.take() {
+                self.send(reveal)?;
+                for message in mem::take(&mut self.early_key_shares).into_values() {
+                    self.receive(message)?;
+                }
+            }
+        }
+        if self.public_key.is_none() && self.key_ceremony.received() == self.key_ceremony.required()
+        {
+            let pk: PublicKey = self.key_ceremony.aggregate()?.clone();
+            let ciphertext: Ciphertext =
+                voter::encrypt_vote(self.vote, &pk, &self.params, &mut self.rng)?;
+            self.public_key = Some(Arc::new(pk));
+            let ciphertext: Vec<u8> = ciphertext.to_bytes();
+            let signature: Vec<u8> = self
+                .signing_key
+                .sign(&ballot_digest(self.trustee, &ciphertext))
+                .to_vec();
+            self.send(GossipMessage::Ballot {
+                trustee: self.trustee,
+                ciphertext,
+                signature,
+            })?;
+        }
+        if self.tally.is_none() && self.ballots.len() == self.trustees.num_trustees() {
+            // Summed in the order the ballots arrived, which differs from one trustee to the
+            // next, and yet every trustee gets the same tally.
+            let mut ballots: Vec<&(usize, Ciphertext)> = self.ballots.values().collect();
+            ballots.sort_by_key(|(arrived, _)| *arrived);
+            let tally: Arc<Ciphertext> = Arc::new(crate::tally::sum(
+                ballots.into_iter().map(|(_, ciphertext)| ciphertext),
+                &self.params,
+            ));
+            self.decryption = Some(DecryptionCeremony::new(&tally, &self.trustees));
+            self.tally = Some(tally.clone());
+            let message: DecryptionShareMessage = DecryptionShareMessage::from_signer(
+                self.slot(),
+                self.trustee,
+                self.signer.as_ref(),
+                &tally,
+            )?;
+            self.send(GossipMessage::DecryptionShare {
+                trustee: self.trustee,
+                slot: message.slot,
+                tally_hash: message.tally_hash,
+                share: message.share.to_bytes(),
+            })?;
+            for message in mem::take(&mut self.early_shares).into_values() {
+                self.receive(message)?;
+            }
+        }
+        if let Some(decryption) = &mut self.decryption {
+            if self.result.is_none() && decryption.progress().is_complete() {
+                let result: Vec<u64> = decryption.decrypt(Choice::ALL.len())?.to_vec();
+                self.result = Some(result.clone());
+                let public_key_hash: [u8; 32] =
+                    self.public_key_hash().expect("the key comes first");
+                let tally_hash: TallyHash = self.tally_hash().expect("the tally comes first");
+                let signature: ResultSignature = ResultSignature::new(
+                    self.trustee,
+                    &certification::result_digest(
+                        &public_key_hash,
+                        &tally_hash,
+                        self.trustees.threshold(),
+                        &result,
+                    ),
+                    &self.signing_key,
+                );
+                self.send(GossipMessage::Outcome {
+                    trustee: self.trustee,
+                    public_key_hash,
+                    tally_hash,
+                    result,
+                    signer: signature.signer,
+                    signature: signature.signature,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    // Checks every outcome received against this trustee's own, once it has one. Outcomes that
+    // arrived before it are kept until then.
+    fn check_outcomes(&self) -> Result<(), GossipError> {
+        let Some(own) = self.outcomes.get(&self.trustee) else {
+            return Ok(());
+        };
+        match self.outcomes.iter().find(|(_, outcome)| *outcome != own) {
+            Some((&trustee, _)) => Err(GossipError::OutcomeMismatch(trustee)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn public_key(&self) -> Option<&PublicKey> {
+        self.public_key.as_deref()
+    }
+
+    // The hash of the public key, for trustees to compare once they are done.
+    pub fn public_key_hash(&self) -> Option<[u8; 32]> {
+        self.public_key
+            .as_deref()
+            .map(|pk| Sha256::digest(pk.to_bytes()).into())
+    }
+
+    pub fn tally(&self) -> Option<&Ciphertext> {
+        self.tally.as_deref()
+    }
+
+    // The hash of the tally, for trustees to compare once they are done.
+    pub fn tally_hash(&self) -> Option<TallyHash> {
+        self.tally.as_deref().map(decryption_ceremony::tally_hash)
+    }
+
+    // The decrypted `[against, for, abstain]` counts, once every decryption share is in.
+    pub fn result(&self) -> Option<&[u64]> {
+        self.result.as_deref()
+    }
+
+    // The result, once every trustee has broadcast the same outcome as this one.
+    pub fn accepted(&self) -> Option<&[u64]> {
+        let own: &Outcome = self.outcomes.get(&self.trustee)?;
+        let agreed: bool = self.outcomes.len() == self.trustees.num_trustees()
+            && self.outcomes.values().all(|outcome| outcome == own);
+        agreed.then_some(own.2.as_slice())
+    }
+
+    // Counts the ballot of a trustee once its signature checks out against the key the trustee
+    // announced.
+    fn take_ballot(
+        &mut self,
+        trustee: PartyId,
+        signer: [u8; 32],
+        ciphertext: &[u8],
+        signature: Vec<u8>,
+    ) -> Result<(), GossipError> {
+        ResultSignature {
+            trustee,
+            signer,
+            signature,
+        }
+        .verify(&ballot_digest(trustee, ciphertext))?;
+        let ciphertext: Ciphertext = Ciphertext::from_bytes(ciphertext, &self.params)?;
+        let arrived: usize = self.ballots.len();
+        self.ballots.insert(trustee, (arrived, ciphertext));
+        Ok(())
+    }
+
+    // Turns away a key share, ballot or decryption share larger than a ciphertext under the
+    // parameters can be, before it is parsed.
+    fn check_len(&self, trustee: PartyId, bytes: &[u8]) -> Result<(), GossipError> {
+        let max: usize = Ballot::max_ciphertext_len(&self.params);
+        if bytes.len() > max {
+            return Err(GossipError::TooLarge {
+                trustee,
+                len: bytes.len(),
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    // Turns away a message for a key slot the trustee doesn't hold.
+    fn check_slot(&self, trustee: PartyId, slot: usize) -> Result<(), GossipError> {
+        if slot >= self.trustees.num_slots() || !self.trustees.holders(slot).contains(&trustee) {
+            return Err(GossipError::UnknownSlot { trustee, slot });
+        }
+        Ok(())
+    }
+
+    // The key this trustee signs its outcome with.
+    pub fn signer(&self) -> [u8; 32] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    // The result with the signatures of a quorum of the trustees who came to the same outcome
+    // as this one, each with the key it announced, once there are enough of them.
+    pub fn certificate(&self) -> Option<ResultCertificate> {
+        let (public_key_hash, tally_hash, result): &Outcome = self.outcomes.get(&self.trustee)?;
+        let signatures: Vec<ResultSignature> = self
+            .signatures
+            .values()
+            .filter(|signature| {
+                self.outcomes.get(&signature.trustee) == self.outcomes.get(&self.trustee)
+                    && self.signers.get(&signature.trustee) == Some(&signature.signer)
+            })
+            .cloned()
+            .collect();
+        let quorum: usize = self.trustees.threshold();
+        (signatures.len() >= quorum).then(|| ResultCertificate {
+            version: certification::CERTIFICATION_VERSION,
+            result: result.clone(),
+            public_key_hash: *public_key_hash,
+            tally_hash: *tally_hash,
+            quorum,
+            signatures,
+        })
+    }
+}
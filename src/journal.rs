@@ -0,0 +1,87 @@
+// The tally journal: the public statement of what was tallied.
+//
+// It binds the tally ciphertext to the parameters it was computed under and to the exact set
+// of ballots that went into it, through their Merkle root and count, and says whether the
+// tally was re-randomized, in which case it is no longer the exact sum of the ballots. The
+// format is versioned and readers refuse versions they don't know, so a change on one side
+// can't be silently misread on the other.
+
+use crate::merkle::Hash;
+use fhe::bfv::{BfvParameters, Ciphertext};
+use fhe_traits::Serialize as FheSerialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{error::Error, fmt};
+
+pub const JOURNAL_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum JournalError {
+    UnsupportedVersion(u32),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "journal version {v} is not supported, expected {JOURNAL_VERSION}"
+                )
+            }
+            JournalError::Json(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for JournalError {}
+
+pub fn params_hash(params: &BfvParameters) -> Hash {
+    Sha256::digest(params.to_bytes()).into()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TallyJournal {
+    pub version: u32,
+    #[serde(with = "hex::serde")]
+    pub params_hash: Hash,
+    #[serde(with = "hex::serde")]
+    pub ballots_root: Hash,
+    pub num_ballots: usize,
+    #[serde(with = "hex::serde")]
+    pub tally_bytes: Vec<u8>,
+    #[serde(default)]
+    pub rerandomized: bool,
+}
+
+impl TallyJournal {
+    pub fn new(
+        params: &BfvParameters,
+        ballots_root: Hash,
+        num_ballots: usize,
+        tally: &Ciphertext,
+        rerandomized: bool,
+    ) -> Self {
+        TallyJournal {
+            version: JOURNAL_VERSION,
+            params_hash: params_hash(params),
+            ballots_root,
+            num_ballots,
+            tally_bytes: tally.to_bytes(),
+            rerandomized,
+        }
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<Self, JournalError> {
+        let journal: TallyJournal = serde_json::from_slice(bytes).map_err(JournalError::Json)?;
+        if journal.version != JOURNAL_VERSION {
+            return Err(JournalError::UnsupportedVersion(journal.version));
+        }
+        Ok(journal)
+    }
+}
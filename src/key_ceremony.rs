@@ -0,0 +1,514 @@
+// The distributed key generation, as an explicit state machine.
+//
+// The coordinator first publishes the common random polynomial (CRP). Each party then commits to
+// a public key share generated from it by sending its hash, see `KeyShareMessage::commitment`.
+// Once every party has committed, each reveals its share, which must match its commitment, and
+// once every share is in they are summed into the public key the voters encrypt to:
+//
+//     AwaitingCrp -> CollectingCommitments -> CollectingShares -> Aggregated
+//
+// No share is revealed before every party is bound to its own, so a party can't wait for the
+// others' shares and pick one that cancels them out: it would have to break the hash to change
+// its share after committing.
+//
+// Every message is checked as it comes in rather than at the end, so a bad commitment or share is
+// pinned on the party that sent it: it must come from a known party, only once, in its round,
+// and it must have been generated from the CRP of this ceremony.
+//
+// Each share also carries a proof that it was honestly generated, see `share_proof.rs`. The
+// proofs are all checked together just before aggregating, and a share whose proof fails is
+// dropped so that its party can send it again.
+
+use crate::{
+    party::{self, Party},
+    share_proof::{BindingProof, ShareProof},
+};
+use fhe::{
+    bfv::{BfvParameters, PublicKey},
+    mbfv::{CommonRandomPoly, PublicKeyShare},
+};
+use fhe_traits::Serialize;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, error::Error, fmt, mem, sync::Arc};
+
+pub type PartyId = usize;
+
+pub type CrpHash = [u8; 32];
+
+pub type ShareCommitment = [u8; 32];
+
+const COMMITMENT_DOMAIN: &[u8] = b"fhe-workshop/key-share-commitment";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCeremonyState {
+    AwaitingCrp,
+    CollectingCommitments,
+    CollectingShares,
+    Aggregated,
+}
+
+#[derive(Debug)]
+pub enum CeremonyError {
+    WrongState(KeyCeremonyState),
+    UnknownParty(PartyId),
+    DuplicateShare(PartyId),
+    DuplicateCommitment(PartyId),
+    CrpMismatch(PartyId),
+    // The revealed share isn't the one the party committed to.
+    CommitmentMismatch(PartyId),
+    InvalidProof(PartyId),
+    TallyMismatch(PartyId),
+    NotHolder { trustee: PartyId, slot: usize },
+    // A share for a key slot past the last one.
+    UnknownSlot { trustee: PartyId, slot: usize },
+    InvalidResult,
+    // Good shares decrypt the tally to nonzero padding, so the tally itself is malformed.
+    InvalidTally,
+    MissingShares { received: usize, required: usize },
+    Fhe(fhe::Error),
+}
+
+impl fmt::Display for CeremonyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CeremonyError::WrongState(state) => {
+                write!(f, "message not expected while the ceremony is {state:?}")
+            }
+            CeremonyError::UnknownParty(party) => write!(f, "party {party} is not in the ceremony"),
+            CeremonyError::DuplicateShare(party) => write!(f, "party {party} already sent a share"),
+            CeremonyError::DuplicateCommitment(party) => {
+                write!(f, "party {party} already committed to a share")
+            }
+            CeremonyError::CrpMismatch(party) => {
+                write!(f, "party {party} generated its share from another CRP")
+            }
+            CeremonyError::CommitmentMismatch(party) => {
+                write!(f, "party {party} sent another share than it committed to")
+            }
+            CeremonyError::InvalidProof(party) => {
+                write!(f, "the proof of party {party}'s key share doesn't verify")
+            }
+            CeremonyError::TallyMismatch(party) => {
+                write!(f, "party {party} sent a share of another tally")
+            }
+            CeremonyError::NotHolder { trustee, slot } => {
+                write!(f, "party {trustee} doesn't hold key slot {slot}")
+            }
+            CeremonyError::UnknownSlot { trustee, slot } => {
+                write!(
+                    f,
+                    "party {trustee} sent a share for key slot {slot}, which doesn't exist"
+                )
+            }
+            CeremonyError::InvalidResult => write!(
+                f,
+                "the decrypted tally is garbage and the bad shares couldn't be singled out, or the \
+                 tally itself is malformed"
+            ),
+            CeremonyError::InvalidTally => write!(
+                f,
+                "the decrypted tally is garbage whichever trustees' shares are used, so the tally \
+                 itself is malformed, e.g. by a ballot with nonzero padding"
+            ),
+            CeremonyError::MissingShares { received, required } => {
+                write!(f, "received {received} of the {required} shares")
+            }
+            CeremonyError::Fhe(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for CeremonyError {}
+
+impl From<fhe::Error> for CeremonyError {
+    fn from(e: fhe::Error) -> Self {
+        CeremonyError::Fhe(e)
+    }
+}
+
+// Hashes the CRP, so a share can say which CRP it was generated from without repeating it.
+pub fn crp_hash(crp: &CommonRandomPoly) -> CrpHash {
+    Sha256::digest(crp.to_bytes()).into()
+}
+
+// Broadcast by the coordinator to open the ceremony.
+#[derive(Clone)]
+pub struct CrpMessage {
+    pub crp: CommonRandomPoly,
+}
+
+// Sent by each party in reply to the CRP, before any share is revealed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyShareCommitment {
+    pub party: PartyId,
+    pub crp_hash: CrpHash,
+    pub commitment: ShareCommitment,
+}
+
+// Sent by each party once every party has committed, see `KeyShareCommitment`.
+#[derive(Clone)]
+pub struct KeyShareMessage {
+    pub party: PartyId,
+    pub crp_hash: CrpHash,
+    pub share: PublicKeyShare,
+    pub proof: Vec<u8>,
+}
+
+impl KeyShareMessage {
+    // Sends the public key share `from` made along with its secret key share, see `Party::new`.
+    // The message takes the share over, it isn't copied.
+    pub fn new(
+        party: PartyId,
+        from: &Party,
+        share: PublicKeyShare,
+        crp: &CommonRandomPoly,
+        prover: &dyn ShareProof,
+    ) -> Result<Self, fhe::Error> {
+        let proof: Vec<u8> = prover.prove(party, from, &share, crp)?;
+        Ok(KeyShareMessage {
+            party,
+            crp_hash: crp_hash(crp),
+            share,
+            proof,
+        })
+    }
+
+    // The commitment to send first: the hash of the party, the CRP and the share.
+    pub fn commitment(&self) -> KeyShareCommitment {
+        KeyShareCommitment {
+            party: self.party,
+            crp_hash: self.crp_hash,
+            commitment: share_commitment(self.party, &self.crp_hash, &self.share),
+        }
+    }
+}
+
+pub fn share_commitment(
+    party: PartyId,
+    crp_hash: &CrpHash,
+    share: &PublicKeyShare,
+) -> ShareCommitment {
+    let mut hasher = Sha256::new();
+    hasher.update(COMMITMENT_DOMAIN);
+    hasher.update((party as u64).to_le_bytes());
+    hasher.update(crp_hash);
+    hasher.update(share.to_bytes());
+    hasher.finalize().into()
+}
+
+pub struct KeyCeremony {
+    params: Arc<BfvParameters>,
+    num_parties: usize,
+    state: KeyCeremonyState,
+    crp: Option<(CommonRandomPoly, CrpHash)>,
+    commitments: BTreeMap<PartyId, ShareCommitment>,
+    // Each share with its proof.
+    shares: BTreeMap<PartyId, (PublicKeyShare, Vec<u8>)>,
+    verifier: Box<dyn ShareProof>,
+    public_key: Option<PublicKey>,
+}
+
+impl KeyCeremony {
+    // A ceremony between the parties `0..num_parties`.
+    pub fn new(params: &Arc<BfvParameters>, num_parties: usize) -> Self {
+        KeyCeremony {
+            params: params.clone(),
+            num_parties,
+            state: KeyCeremonyState::AwaitingCrp,
+            crp: None,
+            commitments: BTreeMap::new(),
+            shares: BTreeMap::new(),
+            verifier: Box::new(BindingProof),
+            public_key: None,
+        }
+    }
+
+    // Checks the proofs of the shares with another proof system than `BindingProof`.
+    pub fn with_share_proof(mut self, verifier: impl ShareProof + 'static) -> Self {
+        self.verifier = Box::new(verifier);
+        self
+    }
+
+    pub fn state(&self) -> KeyCeremonyState {
+        self.state
+    }
+
+    // Samples the CRP and opens the ceremony. The returned message goes to every party.
+    pub fn publish_crp<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<CrpMessage, CeremonyError> {
+        self.expect(KeyCeremonyState::AwaitingCrp)?;
+        let crp: CommonRandomPoly = CommonRandomPoly::new(&self.params, rng)?;
+        self.open_with_crp(crp.clone())?;
+        Ok(CrpMessage { crp })
+    }
+
+    // Opens the ceremony with a CRP the parties agreed on some other way, e.g. derived from a
+    // shared seed when there is no coordinator to publish one, see `gossip`.
+    pub fn open_with_crp(&mut self, crp: CommonRandomPoly) -> Result<(), CeremonyError> {
+        self.expect(KeyCeremonyState::AwaitingCrp)?;
+        let hash: CrpHash = crp_hash(&crp);
+        self.crp = Some((crp, hash));
+        self.state = KeyCeremonyState::CollectingCommitments;
+        Ok(())
+    }
+
+    pub fn crp(&self) -> Option<&CommonRandomPoly> {
+        self.crp.as_ref().map(|(crp, _)| crp)
+    }
+
+    // Records a party's commitment to its share. Once every party has committed, the shares can
+    // be revealed.
+    pub fn commit(&mut self, message: KeyShareCommitment) -> Result<(), CeremonyError> {
+        self.expect(KeyCeremonyState::CollectingCommitments)?;
+        if message.party >= self.num_parties {
+            return Err(CeremonyError::UnknownParty(message.party));
+        }
+        if self.commitments.contains_key(&message.party) {
+            return Err(CeremonyError::DuplicateCommitment(message.party));
+        }
+        if self.crp.as_ref().map(|(_, hash)| hash) != Some(&message.crp_hash) {
+            return Err(CeremonyError::CrpMismatch(message.party));
+        }
+        self.commitments.insert(message.party, message.commitment);
+        if self.commitments.len() == self.num_parties {
+            self.state = KeyCeremonyState::CollectingShares;
+        }
+        Ok(())
+    }
+
+    pub fn has_commitment(&self, party: PartyId) -> bool {
+        self.commitments.contains_key(&party)
+    }
+
+    // Validates a public key share against the party's commitment and holds on to it until every
+    // party has sent one.
+    pub fn receive(&mut self, message: KeyShareMessage) -> Result<(), CeremonyError> {
+        self.expect(KeyCeremonyState::CollectingShares)?;
+        if message.party >= self.num_parties {
+            return Err(CeremonyError::UnknownParty(message.party));
+        }
+        if self.shares.contains_key(&message.party) {
+            return Err(CeremonyError::DuplicateShare(message.party));
+        }
+        if self.crp.as_ref().map(|(_, hash)| hash) != Some(&message.crp_hash) {
+            return Err(CeremonyError::CrpMismatch(message.party));
+        }
+        if self.commitments.get(&message.party)
+            != Some(&share_commitment(
+                message.party,
+                &message.crp_hash,
+                &message.share,
+            ))
+        {
+            return Err(CeremonyError::CommitmentMismatch(message.party));
+        }
+        self.shares
+            .insert(message.party, (message.share, message.proof));
+        Ok(())
+    }
+
+    pub fn has_share(&self, party: PartyId) -> bool {
+        self.shares.contains_key(&party)
+    }
+
+    pub fn received(&self) -> usize {
+        match self.state {
+            // The shares were used up by the aggregation.
+            KeyCeremonyState::Aggregated => self.num_parties,
+            _ => self.shares.len(),
+        }
+    }
+
+    pub fn required(&self) -> usize {
+        self.num_parties
+    }
+
+    // Sums the shares into the public key, once every party has sent theirs and every proof
+    // verifies. fhe.rs only aggregates shares it owns, so the ceremony hands over the ones it
+    // holds rather than cloning each of them.
+    //
+    // If any proof fails, the shares with a failing proof are dropped, the first of their
+    // parties is reported and the ceremony goes on collecting shares. A party can only send the
+    // share it committed to again.
+    pub fn aggregate(&mut self) -> Result<&PublicKey, CeremonyError> {
+        self.expect(KeyCeremonyState::CollectingShares)?;
+        if self.received() < self.required() {
+            return Err(CeremonyError::MissingShares {
+                received: self.received(),
+                required: self.required(),
+            });
+        }
+        let crp: &CommonRandomPoly = self
+            .crp()
+            .expect("the CRP is published before shares are collected");
+        let invalid: Vec<PartyId> = self
+            .shares
+            .iter()
+            .filter(|(&party, (share, proof))| !self.verifier.verify(party, share, crp, proof))
+            .map(|(&party, _)| party)
+            .collect();
+        if let Some(&party) = invalid.first() {
+            for party in &invalid {
+                self.shares.remove(party);
+            }
+            return Err(CeremonyError::InvalidProof(party));
+        }
+        let pk: PublicKey = party::aggregate_public_key(
+            mem::take(&mut self.shares)
+                .into_values()
+                .map(|(share, _)| share),
+        )?;
+        self.state = KeyCeremonyState::Aggregated;
+        Ok(self.public_key.insert(pk))
+    }
+
+    pub fn public_key(&self) -> Option<&PublicKey> {
+        self.public_key.as_ref()
+    }
+
+    fn expect(&self, state: KeyCeremonyState) -> Result<(), CeremonyError> {
+        if self.state != state {
+            return Err(CeremonyError::WrongState(self.state));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params;
+    use rand::thread_rng;
+
+    // A ceremony with its CRP published, and the share message of each of its parties.
+    fn opened(num_parties: usize) -> (KeyCeremony, Vec<KeyShareMessage>) {
+        let params: Arc<BfvParameters> = params::build(
+            params::DEGREE,
+            params::plaintext_modulus(1),
+            &params::MODULI,
+        )
+        .unwrap();
+        let mut ceremony: KeyCeremony = KeyCeremony::new(&params, num_parties);
+        let CrpMessage { crp } = ceremony.publish_crp(&mut thread_rng()).unwrap();
+        let messages: Vec<KeyShareMessage> = (0..num_parties)
+            .map(|party| {
+                let (key, share): (Party, PublicKeyShare) =
+                    Party::new(&params, &crp, &mut thread_rng()).unwrap();
+                KeyShareMessage::new(party, &key, share, &crp, &BindingProof).unwrap()
+            })
+            .collect();
+        (ceremony, messages)
+    }
+
+    #[test]
+    fn shares_wait_for_every_commitment() {
+        let (mut ceremony, messages) = opened(3);
+        ceremony.commit(messages[0].commitment()).unwrap();
+        ceremony.commit(messages[1].commitment()).unwrap();
+        assert!(matches!(
+            ceremony.receive(messages[0].clone()),
+            Err(CeremonyError::WrongState(
+                KeyCeremonyState::CollectingCommitments
+            ))
+        ));
+        ceremony.commit(messages[2].commitment()).unwrap();
+        assert_eq!(ceremony.state(), KeyCeremonyState::CollectingShares);
+        assert!(matches!(
+            ceremony.commit(messages[2].commitment()),
+            Err(CeremonyError::WrongState(
+                KeyCeremonyState::CollectingShares
+            ))
+        ));
+        for message in messages {
+            ceremony.receive(message).unwrap();
+        }
+        ceremony.aggregate().unwrap();
+        assert_eq!(ceremony.state(), KeyCeremonyState::Aggregated);
+    }
+
+    #[test]
+    fn duplicate_and_unknown_commitments_are_refused() {
+        let (mut ceremony, messages) = opened(2);
+        ceremony.commit(messages[0].commitment()).unwrap();
+        assert!(matches!(
+            ceremony.commit(messages[0].commitment()),
+            Err(CeremonyError::DuplicateCommitment(0))
+        ));
+        let stranger: KeyShareCommitment = KeyShareCommitment {
+            party: 2,
+            ..messages[1].commitment()
+        };
+        assert!(matches!(
+            ceremony.commit(stranger),
+            Err(CeremonyError::UnknownParty(2))
+        ));
+        let other_crp: KeyShareCommitment = KeyShareCommitment {
+            crp_hash: [0; 32],
+            ..messages[1].commitment()
+        };
+        assert!(matches!(
+            ceremony.commit(other_crp),
+            Err(CeremonyError::CrpMismatch(1))
+        ));
+        assert_eq!(ceremony.state(), KeyCeremonyState::CollectingCommitments);
+    }
+
+    #[test]
+    fn share_must_match_its_commitment() {
+        let (mut ceremony, messages) = opened(2);
+        for message in &messages {
+            ceremony.commit(message.commitment()).unwrap();
+        }
+        // Party 0 reveals party 1's share as its own.
+        let swapped: KeyShareMessage = KeyShareMessage {
+            party: 0,
+            ..messages[1].clone()
+        };
+        assert!(matches!(
+            ceremony.receive(swapped),
+            Err(CeremonyError::CommitmentMismatch(0))
+        ));
+        assert!(!ceremony.has_share(0));
+        ceremony.receive(messages[0].clone()).unwrap();
+        assert!(matches!(
+            ceremony.receive(messages[0].clone()),
+            Err(CeremonyError::DuplicateShare(0))
+        ));
+    }
+
+    #[test]
+    fn share_with_failing_proof_is_dropped_and_can_be_sent_again() {
+        let (mut ceremony, messages) = opened(2);
+        for message in &messages {
+            ceremony.commit(message.commitment()).unwrap();
+        }
+        ceremony.receive(messages[0].clone()).unwrap();
+        // The commitment covers the share but not its proof.
+        ceremony
+            .receive(KeyShareMessage {
+                proof: vec![0; 32],
+                ..messages[1].clone()
+            })
+            .unwrap();
+        assert!(matches!(
+            ceremony.aggregate(),
+            Err(CeremonyError::InvalidProof(1))
+        ));
+        assert_eq!(ceremony.state(), KeyCeremonyState::CollectingShares);
+        assert!(ceremony.has_share(0));
+        assert!(!ceremony.has_share(1));
+        assert!(matches!(
+            ceremony.aggregate(),
+            Err(CeremonyError::MissingShares {
+                received: 1,
+                required: 2
+            })
+        ));
+        ceremony.receive(messages[1].clone()).unwrap();
+        ceremony.aggregate().unwrap();
+        assert!(ceremony.public_key().is_some());
+    }
+}
@@ -0,0 +1,57 @@
+pub mod airgap;
+pub mod armor;
+pub mod artifacts;
+pub mod audit;
+pub mod ballot;
+pub mod candidates;
+pub mod certification;
+pub mod challenge;
+pub mod checkpoint;
+pub mod codec;
+pub mod decryption_ceremony;
+pub mod delegation;
+pub mod distribution;
+pub mod e3;
+#[cfg(feature = "eth")]
+pub mod eth;
+pub mod gossip;
+pub mod journal;
+pub mod key_ceremony;
+pub mod lifecycle;
+pub mod manifest;
+pub mod merkle;
+pub mod mnemonic;
+pub mod parallel;
+pub mod params;
+pub mod party;
+#[cfg(feature = "async")]
+pub mod pipeline;
+pub mod precinct;
+pub mod privacy;
+pub mod projection;
+#[cfg(feature = "proto")]
+pub mod proto;
+pub mod questions;
+pub mod report;
+pub mod reshare;
+pub mod rounds;
+pub mod seats;
+pub mod share_proof;
+pub mod signer;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod stats;
+pub mod store;
+pub mod tally;
+pub mod threshold;
+pub mod tracking;
+pub mod transcript;
+pub mod transparency;
+pub mod turnout;
+pub mod validity;
+pub mod voter;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wire;
+pub mod write_in;
+pub mod zero_pool;
@@ -0,0 +1,136 @@
+// The lifecycle of an election: initialized, open for voting, closed, finalized.
+//
+// The state is kept in a small JSON file next to the election, so each step can be run as a
+// separate command by the election administrator. Each step only follows the one before it:
+// voting can't open twice, nor close before it opened, and a finalized election is locked for
+// good. Finalizing is what triggers the tally.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElectionPhase {
+    Initialized,
+    Open,
+    Closed,
+    Finalized,
+}
+
+#[derive(Debug)]
+pub enum LifecycleError {
+    WrongPhase {
+        expected: ElectionPhase,
+        actual: ElectionPhase,
+    },
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LifecycleError::WrongPhase { expected, actual } => {
+                write!(f, "election is {actual:?}, expected {expected:?}")
+            }
+            LifecycleError::Io(e) => write!(f, "{e}"),
+            LifecycleError::Json(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for LifecycleError {}
+
+impl From<io::Error> for LifecycleError {
+    fn from(e: io::Error) -> Self {
+        LifecycleError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LifecycleError {
+    fn from(e: serde_json::Error) -> Self {
+        LifecycleError::Json(e)
+    }
+}
+
+// The persisted state of an election, along with its configuration `C`.
+//
+// Timestamps are seconds since the Unix epoch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElectionState<C> {
+    pub phase: ElectionPhase,
+    pub config: C,
+    pub initialized_at: u64,
+    pub opened_at: Option<u64>,
+    pub closed_at: Option<u64>,
+    pub finalized_at: Option<u64>,
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+impl<C: Serialize + DeserializeOwned> ElectionState<C> {
+    pub fn new(config: C) -> Self {
+        ElectionState {
+            phase: ElectionPhase::Initialized,
+            config,
+            initialized_at: now(),
+            opened_at: None,
+            closed_at: None,
+            finalized_at: None,
+        }
+    }
+
+    // Opens voting. Ballots are only accepted while the election is open.
+    pub fn open(&mut self) -> Result<(), LifecycleError> {
+        self.advance(ElectionPhase::Initialized, ElectionPhase::Open)?;
+        self.opened_at = Some(now());
+        Ok(())
+    }
+
+    pub fn close(&mut self) -> Result<(), LifecycleError> {
+        self.advance(ElectionPhase::Open, ElectionPhase::Closed)?;
+        self.closed_at = Some(now());
+        Ok(())
+    }
+
+    // Locks the election once it is closed. Nothing can change it afterwards.
+    pub fn finalize(&mut self) -> Result<(), LifecycleError> {
+        self.advance(ElectionPhase::Closed, ElectionPhase::Finalized)?;
+        self.finalized_at = Some(now());
+        Ok(())
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.phase == ElectionPhase::Open
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LifecycleError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), LifecycleError> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn advance(&mut self, from: ElectionPhase, to: ElectionPhase) -> Result<(), LifecycleError> {
+        if self.phase != from {
+            return Err(LifecycleError::WrongPhase {
+                expected: from,
+                actual: self.phase,
+            });
+        }
+        self.phase = to;
+        Ok(())
+    }
+}
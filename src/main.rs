@@ -1,21 +1,87 @@
+mod dashboard;
+mod feed;
+mod file_drop;
+#[cfg(feature = "p2p")]
+mod p2p;
+
+#[cfg(feature = "eth")]
+use alloy::primitives::{Address, TxHash};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use fhe::{
-    bfv::{self, Ciphertext, Encoding, Plaintext, PublicKey, SecretKey},
-    mbfv::{AggregateIter, CommonRandomPoly, DecryptionShare, PublicKeyShare},
+    bfv::{BfvParameters, Ciphertext, PublicKey},
+    mbfv::{DecryptionShare, PublicKeyShare},
+};
+use fhe_traits::Serialize;
+#[cfg(feature = "eth")]
+use fhe_workshop::eth::{self, Commitment};
+use fhe_workshop::{
+    airgap::{DecryptionRequest, ShareResponse},
+    artifacts::{Artifacts, PublishedResult, ARTIFACTS_VERSION},
+    audit::{self, AuditReport},
+    ballot::{Ballot, BallotBox, BallotError, DuplicatePolicy, Nullifier},
+    candidates::{Candidate, Candidates, Standings, WriteInRule},
+    certification::{self, Certification, CERTIFICATION_FILE},
+    challenge::{self, Opening},
+    checkpoint::{self, Checkpoint, CheckpointError, Phase, Seed},
+    codec::{self, CodecError, Compression},
+    decryption_ceremony::{self, DecryptionCeremony, DecryptionShareMessage, Progress, TallyHash},
+    distribution::{Distribution, Turnout},
+    e3::E3Transcript,
+    gossip::{self, Session},
+    journal::{self, TallyJournal},
+    key_ceremony::{self, CrpMessage, KeyCeremony, KeyShareCommitment, KeyShareMessage, PartyId},
+    lifecycle::{ElectionPhase, ElectionState},
+    manifest::BallotManifest,
+    merkle::{self, Hash, InclusionProof, MerkleTree},
+    mnemonic,
+    parallel::{self, prelude::*},
+    params::{self, ModuliChain},
+    party::Party,
+    precinct::{self, PrecinctId, PrecinctStores, PrecinctTally, RunningTally},
+    projection::{self, Projection},
+    questions::Questions,
+    report::{ArtifactSizes, PhaseTiming, RunParams, RunReport},
+    reshare::{self, Reshare},
+    seats::{self, Allocation, SeatMethod},
+    share_proof::BindingProof,
+    signer::{self, ChannelKey, ChannelKeys, LocalSigner},
+    snapshot::{self, Manifest, Sources},
+    stats::{IoSplit, ItemStats, Latencies},
+    store::{CiphertextStore, FileStore, MemoryStore, StoreError},
+    tally,
+    threshold::TrusteeSet,
+    tracking::TrackingCode,
+    transcript::{self, Replay, Transcript, TranscriptEntry, TranscriptError, TranscriptMessage},
+    transparency::{self, Event, LogEntry, LogError, TransparencyLog},
+    turnout::{self, TurnoutCounter},
+    validity::{BallotProof, ProofCheck, TrustedProof},
+    voter::{self, Choice},
+    write_in::{Reveal, WriteIn, WriteIns},
+    zero_pool::ZeroPool,
+};
+#[cfg(feature = "parallel")]
+use indicatif::ParallelProgressIterator;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressIterator, ProgressStyle};
+use rand::{
+    rngs::StdRng,
+    seq::{index, SliceRandom},
+    thread_rng, CryptoRng, Rng, RngCore, SeedableRng,
 };
-use fhe_traits::{FheDecoder, FheEncoder, FheEncrypter};
-use indicatif::{ProgressBar, ProgressStyle};
-use rand::{distributions::Uniform, prelude::Distribution, thread_rng};
-use rayon::prelude::*;
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
     error::Error,
-    sync::Arc,
+    fs::{self, File},
+    io, mem,
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
-
-struct Party {
-    sk_share: SecretKey,
-    pk_share: PublicKeyShare,
-}
+use tracing::{debug, error, field, info, info_span, warn, Span};
+use tracing_subscriber::EnvFilter;
 
 // This example demonstrates a simple secret ballot system using the combination of
 // Fully Homomorphic Encryption (FHE) and threshold cryptography (a multi-party computation).
@@ -38,18 +104,989 @@ struct Party {
 // This example is designed to demonstrate the concepts of FHE and threshold cryptography
 // and is not intended to be used in a production environment.
 
+#[derive(Parser)]
+#[command(version, about = "Practical FHE Workshop: Secret Ballot")]
+struct Cli {
+    /// How log lines are written: `pretty` for people, `json` for log pipelines.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Pretty)]
+    log_format: LogFormat,
+
+    /// Hide the progress bars, e.g. when running in CI.
+    #[arg(long, global = true)]
+    no_progress: bool,
+
+    /// Show a terminal dashboard of the run instead of log lines and progress bars.
+    #[arg(long, global = true)]
+    tui: bool,
+
+    /// Serve a live feed of the run's progress on this address, with a dashboard page for a
+    /// browser at `/dashboard`. Only counts are sent, never votes.
+    #[arg(long, global = true)]
+    feed: Option<SocketAddr>,
+
+    /// Run with parameters giving less than 128 bits of security rather than refusing to.
+    #[arg(long, global = true)]
+    allow_insecure: bool,
+
+    /// Number of threads to run the phases on. Defaults to one per core.
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// Pin each thread to a core of its own, so runs are comparable across machines.
+    #[arg(long, global = true)]
+    pin_threads: bool,
+
+    /// Write a JSON report of the run (parameters, timings, sizes and tally) to this file.
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Write the public artifacts of the election to this directory, for `verify`.
+    #[arg(long)]
+    publish: Option<PathBuf>,
+
+    /// Write the trustees' signing keys to this file, for `verify --roster`. Hand it to
+    /// auditors separately from the artifacts.
+    #[arg(long)]
+    roster: Option<PathBuf>,
+
+    /// Number of voters, who all cast a ballot unless `--turnout` is lower.
+    #[arg(long, default_value_t = 1000)]
+    votes: usize,
+
+    /// Number of parties sharing the key.
+    #[arg(long, default_value_t = 1000)]
+    parties: usize,
+
+    /// Number of parties needed to decrypt. Defaults to all of them.
+    #[arg(long)]
+    threshold: Option<usize>,
+
+    /// Keep the ciphertexts of the ballots in a store, as `--store` would, in a run estimated to
+    /// take more memory than this, e.g. `512M` or `8G`, and refuse to start one that still would.
+    #[arg(long, value_parser = parse_size)]
+    max_memory: Option<u64>,
+
+    /// Plaintext modulus, large enough for the tally. Defaults to the smallest prime from a
+    /// table that fits the number of votes.
+    #[arg(long)]
+    plaintext_modulus: Option<u64>,
+
+    /// Bit sizes of the ciphertext moduli, comma separated, e.g. `60,60,60`. Defaults to a
+    /// single 54 bit modulus, all an election that only adds ballots needs.
+    #[arg(long, value_delimiter = ',', conflicts_with = "depth")]
+    moduli_sizes: Vec<usize>,
+
+    /// Pick the ciphertext moduli for this many multiplications of ciphertexts.
+    #[arg(long)]
+    depth: Option<usize>,
+
+    /// Fraction of the parties that never submit a decryption share.
+    #[arg(long, default_value_t = 0.0)]
+    dropout_rate: f64,
+
+    /// Number of parties that submit garbage decryption shares. These are caught, but a share
+    /// shifted only in the vote slots would not be: it gives a wrong result that looks clean.
+    #[arg(long, default_value_t = 0)]
+    byzantine: usize,
+
+    /// Number of precincts the voters are spread over.
+    #[arg(long, default_value_t = 1)]
+    precincts: PrecinctId,
+
+    /// Also decrypt the total of every precinct with at least this many ballots.
+    #[arg(long)]
+    precinct_min_ballots: Option<usize>,
+
+    /// What the voters vote on.
+    #[arg(long, value_enum, default_value_t = VotingMode::YesNo)]
+    voting: VotingMode,
+
+    /// Number of options to spread votes over, with `--voting quadratic`.
+    #[arg(long, default_value_t = 4)]
+    options: usize,
+
+    /// Credits each voter can spend, with `--voting quadratic`.
+    #[arg(long, default_value_t = 100)]
+    credits: u64,
+
+    /// JSON file listing the candidates, with `--voting candidates`.
+    #[arg(long)]
+    candidates: Option<PathBuf>,
+
+    /// Hand out this many seats to the candidates in proportion to their votes, with `--voting
+    /// candidates`.
+    #[arg(long)]
+    seats: Option<u64>,
+
+    /// How seats are handed out with `--seats`: dhondt or sainte-lague.
+    #[arg(long, default_value_t = SeatMethod::Dhondt)]
+    seat_method: SeatMethod,
+
+    /// Keep a hash-chained log of every protocol event in this file, for `verify-log`.
+    #[arg(long)]
+    log: Option<PathBuf>,
+
+    /// Record every message of the election in this file, for `transcript verify`.
+    #[arg(long)]
+    transcript: Option<PathBuf>,
+
+    /// Add an encryption of zero to the tally before it is published.
+    #[arg(long)]
+    rerandomize: bool,
+
+    /// Precompute an encryption of zero for every voter before voting opens, so that casting a
+    /// ballot only adds the vote to one.
+    #[arg(long)]
+    precompute: bool,
+
+    /// Refresh the parties' key shares this many times once voting closes, each time adding a
+    /// sharing of zero to them. The key doesn't change, only the shares.
+    #[arg(long, default_value_t = 0)]
+    refresh: usize,
+
+    /// Number of options of each question, comma separated, with `--voting questions`.
+    #[arg(long, value_delimiter = ',', default_value = "2,3")]
+    questions: Vec<usize>,
+
+    /// How the voters pick an option: `uniform`, `skewed:<p>`, `polarized`,
+    /// `weights:<w1,w2,...>` or `near-tie`.
+    #[arg(long, default_value = "uniform")]
+    distribution: Distribution,
+
+    /// Fraction of the voters who cast a ballot, either one for every precinct or one per
+    /// precinct, comma separated. Defaults to everyone.
+    #[arg(long, value_delimiter = ',')]
+    turnout: Vec<f64>,
+
+    /// Have the parties decrypt offline, exchanging signed request and response files in this
+    /// directory.
+    #[arg(long)]
+    airgap: Option<PathBuf>,
+
+    /// Append the ciphertexts of the ballots to segment files in this directory as they are
+    /// accepted, instead of holding them in memory.
+    #[arg(long)]
+    store: Option<PathBuf>,
+
+    /// Map the store's segment files into memory, deserializing each ciphertext in place as the
+    /// tally reaches it.
+    #[arg(long, requires = "store")]
+    mmap: bool,
+
+    /// Sum the ciphertexts of each precinct this many at a time, recording the sum of each
+    /// chunk in the checkpoint so that a tally cut short resumes from the last one.
+    #[arg(long, value_parser = clap::value_parser!(usize).range(1..))]
+    tally_chunk: Option<usize>,
+
+    /// Record the coordinator's progress in this directory, and resume from it if a run left
+    /// it behind.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the election over a grid of sizes and write the timings of every phase.
+    BenchSweep(Sweep),
+    /// Run a few small elections and project the time, memory and bandwidth of a larger one.
+    Project(ProjectArgs),
+    /// Check the artifacts published by a run against the published ballots root.
+    Verify(VerifyArgs),
+    /// Look up a ballot by the tracking code the ballot box gave its voter.
+    Track(TrackArgs),
+    /// Re-check every step of a run from its published artifacts alone, and report each check.
+    Audit(AuditArgs),
+    /// Step an election through its lifecycle, kept in a state file.
+    Election(ElectionArgs),
+    /// Check the hash chain of a transparency log, and the published artifacts against it.
+    VerifyLog(VerifyLogArgs),
+    /// Write the published artifacts of a run as the request, inputs and output of an Enclave E3.
+    E3Export(E3ExportArgs),
+    /// Bundle the artifacts, checkpoint, state file and transparency log of an election into a
+    /// single archive.
+    ExportState(ExportStateArgs),
+    /// Unpack an archive written by `export-state`, checking it against its manifest.
+    ImportState(ImportStateArgs),
+    /// Replay the transcript written with `--transcript`.
+    Transcript(TranscriptArgs),
+    /// Create or recover the mnemonic a trustee of `p2p` derives its key share from.
+    Trustee(TrusteeArgs),
+    /// Run one trustee of an election held over a gossip network, without a coordinator.
+    #[cfg(feature = "p2p")]
+    P2p(p2p::P2pArgs),
+    /// Run one trustee of an election held over a shared directory, without a coordinator.
+    FileDrop(file_drop::FileDropArgs),
+    /// Publish the commitments of a run to the `ElectionRegistry` contract, signed with the
+    /// private key in `ETH_PRIVATE_KEY`.
+    #[cfg(feature = "eth")]
+    PublishOnchain(OnchainArgs),
+    /// Check the published artifacts against the commitments in the `ElectionRegistry` contract.
+    #[cfg(feature = "eth")]
+    VerifyOnchain(OnchainArgs),
+}
+
+#[derive(Args)]
+struct E3ExportArgs {
+    /// Directory the artifacts were published to with `--publish`.
+    #[arg(long)]
+    artifacts: PathBuf,
+
+    /// Identifier of the E3.
+    #[arg(long, default_value_t = 0)]
+    e3_id: u64,
+
+    /// Number of ciphernodes needed to decrypt. Defaults to the whole committee.
+    #[arg(long)]
+    threshold: Option<usize>,
+
+    /// Number of ciphernodes in the committee, the parties of the run.
+    #[arg(long)]
+    committee: usize,
+
+    /// File to write the E3 transcript to.
+    #[arg(long)]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct ExportStateArgs {
+    /// Directory the artifacts were published to with `--publish`.
+    #[arg(long)]
+    artifacts: Option<PathBuf>,
+
+    /// Checkpoint directory of the coordinator, from `--checkpoint`.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// State file of the `election` subcommand.
+    #[arg(long)]
+    state: Option<PathBuf>,
+
+    /// Transparency log, from `--log`.
+    #[arg(long)]
+    log: Option<PathBuf>,
+
+    /// File to write the archive to.
+    #[arg(long)]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct ImportStateArgs {
+    /// Archive written by `export-state`.
+    #[arg(long)]
+    archive: PathBuf,
+
+    /// Directory to unpack the archive into. It must not exist yet.
+    #[arg(long)]
+    into: PathBuf,
+}
+
+#[derive(Args)]
+struct TranscriptArgs {
+    #[command(subcommand)]
+    action: TranscriptAction,
+}
+
+#[derive(Subcommand)]
+enum TranscriptAction {
+    /// Check the hash chain of a transcript, re-derive the public key, tally and result from
+    /// its messages and check them against the recorded ones.
+    Verify(TranscriptVerifyArgs),
+}
+
+#[derive(Args)]
+struct TranscriptVerifyArgs {
+    /// The transcript written with `--transcript`.
+    #[arg(long)]
+    transcript: PathBuf,
+}
+
+#[derive(Args)]
+struct TrusteeArgs {
+    #[command(subcommand)]
+    action: TrusteeAction,
+}
+
+#[derive(Subcommand)]
+enum TrusteeAction {
+    /// Print a fresh mnemonic. Set it in `TRUSTEE_MNEMONIC` when running `p2p`.
+    New,
+    /// Regenerate the key share of the mnemonic in `TRUSTEE_MNEMONIC` and print its hash.
+    Recover(RecoverArgs),
+    /// Hold the trustee's key share in a mock HSM, for `p2p --signer`. The share is derived
+    /// from `TRUSTEE_MNEMONIC` if set, and lost when the HSM stops otherwise.
+    Hsm(HsmArgs),
+    /// Print a fresh key pair for one end of the channel between `p2p --signer` and
+    /// `trustee hsm`. Set the secret key in `CHANNEL_KEY`, and give the public key to the other
+    /// end.
+    ChannelKey,
+}
+
+#[derive(Args)]
+struct RecoverArgs {
+    /// The trustee's number, from 0.
+    #[arg(long)]
+    trustee: usize,
+
+    /// Number of trustees in the election.
+    #[arg(long)]
+    trustees: usize,
+
+    /// Name of the election, the same for every trustee.
+    #[arg(long, default_value = "fhe-workshop")]
+    session: String,
+}
+
+#[derive(Args)]
+struct HsmArgs {
+    /// The trustee's number, from 0.
+    #[arg(long)]
+    trustee: usize,
+
+    /// Number of trustees in the election.
+    #[arg(long)]
+    trustees: usize,
+
+    /// Name of the election, the same for every trustee.
+    #[arg(long, default_value = "fhe-workshop")]
+    session: String,
+
+    /// Address to listen on. Only the trustee holding the secret key of `--peer` gets past the
+    /// handshake.
+    #[arg(long, default_value = "127.0.0.1:7070")]
+    listen: String,
+
+    /// The public channel key of the trustee, in hex, see `trustee channel-key`. This end's
+    /// secret key is read from `CHANNEL_KEY`.
+    #[arg(long, value_parser = signer::parse_channel_key)]
+    peer: ChannelKey,
+
+    /// File of the tally hashes, in hex, one per line, the HSM may compute decryption shares of.
+    /// A request for any other tally waits until its hash is added, so check the hash against
+    /// the tally first.
+    #[arg(long)]
+    approved: PathBuf,
+}
+
+#[cfg(feature = "eth")]
+#[derive(Args)]
+struct OnchainArgs {
+    /// Directory the artifacts were published to with `--publish`.
+    #[arg(long)]
+    artifacts: PathBuf,
+
+    /// JSON-RPC endpoint of an Ethereum node.
+    #[arg(long, default_value = "http://localhost:8545")]
+    rpc_url: String,
+
+    /// Address of the `ElectionRegistry` contract.
+    #[arg(long)]
+    contract: Address,
+}
+
+#[derive(Args)]
+struct VerifyLogArgs {
+    /// The log written with `--log`.
+    #[arg(long)]
+    log: PathBuf,
+
+    /// Directory the artifacts were published to with `--publish`, to compare with the log.
+    #[arg(long)]
+    artifacts: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ElectionArgs {
+    /// The election's state file.
+    #[arg(long, default_value = "election.json")]
+    state: PathBuf,
+
+    #[command(subcommand)]
+    action: ElectionAction,
+}
+
+#[derive(Subcommand)]
+enum ElectionAction {
+    /// Create the election and its state file.
+    Init(InitArgs),
+    /// Open voting.
+    Open,
+    /// Close voting.
+    Close,
+    /// Lock the election and run the tally.
+    Finalize,
+}
+
+#[derive(Args)]
+struct InitArgs {
+    /// Number of votes to cast.
+    #[arg(long, default_value_t = 1000)]
+    votes: usize,
+
+    /// Number of parties sharing the key.
+    #[arg(long, default_value_t = 1000)]
+    parties: usize,
+
+    /// Number of parties needed to decrypt. Defaults to all of them.
+    #[arg(long)]
+    threshold: Option<usize>,
+
+    /// Write the public artifacts of the election to this directory once finalized.
+    #[arg(long)]
+    publish: Option<PathBuf>,
+
+    /// Bit sizes of the ciphertext moduli, comma separated, e.g. `60,60,60`.
+    #[arg(long, value_delimiter = ',', conflicts_with = "depth")]
+    moduli_sizes: Vec<usize>,
+
+    /// Pick the ciphertext moduli for this many multiplications of ciphertexts.
+    #[arg(long)]
+    depth: Option<usize>,
+
+    /// JSON file listing the candidates to vote for, rather than a yes or no question.
+    #[arg(long)]
+    candidates: Option<PathBuf>,
+}
+
+// What `election init` records, for `election finalize` to run.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ElectionConfig {
+    votes: usize,
+    parties: usize,
+    threshold: Option<usize>,
+    publish: Option<PathBuf>,
+    // Missing from the state files of elections initialized before the moduli were configurable.
+    #[serde(default)]
+    moduli_sizes: Vec<usize>,
+    #[serde(default)]
+    depth: Option<usize>,
+    // The registry itself rather than its file, so the ballots keep the layout they were cast
+    // with.
+    #[serde(default)]
+    candidates: Option<Candidates>,
+}
+
+// The chain of ciphertext moduli picked with `--moduli-sizes` or `--depth`, if any.
+fn moduli_chain(sizes: &[usize], depth: Option<usize>) -> ModuliChain {
+    match depth {
+        Some(depth) => ModuliChain::Depth(depth),
+        None if sizes.is_empty() => ModuliChain::Workshop,
+        None => ModuliChain::Sizes(sizes.to_vec()),
+    }
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// Directory the artifacts were published to with `--publish`.
+    #[arg(long)]
+    artifacts: PathBuf,
+
+    /// The published ballots root, hex encoded.
+    #[arg(long)]
+    ballots_root: String,
+
+    /// JSON list of the trustees' signing keys, as written by `--roster`, to check the
+    /// signatures in `certification.json` against. Without it they are only checked against
+    /// the keys they name, which anyone could have made.
+    #[arg(long)]
+    roster: Option<PathBuf>,
+
+    /// How many trustees it takes to decrypt, the quorum `certification.json` needs. The
+    /// threshold in the published result is used otherwise.
+    #[arg(long)]
+    threshold: Option<usize>,
+}
+
+#[derive(Args)]
+struct TrackArgs {
+    /// The ballot's tracking code, e.g. `7K2M-Q9XD-4RTB-0WZE`.
+    code: TrackingCode,
+
+    /// Directory the artifacts were published to with `--publish`.
+    #[arg(long)]
+    artifacts: PathBuf,
+
+    /// The published ballots root, hex encoded. The root in the artifacts is used otherwise.
+    #[arg(long)]
+    ballots_root: Option<String>,
+}
+
+#[derive(Args)]
+struct AuditArgs {
+    /// Directory the artifacts were published to with `--publish`.
+    #[arg(long)]
+    artifacts: PathBuf,
+
+    /// File to write the report to, as JSON.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct Sweep {
+    /// Numbers of votes to run with, comma separated.
+    #[arg(long, value_delimiter = ',', default_value = "100,1000")]
+    votes: Vec<usize>,
+
+    /// Numbers of parties to run with, comma separated.
+    #[arg(long, value_delimiter = ',', default_value = "10,100")]
+    parties: Vec<usize>,
+
+    /// Polynomial degrees to run with, comma separated.
+    #[arg(long, value_delimiter = ',', default_value = "2048")]
+    degree: Vec<usize>,
+
+    /// Where to write the report.
+    #[arg(long, default_value = "sweep.csv")]
+    output: PathBuf,
+
+    #[arg(long, value_enum, default_value_t = SweepFormat::Csv)]
+    format: SweepFormat,
+}
+
+#[derive(Args)]
+struct ProjectArgs {
+    /// Numbers of votes of the calibration runs, comma separated.
+    #[arg(long, value_delimiter = ',', default_value = "100,200,400")]
+    votes: Vec<usize>,
+
+    /// Numbers of parties of the calibration runs, comma separated.
+    #[arg(long, value_delimiter = ',', default_value = "4,8,16")]
+    parties: Vec<usize>,
+
+    /// Polynomial degree of the calibration runs.
+    #[arg(long, default_value_t = params::DEGREE)]
+    degree: usize,
+
+    /// Number of votes to project to.
+    #[arg(long, default_value_t = 10_000_000)]
+    target_votes: usize,
+
+    /// Number of parties to project to.
+    #[arg(long, default_value_t = 50)]
+    target_parties: usize,
+
+    /// File to write the projection to, as JSON.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SweepFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum VotingMode {
+    YesNo,
+    Quadratic,
+    Questions,
+    Candidates,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Pretty,
+    Json,
+}
+
+// Sends log lines to stdout, or to the dashboard with `--tui`, filtered by `RUST_LOG`
+// (defaulting to `info`).
+fn init_tracing(format: LogFormat, tui: bool) {
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_target(false);
+    match format {
+        _ if tui => subscriber
+            .with_ansi(false)
+            .with_writer(dashboard::log_writer)
+            .init(),
+        LogFormat::Pretty => subscriber.init(),
+        LogFormat::Json => subscriber.json().with_current_span(true).init(),
+    }
+}
+
+// Parses a size in bytes, with an optional `K`, `M` or `G` suffix for powers of 1024.
+fn parse_size(size: &str) -> Result<u64, String> {
+    let (digits, unit): (&str, u64) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&size[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| format!("{size} is not a size, e.g. 512M or 8G"))
+}
+
+// A progress bar over the `len` items of a phase, with an ETA. Hidden with `--no-progress`,
+// and drawn as a gauge of the dashboard instead with `--tui`. With `--feed`, it is followed by
+// the live feed as well.
+fn progress_bar(len: usize, message: &'static str, show: bool) -> ProgressBar {
+    if dashboard::is_active() || !show {
+        let pb: ProgressBar =
+            ProgressBar::with_draw_target(Some(len as u64), ProgressDrawTarget::hidden())
+                .with_message(message);
+        dashboard::track(&pb);
+        feed::track(&pb);
+        return pb;
+    }
+    let pb: ProgressBar = ProgressBar::new(len as u64)
+        .with_style(
+            ProgressStyle::with_template(
+                "{msg:>12} [{bar:40}] {human_pos}/{human_len} ({per_sec}, eta {eta})",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+        )
+        .with_message(message);
+    feed::track(&pb);
+    pb
+}
+
+// Peak resident set size of the process in bytes. Only available on Linux.
+fn peak_rss() -> Option<u64> {
+    let status: String = fs::read_to_string("/proc/self/status").ok()?;
+    let line: &str = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+// Resets the peak resident set size, so each run of a sweep is measured on its own.
+fn reset_peak_rss() {
+    let _ = fs::write("/proc/self/clear_refs", "5");
+}
+
+// What each voter votes on, and how their vote is laid out in the slots of the plaintext.
+#[derive(Debug, Clone)]
+enum Voting {
+    // A single yes or no question where voters can also abstain, counted as
+    // `[against, for, abstain]`.
+    YesNo,
+    // Each voter spreads a budget of credits over the options, `v` votes for an option costing
+    // `v²` credits, counted as the votes for each option.
+    Quadratic { options: usize, credits: u64 },
+    // Several questions, each answered by picking one of its options, see `questions`.
+    Questions(Questions),
+    // A vote for one of the candidates, see `candidates`.
+    Candidates(Candidates),
+}
+
+impl Voting {
+    // The number of slots of the tally.
+    fn len(&self) -> usize {
+        match self {
+            Voting::YesNo => Choice::ALL.len(),
+            Voting::Quadratic { options, .. } => *options,
+            Voting::Questions(questions) => questions.num_slots(),
+            Voting::Candidates(candidates) => candidates.len(),
+        }
+    }
+
+    // The most a single ballot can add to a slot of the tally.
+    fn max_per_ballot(&self) -> u64 {
+        match self {
+            Voting::YesNo | Voting::Questions(_) | Voting::Candidates(_) => 1,
+            Voting::Quadratic { credits, .. } => (*credits as f64).sqrt() as u64,
+        }
+    }
+
+    // Picks the vote of a voter from the distribution, as the slots of its plaintext.
+    fn random_vote<R: Rng>(
+        &self,
+        distribution: &Distribution,
+        voter: usize,
+        rng: &mut R,
+    ) -> Vec<u64> {
+        match self {
+            Voting::YesNo => {
+                let choice: usize = distribution.pick(voter, Choice::ALL.len(), rng);
+                Choice::ALL[choice].slots().to_vec()
+            }
+            // Buys one vote at a time for an option until the next one is unaffordable.
+            Voting::Quadratic { options, credits } => {
+                let mut allocation: Vec<u64> = vec![0; *options];
+                loop {
+                    let option: usize = distribution.pick(voter, *options, rng);
+                    allocation[option] += 1;
+                    if voter::quadratic_cost(&allocation) > *credits {
+                        allocation[option] -= 1;
+                        return allocation;
+                    }
+                }
+            }
+            Voting::Questions(questions) => {
+                let answers: Vec<usize> = (0..questions.num_questions())
+                    .map(|question| distribution.pick(voter, questions.options(question), rng))
+                    .collect();
+                questions.slots(&answers).unwrap()
+            }
+            Voting::Candidates(candidates) => {
+                let mut slots: Vec<u64> = vec![0; candidates.len()];
+                slots[distribution.pick(voter, candidates.len(), rng)] = 1;
+                slots
+            }
+        }
+    }
+
+    fn encrypt<R: RngCore + CryptoRng>(
+        &self,
+        vote: &[u64],
+        pk: &PublicKey,
+        params: &Arc<BfvParameters>,
+        rng: &mut R,
+    ) -> Result<Ciphertext, fhe::Error> {
+        match self {
+            Voting::YesNo => {
+                let choice: Choice = Choice::ALL
+                    .into_iter()
+                    .find(|choice| choice.slots() == vote)
+                    .ok_or_else(|| fhe::Error::DefaultError("not a single choice".to_string()))?;
+                voter::encrypt_vote(choice, pk, params, rng)
+            }
+            Voting::Quadratic { credits, .. } => {
+                voter::encrypt_quadratic(vote, *credits, pk, params, rng)
+            }
+            Voting::Questions(questions) => {
+                let answers: Vec<usize> = questions.answers(vote).ok_or_else(|| {
+                    fhe::Error::DefaultError("not one answer per question".to_string())
+                })?;
+                voter::encrypt_answers(questions, &answers, pk, params, rng)
+            }
+            Voting::Candidates(candidates) => {
+                let candidate: &Candidate = candidates.choice(vote).ok_or_else(|| {
+                    fhe::Error::DefaultError("not a single candidate".to_string())
+                })?;
+                voter::encrypt_candidate(candidates, &candidate.id, pk, params, rng)
+            }
+        }
+    }
+}
+
+// Keeps the items of the voters who turned out.
+fn cast<T>(items: Vec<T>, turned_out: &[bool]) -> Vec<T> {
+    items
+        .into_iter()
+        .zip(turned_out)
+        .filter_map(|(item, &turned_out)| turned_out.then_some(item))
+        .collect()
+}
+
+// Adds up the votes slot by slot, for checking the decrypted tally.
+fn expected_tally<'a>(votes: impl IntoIterator<Item = &'a Vec<u64>>, len: usize) -> Vec<u64> {
+    let mut tally: Vec<u64> = vec![0; len];
+    for vote in votes {
+        for (total, votes) in tally.iter_mut().zip(vote) {
+            *total += votes;
+        }
+    }
+    tally
+}
+
+// The election to run.
+struct Election {
+    voting: Voting,
+    distribution: Distribution,
+    // The number of voters registered, of whom those turning out cast a ballot.
+    num_votes: usize,
+    num_parties: usize,
+    threshold: usize,
+    dropout_rate: f64,
+    byzantine: usize,
+    precincts: PrecinctId,
+    turnout: Turnout,
+    precinct_min_ballots: Option<usize>,
+    rerandomize: bool,
+    precompute: bool,
+    refresh: usize,
+    degree: usize,
+    plaintext_modulus: Option<u64>,
+    moduli: ModuliChain,
+    allow_insecure: bool,
+    max_memory: Option<u64>,
+    publish: Option<PathBuf>,
+    roster: Option<PathBuf>,
+    log: Option<PathBuf>,
+    transcript: Option<PathBuf>,
+    airgap: Option<PathBuf>,
+    store: Option<PathBuf>,
+    mmap: bool,
+    tally_chunk: Option<usize>,
+    checkpoint: Option<PathBuf>,
+    // The state file of an election stepped through `election open` and `election finalize`,
+    // whose phase decides whether voting is open, see `lifecycle.rs`.
+    lifecycle: Option<PathBuf>,
+    // The seats handed out in proportion to the votes, with `--voting candidates`.
+    seats: Option<(u64, SeatMethod)>,
+}
+
+// The size of a serialized artifact once framed and compressed with zstd.
+fn compressed_len(bytes: &[u8]) -> Result<usize, CodecError> {
+    Ok(codec::encode(bytes, Compression::Zstd)?.len())
+}
+
+// How many votes are encrypted at once, between updates of the progress bar.
+const ENCRYPTION_BATCH: usize = 4096;
+
+// How many encrypted batches may wait for the ballot box before encryption blocks.
+const PIPELINE_BATCHES: usize = 2;
+
+// The names the voters of the write-in bucket pick from, spelled a little differently at times.
+const WRITE_IN_NAMES: [&str; 4] = ["Grace Hopper", "grace  hopper", "Edsger Dijkstra", "Nobody"];
+
+// A rough estimate of the memory a run takes, given the size of a ciphertext in memory. Every
+// ballot holds two ciphertexts, the vote and the voter's turnout, see `turnout.rs`, and is held
+// until the end, and copied once more to be published. With `--store` the ciphertexts go to
+// disk as the ballots are accepted, and only the rest of each ballot is held, but publishing
+// still reads every ballot back whole. The tally is kept running as the ballots come in, or
+// streamed from disk, so it adds only a ciphertext per precinct. Each key slot holds a secret
+// key and a public key share, then a decryption share, each about the size of a ciphertext.
+fn estimate_memory(
+    num_votes: usize,
+    num_slots: usize,
+    ciphertext: u64,
+    store: bool,
+    publish: bool,
+) -> u64 {
+    // The voter's key, nullifier, signature and proof.
+    const BALLOT_OVERHEAD: u64 = 256;
+    let ballot: u64 = 2 * ciphertext + BALLOT_OVERHEAD;
+    let held: u64 = if store { BALLOT_OVERHEAD } else { ballot };
+    let published: u64 = if publish { ballot } else { 0 };
+    let ballots: u64 = num_votes as u64 * (held + published);
+    let keys: u64 = num_slots as u64 * ciphertext * 3 / 2;
+    ballots + keys
+}
+
+// Where the ballot box appends the ciphertexts of the ballots, see `BallotBox::with_store`: in
+// memory, or with `--store` in a directory of their own, `name`, for each precinct's votes and
+// one for the turnouts. With `replace`, a store left behind by an interrupted run is cleared
+// first, as it is filled again from the ballots replayed out of the checkpoint.
+fn ballot_store(
+    dir: Option<&Path>,
+    mmap: bool,
+    replace: bool,
+    name: &str,
+    params: &Arc<BfvParameters>,
+) -> Result<Box<dyn CiphertextStore>, StoreError> {
+    Ok(match dir {
+        Some(dir) => {
+            let dir: PathBuf = dir.join(name);
+            if replace && dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+            let store: FileStore = FileStore::create(dir, params)?;
+            Box::new(if mmap { store.with_mmap() } else { store })
+        }
+        None => Box::new(MemoryStore::new()),
+    })
+}
+
+// Times a phase, noting the peak memory use of the process by the time it was done.
+fn timing(name: &str, elapsed: Duration) -> PhaseTiming {
+    PhaseTiming::new(name, elapsed, peak_rss())
+}
+
+// Times a phase that worked through a list of items, with their statistics.
+fn item_timing(name: &str, elapsed: Duration, items: ItemStats) -> PhaseTiming {
+    info!(
+        phase = name,
+        items = items.count,
+        per_sec = items.per_sec,
+        bytes = items.bytes,
+        p50_us = items.latency.as_ref().map(|latency| latency.p50_us),
+        p95_us = items.latency.as_ref().map(|latency| latency.p95_us),
+        p99_us = items.latency.as_ref().map(|latency| latency.p99_us),
+        "throughput"
+    );
+    timing(name, elapsed).with_items(items)
+}
+
+// One line of the sweep report.
+#[derive(serde::Serialize)]
+struct SweepRow {
+    votes: usize,
+    parties: usize,
+    degree: usize,
+    threads: usize,
+    phase: String,
+    elapsed_ms: f64,
+    peak_rss_bytes: Option<u64>,
+    held_bytes: Option<u64>,
+    per_sec: Option<f64>,
+    p50_us: Option<f64>,
+    p95_us: Option<f64>,
+    p99_us: Option<f64>,
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let pb: ProgressBar = ProgressBar::new_spinner();
-    pb.set_style(ProgressStyle::default_spinner());
-    let main: Instant = Instant::now();
+    let cli: Cli = Cli::parse();
+    let dashboard: Option<JoinHandle<io::Result<()>>> = cli.tui.then(dashboard::start);
+    init_tracing(cli.log_format, cli.tui);
+    parallel::init(cli.threads, cli.pin_threads)?;
+    if cli.threads.is_some_and(|threads| threads > 1) && parallel::current_num_threads() == 1 {
+        warn!("built without the `parallel` feature, running on a single thread");
+    }
+    if let Some(addr) = cli.feed {
+        let addr: SocketAddr = feed::start(addr)?;
+        info!("dashboard at http://{addr}/dashboard");
+    }
+    let result: Result<(), Box<dyn Error>> = demo(cli);
+    feed::finish();
+    if let Some(handle) = dashboard {
+        dashboard::finish(handle)?;
+    }
+    result
+}
 
-    println!("\n\x1b[1mPractical FHE Workshop: Secret Ballot\x1b[0m");
+// Runs the subcommand given, or else the election.
+fn demo(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let show_progress: bool = !cli.no_progress;
+    let allow_insecure: bool = cli.allow_insecure;
+
+    match cli.command {
+        Some(Command::BenchSweep(sweep)) => {
+            return bench_sweep(&sweep, show_progress, allow_insecure)
+        }
+        Some(Command::Project(args)) => return project(&args, show_progress, allow_insecure),
+        Some(Command::Verify(args)) => return verify(&args),
+        Some(Command::Track(args)) => return track(&args),
+        Some(Command::Audit(args)) => return audit(&args),
+        Some(Command::Election(args)) => {
+            return manage_election(&args, show_progress, allow_insecure)
+        }
+        Some(Command::VerifyLog(args)) => return verify_log(&args),
+        Some(Command::E3Export(args)) => return e3_export(&args),
+        Some(Command::ExportState(args)) => return export_state(&args),
+        Some(Command::ImportState(args)) => return import_state(&args),
+        Some(Command::Transcript(args)) => return transcript(&args),
+        Some(Command::Trustee(args)) => return trustee(&args),
+        #[cfg(feature = "p2p")]
+        Some(Command::P2p(args)) => return p2p::run(&args),
+        Some(Command::FileDrop(args)) => return file_drop::run(&args),
+        #[cfg(feature = "eth")]
+        Some(Command::PublishOnchain(args)) => return publish_onchain(&args),
+        #[cfg(feature = "eth")]
+        Some(Command::VerifyOnchain(args)) => return verify_onchain(&args),
+        None => {}
+    }
+
+    info!("Practical FHE Workshop: Secret Ballot");
 
     // The number of votes that will be cast.
     //
-    // Try changing this number to see how the system scales with the number of voters.
-    let num_votes: usize = 1000;
-    println!("  \x1b[1mVotes:\x1b[0m\t\t{num_votes}");
+    // Try changing this number (with `--votes`) to see how the system scales with the number
+    // of voters.
+    let num_votes: usize = cli.votes;
 
     // The number of parties that will generate a shared key and decrypt the result.
     //
@@ -57,17 +1094,1246 @@ fn main() -> Result<(), Box<dyn Error>> {
     // collaborate to decrypt the result. In this example, we obviously control all
     // of the parties, but we'll still simulate the process.
     //
-    // Try changing this number to see how the system scales with the number of parties.
-    let num_parties: usize = 1000;
-    println!("  \x1b[1mParties:\x1b[0m\t\t{num_parties}");
+    // Try changing this number (with `--parties`) to see how the system scales with the number
+    // of parties.
+    let num_parties: usize = cli.parties;
+
+    // The number of parties that must take part in decrypting the result, and the fraction of
+    // them that we'll pretend never show up.
+    //
+    // By default every party is needed. Try `--dropout-rate 0.1` to see decryption fail with a
+    // quorum error, then add a threshold, e.g. `--parties 7 --threshold 4 --dropout-rate 0.4`,
+    // and see it succeed anyway.
+    let threshold: usize = cli.threshold.unwrap_or(num_parties);
+    let dropout_rate: f64 = cli.dropout_rate;
+
+    // The number of parties that will try to sabotage the decryption.
+    //
+    // Try `--byzantine 1`: with every party needed, the result can't be trusted and the run
+    // stops. With `--parties 7 --threshold 4 --byzantine 2`, the bad parties are singled out and
+    // left out instead.
+    let byzantine: usize = cli.byzantine;
+
+    // The number of precincts the voters are spread over.
+    //
+    // Each precinct is tallied on its own and the precinct tallies are added up into the overall
+    // tally. Only the overall tally is decrypted, unless `--precinct-min-ballots` is given, in
+    // which case the totals of the precincts with at least that many ballots are decrypted too.
+    let precincts: PrecinctId = cli.precincts;
+
+    // What the voters vote on.
+    //
+    // By default it's a single yes or no question, where voters can also abstain. With `--voting
+    // quadratic`, each voter gets `--credits` to spread over `--options`, and casting `v` votes
+    // for an option costs `v²`. With `--voting questions`, every ballot answers each of
+    // `--questions` at once, and with `--voting candidates` every ballot is a vote for one of the
+    // candidates listed in the `--candidates` file. Add `--seats 10` to hand out seats to them in
+    // proportion to their votes, by D'Hondt or with `--seat-method sainte-lague`.
+    if matches!(cli.voting, VotingMode::Quadratic) && cli.options == 0 {
+        return Err("quadratic voting needs at least one option".into());
+    }
+    let voting: Voting = match cli.voting {
+        VotingMode::YesNo => Voting::YesNo,
+        VotingMode::Quadratic => Voting::Quadratic {
+            options: cli.options,
+            credits: cli.credits,
+        },
+        VotingMode::Questions => Voting::Questions(Questions::new(cli.questions)?),
+        VotingMode::Candidates => {
+            let path: &PathBuf = cli
+                .candidates
+                .as_ref()
+                .ok_or("--voting candidates needs --candidates")?;
+            Voting::Candidates(Candidates::from_json(&fs::read_to_string(path)?)?)
+        }
+    };
+    if cli.seats.is_some() && !matches!(voting, Voting::Candidates(_)) {
+        return Err("--seats needs --voting candidates".into());
+    }
+
+    // How the voters vote, and how many of them turn out.
+    //
+    // By default every voter turns out and picks an option uniformly at random. Try
+    // `--distribution near-tie` for a race decided by a single vote, or `--precincts 3
+    // --turnout 0.9,0.5,0.2` to compare precincts at different levels of turnout.
+    let turnout: Turnout = Turnout::new(cli.turnout, precincts)?;
+
+    let election: Election = Election {
+        voting,
+        distribution: cli.distribution,
+        num_votes,
+        num_parties,
+        threshold,
+        dropout_rate,
+        byzantine,
+        precincts,
+        turnout,
+        precinct_min_ballots: cli.precinct_min_ballots,
+        rerandomize: cli.rerandomize,
+        precompute: cli.precompute,
+        refresh: cli.refresh,
+        degree: params::DEGREE,
+        plaintext_modulus: cli.plaintext_modulus,
+        moduli: moduli_chain(&cli.moduli_sizes, cli.depth),
+        allow_insecure,
+        max_memory: cli.max_memory,
+        publish: cli.publish,
+        roster: cli.roster,
+        log: cli.log,
+        transcript: cli.transcript,
+        airgap: cli.airgap,
+        store: cli.store,
+        mmap: cli.mmap,
+        tally_chunk: cli.tally_chunk,
+        checkpoint: cli.checkpoint,
+        lifecycle: None,
+        seats: cli.seats.map(|seats| (seats, cli.seat_method)),
+    };
+    let report: RunReport = run(&election, show_progress).inspect_err(|e| error!("{e}"))?;
+    if let Some(path) = cli.report {
+        serde_json::to_writer_pretty(File::create(&path)?, &report)?;
+        info!(report = %path.display(), "report written");
+    }
+    Ok(())
+}
+
+// A plain yes or no election of the given size, with every party needed to decrypt and nothing
+// written out, as run by `bench-sweep` and `project`.
+fn sweep_election(
+    num_votes: usize,
+    num_parties: usize,
+    degree: usize,
+    allow_insecure: bool,
+) -> Election {
+    Election {
+        voting: Voting::YesNo,
+        distribution: Distribution::Uniform,
+        num_votes,
+        num_parties,
+        threshold: num_parties,
+        dropout_rate: 0.0,
+        byzantine: 0,
+        precincts: 1,
+        turnout: Turnout::default(),
+        precinct_min_ballots: None,
+        rerandomize: false,
+        precompute: false,
+        refresh: 0,
+        degree,
+        plaintext_modulus: None,
+        moduli: ModuliChain::Workshop,
+        allow_insecure,
+        max_memory: None,
+        publish: None,
+        roster: None,
+        log: None,
+        transcript: None,
+        airgap: None,
+        store: None,
+        mmap: false,
+        tally_chunk: None,
+        checkpoint: None,
+        lifecycle: None,
+        seats: None,
+    }
+}
+
+// Runs the pipeline over every combination of sizes in the grid, one run at a time, and writes
+// a row per phase of every run.
+fn bench_sweep(
+    sweep: &Sweep,
+    show_progress: bool,
+    allow_insecure: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut rows: Vec<SweepRow> = Vec::new();
+    for &degree in &sweep.degree {
+        for &num_parties in &sweep.parties {
+            for &num_votes in &sweep.votes {
+                let _run =
+                    info_span!("run", votes = num_votes, parties = num_parties, degree).entered();
+                let election: Election =
+                    sweep_election(num_votes, num_parties, degree, allow_insecure);
+                let report: RunReport = run(&election, show_progress)?;
+                rows.extend(report.phases.into_iter().map(|phase| {
+                    let latency = phase
+                        .items
+                        .as_ref()
+                        .and_then(|items| items.latency.as_ref());
+                    SweepRow {
+                        votes: num_votes,
+                        parties: num_parties,
+                        degree,
+                        threads: parallel::current_num_threads(),
+                        per_sec: phase.items.as_ref().map(|items| items.per_sec),
+                        p50_us: latency.map(|latency| latency.p50_us),
+                        p95_us: latency.map(|latency| latency.p95_us),
+                        p99_us: latency.map(|latency| latency.p99_us),
+                        phase: phase.name,
+                        elapsed_ms: phase.elapsed_ms,
+                        peak_rss_bytes: phase.peak_rss_bytes,
+                        held_bytes: phase.held_bytes,
+                    }
+                }));
+            }
+        }
+    }
+
+    let file: File = File::create(&sweep.output)?;
+    match sweep.format {
+        SweepFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(file);
+            for row in &rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+        SweepFormat::Json => serde_json::to_writer_pretty(file, &rows)?,
+    }
+    info!(rows = rows.len(), output = %sweep.output.display(), "sweep written");
+    Ok(())
+}
+
+// Runs a small election for every combination of calibration sizes, fits a cost model of each
+// phase to the runs and prints what it projects for the target size, see `projection.rs`.
+fn project(
+    args: &ProjectArgs,
+    show_progress: bool,
+    allow_insecure: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut reports: Vec<RunReport> = Vec::new();
+    for &num_parties in &args.parties {
+        for &num_votes in &args.votes {
+            let _run =
+                info_span!("calibration", votes = num_votes, parties = num_parties).entered();
+            let election: Election =
+                sweep_election(num_votes, num_parties, args.degree, allow_insecure);
+            reports.push(run(&election, show_progress)?);
+        }
+    }
+    let projection: Projection =
+        projection::project(&reports, args.target_votes, args.target_parties);
+    println!("{projection}");
+    if let Some(output) = &args.output {
+        serde_json::to_writer_pretty(File::create(output)?, &projection)?;
+        info!(output = %output.display(), "projection written");
+    }
+    Ok(())
+}
+
+// The transparency log of a run, if it keeps one. Every event also goes out on the feed's event
+// stream, log or not.
+struct EventLog(Option<TransparencyLog>);
+
+impl EventLog {
+    fn record(&mut self, event: Event) -> Result<(), LogError> {
+        feed::publish(&event);
+        if let Some(log) = &mut self.0 {
+            log.append(event)?;
+        }
+        Ok(())
+    }
+}
+
+// The transcript of a run, if it keeps one.
+struct TranscriptLog(Option<Transcript>);
+
+impl TranscriptLog {
+    fn record(&mut self, message: TranscriptMessage) -> Result<(), TranscriptError> {
+        if let Some(transcript) = &mut self.0 {
+            transcript.append(message)?;
+        }
+        Ok(())
+    }
+}
+
+// The checkpoint of a run, if it keeps one, see `checkpoint.rs`. Without one there is nothing
+// to resume from and nothing is recorded.
+struct Recovery(Option<Checkpoint>);
+
+impl Recovery {
+    fn resumed(&self) -> bool {
+        self.0.as_ref().is_some_and(Checkpoint::resumed)
+    }
+
+    fn advance(&mut self, phase: Phase) -> Result<(), CheckpointError> {
+        match &mut self.0 {
+            Some(checkpoint) => checkpoint.advance(phase),
+            None => Ok(()),
+        }
+    }
+
+    fn ballots(&self) -> Result<Vec<Ballot>, CheckpointError> {
+        match &self.0 {
+            Some(checkpoint) => checkpoint.ballots(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn record_ballot(&mut self, ballot: &Ballot) -> Result<(), CheckpointError> {
+        match &mut self.0 {
+            Some(checkpoint) => checkpoint.record_ballot(ballot),
+            None => Ok(()),
+        }
+    }
+
+    fn tally(&self) -> Result<Option<Ciphertext>, CheckpointError> {
+        match &self.0 {
+            Some(checkpoint) => checkpoint.tally(),
+            None => Ok(None),
+        }
+    }
+
+    fn record_tally(&mut self, tally: &Ciphertext) -> Result<(), CheckpointError> {
+        match &mut self.0 {
+            Some(checkpoint) => checkpoint.record_tally(tally),
+            None => Ok(()),
+        }
+    }
+
+    fn partial_sums(
+        &self,
+        precinct: PrecinctId,
+        chunk_size: usize,
+    ) -> Result<Vec<Ciphertext>, CheckpointError> {
+        match &self.0 {
+            Some(checkpoint) => checkpoint.partial_sums(precinct, chunk_size),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn record_partial_sum(
+        &mut self,
+        precinct: PrecinctId,
+        chunk_size: usize,
+        chunk: usize,
+        sum: &Ciphertext,
+    ) -> Result<(), CheckpointError> {
+        match &mut self.0 {
+            Some(checkpoint) => checkpoint.record_partial_sum(precinct, chunk_size, chunk, sum),
+            None => Ok(()),
+        }
+    }
+
+    fn shares(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+    ) -> Result<Vec<DecryptionShareMessage>, CheckpointError> {
+        match &self.0 {
+            Some(checkpoint) => checkpoint.shares(ciphertext),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn record_share(&mut self, message: &DecryptionShareMessage) -> Result<(), CheckpointError> {
+        match &mut self.0 {
+            Some(checkpoint) => checkpoint.record_share(message),
+            None => Ok(()),
+        }
+    }
+}
+
+// Bundles whichever parts of an election were given into a snapshot archive.
+fn export_state(args: &ExportStateArgs) -> Result<(), Box<dyn Error>> {
+    let manifest: Manifest = snapshot::export(
+        &Sources {
+            artifacts: args.artifacts.clone(),
+            checkpoint: args.checkpoint.clone(),
+            state: args.state.clone(),
+            log: args.log.clone(),
+        },
+        &args.output,
+    )?;
+    info!(
+        entries = manifest.entries.len(),
+        bytes = manifest.entries.iter().map(|entry| entry.size).sum::<u64>(),
+        output = %args.output.display(),
+        "snapshot exported"
+    );
+    Ok(())
+}
+
+// Unpacks a snapshot archive, after which a checkpoint in it can be resumed with `--checkpoint`.
+fn import_state(args: &ImportStateArgs) -> Result<(), Box<dyn Error>> {
+    let manifest: Manifest = snapshot::import(&args.archive, &args.into)?;
+    for entry in &manifest.entries {
+        debug!(
+            path = %entry.path,
+            sha256 = %hex::encode(entry.sha256),
+            "entry checked"
+        );
+    }
+    info!(
+        entries = manifest.entries.len(),
+        into = %args.into.display(),
+        "snapshot imported"
+    );
+    Ok(())
+}
+
+fn trustee(args: &TrusteeArgs) -> Result<(), Box<dyn Error>> {
+    match &args.action {
+        TrusteeAction::New => {
+            // Printed rather than logged, so that it doesn't end up wherever the logs are kept.
+            println!("{}", mnemonic::generate(&mut thread_rng()));
+            warn!("write the mnemonic down and keep it private, it is the trustee's key share");
+        }
+        TrusteeAction::Recover(recover) => {
+            let words: String = std::env::var("TRUSTEE_MNEMONIC")
+                .map_err(|_| "set TRUSTEE_MNEMONIC to the trustee's mnemonic")?;
+            let (_, share): (Party, PublicKeyShare) = gossip::mnemonic_key(
+                recover.trustee,
+                recover.trustees,
+                &recover.session,
+                &mnemonic::parse(&words)?,
+            )?;
+            // The same hash the trustee logged when it started and the transparency log
+            // records, so the recovered share can be checked against the one that was sent.
+            info!(
+                trustee = recover.trustee,
+                key_share = %hex::encode(transparency::artifact_hash(&share.to_bytes())),
+                "key share recovered"
+            );
+        }
+        TrusteeAction::Hsm(hsm) => serve_hsm(hsm)?,
+        TrusteeAction::ChannelKey => {
+            let (secret, public): (ChannelKey, ChannelKey) = signer::generate_channel_key()?;
+            // Printed rather than logged, the same as a mnemonic.
+            println!("secret: {}", hex::encode(secret));
+            println!("public: {}", hex::encode(public));
+            warn!("keep the secret key private, whoever holds it can reach the key share");
+        }
+    }
+    Ok(())
+}
+
+// The mock HSM: holds the key share and answers the requests of one trustee at a time, see
+// `signer.rs`.
+fn serve_hsm(args: &HsmArgs) -> Result<(), Box<dyn Error>> {
+    let session: Session = Session::new(args.trustee, args.trustees, &args.session)?;
+    let (key, share): (Party, PublicKeyShare) = match std::env::var("TRUSTEE_MNEMONIC") {
+        Ok(words) => session.mnemonic_key(&mnemonic::parse(&words)?)?,
+        Err(_) => session.random_key(&mut thread_rng())?,
+    };
+    let signer: LocalSigner =
+        LocalSigner::new(key, share, session.crp().clone(), &mut thread_rng());
+    let keys: ChannelKeys = ChannelKeys {
+        secret: signer::parse_channel_key(
+            &std::env::var("CHANNEL_KEY")
+                .map_err(|_| "set CHANNEL_KEY to the HSM's secret channel key")?,
+        )?,
+        peer: args.peer,
+        prologue: signer::channel_prologue(&args.session, args.trustee),
+    };
+    let listener: TcpListener = TcpListener::bind(&args.listen)?;
+    info!(
+        trustee = args.trustee,
+        address = %listener.local_addr()?,
+        key_share = %hex::encode(transparency::artifact_hash(
+            &signer.public_key_share().to_bytes()
+        )),
+        "mock HSM listening"
+    );
+    // The file is read again for every request, so a tally is approved without a restart, while
+    // the trustee's request waits.
+    let approved = |tally: &TallyHash| -> bool {
+        let hash: String = hex::encode(tally);
+        let is_approved = || {
+            fs::read_to_string(&args.approved)
+                .unwrap_or_default()
+                .lines()
+                .any(|line| line.trim() == hash)
+        };
+        if !is_approved() {
+            warn!(
+                tally = %hash,
+                approved = %args.approved.display(),
+                "decryption share requested, waiting for the tally to be approved"
+            );
+            while !is_approved() {
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+        info!(tally = %hash, "computing a decryption share of an approved tally");
+        true
+    };
+    for stream in listener.incoming() {
+        let stream: TcpStream = stream?;
+        let peer: SocketAddr = stream.peer_addr()?;
+        info!(%peer, "trustee connected");
+        match signer::serve(stream, &keys, session.params(), &signer, &approved) {
+            Ok(()) => info!(%peer, "trustee disconnected"),
+            Err(e) => warn!(%peer, error = %e, "trustee connection failed"),
+        }
+    }
+    Ok(())
+}
+
+// Recasts a published run as an E3, checking the transcript against the run's tally journal.
+fn e3_export(args: &E3ExportArgs) -> Result<(), Box<dyn Error>> {
+    let artifacts: Artifacts = Artifacts::read(&args.artifacts)?;
+    let transcript: E3Transcript = E3Transcript::new(
+        args.e3_id,
+        args.threshold.unwrap_or(args.committee),
+        args.committee,
+        &artifacts,
+    );
+    if !transcript.matches(&artifacts.journal) {
+        return Err("the artifacts don't match their tally journal, run `verify`".into());
+    }
+    fs::write(&args.output, transcript.to_json())?;
+    info!(
+        e3_id = args.e3_id,
+        inputs = transcript.inputs.ciphertexts.len(),
+        output = %args.output.display(),
+        "E3 transcript written"
+    );
+    Ok(())
+}
+
+// The Merkle root of the ballots, as committed to at the end of voting.
+fn ballots_root(ballots: &[Ballot]) -> Hash {
+    let leaves: Vec<Hash> = ballots
+        .iter()
+        .map(|ballot| merkle::hash_leaf(&ballot.ciphertext.to_bytes()))
+        .collect();
+    MerkleTree::new(leaves).root()
+}
+
+// Runs a future to completion, for the subcommands talking to an Ethereum node.
+#[cfg(feature = "eth")]
+fn block_on<F: std::future::Future>(future: F) -> io::Result<F::Output> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(future))
+}
+
+#[cfg(feature = "eth")]
+fn publish_onchain(args: &OnchainArgs) -> Result<(), Box<dyn Error>> {
+    let private_key: String = std::env::var("ETH_PRIVATE_KEY")
+        .map_err(|_| "set ETH_PRIVATE_KEY to the private key to publish with")?;
+    let commitment: Commitment = Commitment::of(&Artifacts::read(&args.artifacts)?);
+    let tx: TxHash = block_on(eth::publish(
+        &args.rpc_url,
+        args.contract,
+        &private_key,
+        &commitment,
+    ))??;
+    info!(
+        %tx,
+        ballots_root = hex::encode(commitment.ballots_root),
+        "commitments published"
+    );
+    Ok(())
+}
+
+// Looks up the commitments published under the root of the ballots in the artifacts, and
+// checks the artifacts match them. Whether the artifacts are consistent with each other is up
+// to `verify`.
+#[cfg(feature = "eth")]
+fn verify_onchain(args: &OnchainArgs) -> Result<(), Box<dyn Error>> {
+    let artifacts: Artifacts = Artifacts::read(&args.artifacts)?;
+    let root: Hash = ballots_root(&artifacts.ballots);
+    let local: Commitment = Commitment::of(&artifacts);
+    let onchain: Commitment = block_on(eth::fetch(&args.rpc_url, args.contract, root))??
+        .ok_or_else(|| {
+            format!(
+                "nothing is published under ballots root {}",
+                hex::encode(root)
+            )
+        })?;
+    let mut failed: Vec<&str> = Vec::new();
+    let mut check = |name: &'static str, passed: bool| {
+        if passed {
+            info!(check = name, "pass");
+        } else {
+            error!(check = name, "fail");
+            failed.push(name);
+        }
+    };
+    check("ballots root", local.ballots_root == onchain.ballots_root);
+    check("tally hash", local.tally_hash == onchain.tally_hash);
+    check("params hash", local.params_hash == onchain.params_hash);
+    check(
+        "ballot count",
+        local.num_ballots == onchain.num_ballots
+            && onchain.num_ballots == artifacts.ballots.len() as u64,
+    );
+    check("result", local.result == onchain.result);
+    if !failed.is_empty() {
+        return Err(format!("checks failed: {}", failed.join(", ")).into());
+    }
+    info!("the artifacts match the commitments on chain");
+    Ok(())
+}
+
+// Replays the hash chain of a transparency log and, given the published artifacts, checks the
+// log accounts for them: the published ballots are the last ballot accepted from each voter,
+// and the tally, decryption shares and result are the ones that were logged.
+fn verify_log(args: &VerifyLogArgs) -> Result<(), Box<dyn Error>> {
+    let entries: Vec<LogEntry> = transparency::read(&args.log)?;
+    let head: Hash = transparency::verify(&entries)?;
+    info!(entries = entries.len(), head = %hex::encode(head), "hash chain intact");
+    let Some(dir) = &args.artifacts else {
+        return Ok(());
+    };
+
+    let artifacts: Artifacts = Artifacts::read(dir)?;
+    let mut failed: Vec<&str> = Vec::new();
+    let mut check = |name: &'static str, passed: bool| {
+        if passed {
+            info!(check = name, "pass");
+        } else {
+            error!(check = name, "fail");
+            failed.push(name);
+        }
+    };
+
+    let mut accepted: BTreeMap<Hash, Hash> = BTreeMap::new();
+    let mut tallies: Vec<(Hash, Hash)> = Vec::new();
+    let mut shares: BTreeSet<Hash> = BTreeSet::new();
+    let mut result: Option<&Vec<u64>> = None;
+    for entry in &entries {
+        match &entry.event {
+            Event::BallotAccepted {
+                nullifier,
+                ciphertext_hash,
+            } => {
+                accepted.insert(*nullifier, *ciphertext_hash);
+            }
+            Event::TallyPublished {
+                tally_hash,
+                ballots_root,
+            } => tallies.push((*tally_hash, *ballots_root)),
+            Event::DecryptionShareReceived { share_hash, .. } => {
+                shares.insert(*share_hash);
+            }
+            Event::ResultPublished { tally } => result = Some(tally),
+            Event::CrpPublished { .. }
+            | Event::KeyShareCommitted { .. }
+            | Event::KeyShareReceived { .. } => {}
+        }
+    }
+
+    check(
+        "ballots",
+        artifacts.ballots.len() == accepted.len()
+            && artifacts.ballots.iter().all(|ballot| {
+                accepted.get(&ballot.nullifier)
+                    == Some(&merkle::hash_leaf(&ballot.ciphertext.to_bytes()))
+            }),
+    );
+    check(
+        "tally",
+        tallies.contains(&(
+            decryption_ceremony::tally_hash(&artifacts.tally),
+            artifacts.journal.ballots_root,
+        )),
+    );
+    check(
+        "decryption shares",
+        artifacts.shares.iter().all(|message| {
+            shares.contains(&transparency::artifact_hash(&message.share.to_bytes()))
+        }),
+    );
+    check("result", result == Some(&artifacts.result.tally));
+
+    if !failed.is_empty() {
+        return Err(format!("checks failed: {}", failed.join(", ")).into());
+    }
+    info!("all checks passed");
+    Ok(())
+}
+
+// Replays a transcript, see `transcript.rs`.
+fn transcript(args: &TranscriptArgs) -> Result<(), Box<dyn Error>> {
+    match &args.action {
+        TranscriptAction::Verify(verify) => {
+            let entries: Vec<TranscriptEntry> = transcript::read(&verify.transcript)?;
+            let replay: Replay = transcript::verify(&entries)?;
+            if replay.rerandomized {
+                warn!("the tally was rerandomized, so it wasn't checked against the ballots");
+            }
+            for trustee in &replay.excluded {
+                warn!(
+                    trustee,
+                    "excluded a party for sending bad decryption shares"
+                );
+            }
+            info!(
+                entries = entries.len(),
+                head = %hex::encode(replay.head),
+                public_key = %hex::encode(replay.public_key_hash),
+                ballots = replay.ballots,
+                tally = %hex::encode(replay.tally_hash),
+                other_shares = replay.other_shares,
+                result = ?replay.result,
+                "transcript replayed"
+            );
+        }
+    }
+    Ok(())
+}
+
+// Steps the election in the state file through its lifecycle. Opening it runs the voting and
+// keeps the ballots in a checkpoint next to the state file, and finalizing it tallies them.
+fn manage_election(
+    args: &ElectionArgs,
+    show_progress: bool,
+    allow_insecure: bool,
+) -> Result<(), Box<dyn Error>> {
+    let state: ElectionState<ElectionConfig> = match &args.action {
+        ElectionAction::Init(init) => {
+            if args.state.exists() {
+                return Err(format!("{} already exists", args.state.display()).into());
+            }
+            params::check_capacity(init.votes, 1, params::plaintext_modulus(init.votes))?;
+            ElectionState::new(ElectionConfig {
+                votes: init.votes,
+                parties: init.parties,
+                threshold: init.threshold,
+                publish: init.publish.clone(),
+                moduli_sizes: init.moduli_sizes.clone(),
+                depth: init.depth,
+                candidates: match &init.candidates {
+                    Some(path) => Some(Candidates::from_json(&fs::read_to_string(path)?)?),
+                    None => None,
+                },
+            })
+        }
+        // The state is saved open before the voting runs, which reads it back.
+        ElectionAction::Open => {
+            let mut state: ElectionState<ElectionConfig> = ElectionState::load(&args.state)?;
+            state.open()?;
+            state.save(&args.state)?;
+            run(
+                &lifecycle_election(&state.config, &args.state, allow_insecure),
+                show_progress,
+            )?;
+            state
+        }
+        ElectionAction::Close => {
+            let mut state: ElectionState<ElectionConfig> = ElectionState::load(&args.state)?;
+            state.close()?;
+            state
+        }
+        // The election only counts as finalized once the tally has gone through.
+        ElectionAction::Finalize => {
+            let mut state: ElectionState<ElectionConfig> = ElectionState::load(&args.state)?;
+            state.finalize()?;
+            run(
+                &lifecycle_election(&state.config, &args.state, allow_insecure),
+                show_progress,
+            )?;
+            state
+        }
+    };
+    state.save(&args.state)?;
+    info!(phase = ?state.phase, state = %args.state.display(), "election updated");
+    Ok(())
+}
+
+// The election in the state file at `path`, checkpointed next to it so the ballots cast while it
+// is open are the ones it tallies.
+fn lifecycle_election(config: &ElectionConfig, path: &Path, allow_insecure: bool) -> Election {
+    Election {
+        voting: config
+            .candidates
+            .clone()
+            .map_or(Voting::YesNo, Voting::Candidates),
+        distribution: Distribution::Uniform,
+        num_votes: config.votes,
+        num_parties: config.parties,
+        threshold: config.threshold.unwrap_or(config.parties),
+        dropout_rate: 0.0,
+        byzantine: 0,
+        precincts: 1,
+        turnout: Turnout::default(),
+        precinct_min_ballots: None,
+        rerandomize: false,
+        precompute: false,
+        refresh: 0,
+        degree: params::DEGREE,
+        plaintext_modulus: None,
+        moduli: moduli_chain(&config.moduli_sizes, config.depth),
+        allow_insecure,
+        max_memory: None,
+        publish: config.publish.clone(),
+        roster: None,
+        log: None,
+        transcript: None,
+        airgap: None,
+        store: None,
+        mmap: false,
+        tally_chunk: None,
+        checkpoint: Some(path.with_extension("checkpoint")),
+        lifecycle: Some(path.to_path_buf()),
+        seats: None,
+    }
+}
+
+// Finds the counted ballot with the tracking code and checks its inclusion proof against the
+// ballots root, see `tracking.rs`.
+fn track(args: &TrackArgs) -> Result<(), Box<dyn Error>> {
+    let artifacts: Artifacts = Artifacts::read(&args.artifacts)?;
+    let root: Hash = match &args.ballots_root {
+        Some(root) => hex::decode(root)?
+            .try_into()
+            .map_err(|_| "the ballots root is not 32 bytes")?,
+        None => artifacts.journal.ballots_root,
+    };
+    let Some(index) = artifacts
+        .ballots
+        .iter()
+        .position(|ballot| TrackingCode::of(ballot) == args.code)
+    else {
+        return Err(format!("no counted ballot has the tracking code {}", args.code).into());
+    };
+    let leaves: Vec<Hash> = artifacts
+        .ballots
+        .iter()
+        .map(|ballot| merkle::hash_leaf(&ballot.ciphertext.to_bytes()))
+        .collect();
+    let proof: InclusionProof = MerkleTree::new(leaves.clone())
+        .proof(index)
+        .expect("the ballot is in the tree");
+    if !proof.verify(&leaves[index], &root) {
+        return Err("the ballot's inclusion proof doesn't match the ballots root".into());
+    }
+    info!(
+        code = %args.code,
+        ballot = index,
+        root = %hex::encode(root),
+        hashes = proof.steps.len(),
+        "ballot counted"
+    );
+    Ok(())
+}
+
+// Re-checks the published artifacts of an election: the ballot signatures, the ballots root,
+// the coordinator's manifest of the ballots, the tally as the sum of the ballots, the result
+// as the decryption of the tally, and the certification bundle against all of them.
+//
+// The tally is computed outside of any zkVM, so there is no receipt to check: the tally is
+// simply summed again from the ballots.
+fn verify(args: &VerifyArgs) -> Result<(), Box<dyn Error>> {
+    let mut artifacts: Artifacts = Artifacts::read(&args.artifacts)?;
+    let mut failed: Vec<&str> = Vec::new();
+    let mut check = |name: &'static str, passed: bool| {
+        if passed {
+            info!(check = name, "pass");
+        } else {
+            error!(check = name, "fail");
+            failed.push(name);
+        }
+    };
+
+    check(
+        "ballot signatures",
+        artifacts
+            .ballots
+            .iter()
+            .all(|ballot| ballot.verify().is_ok()),
+    );
+    let root: Hash = ballots_root(&artifacts.ballots);
+    let journal: &TallyJournal = &artifacts.journal;
+    check(
+        "ballots root",
+        hex::encode(root) == args.ballots_root && root == journal.ballots_root,
+    );
+    check(
+        "ballot count",
+        artifacts.ballots.len() == journal.num_ballots,
+    );
+    let manifest: &BallotManifest = &artifacts.manifest;
+    info!(signer = %hex::encode(manifest.signer), "manifest signed");
+    check("manifest signature", manifest.verify_signature().is_ok());
+    check(
+        "manifest hashes",
+        manifest.check_ballots(&artifacts.ballots).is_ok(),
+    );
+    check("manifest root", manifest.ballots_root == root);
+    check(
+        "parameters",
+        journal::params_hash(&artifacts.params) == journal.params_hash,
+    );
+    // A re-randomized tally is no longer the sum of the ballots, so it can only be checked
+    // against the journal.
+    if journal.rerandomized {
+        warn!("tally was re-randomized, not checking it against the ballots");
+        check("tally", artifacts.tally.to_bytes() == journal.tally_bytes);
+    } else {
+        let sum: Vec<u8> = tally::sum(
+            artifacts.ballots.iter().map(|ballot| &ballot.ciphertext),
+            &artifacts.params,
+        )
+        .to_bytes();
+        check(
+            "tally",
+            sum == artifacts.tally.to_bytes() && sum == journal.tally_bytes,
+        );
+    }
+    let holders: BTreeSet<PartyId> = artifacts
+        .shares
+        .iter()
+        .map(|message| message.trustee)
+        .collect();
+    check(
+        "decryption shares",
+        artifacts.shares.iter().enumerate().all(|(slot, message)| {
+            message.slot == slot
+                && message.tally_hash == decryption_ceremony::tally_hash(&artifacts.tally)
+        }),
+    );
+    let decrypted: Vec<u64> = tally::decrypt(
+        mem::take(&mut artifacts.shares)
+            .into_iter()
+            .map(|message| message.share),
+        artifacts.result.tally.len(),
+    )?;
+    check("result", decrypted == artifacts.result.tally);
+
+    // The certification bundle, if it was published, has to agree with everything above. The
+    // trustees whose shares decrypted the result must be among those it names, and as many as
+    // it takes to decrypt must have signed it. Only the roster, which doesn't come with the
+    // artifacts, says whether the keys that signed are the trustees'.
+    let path: PathBuf = args.artifacts.join(CERTIFICATION_FILE);
+    if path.exists() {
+        let certification: Certification = Certification::from_json(&fs::read(path)?)?;
+        check(
+            "certification contributors",
+            holders
+                .iter()
+                .all(|trustee| certification.contributors.binary_search(trustee).is_ok()),
+        );
+        let quorum: usize = args.threshold.unwrap_or(artifacts.result.threshold);
+        if args.threshold.is_none() {
+            warn!(
+                quorum,
+                "no --threshold, taking the quorum from the published result"
+            );
+        }
+        let mut expected: Certification = Certification::new(
+            &artifacts.result.tally,
+            &artifacts.params,
+            &artifacts.public_key,
+            root,
+            artifacts.ballots.len(),
+            &artifacts.tally,
+            journal.rerandomized,
+            certification.contributors.iter().copied(),
+            certification.quorum,
+        );
+        expected.receipt_digest = certification.receipt_digest;
+        check("certification", certification.hash() == expected.hash());
+        match &args.roster {
+            Some(roster) => {
+                let roster: Vec<VerifyingKey> =
+                    certification::roster_from_json(&fs::read(roster)?)?;
+                check(
+                    "certification signatures",
+                    certification.certify(&roster, quorum).is_ok(),
+                );
+            }
+            None => {
+                warn!(
+                    "no --roster, the certification is UNAUTHENTICATED: its signatures are only \
+                     checked against the keys they name, and anyone could have made them"
+                );
+                check(
+                    "certification signatures",
+                    quorum > 0
+                        && certification.quorum >= quorum
+                        && certification.signatures.len() >= quorum
+                        && certification.verify_signatures().is_ok(),
+                );
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(format!("checks failed: {}", failed.join(", ")).into());
+    }
+    info!("all checks passed");
+    Ok(())
+}
+
+// Re-checks the published artifacts of an election without anything from outside them, see
+// `audit.rs`, and prints a line per check.
+fn audit(args: &AuditArgs) -> Result<(), Box<dyn Error>> {
+    let report: AuditReport = audit::audit(Artifacts::read(&args.artifacts)?);
+    println!("{report}");
+    if let Some(output) = &args.output {
+        serde_json::to_writer_pretty(File::create(output)?, &report)?;
+        info!(output = %output.display(), "audit report written");
+    }
+    if !report.passed() {
+        let failed: Vec<&str> = report.failed().map(|check| check.name.as_str()).collect();
+        return Err(format!("checks failed: {}", failed.join(", ")).into());
+    }
+    Ok(())
+}
+
+// The parties that show up to decrypt, and what they send.
+struct Decryptors<'a> {
+    trustees: &'a TrusteeSet,
+    // The key of each slot.
+    keys: &'a [Party],
+    present: BTreeSet<PartyId>,
+    // The parties sending decryption shares made with the impostor's key.
+    byzantine: BTreeSet<PartyId>,
+    impostor: Party,
+    show_progress: bool,
+    airgap: Option<AirGap>,
+    // How long each decryption share took, or each share response when air-gapped.
+    latencies: Latencies,
+}
+
+// Parties decrypting on machines that are never connected, exchanging files with the
+// coordinator through a directory.
+struct AirGap {
+    dir: PathBuf,
+    params: Arc<BfvParameters>,
+    // Which election the requests are for.
+    context: Hash,
+    coordinator: SigningKey,
+    // The signing key of each party.
+    trustee_keys: Vec<SigningKey>,
+}
+
+impl Decryptors<'_> {
+    // Runs a decryption ceremony over the ciphertext and decodes its first `len` slots. The
+    // parties' smudging noise is drawn from `rng`.
+    fn decrypt<R: RngCore + CryptoRng>(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+        len: usize,
+        log: &mut EventLog,
+        transcript: &mut TranscriptLog,
+        recovery: &mut Recovery,
+        rng: &mut R,
+    ) -> Result<DecryptionCeremony, Box<dyn Error>> {
+        // After a restart, the shares that arrived before it are taken from the checkpoint, and
+        // only the missing ones are asked for.
+        let recorded: Vec<DecryptionShareMessage> = recovery.shares(ciphertext)?;
+        let received: BTreeSet<(usize, PartyId)> = recorded
+            .iter()
+            .map(|message| (message.slot, message.trustee))
+            .collect();
+        let share_messages: Vec<DecryptionShareMessage> = match &self.airgap {
+            Some(airgap) => self.exchange(airgap, ciphertext, &received, rng)?,
+            None => self.share_messages(ciphertext, &received, rng)?,
+        };
+
+        // The decryption ceremony takes the shares one at a time as they are published, checking
+        // each is a share of this ciphertext from a party holding the slot that hasn't already
+        // sent one, and only decrypts once the quorum is reached.
+        let mut ceremony: DecryptionCeremony = DecryptionCeremony::new(ciphertext, self.trustees);
+        let replayed: usize = recorded.len();
+        for (index, message) in recorded.into_iter().chain(share_messages).enumerate() {
+            if index >= replayed {
+                recovery.record_share(&message)?;
+            }
+            log.record(Event::DecryptionShareReceived {
+                slot: message.slot,
+                trustee: message.trustee,
+                tally_hash: message.tally_hash,
+                share_hash: transparency::artifact_hash(&message.share.to_bytes()),
+            })?;
+            transcript.record(TranscriptMessage::decryption_share(&message))?;
+            let progress: Progress = ceremony.receive(message)?;
+            feed::share_received();
+            debug!(%progress, "decryption share received");
+        }
+        ceremony.decrypt(len)?;
+        for trustee in ceremony.excluded() {
+            warn!(
+                trustee,
+                "excluded a party for sending bad decryption shares"
+            );
+        }
+        Ok(ceremony)
+    }
+
+    // The key a party decrypts the slot with: the impostor's, if the party is byzantine.
+    fn key(&self, slot: usize, trustee: PartyId) -> &Party {
+        if self.byzantine.contains(&trustee) {
+            &self.impostor
+        } else {
+            &self.keys[slot]
+        }
+    }
+
+    // Every party that shows up sends a decryption share for each key slot it holds, unless it
+    // was already received. The shares are computed in parallel, each with a generator seeded
+    // from `rng`.
+    fn share_messages<R: RngCore + CryptoRng>(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+        received: &BTreeSet<(usize, PartyId)>,
+        rng: &mut R,
+    ) -> Result<Vec<DecryptionShareMessage>, Box<dyn Error>> {
+        let requests: Vec<(usize, PartyId, [u8; 32])> = self
+            .present
+            .iter()
+            .flat_map(|&trustee| {
+                self.trustees
+                    .slots_of(trustee)
+                    .map(move |slot| (slot, trustee))
+            })
+            .filter(|request| !received.contains(request))
+            .map(|(slot, trustee)| (slot, trustee, rng.gen()))
+            .collect();
+        let pb: ProgressBar = progress_bar(requests.len(), "decrypting", self.show_progress);
+        let share_messages: Vec<DecryptionShareMessage> = requests
+            .par_iter()
+            .map(|&(slot, trustee, seed)| {
+                self.latencies.time(|| {
+                    feed::time("decryption_share", || {
+                        DecryptionShareMessage::new(
+                            slot,
+                            trustee,
+                            self.key(slot, trustee),
+                            ciphertext,
+                            &mut StdRng::from_seed(seed),
+                        )
+                    })
+                })
+            })
+            .progress_with(pb.clone())
+            .collect::<Result<_, _>>()?;
+        pb.finish_and_clear();
+        Ok(share_messages)
+    }
+
+    // The same, with the parties offline, see `airgap.rs`. The coordinator writes a signed
+    // decryption request to the directory, each party that shows up carries it to its own
+    // machine and brings back a signed share response, which the coordinator checks and imports.
+    // A party whose response was already received isn't asked again.
+    fn exchange<R: RngCore + CryptoRng>(
+        &self,
+        airgap: &AirGap,
+        ciphertext: &Arc<Ciphertext>,
+        received: &BTreeSet<(usize, PartyId)>,
+        rng: &mut R,
+    ) -> Result<Vec<DecryptionShareMessage>, Box<dyn Error>> {
+        let pending: Vec<PartyId> = self
+            .present
+            .iter()
+            .copied()
+            .filter(|&trustee| !received.iter().any(|&(_, sender)| sender == trustee))
+            .collect();
+        let request: DecryptionRequest = DecryptionRequest::new(
+            &airgap.params,
+            ciphertext,
+            airgap.context,
+            &airgap.coordinator,
+        )?;
+        let name: String = hex::encode(&request.hash()[..8]);
+        let request_path: PathBuf = airgap.dir.join(format!("request-{name}.json"));
+        let response_path =
+            |trustee: PartyId| airgap.dir.join(format!("response-{name}-{trustee}.json"));
+        fs::write(&request_path, request.to_json())?;
+        info!(request = %request_path.display(), "decryption request exported");
+
+        // On each party's machine, knowing only the coordinator's verifying key.
+        let coordinator: VerifyingKey = airgap.coordinator.verifying_key();
+        for &trustee in &pending {
+            let request: DecryptionRequest =
+                DecryptionRequest::from_json(&fs::read(&request_path)?)?;
+            let response: ShareResponse = self.latencies.time(|| {
+                ShareResponse::new(
+                    &request,
+                    &coordinator,
+                    trustee,
+                    self.trustees
+                        .slots_of(trustee)
+                        .map(|slot| (slot, self.key(slot, trustee))),
+                    &airgap.trustee_keys[trustee],
+                    rng,
+                )
+            })?;
+            fs::write(response_path(trustee), response.to_json())?;
+        }
+
+        // Back on the coordinator's.
+        let mut share_messages: Vec<DecryptionShareMessage> = Vec::new();
+        for &trustee in &pending {
+            let response: ShareResponse =
+                ShareResponse::from_json(&fs::read(response_path(trustee))?)?;
+            share_messages.extend(response.open(
+                &request,
+                &airgap.params,
+                ciphertext,
+                &airgap.trustee_keys[trustee].verifying_key(),
+            )?);
+        }
+        info!(responses = pending.len(), "share responses imported");
+        Ok(share_messages)
+    }
+}
+
+// Runs the whole election once and reports on it.
+fn run(election: &Election, show_progress: bool) -> Result<RunReport, Box<dyn Error>> {
+    reset_peak_rss();
+    let mut phases: Vec<PhaseTiming> = Vec::new();
+    let mut sizes: ArtifactSizes = ArtifactSizes::default();
+    let mut compressed_sizes: ArtifactSizes = ArtifactSizes::default();
+    let main: Instant = Instant::now();
+
+    let num_votes: usize = election.num_votes;
+    info!(num_votes, "votes");
+    let num_parties: usize = election.num_parties;
+    info!(num_parties, "parties");
+    let voting: &Voting = &election.voting;
+    info!(?voting, "voting");
+
+    // Every protocol event is appended to a hash-chained log, if asked to keep one, see
+    // `transparency.rs`.
+    let mut log: EventLog = EventLog(
+        election
+            .log
+            .as_deref()
+            .map(TransparencyLog::create)
+            .transpose()?,
+    );
+    let mut transcript: TranscriptLog = TranscriptLog(
+        election
+            .transcript
+            .as_deref()
+            .map(Transcript::create)
+            .transpose()?,
+    );
+
+    // Split the key so that any `threshold` of the parties can decrypt, see `threshold.rs`.
+    //
+    // The secret key is made of key slots, each held by several parties. Each slot takes part in
+    // the key and decryption ceremonies like a party would in the plain additive scheme. With
+    // the default threshold of every party, there is exactly one slot per party.
+    let trustees: TrusteeSet = TrusteeSet::new(num_parties, election.threshold)?;
+    let num_slots: usize = trustees.num_slots();
+    info!(
+        threshold = trustees.threshold(),
+        slots = num_slots,
+        "threshold"
+    );
+
+    // What happens when a voter casts more than one ballot.
+    //
+    // With `KeepLatest`, a voter can change their mind and vote again, and only their most
+    // recent ballot is counted. With `Reject`, the first ballot is final.
+    //
+    // Try switching to `DuplicatePolicy::Reject` and watch the re-vote get turned away.
+    let duplicate_policy: DuplicatePolicy = DuplicatePolicy::KeepLatest;
+    info!(?duplicate_policy, "duplicates");
 
     // Set the parameters for the FHE scheme
     //
     // The degree of the polynomial, usually denoted as `n` in the literature,
     // it determines the size of the ciphertext. A larger degree increases the security,
     // but will also increase the computation and storage.
-    let degree: usize = 2048;
-    println!("  \x1b[1mDegree:\x1b[0m\t\t{degree}");
+    let degree: usize = election.degree;
+    info!(degree, "degree");
 
     // The plaintext modulus determines the size of the plaintext space. Quite literally, how
     // large the plaintexts you want to represent can be. Plaintexts are typically represented
@@ -76,41 +2342,121 @@ fn main() -> Result<(), Box<dyn Error>> {
     // which can limit the number of computations that can be performed on the ciphertexts.
     // In our case, each vote will be a single bit and we'll sum each vote to produce the tally.
     // The upper bound on the plaintext size is equal to the number of votes cast, so a plaintext
-    // modulus of 1032193 is sufficient for a little over 1M votes.
-    let plaintext_modulus: u64 = match num_votes {
-        1..=999 => 1009,
-        1000..=9999 => 10007,
-        10000..=99999 => 100003,
-        100000..=199999 => 200003,
-        200000..=299999 => 300007,
-        300000..=399999 => 400009,
-        400000..=499999 => 500009,
-        500000..=599999 => 600011,
-        600000..=699999 => 700001,
-        700000..=799999 => 800011,
-        800000..=899999 => 900001,
-        _ => 1032193,
-    };
-    println!("  \x1b[1mPlaintext Modulus:\x1b[0m\t{plaintext_modulus}");
+    // modulus of 1032193 is sufficient for a little over 1M votes. With quadratic voting, a
+    // ballot can put up to the square root of the credits into a single option.
+    //
+    // A tally that reaches the plaintext modulus wraps around and decrypts to a wrong but
+    // plausible looking result, so the election refuses to start if that can happen.
+    let plaintext_modulus: u64 = election
+        .plaintext_modulus
+        .unwrap_or_else(|| params::plaintext_modulus(num_votes * voting.max_per_ballot() as usize));
+    params::check_capacity(num_votes, voting.max_per_ballot(), plaintext_modulus)?;
+    info!(plaintext_modulus, "plaintext modulus");
 
     // The moduli are used to control the noise growth in the ciphertexts in a leveled FHE scheme,
     // using a technique called "modulus switching". Each modulus in the vector  is a large prime corresponding
     // to a level in the computation, and computations are performed modulo the current level's modulus.
     // A larger modulus allows for more computations, but also increases the computation and storage costs.
     //
-    // Note: In this example, we're using a single modulus, so we're not making use of modulus switching.
+    // Note: By default, we're using a single modulus, so we're not making use of modulus switching.
     // This is possible because we're only performing addition over the ciphertexts, which leads to little
     // noise growth in the BFV encryption scheme. If our computation was also using multiplication, we would
-    // need to use multiple moduli to manage the noise growth.
-    let moduli: Vec<u64> = vec![0x3FFFFFFF000001];
-    println!("  \x1b[1mModuli:\x1b[0m\t\t{:?}", moduli);
+    // need to use multiple moduli to manage the noise growth: `--depth` picks a chain for a
+    // number of multiplications, and `--moduli-sizes` gives the bit size of each modulus outright.
+    let params: Arc<BfvParameters> = election.moduli.build(degree, plaintext_modulus)?;
+    let moduli: Vec<u64> = params.moduli().to_vec();
+    info!(?moduli, "moduli");
+
+    // How hard the key is to recover from the public key and the ciphertexts depends on the
+    // degree and the size of the ciphertext modulus: the larger the modulus for a given degree,
+    // the easier. The estimate comes from the table of the Homomorphic Encryption Standard.
+    let modulus_bits: usize = params::modulus_bits(&moduli);
+    match params::check_security(degree, modulus_bits) {
+        Ok(security) => info!(security, modulus_bits, "security level in bits"),
+        Err(e) if election.allow_insecure => warn!("{e}"),
+        Err(e) => return Err(format!("{e}, or pass --allow-insecure").into()),
+    }
+
+    // Every ballot is held until the end of the run, so a large enough election runs out of
+    // memory. With `--max-memory`, a run estimated to take more keeps the ciphertexts of the
+    // ballots on disk instead, as `--store` would, in the checkpoint's directory if there is one
+    // so that a restart finds them again. If even that is over the cap, it refuses to start.
+    let ciphertext_memory: u64 = params::ciphertext_memory(degree, moduli.len());
+    let mut store: Option<PathBuf> = election.store.clone();
+    let mut estimated_memory: u64 = estimate_memory(
+        num_votes,
+        num_slots,
+        ciphertext_memory,
+        store.is_some(),
+        election.publish.is_some(),
+    );
+    if let Some(max_memory) = election.max_memory {
+        if estimated_memory > max_memory && store.is_none() {
+            let dir: PathBuf = match &election.checkpoint {
+                Some(checkpoint) => checkpoint.join("store"),
+                None => {
+                    std::env::temp_dir().join(format!("fhe-workshop-store-{}", std::process::id()))
+                }
+            };
+            estimated_memory = estimate_memory(
+                num_votes,
+                num_slots,
+                ciphertext_memory,
+                true,
+                election.publish.is_some(),
+            );
+            warn!(
+                dir = %dir.display(),
+                max_memory,
+                "over the --max-memory cap, keeping the ciphertexts of the ballots in a store"
+            );
+            store = Some(dir);
+        }
+        if estimated_memory > max_memory {
+            return Err(format!(
+                "the run is estimated to take {estimated_memory} bytes of memory, over the \
+                 --max-memory cap of {max_memory}: cast fewer votes, don't publish the \
+                 ballots or raise the cap"
+            )
+            .into());
+        }
+    }
+    info!(estimated_memory, "memory estimate in bytes");
+    // The serialized size of the ciphertexts, keys and shares held so far, for the report.
+    let mut held_bytes: u64 = 0;
+
+    // With `--checkpoint`, the coordinator records its progress as it goes and a restart picks
+    // up where it stopped, see `checkpoint.rs`. The keys of the parties and the voters, their
+    // votes and who drops out are all drawn from random generators derived from one seed, kept
+    // in the checkpoint, so a restart comes up with the same ones.
+    let mut recovery: Recovery = Recovery(
+        election
+            .checkpoint
+            .as_deref()
+            .map(|dir| Checkpoint::open(dir, &params, &mut thread_rng()))
+            .transpose()?,
+    );
+    let seed: Seed = match &recovery.0 {
+        Some(checkpoint) => *checkpoint.seed(),
+        None => thread_rng().gen(),
+    };
+    if let Some(checkpoint) = &recovery.0 {
+        if checkpoint.resumed() {
+            info!(
+                phase = ?checkpoint.phase(),
+                dir = %checkpoint.dir().display(),
+                "resuming from checkpoint"
+            );
+        }
+    }
 
-    let params = bfv::BfvParametersBuilder::new()
-        .set_degree(degree)
-        .set_plaintext_modulus(plaintext_modulus)
-        .set_moduli(&moduli)
-        .build_arc()
-        .unwrap();
+    let keygen_span: Span = info_span!(
+        "keygen",
+        parties = num_parties,
+        pk_share_bytes = field::Empty
+    );
+    let phase = keygen_span.enter();
+    let keygen_timer: Instant = Instant::now();
 
     // Generate the Common Random Polynomial (CRP)
     //
@@ -118,20 +2464,56 @@ fn main() -> Result<(), Box<dyn Error>> {
     // In this example, we're just grabbing some randomness seeded by the system.
     // In a production environment, we would use some public source of randomness that all
     // of the parties agree on.
-    let crp: CommonRandomPoly = CommonRandomPoly::new(&params, &mut thread_rng())?;
+    //
+    // The key ceremony walks through its phases in order: it publishes the CRP, collects a
+    // commitment to a share from every party, then the shares themselves, then aggregates them.
+    // Each step is refused out of order.
+    let mut ceremony: KeyCeremony = KeyCeremony::new(&params, num_slots);
+    let crp_message: CrpMessage =
+        ceremony.publish_crp(&mut checkpoint::derive_rng(&seed, "crp", 0))?;
+    transcript.record(TranscriptMessage::parameters(&params, &trustees))?;
+    transcript.record(TranscriptMessage::crp(&crp_message))?;
+    let CrpMessage { crp } = crp_message;
+    log.record(Event::CrpPublished {
+        crp_hash: key_ceremony::crp_hash(&crp),
+    })?;
 
     // Create the parties and their keys
     //
-    // Each party generates a secret key share and a public key share using the CRP.
-    let parties: Vec<Party> = (0..num_parties)
+    // Each party generates a secret key share and a public key share using the CRP. With a
+    // threshold, a key slot is generated by one of its holders and handed to the others.
+    let pb: ProgressBar = progress_bar(num_slots, "key shares", show_progress);
+    let keygen_latencies: Latencies = Latencies::new();
+    let keys: Vec<(Party, PublicKeyShare)> = (0..num_slots)
         .into_par_iter()
-        .map(|_| {
-            let sk_share: SecretKey = SecretKey::random(&params, &mut thread_rng());
-            let pk_share: PublicKeyShare =
-                PublicKeyShare::new(&sk_share, crp.clone(), &mut thread_rng()).unwrap();
-            Party { sk_share, pk_share }
+        .map(|slot| {
+            keygen_latencies.time(|| {
+                Party::new(
+                    &params,
+                    &crp,
+                    &mut checkpoint::derive_rng(&seed, "party", slot as u64),
+                )
+            })
         })
-        .collect();
+        .progress_with(pb.clone())
+        .collect::<Result<_, _>>()?;
+    let (parties, pk_shares): (Vec<Party>, Vec<PublicKeyShare>) = keys.into_iter().unzip();
+    pb.finish_and_clear();
+    let pk_share_bytes: Vec<u8> = pk_shares[0].to_bytes();
+    sizes.pk_share = pk_share_bytes.len();
+    compressed_sizes.pk_share = compressed_len(&pk_share_bytes)?;
+    keygen_span.record("pk_share_bytes", sizes.pk_share);
+    info!(elapsed = ?keygen_timer.elapsed(), "key shares generated");
+    held_bytes += (num_slots * sizes.pk_share) as u64;
+    phases.push(
+        item_timing(
+            "keygen",
+            keygen_timer.elapsed(),
+            keygen_latencies.stats(keygen_timer.elapsed(), (num_slots * sizes.pk_share) as u64),
+        )
+        .with_held_bytes(held_bytes),
+    );
+    drop(phase);
 
     // Aggregate the public keys
     //
@@ -144,21 +2526,125 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Note: because the shared public key is the sum of the public key shares, the
     // the public key shares can be aggregated in any order. Meaning the public key shares can
     // be generated asynchronously and aggregated in parallel (although we're not doing that here).
-    let pk: PublicKey = parties.iter().map(|p| p.pk_share.clone()).aggregate()?;
+    let aggregation_span: Span = info_span!("aggregation", pk_bytes = field::Empty);
+    let phase = aggregation_span.enter();
+    let aggregation_timer: Instant = Instant::now();
+    // Every party first commits to its share, and only once every commitment is in do the
+    // parties reveal their shares, so none of them can pick its share after seeing the others'.
+    // Each share is validated as it arrives: it must come from a party of the ceremony, only
+    // once, it must have been generated from the CRP the ceremony published, and it must match
+    // the party's commitment. Each also carries a proof that it was honestly generated, and the
+    // proofs are all checked before the shares are summed, see `share_proof.rs`.
+    //
+    // Note: the proof is a stand-in for now, `BindingProof`, which proves nothing about the
+    // party's secret.
+    let messages: Vec<KeyShareMessage> = parties
+        .iter()
+        .zip(pk_shares)
+        .enumerate()
+        .map(|(id, (party, share))| KeyShareMessage::new(id, party, share, &crp, &BindingProof))
+        .collect::<Result<_, _>>()?;
+    for message in &messages {
+        let commitment: KeyShareCommitment = message.commitment();
+        log.record(Event::KeyShareCommitted {
+            party: commitment.party,
+            commitment: commitment.commitment,
+        })?;
+        transcript.record(TranscriptMessage::key_share_commitment(&commitment))?;
+        ceremony.commit(commitment)?;
+    }
+    for message in messages {
+        log.record(Event::KeyShareReceived {
+            party: message.party,
+            share_hash: transparency::artifact_hash(&message.share.to_bytes()),
+        })?;
+        transcript.record(TranscriptMessage::key_share(&message))?;
+        ceremony.receive(message)?;
+    }
+    let pk: PublicKey = ceremony.aggregate()?.clone();
+    let pk_bytes: Vec<u8> = pk.to_bytes();
+    sizes.public_key = pk_bytes.len();
+    compressed_sizes.public_key = compressed_len(&pk_bytes)?;
+    aggregation_span.record("pk_bytes", sizes.public_key);
+    info!(elapsed = ?aggregation_timer.elapsed(), "public key aggregated");
+    held_bytes += sizes.public_key as u64;
+    phases.push(timing("aggregation", aggregation_timer.elapsed()).with_held_bytes(held_bytes));
+    drop(phase);
+    recovery.advance(Phase::Voting)?;
 
     // Create the plaintext votes
     //
-    // Each voter will cast a 1 for yes or a 0 for no. We'll simulate this by generating
-    // a random bit for each voter.
-    let dist: Uniform<u64> = Uniform::new_inclusive(0, 1);
-    let votes: Vec<u64> = (0..num_votes)
+    // Each voter will vote for, against, or abstain. We'll simulate this by picking a choice for
+    // each voter from the distribution, uniform unless `--distribution` says otherwise. With
+    // quadratic voting, each voter instead spends their credits on options picked the same way.
+    let votes: Vec<Vec<u64>> = (0..num_votes)
         .into_par_iter()
-        .map(|_| dist.sample(&mut thread_rng()))
+        .map(|voter| {
+            voting.random_vote(
+                &election.distribution,
+                voter,
+                &mut checkpoint::derive_rng(&seed, "vote", voter as u64),
+            )
+        })
+        .collect();
+
+    // Each voter lives in one of the precincts, picked at random.
+    let voter_precincts: Vec<PrecinctId> = (0..num_votes)
+        .map(|voter| {
+            checkpoint::derive_rng(&seed, "precinct", voter as u64).gen_range(0..election.precincts)
+        })
         .collect();
 
+    // Register the voters
+    //
+    // Each voter generates an Ed25519 signing keypair and the verifying (public) keys make up
+    // the voter roll. Only ballots signed by a key on the voter roll will be accepted.
+    //
+    // Note: In a production environment, voter registration would be handled by some identity
+    // process outside of this system, and the voters would generate their own keys.
+    //
+    // The ballot box also sums the encryption of 1 every voter sends along with their vote on
+    // an encrypted turnout counter, see `turnout.rs`, and only accepts ballots carrying a proof
+    // that they hold a valid vote, see `validity.rs`.
+    let voter_keys: Vec<SigningKey> = (0..num_votes)
+        .into_par_iter()
+        .map(|voter| {
+            SigningKey::generate(&mut checkpoint::derive_rng(&seed, "voter", voter as u64))
+        })
+        .collect();
+    let mut ballot_box: BallotBox = BallotBox::new(
+        voter_keys.iter().map(|k| k.verifying_key()),
+        duplicate_policy,
+    )
+    .with_validity(ProofCheck(TrustedProof))
+    .with_turnout(TurnoutCounter::new(&params));
+
+    // Only the voters who turn out cast a ballot, each with the turnout of their precinct. The
+    // others stay on the voter roll but are left out from here on.
+    let turned_out: Vec<bool> = voter_precincts
+        .iter()
+        .enumerate()
+        .map(|(voter, &precinct)| {
+            election.turnout.turns_out(
+                precinct,
+                &mut checkpoint::derive_rng(&seed, "turnout", voter as u64),
+            )
+        })
+        .collect();
+    let mut votes: Vec<Vec<u64>> = cast(votes, &turned_out);
+    let voter_precincts: Vec<PrecinctId> = cast(voter_precincts, &turned_out);
+    let voter_keys: Vec<SigningKey> = cast(voter_keys, &turned_out);
+    let registered: usize = num_votes;
+    let num_votes: usize = votes.len();
+    info!(registered, turned_out = num_votes, distribution = %election.distribution, "voters");
+
     // Encrypt the votes
     //
-    // Each vote is encrypted using the shared public key.
+    // Each vote is encrypted using the shared public key, a proof that the ciphertext holds a
+    // valid vote is attached, and the resulting ciphertext is signed with the voter's signing
+    // key to produce a ballot.
+    //
+    // Note: the proof is a stub for now, `TrustedProof`, which proves nothing.
     //
     // Note: In a production environment, the votes would be encrypted independently by each
     // of the voters and only the ciphertexts would be published.
@@ -166,30 +2652,442 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Note: encrypting votes is what takes the bulk of the execution time in this example.
     // In a production environment, this cost would be distributed across the voters.
     //
-    // Note: votes are encrypted as an array of two integers, where the first column represents
-    // the vote against and the second column represents the vote for. This is done to demonstrate
-    // the ability to perform arithmetic operations over arrays of integers.
-    pb.enable_steady_tick(Duration::from_millis(100));
-    let encryption_timer: Instant = Instant::now();
-    let results: Vec<_> = votes
-        .par_iter()
-        .map(|vote| {
-            let pt: Plaintext =
-                Plaintext::try_encode(&[*vote, 1 - *vote].to_vec(), Encoding::poly(), &params)
-                    .unwrap();
-            let ct: Ciphertext = pk.try_encrypt(&pt, &mut thread_rng()).unwrap();
-            Ok::<fhe::bfv::Ciphertext, std::io::Error>(ct)
-        })
-        .collect();
+    // Note: votes are encrypted as an array of three integers, where the first column represents
+    // the vote against, the second the vote for and the third an abstention, with exactly one of
+    // them set. This is done to demonstrate the ability to perform arithmetic operations over
+    // arrays of integers.
+    //
+    // After a restart, the ballots accepted before it are replayed from the checkpoint, and only
+    // the votes of the voters after them are encrypted.
+    //
+    // Encryption and submission run at the same time: the votes are encrypted a batch at a time
+    // on a thread of their own, and each batch of ballots is handed to the ballot box as soon as
+    // it is ready, while the next one is encrypted. The ballot box adds every ballot it accepts
+    // to a running tally, see `precinct::RunningTally`, so the ciphertexts never pile up in a
+    // list of their own before being summed. With `--store` or `--tally-chunk`, it appends the
+    // ciphertexts of every ballot it accepts to a store instead and keeps only the rest of the
+    // ballot, see `BallotBox::with_store`, and the tally streams them back out.
+    //
+    // With `election open`, voting follows the election's state file, see `lifecycle.rs`. The
+    // ballots cast while it is open are kept in the checkpoint, and once it has closed they are
+    // replayed into a closed ballot box and no voter after them casts a ballot.
+    let election_phase: Option<ElectionPhase> = election
+        .lifecycle
+        .as_deref()
+        .map(|path| ElectionState::<ElectionConfig>::load(path).map(|state| state.phase))
+        .transpose()?;
+    let voting_open: bool = election_phase.is_none_or(|phase| phase == ElectionPhase::Open);
+    let recorded: Vec<Ballot> = recovery.ballots()?;
+    let replayed: usize = recorded.len();
+    let num_votes: usize = if voting_open {
+        num_votes
+    } else {
+        replayed.min(num_votes)
+    };
+    votes.truncate(num_votes);
+    let resume_from: usize = replayed.min(num_votes);
+    if replayed == 0 && num_votes == 0 {
+        return Err("there are no votes to encrypt".into());
+    }
+    if store.is_none() && election.tally_chunk.is_none() {
+        ballot_box = ballot_box.with_tally(RunningTally::new(&params));
+    } else {
+        let (dir, mmap, replace): (Option<PathBuf>, bool, bool) =
+            (store.clone(), election.mmap, recovery.resumed());
+        let store_params: Arc<BfvParameters> = params.clone();
+        let votes: PrecinctStores = PrecinctStores::new(
+            &params,
+            Box::new(move |precinct| {
+                let name: String = format!("precinct-{precinct}");
+                ballot_store(dir.as_deref(), mmap, replace, &name, &store_params)
+            }),
+        );
+        let turnouts: Box<dyn CiphertextStore> =
+            ballot_store(store.as_deref(), mmap, replace, "turnout", &params)?;
+        ballot_box = ballot_box.with_store(votes, turnouts);
+    }
+    // With `--precompute`, an encryption of zero is computed for every voter before voting opens,
+    // and each vote is then encrypted by adding it to one, see `zero_pool`. Otherwise the pool
+    // stays empty and every vote is encrypted from scratch.
+    let pool: ZeroPool = ZeroPool::new(&pk, &params);
+    if election.precompute {
+        let zeros: usize = num_votes - resume_from;
+        let precomputation_span: Span = info_span!("precomputation", zeros);
+        let _phase = precomputation_span.enter();
+        let precomputation_timer: Instant = Instant::now();
+        pool.fill(zeros, &mut thread_rng())?;
+        info!(elapsed = ?precomputation_timer.elapsed(), "encryptions of zero precomputed");
+        phases.push(
+            item_timing(
+                "precomputation",
+                precomputation_timer.elapsed(),
+                ItemStats::new(zeros, precomputation_timer.elapsed(), 0),
+            )
+            .with_held_bytes(held_bytes),
+        );
+    }
+    let encryption_span: Span = info_span!(
+        "encryption",
+        ballots = num_votes,
+        ciphertext_bytes = field::Empty
+    );
+    let pb: ProgressBar = progress_bar(num_votes - resume_from, "encrypting", show_progress);
+
+    // Submit the ballots
+    //
+    // The ballot box checks that each ballot is signed by a registered voter and that the
+    // signature is valid for the ciphertext. Each ballot also carries the voter's nullifier,
+    // which the ballot box uses to apply the duplicate policy. Only the surviving ballots,
+    // at most one per nullifier, make it into the tally.
+    //
+    // To demonstrate, the first voter changes their mind and casts a second ballot.
+    //
+    // The replayed ballots go through the ballot box again like any other, and every newly
+    // accepted ballot is recorded in the checkpoint.
+    let submission_span: Span = info_span!(
+        "submission",
+        re_votes = field::Empty,
+        resubmitted = field::Empty
+    );
+    let phase = submission_span.enter();
+    let submission_timer: Instant = Instant::now();
+    let submission_latencies: Latencies = Latencies::new();
+    let mut submit =
+        |ballot_box: &mut BallotBox, ballot: Ballot, record: bool| -> Result<(), Box<dyn Error>> {
+            let accepted: Event = Event::ballot_accepted(&ballot);
+            let recorded: TranscriptMessage = TranscriptMessage::ballot(&ballot);
+            let nullifier: Nullifier = ballot.nullifier;
+            let submitted: Result<TrackingCode, BallotError> = submission_latencies
+                .time(|| feed::time("submission", || ballot_box.submit(ballot)));
+            if let Err(e) = &submitted {
+                feed::ballot_rejected(e.kind());
+            }
+            submitted?;
+            feed::ballot_accepted();
+            feed::set_ballots(ballot_box.len());
+            log.record(accepted)?;
+            transcript.record(recorded)?;
+            if record {
+                recovery.record_ballot(
+                    &ballot_box
+                        .ballot(&nullifier)?
+                        .expect("the ballot was just accepted"),
+                )?;
+            }
+            Ok(())
+        };
+    for ballot in recorded {
+        submit(&mut ballot_box, ballot, false)?;
+    }
+    if !voting_open {
+        ballot_box.close();
+    }
+    // Each batch is spread over every core, see `ZeroPool::encrypt_votes`. The votes drawn by
+    // `random_vote` are valid, so they skip the checks `Voting::encrypt` makes. At most
+    // `PIPELINE_BATCHES` encrypted batches wait for the ballot box, so encryption can't run
+    // ahead of it and hold the ballots twice.
+    let (sender, receiver) = mpsc::sync_channel::<Vec<Ballot>>(PIPELINE_BATCHES);
+    let (votes_left, keys_left, precincts_left): (&[Vec<u64>], &[SigningKey], &[PrecinctId]) = (
+        &votes[resume_from..],
+        &voter_keys[resume_from..],
+        &voter_precincts[resume_from..],
+    );
+    let (span, encryption_pb, pool_ref): (&Span, ProgressBar, &ZeroPool) =
+        (&encryption_span, pb.clone(), &pool);
+    let encryption_elapsed: Duration =
+        thread::scope(|scope| -> Result<Duration, Box<dyn Error>> {
+            // The sender is moved into the thread, so the ballot box sees the end of the ballots
+            // once the last batch is encrypted.
+            let encryption = scope.spawn(move || -> Result<Duration, fhe::Error> {
+                let _phase = span.enter();
+                let encryption_timer: Instant = Instant::now();
+                for ((batch, keys), precincts) in votes_left
+                    .chunks(ENCRYPTION_BATCH)
+                    .zip(keys_left.chunks(ENCRYPTION_BATCH))
+                    .zip(precincts_left.chunks(ENCRYPTION_BATCH))
+                {
+                    let ciphertexts: Vec<Ciphertext> = feed::time("encryption_batch", || {
+                        pool_ref.encrypt_votes(batch, &mut thread_rng())
+                    })?;
+                    let ballots: Vec<Ballot> = ciphertexts
+                        .into_par_iter()
+                        .zip(batch.par_iter())
+                        .zip(keys.par_iter())
+                        .zip(precincts.par_iter())
+                        .map(|(((ct, vote), key), precinct)| {
+                            let proof: Vec<u8> = TrustedProof.prove(vote, &ct)?;
+                            // The voter's 1 for the turnout, as `turnout::encrypt_one` would.
+                            let one: Ciphertext = pool_ref.encrypt(&[1], &mut thread_rng())?;
+                            Ok::<Ballot, fhe::Error>(Ballot::with_turnout(
+                                ct, one, *precinct, proof, key,
+                            ))
+                        })
+                        .collect::<Result<_, _>>()?;
+                    encryption_pb.inc(batch.len() as u64);
+                    // The ballot box only hangs up on an error, which it reports itself.
+                    if sender.send(ballots).is_err() {
+                        break;
+                    }
+                }
+                Ok(encryption_timer.elapsed())
+            });
+            // Dropping the receiver on an error stops the encryption at its next batch.
+            for ballots in receiver {
+                for ballot in ballots {
+                    submit(&mut ballot_box, ballot, true)?;
+                }
+            }
+            Ok(encryption.join().expect("the encryption thread panicked")?)
+        })?;
+    pb.finish_and_clear();
+    // After a restart, the revote is only cast if it isn't among the replayed ballots already.
+    let revote: Vec<u64> = voting.random_vote(
+        &election.distribution,
+        0,
+        &mut checkpoint::derive_rng(&seed, "revote", 0),
+    );
+    if replayed > num_votes {
+        votes[0] = revote;
+    } else if ballot_box.is_open() {
+        let revote_ct: Ciphertext = voting.encrypt(&revote, &pk, &params, &mut thread_rng())?;
+        let revote_proof: Vec<u8> = TrustedProof.prove(&revote, &revote_ct)?;
+        let revote_ballot: Ballot = Ballot::with_turnout(
+            revote_ct,
+            turnout::encrypt_one(&pk, &params, &mut thread_rng())?,
+            voter_precincts[0],
+            revote_proof,
+            &voter_keys[0],
+        );
+        match submit(&mut ballot_box, revote_ballot, true) {
+            Ok(()) => votes[0] = revote,
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<BallotError>(),
+                    Some(BallotError::DuplicateVoter)
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    // The voters of the write-in bucket hand the name they write in to a collector of its own,
+    // off the path of their ballot, see `write_in.rs`.
+    let mut write_ins: WriteIns = WriteIns::new();
+    let write_in: Option<(usize, WriteInRule)> = match voting {
+        Voting::Candidates(candidates) => candidates.write_in(),
+        _ => None,
+    };
+    if let Some((slot, _)) = write_in {
+        for (voter, vote) in votes.iter().enumerate() {
+            if vote[slot] == 1 {
+                let mut rng: StdRng = checkpoint::derive_rng(&seed, "write-in", voter as u64);
+                let name: &str = WRITE_IN_NAMES[rng.gen_range(0..WRITE_IN_NAMES.len())];
+                write_ins.submit(WriteIn::new(name, &mut rng)?);
+            }
+        }
+        info!(names = write_ins.len(), "write-ins collected");
+    }
+
+    // To demonstrate a Benaloh challenge, the first voter has their client encrypt their vote
+    // once more and spoils that ballot rather than casting it. The opening shows the client
+    // encrypted what it was asked to, see `challenge.rs`, and the spoiled ballot is never
+    // counted. Like a ballot, it is only taken while voting is open.
+    let (challenged_ct, opening): (Ciphertext, Opening) =
+        challenge::encrypt_challengeable(&votes[0], &pk, &params, &mut thread_rng())?;
+    let challenged: Ballot = Ballot::new(challenged_ct, voter_precincts[0], &voter_keys[0]);
+    if !opening.verify(&challenged.ciphertext, &pk, &params)? {
+        return Err("the client encrypted another vote than the voter's".into());
+    }
+    if ballot_box.is_open() {
+        ballot_box.spoil(challenged)?;
+        info!(
+            spoiled = ballot_box.spoiled().len(),
+            "ballot spoiled and opened"
+        );
+    }
+    let first: Cow<Ballot> = ballot_box.ballot_at(0)?;
+    let ballot_bytes: Vec<u8> = first.to_bytes();
+    sizes.ballot = ballot_bytes.len();
+    compressed_sizes.ballot = compressed_len(&ballot_bytes)?;
+    let ciphertext_bytes: usize = first.ciphertext.to_bytes().len();
+    encryption_span.record("ciphertext_bytes", ciphertext_bytes);
+    info!(elapsed = ?encryption_elapsed, "votes encrypted");
+    held_bytes += (num_votes * sizes.ballot) as u64;
+    phases.push(
+        item_timing(
+            "encryption",
+            encryption_elapsed,
+            // Encrypted in batches, so only the throughput is known.
+            ItemStats::new(
+                num_votes,
+                encryption_elapsed,
+                (num_votes * sizes.ballot) as u64,
+            ),
+        )
+        .with_held_bytes(held_bytes),
+    );
+
+    // Announce the turnout
+    //
+    // While voting is still open, the turnout counter is decrypted through a decryption ceremony
+    // of its own. It sums the encryption of 1 each voter sent along with their vote, so its
+    // decryption shares are for a different ciphertext than the tally and reveal nothing about
+    // the votes. Every party takes part: the dropouts and byzantine parties are only simulated
+    // for the tally, further down.
+    let turnout_ciphertext: Arc<Ciphertext> = Arc::new(
+        ballot_box
+            .turnout()
+            .ok_or("the ballot box is not counting turnout")?
+            .clone(),
+    );
+    let turnout: u64 = Decryptors {
+        trustees: &trustees,
+        keys: &parties,
+        present: (0..num_parties).collect(),
+        byzantine: BTreeSet::new(),
+        impostor: Party::new(
+            &params,
+            &crp,
+            &mut checkpoint::derive_rng(&seed, "impostor", 0),
+        )?
+        .0,
+        show_progress,
+        latencies: Latencies::new(),
+        airgap: None,
+    }
+    .decrypt(
+        &turnout_ciphertext,
+        1,
+        &mut log,
+        &mut transcript,
+        &mut recovery,
+        &mut thread_rng(),
+    )?
+    .decrypt(1)?[0];
+    info!(turnout, open = ballot_box.is_open(), "turnout decrypted");
+
+    // With `election open`, voting stays open once the voters have cast their ballots. They wait
+    // in the checkpoint for `election finalize`, after `election close`.
+    if election_phase == Some(ElectionPhase::Open) {
+        info!(
+            ballots = ballot_box.len(),
+            "voting stays open until `election close`"
+        );
+        return Ok(RunReport {
+            phases,
+            ..RunReport::default()
+        });
+    }
+
+    // Voting closes. From here on the ballot box turns every ballot away, see `lifecycle.rs`.
+    ballot_box.close();
+    recovery.advance(Phase::Tallying)?;
+    submission_span.record("re_votes", ballot_box.replaced());
+    submission_span.record("resubmitted", ballot_box.resubmitted());
+    info!(elapsed = ?submission_timer.elapsed(), "ballots submitted");
+    phases.push(
+        item_timing(
+            "submission",
+            submission_timer.elapsed(),
+            submission_latencies.stats(
+                submission_timer.elapsed(),
+                (submission_latencies.len() * sizes.ballot) as u64,
+            ),
+        )
+        .with_held_bytes(held_bytes),
+    );
+    drop(phase);
+
+    // Refresh the key shares
+    //
+    // With `--refresh`, the parties rerandomize their key shares before the tally is decrypted,
+    // as they would every so often during a long election: each key slot sends every slot a
+    // part of a sharing of zero, and adds the parts it is sent to its share, see `reshare.rs`.
+    // The secret key doesn't change, so the public key the ballots were encrypted to still
+    // holds, which is checked after each round by decrypting an encryption to it with the new
+    // shares. The randomness is derived from the seed, so a resumed run refreshes the shares the
+    // same way and the decryption shares it recorded still add up.
+    let refresh_timer: Instant = Instant::now();
+    let mut parties: Vec<Party> = parties;
+    for round in 0..election.refresh {
+        let mut refresh: Reshare = Reshare::refresh(&params, &crp, num_slots)?;
+        for slot in 0..num_slots {
+            let index: u64 = (round * num_slots + slot) as u64;
+            let mut rng: StdRng = checkpoint::derive_rng(&seed, "refresh", index);
+            for part in reshare::zero_sharing(slot, num_slots, &params, &mut rng)? {
+                refresh.receive(part)?;
+            }
+        }
+        parties = parties
+            .iter()
+            .enumerate()
+            .map(|(slot, key)| {
+                let index: u64 = (round * num_slots + slot) as u64;
+                let mut rng: StdRng = checkpoint::derive_rng(&seed, "refreshed", index);
+                // The public key share is left out, the public key stays the same.
+                refresh
+                    .refreshed_share(slot, key, &mut rng)
+                    .map(|(refreshed, _)| refreshed)
+            })
+            .collect::<Result<_, _>>()?;
+        if !reshare::check_same_key(&pk, &parties, &params, &mut thread_rng())? {
+            return Err("the refreshed key shares don't hold the election's key".into());
+        }
+        info!(round, "key shares refreshed");
+    }
+    if election.refresh > 0 {
+        phases.push(timing("refresh", refresh_timer.elapsed()).with_held_bytes(held_bytes));
+    }
 
-    let encrypted_votes: Result<Vec<_>, _> = results.into_iter().collect();
+    // Commit to the ballot box
+    //
+    // Each accepted ciphertext is hashed and the hashes become the leaves of a Merkle tree.
+    // The root of the tree is published alongside the tally, committing the election to the
+    // exact set of ballots that were summed.
+    //
+    // Note: the commitment is over the ciphertexts, not the votes, so publishing it reveals
+    // nothing about how anyone voted.
+    //
+    // With `--store`, the ballot box hashed each ciphertext as it stored it.
+    let commitment_span: Span = info_span!("commitment", ballots = ballot_box.len());
+    let phase = commitment_span.enter();
+    let pb: ProgressBar = progress_bar(ballot_box.len(), "hashing", show_progress);
+    let commitment_timer: Instant = Instant::now();
+    let commitment_latencies: Latencies = Latencies::new();
+    let ballot_hashes: Vec<Hash> = match ballot_box.stored() {
+        Some(stored) => stored
+            .iter()
+            .map(|ballot| commitment_latencies.time(|| ballot.ciphertext_hash))
+            .progress_with(pb.clone())
+            .collect(),
+        None => ballot_box
+            .ballots()
+            .par_iter()
+            .map(|ballot| {
+                commitment_latencies.time(|| merkle::hash_leaf(&ballot.ciphertext.to_bytes()))
+            })
+            .progress_with(pb.clone())
+            .collect(),
+    };
+    let ballot_tree: MerkleTree = MerkleTree::new(ballot_hashes);
+    let ballots_root: Hash = ballot_tree.root();
     pb.finish_and_clear();
-    println!(
-        "  \x1b[1mEncryption Time:\x1b[0m\t{:#?}",
-        encryption_timer.elapsed()
+    info!(elapsed = ?commitment_timer.elapsed(), "ballot box committed");
+    held_bytes += (ballot_tree.len() * std::mem::size_of::<Hash>()) as u64;
+    phases.push(
+        item_timing(
+            "commitment",
+            commitment_timer.elapsed(),
+            commitment_latencies.stats(
+                commitment_timer.elapsed(),
+                (ballot_tree.len() * ciphertext_bytes) as u64,
+            ),
+        )
+        .with_held_bytes(held_bytes),
     );
+    drop(phase);
 
-    pb.enable_steady_tick(Duration::from_millis(100));
+    let tally_span: Span = info_span!("tally", tally_bytes = field::Empty);
+    let phase = tally_span.enter();
+    let pb: ProgressBar = progress_bar(ballot_box.len(), "tallying", show_progress);
     let tally_timer: Instant = Instant::now();
     // Tally the votes
     //
@@ -197,16 +3095,103 @@ fn main() -> Result<(), Box<dyn Error>> {
     // The result is an encrypted tally of the votes.
     // This is the real magic of homomorphic encryption, we can perform operations on the
     // ciphertexts that correspond to operations on the plaintexts!
-    let mut sum: Ciphertext = Ciphertext::zero(&params);
-    for vote in encrypted_votes.unwrap().iter() {
-        sum += vote;
-    }
-    let tally: Arc<Ciphertext> = Arc::new(sum);
+    //
+    // Note: the ballots of each precinct are summed into a precinct tally first, and the
+    // precinct tallies are then summed into the overall tally. Addition doesn't care about the
+    // order, so this gives exactly the same ciphertext as summing all the ballots at once.
+    //
+    // Without `--store`, the ballot box has kept the precinct tallies running as it accepted the
+    // ballots, so they are ready as is. Otherwise it has appended the ciphertexts of each
+    // precinct to a store of their own, and they are streamed back out of it, so with `--store`
+    // the tally doesn't need them in memory.
+    //
+    // With `--tally-chunk`, each store is summed a chunk at a time and the sum of every chunk
+    // is recorded in the checkpoint. After a restart, the chunks recorded are skipped, and the
+    // chunk sums are added up once the last one is done.
+    let (precinct_tallies, io_split): (BTreeMap<PrecinctId, PrecinctTally>, IoSplit) =
+        match ballot_box.take_tally() {
+            Some(running) => running.finish(),
+            None => {
+                let stores: &PrecinctStores = ballot_box
+                    .precinct_stores()
+                    .expect("the ballot box keeps a store unless it keeps a running tally");
+                match election.tally_chunk {
+                    Some(chunk_size) => {
+                        let mut done: BTreeMap<PrecinctId, Vec<Ciphertext>> = BTreeMap::new();
+                        for id in stores.precincts() {
+                            done.insert(id, recovery.partial_sums(id, chunk_size)?);
+                        }
+                        let resumed_chunks: usize = done.values().map(Vec::len).sum();
+                        if resumed_chunks > 0 {
+                            info!(chunks = resumed_chunks, "resuming the tally");
+                        }
+                        precinct::tally_stores_chunked(
+                            stores,
+                            &params,
+                            chunk_size,
+                            done,
+                            |id, chunk, sum| -> Result<(), Box<dyn Error>> {
+                                Ok(recovery.record_partial_sum(id, chunk_size, chunk, sum)?)
+                            },
+                        )?
+                    }
+                    None => precinct::tally_stores(stores, &params)?,
+                }
+            }
+        };
+    pb.inc(ballot_box.len() as u64);
+    info!(
+        io_ms = io_split.io_ms,
+        compute_ms = io_split.compute_ms,
+        "tally loading and adding"
+    );
+    let sum: Ciphertext = precinct::aggregate(precinct_tallies.values(), &params);
     pb.finish_and_clear();
-    println!(
-        "  \x1b[1mTallying time:\x1b[0m\t{:#?}",
-        tally_timer.elapsed()
+
+    // With `--rerandomize`, an encryption of zero is added to the tally before it is published.
+    // It decrypts to the same result but can't be linked to the sum of the ballots, which also
+    // means `verify` can no longer recompute it from the ballots.
+    //
+    // After a restart, the tally published before it stands, so that the decryption shares
+    // recorded for it still apply.
+    let tally: Arc<Ciphertext> = match recovery.tally()? {
+        Some(published) => Arc::new(published),
+        None if election.rerandomize => {
+            Arc::new(tally::rerandomize(&sum, &pk, &params, &mut thread_rng())?)
+        }
+        None => Arc::new(sum),
+    };
+    recovery.record_tally(&tally)?;
+    recovery.advance(Phase::Decryption)?;
+    log.record(Event::TallyPublished {
+        tally_hash: decryption_ceremony::tally_hash(&tally),
+        ballots_root,
+    })?;
+    transcript.record(TranscriptMessage::tally(&tally, election.rerandomize))?;
+    let tally_bytes: Vec<u8> = tally.to_bytes();
+    sizes.tally = tally_bytes.len();
+    feed::set_tally_bytes(sizes.tally);
+    compressed_sizes.tally = compressed_len(&tally_bytes)?;
+    tally_span.record("tally_bytes", sizes.tally);
+    info!(elapsed = ?tally_timer.elapsed(), "votes tallied");
+    // The ballots are summed inside `precinct::tally_stores`, so only the throughput is
+    // known, not how long each addition took.
+    let num_ballots: usize = ballot_box.len();
+    held_bytes += ((precinct_tallies.len() + 1) * sizes.tally) as u64;
+    phases.push(
+        item_timing(
+            "tally",
+            tally_timer.elapsed(),
+            ItemStats::new(
+                num_ballots,
+                tally_timer.elapsed(),
+                (num_ballots * ciphertext_bytes) as u64,
+            ),
+        )
+        .with_held_bytes(held_bytes)
+        .with_io_split(io_split),
     );
+    drop(phase);
 
     // Decrypt the tally
     //
@@ -216,37 +3201,446 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Note: As with the public key shares, aggregation of the decryption shares simply involves
     // summing them together. This means the decryption shares can be aggregated in any order
     // and can be generated asynchronously and aggregated in parallel as shares are published.
-    pb.enable_steady_tick(Duration::from_millis(100));
+    let decryption_span: Span = info_span!(
+        "decryption",
+        shares = field::Empty,
+        dropouts = field::Empty,
+        share_bytes = field::Empty
+    );
+    let phase = decryption_span.enter();
     let decryption_timer: Instant = Instant::now();
-    let decryption_shares: Result<Vec<DecryptionShare>, _> = parties
-        .par_iter()
-        .map(|party| {
-            let sh = DecryptionShare::new(&party.sk_share, &tally, &mut thread_rng()).unwrap();
-            Ok::<fhe::mbfv::DecryptionShare, std::io::Error>(sh)
+
+    // Simulate dropouts
+    //
+    // Some parties never show up to decrypt. Each key slot needs one of its holders to be
+    // present to produce its decryption share. If a slot has no holder left, the quorum can't
+    // be reached and decryption fails with a quorum error.
+    let num_dropouts: usize = (num_parties as f64 * election.dropout_rate).round() as usize;
+    let dropouts: BTreeSet<PartyId> = index::sample(
+        &mut checkpoint::derive_rng(&seed, "dropouts", 0),
+        num_parties,
+        num_dropouts.min(num_parties),
+    )
+    .into_iter()
+    .collect();
+    let present: BTreeSet<PartyId> = (0..num_parties)
+        .filter(|party| !dropouts.contains(party))
+        .collect();
+    decryption_span.record("dropouts", dropouts.len());
+    trustees.assign(&present)?;
+
+    // Simulate byzantine parties
+    //
+    // Some of the parties that do show up send garbage: a decryption share made with a secret
+    // key that has nothing to do with the election. The decrypted tally then comes out as noise,
+    // which the decryption ceremony notices. If the key slots of the bad parties are also held
+    // by honest ones, it leaves the bad parties out and decrypts without them.
+    //
+    // Note: the ceremony only notices noise in the slots past the votes. A share shifted in the
+    // vote slots alone would go unnoticed, see `decryption_ceremony.rs`.
+    let byzantine: BTreeSet<PartyId> = present
+        .iter()
+        .copied()
+        .collect::<Vec<PartyId>>()
+        .choose_multiple(
+            &mut checkpoint::derive_rng(&seed, "byzantine", 0),
+            election.byzantine,
+        )
+        .copied()
+        .collect();
+    // The signing key of each party, for the air-gapped exchange and the result's certification.
+    let trustee_keys: Vec<SigningKey> = (0..num_parties)
+        .map(|trustee| {
+            SigningKey::generate(&mut checkpoint::derive_rng(
+                &seed,
+                "trustee",
+                trustee as u64,
+            ))
         })
         .collect();
-    let pt: Plaintext = decryption_shares.unwrap().into_iter().aggregate()?;
-    let tally_vec: Vec<u64> = Vec::<u64>::try_decode(&pt, Encoding::poly())?;
-    let tally_result: Vec<u64> = [tally_vec[0], tally_vec[1]].to_vec();
-    pb.finish_and_clear();
+    let decryptors: Decryptors = Decryptors {
+        trustees: &trustees,
+        keys: &parties,
+        present,
+        byzantine,
+        impostor: Party::new(
+            &params,
+            &crp,
+            &mut checkpoint::derive_rng(&seed, "impostor", 0),
+        )?
+        .0,
+        show_progress,
+        latencies: Latencies::new(),
+        airgap: election
+            .airgap
+            .as_ref()
+            .map(|dir| -> Result<AirGap, Box<dyn Error>> {
+                fs::create_dir_all(dir)?;
+                Ok(AirGap {
+                    dir: dir.clone(),
+                    params: params.clone(),
+                    context: ballots_root,
+                    coordinator: SigningKey::generate(&mut checkpoint::derive_rng(
+                        &seed,
+                        "coordinator",
+                        0,
+                    )),
+                    trustee_keys: trustee_keys.clone(),
+                })
+            })
+            .transpose()?,
+    };
+
+    let mut decryption: DecryptionCeremony = decryptors.decrypt(
+        &tally,
+        voting.len(),
+        &mut log,
+        &mut transcript,
+        &mut recovery,
+        &mut thread_rng(),
+    )?;
+    let tally_result: Vec<u64> = decryption.decrypt(voting.len())?.to_vec();
+    let shares_used: Vec<&DecryptionShare> = decryption.shares_used();
+    decryption_span.record("shares", shares_used.len());
+    let share_bytes: Vec<u8> = shares_used[0].to_bytes();
+    sizes.decryption_share = share_bytes.len();
+    compressed_sizes.decryption_share = compressed_len(&share_bytes)?;
+    decryption_span.record("share_bytes", sizes.decryption_share);
+    info!(elapsed = ?decryption_timer.elapsed(), "tally decrypted");
+
+    // Sign the result
+    //
+    // Every party that contributed a decryption share of the tally signs the result together
+    // with the hashes it stands on, see `certification.rs`. The result is only certified once
+    // as many parties as it takes to decrypt have signed, each with their own key. The bundle
+    // is published with the artifacts, for systems downstream that only want the one file.
+    let mut certification: Certification = Certification::new(
+        &tally_result,
+        &params,
+        &pk,
+        ballots_root,
+        ballot_tree.len(),
+        &tally,
+        election.rerandomize,
+        decryption.contributors(),
+        trustees.threshold(),
+    );
+    for trustee in certification.contributors.clone() {
+        certification.sign(trustee, &trustee_keys[trustee]);
+    }
+    let roster: Vec<VerifyingKey> = trustee_keys.iter().map(SigningKey::verifying_key).collect();
+    certification.certify(&roster, trustees.threshold())?;
+    if let Some(path) = &election.roster {
+        fs::write(path, certification::roster_to_json(&roster))?;
+    }
+    info!(
+        signatures = certification.signatures.len(),
+        quorum = certification.quorum,
+        certification = %hex::encode(certification.hash()),
+        "result certified"
+    );
 
-    println!(
-        "  \x1b[1mDecryption time:\x1b[0m\t{:#?}",
-        decryption_timer.elapsed()
+    // Decrypt the precinct totals
+    //
+    // Only for precincts with enough ballots that their total doesn't give away how any one
+    // voter voted. Each precinct tally goes through a decryption ceremony of its own.
+    let mut precinct_results: BTreeMap<PrecinctId, Vec<u64>> = BTreeMap::new();
+    if let Some(min_ballots) = election.precinct_min_ballots {
+        for (id, precinct) in precinct::decryptable(&precinct_tallies, min_ballots) {
+            let ciphertext: Arc<Ciphertext> = Arc::new(precinct.ciphertext.clone());
+            let result: Vec<u64> = decryptors
+                .decrypt(
+                    &ciphertext,
+                    voting.len(),
+                    &mut log,
+                    &mut transcript,
+                    &mut recovery,
+                    &mut thread_rng(),
+                )?
+                .decrypt(voting.len())?
+                .to_vec();
+            info!(
+                precinct = id,
+                ballots = precinct.ballots,
+                tally = ?result,
+                "precinct result"
+            );
+            precinct_results.insert(id, result);
+        }
+        info!(
+            decrypted = precinct_results.len(),
+            withheld = precinct_tallies.len() - precinct_results.len(),
+            min_ballots,
+            "precinct totals"
+        );
+    }
+    held_bytes += (decryptors.latencies.len() * sizes.decryption_share) as u64;
+    phases.push(
+        item_timing(
+            "decryption",
+            decryption_timer.elapsed(),
+            decryptors.latencies.stats(
+                decryption_timer.elapsed(),
+                (decryptors.latencies.len() * sizes.decryption_share) as u64,
+            ),
+        )
+        .with_held_bytes(held_bytes),
     );
-    println!("  \x1b[1mExecution time:\x1b[0m\t{:#?}", main.elapsed());
+    drop(phase);
+    info!(elapsed = ?main.elapsed(), "execution time");
+    phases.push(timing("total", main.elapsed()).with_held_bytes(held_bytes));
+
+    log.record(Event::ResultPublished {
+        tally: tally_result.clone(),
+    })?;
+    transcript.record(TranscriptMessage::Result {
+        result: tally_result.clone(),
+    })?;
+    recovery.advance(Phase::Done)?;
 
     // Print the result
-    println!("  \x1b[1mVotes Against:\x1b[0m\t{}", tally_result[0]);
-    println!("  \x1b[1mVotes For:\x1b[0m\t\t{}", tally_result[1]);
-    pb.finish_and_clear();
+    dashboard::show_result(match voting {
+        Voting::YesNo => Choice::ALL
+            .iter()
+            .map(|choice| format!("{choice:?}").to_lowercase())
+            .zip(tally_result.iter().copied())
+            .collect(),
+        Voting::Quadratic { .. } => tally_result
+            .iter()
+            .enumerate()
+            .map(|(option, &votes)| (format!("option {option}"), votes))
+            .collect(),
+        Voting::Questions(questions) => questions
+            .split(&tally_result)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(question, tally)| {
+                tally
+                    .iter()
+                    .enumerate()
+                    .map(move |(option, &votes)| (format!("q{question} #{option}"), votes))
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+        Voting::Candidates(candidates) => candidates
+            .standings(&tally_result)?
+            .standings
+            .iter()
+            .map(|standing| (standing.candidate.name.clone(), standing.votes))
+            .collect(),
+    });
+    // Seats in proportion to the votes, see `seats`. Write-ins are not on the list, so the
+    // write-in bucket wins none.
+    let allocation: Option<Allocation> = match (voting, election.seats) {
+        (Voting::Candidates(candidates), Some((num_seats, method))) => {
+            let mut votes: Vec<u64> = tally_result.clone();
+            if let Some((slot, _)) = candidates.write_in() {
+                votes[slot] = 0;
+            }
+            Some(seats::allocate(&votes, num_seats, method)?)
+        }
+        _ => None,
+    };
+    match voting {
+        Voting::YesNo => info!(
+            votes_against = tally_result[0],
+            votes_for = tally_result[1],
+            abstentions = tally_result[2],
+            ballots_root = %hex::encode(ballots_root),
+            ballots = ballot_tree.len(),
+            "result"
+        ),
+        Voting::Quadratic { .. } => info!(
+            votes = ?tally_result,
+            ballots_root = %hex::encode(ballots_root),
+            ballots = ballot_tree.len(),
+            "result"
+        ),
+        Voting::Questions(questions) => {
+            for (question, tally) in questions.split(&tally_result).into_iter().enumerate() {
+                info!(question, tally = ?tally, "question result");
+            }
+            info!(
+                ballots_root = %hex::encode(ballots_root),
+                ballots = ballot_tree.len(),
+                "result"
+            );
+        }
+        Voting::Candidates(candidates) => {
+            let standings: Standings = candidates.standings(&tally_result)?;
+            for standing in &standings.standings {
+                info!(
+                    candidate = %standing.candidate.name,
+                    id = %standing.candidate.id,
+                    votes = standing.votes,
+                    percent = %format!("{:.1}%", standing.percent),
+                    "candidate result"
+                );
+            }
+            let leaders: Vec<&str> = standings
+                .leaders
+                .iter()
+                .map(|leader| leader.name.as_str())
+                .collect();
+            if standings.is_tie() {
+                warn!(?leaders, votes = standings.total(), "tie");
+            }
+            if let Some(allocation) = &allocation {
+                for slot in candidates.display_order() {
+                    info!(
+                        candidate = %candidates.get(slot).expect("slot of a candidate").name,
+                        seats = allocation.allocation[slot],
+                        "seats"
+                    );
+                }
+                if allocation.tie {
+                    warn!(
+                        method = %allocation.method,
+                        "tie for the last seat, to be settled by the rules of the election"
+                    );
+                }
+            }
+            if let Some((slot, rule)) = candidates.write_in() {
+                match write_ins.reveal(tally_result[slot], standings.total(), rule)? {
+                    Reveal::Withheld { commitments } => info!(
+                        names = commitments.len(),
+                        reveal_at = %format!("{}%", rule.reveal_at),
+                        "write-ins withheld, only their commitments are published"
+                    ),
+                    Reveal::Revealed { counts, .. } => {
+                        for (name, votes) in counts {
+                            info!(write_in = %name, votes, "write-in result");
+                        }
+                    }
+                }
+            }
+            info!(
+                ?leaders,
+                ballots_root = %hex::encode(ballots_root),
+                ballots = ballot_tree.len(),
+                "result"
+            );
+        }
+    }
+
+    // Compare the size of each artifact as serialized by fhe.rs with its zstd compressed form,
+    // which is what we'd write to disk or send over the wire.
+    info!(
+        pk_share = sizes.pk_share,
+        pk_share_compressed = compressed_sizes.pk_share,
+        public_key = sizes.public_key,
+        public_key_compressed = compressed_sizes.public_key,
+        ballot = sizes.ballot,
+        ballot_compressed = compressed_sizes.ballot,
+        tally = sizes.tally,
+        tally_compressed = compressed_sizes.tally,
+        decryption_share = sizes.decryption_share,
+        decryption_share_compressed = compressed_sizes.decryption_share,
+        "artifact sizes in bytes"
+    );
+
+    // Multiplied out over the run: the ballots, one per vote cast, and everything exchanged
+    // with the coordinator, see `ArtifactSizes::transferred`.
+    let ballot_bytes: u64 = sizes.ballot_bandwidth(num_votes);
+    let bytes_transferred: u64 = sizes.transferred(num_votes, num_slots);
+    info!(
+        ballots = num_votes,
+        ballot_bytes,
+        ballot_bytes_compressed = compressed_sizes.ballot_bandwidth(num_votes),
+        bytes_transferred,
+        "bandwidth in bytes"
+    );
+
+    // Publish the artifacts
+    //
+    // Everything an auditor needs to re-check the result goes in one directory, to be checked
+    // with the `verify` subcommand against the ballots root published above.
+    if let Some(dir) = &election.publish {
+        let ballots: Vec<Ballot> = ballot_box
+            .iter()
+            .map(|ballot| ballot.map(Cow::into_owned))
+            .collect::<Result<_, _>>()?;
+        // Signed with the same coordinator key as the air-gapped requests.
+        let manifest: BallotManifest = BallotManifest::new(
+            ballots
+                .iter()
+                .zip(ballot_box.submitted_at().iter().copied()),
+            &SigningKey::generate(&mut checkpoint::derive_rng(&seed, "coordinator", 0)),
+        );
+        let artifacts: Artifacts = Artifacts {
+            params: params.clone(),
+            public_key: pk.clone(),
+            ballots,
+            tally: tally.clone(),
+            shares: decryption.into_shares_used(),
+            result: PublishedResult {
+                version: ARTIFACTS_VERSION,
+                tally: tally_result.clone(),
+                threshold: trustees.threshold(),
+            },
+            journal: TallyJournal::new(
+                &params,
+                ballots_root,
+                ballot_tree.len(),
+                &tally,
+                election.rerandomize,
+            ),
+            manifest,
+        };
+        artifacts.write(dir, Compression::Zstd)?;
+        fs::write(dir.join(CERTIFICATION_FILE), certification.to_json())?;
+        info!(dir = %dir.display(), "artifacts published");
+    }
+
+    // Verify that a ballot was recorded as cast
+    //
+    // Any voter can hash the ciphertext they submitted and request an inclusion proof for it.
+    // The proof is the list of sibling hashes on the path from their leaf to the root, so the
+    // voter can recompute the root themselves and compare it with the published one.
+    //
+    // Note: we pick a random voter here, in production each voter would check their own ballot.
+    let voter: usize = thread_rng().gen_range(0..num_votes);
+    let voter_ballot: Cow<Ballot> = ballot_box.ballot_at(voter)?;
+    let voter_hash: Hash = merkle::hash_leaf(&voter_ballot.ciphertext.to_bytes());
+    let proof: InclusionProof = ballot_tree.proof(voter).unwrap();
+    assert!(proof.verify(&voter_hash, &ballots_root));
+    info!(
+        code = %TrackingCode::of(&voter_ballot),
+        ballot = proof.index,
+        hashes = proof.steps.len(),
+        "inclusion proof verified"
+    );
 
     // Check that the results match the expected result
     //
     // Note: this is not possible in production, since we would not know the plaintext inputs.
-    let vote_sum: u64 = votes.par_iter().sum();
-    let expected_tally: Vec<u64> = [vote_sum as u64, num_votes as u64 - vote_sum].to_vec();
-    assert_eq!(tally_result, expected_tally);
+    assert_eq!(tally_result, expected_tally(&votes, voting.len()));
+    assert_eq!(turnout, ballot_box.len() as u64);
+    for (id, result) in &precinct_results {
+        let precinct_votes = votes
+            .iter()
+            .zip(&voter_precincts)
+            .filter(|(_, precinct)| *precinct == id)
+            .map(|(vote, _)| vote);
+        assert_eq!(*result, expected_tally(precinct_votes, voting.len()));
+    }
 
-    Ok(())
+    Ok(RunReport {
+        params: RunParams {
+            num_votes,
+            num_parties,
+            degree,
+            plaintext_modulus,
+            moduli,
+            threads: parallel::current_num_threads(),
+        },
+        phases,
+        bytes_transferred,
+        ballot_bytes,
+        estimated_memory_bytes: estimated_memory,
+        sizes,
+        compressed_sizes,
+        tally: tally_result,
+        seats: allocation,
+    })
 }
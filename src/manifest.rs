@@ -0,0 +1,180 @@
+// The ballot manifest: every accepted ballot's hash, when it was submitted and who signed it,
+// signed by the coordinator.
+//
+// The hashes are those of the ballots' ciphertexts, the leaves of the Merkle tree whose root is
+// published with the tally, in the same order. Auditors diff the manifest against that root and
+// against the inputs an E3 committed to, see `e3.rs`, and `verify` recomputes every hash from the
+// published ciphertexts and checks it against the manifest. Timestamps are seconds since the
+// Unix epoch, as the coordinator's clock had them.
+//
+// The manifest is JSON, like the tally journal it is published next to, with a format version
+// that readers check.
+
+use crate::{
+    ballot::Ballot,
+    merkle::{self, Hash, MerkleTree},
+};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use fhe_traits::Serialize as FheSerialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{error::Error, fmt};
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+const SIGNATURE_DOMAIN: &[u8] = b"fhe-workshop/manifest";
+
+#[derive(Debug)]
+pub enum ManifestError {
+    UnsupportedVersion(u32),
+    Json(serde_json::Error),
+    BadSignature,
+    // The manifest lists a different number of ballots than were published.
+    Count { listed: usize, published: usize },
+    // The ballot at this position doesn't match its entry.
+    Mismatch(usize),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::UnsupportedVersion(v) => write!(
+                f,
+                "manifest version {v} is not supported, expected {MANIFEST_VERSION}"
+            ),
+            ManifestError::Json(e) => write!(f, "{e}"),
+            ManifestError::BadSignature => write!(f, "manifest signature is invalid"),
+            ManifestError::Count { listed, published } => write!(
+                f,
+                "the manifest lists {listed} ballots, but {published} were published"
+            ),
+            ManifestError::Mismatch(position) => {
+                write!(f, "ballot {position} doesn't match its manifest entry")
+            }
+        }
+    }
+}
+
+impl Error for ManifestError {}
+
+impl From<serde_json::Error> for ManifestError {
+    fn from(e: serde_json::Error) -> Self {
+        ManifestError::Json(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    #[serde(with = "hex::serde")]
+    pub ciphertext_hash: Hash,
+    pub submitted_at: u64,
+    #[serde(with = "hex::serde")]
+    pub voter: [u8; 32],
+}
+
+impl ManifestEntry {
+    pub fn new(ballot: &Ballot, submitted_at: u64) -> Self {
+        ManifestEntry {
+            ciphertext_hash: merkle::hash_leaf(&ballot.ciphertext.to_bytes()),
+            submitted_at,
+            voter: ballot.voter.to_bytes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BallotManifest {
+    pub version: u32,
+    pub entries: Vec<ManifestEntry>,
+    #[serde(with = "hex::serde")]
+    pub ballots_root: Hash,
+    #[serde(with = "hex::serde")]
+    pub signer: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl BallotManifest {
+    // Lists the ballots, each with when it was submitted, and signs the list.
+    pub fn new<'a>(
+        ballots: impl IntoIterator<Item = (&'a Ballot, u64)>,
+        signing_key: &SigningKey,
+    ) -> Self {
+        let entries: Vec<ManifestEntry> = ballots
+            .into_iter()
+            .map(|(ballot, submitted_at)| ManifestEntry::new(ballot, submitted_at))
+            .collect();
+        let ballots_root: Hash =
+            MerkleTree::new(entries.iter().map(|entry| entry.ciphertext_hash).collect()).root();
+        let mut manifest = BallotManifest {
+            version: MANIFEST_VERSION,
+            entries,
+            ballots_root,
+            signer: signing_key.verifying_key().to_bytes(),
+            signature: Vec::new(),
+        };
+        manifest.signature = signing_key.sign(&manifest.hash()).to_vec();
+        manifest
+    }
+
+    // The hash the coordinator signs: every field but the signature, in order.
+    fn hash(&self) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update(SIGNATURE_DOMAIN);
+        hasher.update(self.version.to_le_bytes());
+        hasher.update((self.entries.len() as u64).to_le_bytes());
+        for entry in &self.entries {
+            hasher.update(entry.ciphertext_hash);
+            hasher.update(entry.submitted_at.to_le_bytes());
+            hasher.update(entry.voter);
+        }
+        hasher.update(self.ballots_root);
+        hasher.update(self.signer);
+        hasher.finalize().into()
+    }
+
+    // Checks the manifest is signed by the key it names. Whether that key is the coordinator's
+    // is up to the reader.
+    pub fn verify_signature(&self) -> Result<(), ManifestError> {
+        let signer: VerifyingKey =
+            VerifyingKey::from_bytes(&self.signer).map_err(|_| ManifestError::BadSignature)?;
+        let signature: Signature =
+            Signature::from_slice(&self.signature).map_err(|_| ManifestError::BadSignature)?;
+        signer
+            .verify(&self.hash(), &signature)
+            .map_err(|_| ManifestError::BadSignature)
+    }
+
+    // Recomputes the hash of every ballot and checks it, and the voter who signed the ballot,
+    // against the entry at the same position.
+    pub fn check_ballots(&self, ballots: &[Ballot]) -> Result<(), ManifestError> {
+        if ballots.len() != self.entries.len() {
+            return Err(ManifestError::Count {
+                listed: self.entries.len(),
+                published: ballots.len(),
+            });
+        }
+        match ballots
+            .iter()
+            .zip(&self.entries)
+            .position(|(ballot, entry)| {
+                merkle::hash_leaf(&ballot.ciphertext.to_bytes()) != entry.ciphertext_hash
+                    || ballot.voter.to_bytes() != entry.voter
+            }) {
+            Some(position) => Err(ManifestError::Mismatch(position)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(self).unwrap()
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<Self, ManifestError> {
+        let manifest: BallotManifest = serde_json::from_slice(bytes)?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(ManifestError::UnsupportedVersion(manifest.version));
+        }
+        Ok(manifest)
+    }
+}
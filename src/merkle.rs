@@ -0,0 +1,118 @@
+// A minimal binary Merkle tree over the hashes of the accepted ballot ciphertexts.
+//
+// Publishing the root next to the tally commits the election to the exact set of ballots
+// that were summed. Any voter can then ask for an inclusion proof for their own ballot and
+// check it against the published root, which gives us a "recorded-as-cast" guarantee without
+// revealing anything about the contents of the ballots.
+//
+// Leaves and interior nodes are hashed with different prefixes so that an interior node can
+// never be passed off as a leaf. When a level has an odd number of nodes, the last node is
+// promoted to the next level unchanged rather than being paired with a copy of itself.
+
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+// Hashes the serialized bytes of a ballot into a leaf of the tree.
+pub fn hash_leaf(bytes: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+// One step of an inclusion proof: the sibling hash and which side of the path it sits on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofStep {
+    Left(Hash),
+    Right(Hash),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InclusionProof {
+    pub index: usize,
+    pub steps: Vec<ProofStep>,
+}
+
+impl InclusionProof {
+    // Recomputes the root from a leaf and checks it against the published root.
+    pub fn verify(&self, leaf: &Hash, root: &Hash) -> bool {
+        let computed = self.steps.iter().fold(*leaf, |acc, step| match step {
+            ProofStep::Left(sibling) => hash_node(sibling, &acc),
+            ProofStep::Right(sibling) => hash_node(&acc, sibling),
+        });
+        &computed == root
+    }
+}
+
+pub struct MerkleTree {
+    // levels[0] holds the leaves, the last level holds the root.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: Vec<Hash>) -> Self {
+        let mut levels = vec![leaves];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        MerkleTree { levels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The root of an empty tree is the hash of the empty string.
+    pub fn root(&self) -> Hash {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => *root,
+            None => Sha256::digest(b"").into(),
+        }
+    }
+
+    pub fn proof(&self, index: usize) -> Option<InclusionProof> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut steps = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if sibling < level.len() {
+                steps.push(if sibling < position {
+                    ProofStep::Left(level[sibling])
+                } else {
+                    ProofStep::Right(level[sibling])
+                });
+            }
+            position /= 2;
+        }
+        Some(InclusionProof { index, steps })
+    }
+}
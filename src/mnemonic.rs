@@ -0,0 +1,58 @@
+// Trustee key shares derived from a BIP-39 mnemonic.
+//
+// A trustee's secret key share is sampled from a random generator. Seeding that generator from
+// a mnemonic rather than the system's randomness makes the share reproducible: a trustee who
+// wrote down their 24 words can regenerate the very same share on a replacement machine and
+// carry on with the election, as long as the CRP is the same.
+//
+// The mnemonic is turned into a 64 byte seed the BIP-39 way, with PBKDF2 and no passphrase,
+// and the seed into the generator's seed with SHA-256, under a domain of its own and the number
+// of the key slot, so one mnemonic gives a different share for every slot. The public key share
+// is drawn from the same generator right after the secret, so it comes out the same too.
+//
+// Note: the mnemonic is as secret as the key share. Anyone who reads it can decrypt with that
+// share.
+
+use crate::party::Party;
+use bip39::Mnemonic;
+use fhe::{
+    bfv::BfvParameters,
+    mbfv::{CommonRandomPoly, PublicKeyShare},
+};
+use rand::{rngs::StdRng, CryptoRng, RngCore, SeedableRng};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+const KEY_DOMAIN: &[u8] = b"fhe-workshop/mnemonic-key-share";
+
+// A fresh 24 word mnemonic.
+pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Mnemonic {
+    let mut entropy: [u8; 32] = [0; 32];
+    rng.fill_bytes(&mut entropy);
+    Mnemonic::from_entropy(&entropy).expect("32 bytes is a valid entropy length")
+}
+
+// Reads a mnemonic, checking its words and checksum. The English word list needs no Unicode
+// normalization.
+pub fn parse(words: &str) -> Result<Mnemonic, bip39::Error> {
+    Mnemonic::parse_normalized(words)
+}
+
+// The random generator the key share of `slot` is sampled from.
+pub fn key_rng(mnemonic: &Mnemonic, slot: usize) -> StdRng {
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_DOMAIN);
+    hasher.update(mnemonic.to_seed_normalized(""));
+    hasher.update((slot as u64).to_le_bytes());
+    StdRng::from_seed(hasher.finalize().into())
+}
+
+// The key share of `slot` and its public key share, derived from the mnemonic.
+pub fn derive_party(
+    params: &Arc<BfvParameters>,
+    crp: &CommonRandomPoly,
+    mnemonic: &Mnemonic,
+    slot: usize,
+) -> Result<(Party, PublicKeyShare), fhe::Error> {
+    Party::new(params, crp, &mut key_rng(mnemonic, slot))
+}
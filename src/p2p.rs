@@ -0,0 +1,274 @@
+// The `p2p` subcommand: one trustee of an election held over a libp2p gossip network.
+//
+// Trustees find each other with mDNS on the local network, e.g. the laptops of a workshop on
+// the same Wi-Fi, or by dialing the addresses given with `--bootstrap`. Every message of the
+// protocol in `gossip` is published on a single gossipsub topic. Gossipsub doesn't hold on to
+// messages for peers that subscribe later, so each trustee publishes everything it has sent
+// again every `REBROADCAST`, and stays on for `LINGER` once the result is accepted so that
+// slower trustees can finish too. The result is accepted once every trustee has broadcast the
+// same public key, tally and result, see `GossipTrustee::accepted`; a trustee that came to
+// something else is logged and the election never finishes. Every trustee signs its outcome,
+// and the result is certified once a quorum of them have, see `GossipTrustee::certificate`,
+// which `--certificate` writes to a file.
+//
+// With a mnemonic in `TRUSTEE_MNEMONIC`, e.g. from `trustee new`, the trustee's key share is
+// derived from it rather than sampled, so it can be regenerated on another machine, see
+// `mnemonic.rs`. With `--signer`, the trustee doesn't hold its key share at all: it asks the
+// mock HSM of `trustee hsm` at that address for its shares, see `signer.rs`. The connection is a
+// Noise channel: the trustee's secret channel key is read from `CHANNEL_KEY`, and the HSM's
+// public key is given with `--signer-key`, so the trustee only ever talks to its own HSM. An HSM
+// that can't be reached yet, e.g. because it's still starting, is retried with exponential
+// backoff, up to `SIGNER_ATTEMPTS` times. A failed handshake isn't retried: the keys won't
+// match any better the next time.
+//
+// Every message is safe to receive more than once, see `GossipTrustee::receive`, which is what
+// makes rebroadcasting them harmless.
+
+use clap::Args;
+use fhe_workshop::{
+    gossip::{GossipMessage, GossipTrustee, Session},
+    mnemonic,
+    signer::{self, ChannelKey, ChannelKeys, RemoteSigner, SignerError},
+    voter::Choice,
+};
+use futures::StreamExt;
+use libp2p::{
+    gossipsub::{self, IdentTopic, MessageAuthenticity},
+    mdns, noise,
+    swarm::{NetworkBehaviour, SwarmEvent},
+    tcp, yamux, Multiaddr, Swarm, SwarmBuilder,
+};
+use rand::thread_rng;
+use std::{env, error::Error, fs, io, path::PathBuf, time::Duration};
+use tokio::time::{self, Instant, Interval};
+use tracing::{info, warn};
+
+const REBROADCAST: Duration = Duration::from_secs(2);
+const LINGER: Duration = Duration::from_secs(10);
+// A ballot or decryption share is a whole ciphertext, hex encoded, well over gossipsub's
+// default limit of 64 KiB.
+const MAX_MESSAGE: usize = 4 << 20;
+const SIGNER_ATTEMPTS: u32 = 6;
+const SIGNER_BACKOFF: Duration = Duration::from_millis(250);
+
+#[derive(Args)]
+pub struct P2pArgs {
+    /// This trustee's number, from 0.
+    #[arg(long)]
+    trustee: usize,
+
+    /// Number of trustees in the election.
+    #[arg(long)]
+    trustees: usize,
+
+    /// Name of the election, the same for every trustee. The CRP is derived from it.
+    #[arg(long, default_value = "fhe-workshop")]
+    session: String,
+
+    /// This trustee's vote: 0 (against), 1 (for) or 2 (abstain).
+    #[arg(long, default_value_t = 1)]
+    vote: u64,
+
+    /// Address to listen on.
+    #[arg(long, default_value = "/ip4/0.0.0.0/tcp/0")]
+    listen: Multiaddr,
+
+    /// Addresses of trustees to dial, for networks where mDNS doesn't reach.
+    #[arg(long, value_delimiter = ',')]
+    bootstrap: Vec<Multiaddr>,
+
+    /// Address of the mock HSM holding this trustee's key share, started with `trustee hsm`.
+    #[arg(long, requires = "signer_key")]
+    signer: Option<String>,
+
+    /// The public channel key of the mock HSM, in hex, see `trustee channel-key`. This
+    /// trustee's secret key is read from `CHANNEL_KEY`.
+    #[arg(long, value_parser = signer::parse_channel_key, requires = "signer")]
+    signer_key: Option<ChannelKey>,
+
+    /// Write the result, with the signatures of a quorum of the trustees, to this file.
+    #[arg(long)]
+    certificate: Option<PathBuf>,
+}
+
+#[derive(NetworkBehaviour)]
+struct Behaviour {
+    gossipsub: gossipsub::Behaviour,
+    mdns: mdns::tokio::Behaviour,
+}
+
+pub fn run(args: &P2pArgs) -> Result<(), Box<dyn Error>> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(trustee(args))
+}
+
+// Connects to the HSM, waiting twice as long after each failed attempt to reach it.
+async fn connect_signer(
+    address: &str,
+    keys: &ChannelKeys,
+    session: &Session,
+) -> Result<RemoteSigner, SignerError> {
+    let mut delay: Duration = SIGNER_BACKOFF;
+    let mut attempt: u32 = 1;
+    loop {
+        match RemoteSigner::connect(address, keys, session.params(), session.crp()) {
+            Err(SignerError::Io(e)) if attempt < SIGNER_ATTEMPTS => {
+                warn!(address, attempt, error = %e, retry_in = ?delay, "signer unreachable");
+                time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn trustee(args: &P2pArgs) -> Result<(), Box<dyn Error>> {
+    let vote: Choice = Choice::try_from(args.vote)?;
+    let mut node: GossipTrustee = match (&args.signer, env::var("TRUSTEE_MNEMONIC")) {
+        (Some(address), _) => {
+            let session: Session = Session::new(args.trustee, args.trustees, &args.session)?;
+            let keys: ChannelKeys = ChannelKeys {
+                secret: signer::parse_channel_key(
+                    &env::var("CHANNEL_KEY")
+                        .map_err(|_| "set CHANNEL_KEY to the trustee's secret channel key")?,
+                )?,
+                peer: args.signer_key.ok_or("--signer needs --signer-key")?,
+                prologue: signer::channel_prologue(&args.session, args.trustee),
+            };
+            let signer: RemoteSigner = connect_signer(address, &keys, &session).await?;
+            GossipTrustee::with_signer(session, vote, Box::new(signer), &mut thread_rng())?
+        }
+        (None, Ok(words)) => GossipTrustee::from_mnemonic(
+            args.trustee,
+            args.trustees,
+            &args.session,
+            vote,
+            &mnemonic::parse(&words)?,
+            &mut thread_rng(),
+        )?,
+        (None, Err(_)) => GossipTrustee::new(
+            args.trustee,
+            args.trustees,
+            &args.session,
+            vote,
+            &mut thread_rng(),
+        )?,
+    };
+    let topic: IdentTopic = IdentTopic::new(format!("fhe-workshop/{}", args.session));
+    let mut swarm: Swarm<Behaviour> = build_swarm()?;
+    swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
+    swarm.listen_on(args.listen.clone())?;
+    for address in &args.bootstrap {
+        swarm.dial(address.clone())?;
+    }
+    info!(
+        trustee = args.trustee,
+        trustees = args.trustees,
+        peer = %swarm.local_peer_id(),
+        key_share = %hex::encode(node.key_share_hash()),
+        "trustee started"
+    );
+
+    let mut rebroadcast: Interval = time::interval(REBROADCAST);
+    let mut decrypted: bool = false;
+    let mut done: Option<Instant> = None;
+    while done.is_none_or(|done| done.elapsed() < LINGER) {
+        tokio::select! {
+            _ = rebroadcast.tick() => {
+                for message in node.sent() {
+                    // Fails while no peer is subscribed yet, which the next round makes up for.
+                    let _ = swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .publish(topic.clone(), message.to_bytes());
+                }
+            }
+            event = swarm.select_next_some() => match event {
+                SwarmEvent::NewListenAddr { address, .. } => info!(%address, "listening"),
+                SwarmEvent::Behaviour(BehaviourEvent::Mdns(mdns::Event::Discovered(peers))) => {
+                    for (peer, _) in peers {
+                        info!(%peer, "trustee discovered");
+                        swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer);
+                    }
+                }
+                SwarmEvent::Behaviour(BehaviourEvent::Mdns(mdns::Event::Expired(peers))) => {
+                    for (peer, _) in peers {
+                        swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer);
+                    }
+                }
+                SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                    message,
+                    ..
+                })) => {
+                    // A bad message is pinned on its sender and dropped, the rest of the
+                    // election goes on.
+                    let received = GossipMessage::from_bytes(&message.data)
+                        .and_then(|message| node.receive(message));
+                    if let Err(e) = received {
+                        warn!(source = ?message.source, "message dropped: {e}");
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !decrypted {
+            if let Some(result) = node.result() {
+                // Every trustee logs the same hashes and result, whatever order their messages
+                // came in.
+                info!(
+                    public_key = hex::encode(node.public_key_hash().unwrap_or_default()),
+                    tally = hex::encode(node.tally_hash().unwrap_or_default()),
+                    ?result,
+                    "election decrypted"
+                );
+                decrypted = true;
+            }
+        }
+        if done.is_none() {
+            if let (Some(result), Some(certificate)) = (node.accepted(), node.certificate()) {
+                info!(?result, "result accepted by every trustee");
+                info!(
+                    signatures = certificate.signatures.len(),
+                    quorum = certificate.quorum,
+                    "result certified"
+                );
+                if let Some(path) = &args.certificate {
+                    fs::write(path, certificate.to_json())?;
+                }
+                done = Some(Instant::now());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn build_swarm() -> Result<Swarm<Behaviour>, Box<dyn Error>> {
+    Ok(SwarmBuilder::with_new_identity()
+        .with_tokio()
+        .with_tcp(
+            tcp::Config::default(),
+            noise::Config::new,
+            yamux::Config::default,
+        )?
+        .with_behaviour(|key| {
+            let config: gossipsub::Config = gossipsub::ConfigBuilder::default()
+                .max_transmit_size(MAX_MESSAGE)
+                .build()
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            Ok(Behaviour {
+                gossipsub: gossipsub::Behaviour::new(
+                    MessageAuthenticity::Signed(key.clone()),
+                    config,
+                )?,
+                mdns: mdns::tokio::Behaviour::new(
+                    mdns::Config::default(),
+                    key.public().to_peer_id(),
+                )?,
+            })
+        })?
+        .with_swarm_config(|config| config.with_idle_connection_timeout(LINGER))
+        .build())
+}
@@ -0,0 +1,112 @@
+// Parallel iteration with rayon when the `parallel` feature is on, plain iteration when it's off.
+//
+// Code that wants to run in parallel imports `parallel::prelude::*` instead of rayon's prelude
+// and keeps calling `par_iter`, `into_par_iter` and `par_chunks`. Without the feature, those
+// hand back the ordinary iterator, so the same code builds and runs on a single thread, e.g. for
+// WebAssembly or to get a deterministic run while debugging.
+//
+// By default rayon spreads the work over every core. `init` sets up its global pool with a given
+// number of threads instead, e.g. to leave cores to other processes on a shared machine, and can
+// pin each thread to a core of its own so runs on different machines are comparable. It must be
+// called before anything runs in parallel, as the global pool is built on first use.
+
+use std::{error::Error, fmt};
+
+#[derive(Debug)]
+pub enum PoolError {
+    // The global pool was already built, e.g. by an earlier call.
+    Build(String),
+    // The cores of the machine couldn't be listed, so threads can't be pinned to them.
+    NoCores,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::Build(e) => write!(f, "can't build the thread pool: {e}"),
+            PoolError::NoCores => write!(f, "can't list the cores to pin threads to"),
+        }
+    }
+}
+
+impl Error for PoolError {}
+
+// Builds the global pool with `threads` threads, or one per core without, pinning thread `i` to
+// core `i` (wrapping around) with `pin`.
+#[cfg(feature = "parallel")]
+pub fn init(threads: Option<usize>, pin: bool) -> Result<(), PoolError> {
+    let mut builder: rayon::ThreadPoolBuilder =
+        rayon::ThreadPoolBuilder::new().num_threads(threads.unwrap_or(0));
+    if pin {
+        let cores: Vec<core_affinity::CoreId> = core_affinity::get_core_ids()
+            .filter(|cores| !cores.is_empty())
+            .ok_or(PoolError::NoCores)?;
+        builder = builder.start_handler(move |thread| {
+            core_affinity::set_for_current(cores[thread % cores.len()]);
+        });
+    }
+    builder
+        .build_global()
+        .map_err(|e| PoolError::Build(e.to_string()))
+}
+
+// Everything runs on the calling thread, so there is no pool to build.
+#[cfg(not(feature = "parallel"))]
+pub fn init(_threads: Option<usize>, _pin: bool) -> Result<(), PoolError> {
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+pub mod prelude {
+    pub use rayon::prelude::*;
+}
+
+#[cfg(feature = "parallel")]
+pub use rayon::current_num_threads;
+
+// The number of threads work is spread over: just the one without the feature.
+#[cfg(not(feature = "parallel"))]
+pub fn current_num_threads() -> usize {
+    1
+}
+
+#[cfg(not(feature = "parallel"))]
+pub mod prelude {
+    // Stands in for rayon's `into_par_iter`.
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<T: IntoIterator> IntoParallelIterator for T {}
+
+    // Stands in for rayon's `par_iter`.
+    pub trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, T: 'a + ?Sized> IntoParallelRefIterator<'a> for T
+    where
+        &'a T: IntoIterator,
+    {
+        type Iter = <&'a T as IntoIterator>::IntoIter;
+
+        fn par_iter(&'a self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    // Stands in for rayon's `par_chunks`.
+    pub trait ParallelSlice<T> {
+        fn par_chunks(&self, chunk_size: usize) -> std::slice::Chunks<'_, T>;
+    }
+
+    impl<T> ParallelSlice<T> for [T] {
+        fn par_chunks(&self, chunk_size: usize) -> std::slice::Chunks<'_, T> {
+            self.chunks(chunk_size)
+        }
+    }
+}
@@ -0,0 +1,275 @@
+// Building the BFV parameters for an election.
+
+use fhe::bfv::{BfvParameters, BfvParametersBuilder};
+use std::{error::Error, fmt, sync::Arc};
+
+// The single ciphertext modulus used by the workshop. Addition-only circuits don't need a
+// chain of moduli to manage noise.
+pub const MODULI: [u64; 1] = [0x3FFFFFFF000001];
+
+// The sizes fhe.rs can generate a prime modulus of, in bits.
+pub const MIN_MODULUS_BITS: usize = 10;
+pub const MAX_MODULUS_BITS: usize = 62;
+
+// Bits of ciphertext modulus a fresh ciphertext needs above the plaintext modulus, for the
+// encryption noise, the noise of summing the ballots and that of the decryption shares.
+const FRESH_NOISE_BITS: usize = 30;
+// Each multiplication of two ciphertexts grows the noise by about `t·n`, plus what the
+// relinearization adds.
+const MULTIPLICATION_NOISE_BITS: usize = 20;
+// The size `moduli_sizes` aims for, a little below the maximum to keep products of two
+// coefficients from overflowing in the NTT.
+const CHAIN_MODULUS_BITS: usize = 60;
+
+pub const DEGREE: usize = 2048;
+
+// Picks the smallest prime plaintext modulus from our table that can hold a tally of
+// `num_votes` without wrapping around. The table stops at 1032193, past which only
+// `check_capacity` notices the tally doesn't fit.
+pub fn plaintext_modulus(num_votes: usize) -> u64 {
+    match num_votes {
+        1..=999 => 1009,
+        1000..=9999 => 10007,
+        10000..=99999 => 100003,
+        100000..=199999 => 200003,
+        200000..=299999 => 300007,
+        300000..=399999 => 400009,
+        400000..=499999 => 500009,
+        500000..=599999 => 600011,
+        600000..=699999 => 700001,
+        700000..=799999 => 800011,
+        800000..=899999 => 900001,
+        _ => 1032193,
+    }
+}
+
+// A slot of the tally could reach the plaintext modulus. Slots are only known modulo `t`, so
+// such a tally would wrap around and decrypt to a smaller, plausible looking, wrong number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowError {
+    pub max_tally: u64,
+    pub plaintext_modulus: u64,
+}
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a slot of the tally can reach {}, which wraps around the plaintext modulus {}: \
+             use a plaintext modulus larger than {}",
+            self.max_tally, self.plaintext_modulus, self.max_tally
+        )
+    }
+}
+
+impl Error for OverflowError {}
+
+// Checks that the largest value a slot of the tally can reach, the number of ballots times the
+// most a single ballot adds to a slot, stays below the plaintext modulus.
+pub fn check_capacity(
+    num_ballots: usize,
+    max_per_ballot: u64,
+    plaintext_modulus: u64,
+) -> Result<(), OverflowError> {
+    let max_tally: u64 = (num_ballots as u64).saturating_mul(max_per_ballot);
+    if max_tally >= plaintext_modulus {
+        return Err(OverflowError {
+            max_tally,
+            plaintext_modulus,
+        });
+    }
+    Ok(())
+}
+
+// The largest ciphertext modulus, in bits, that keeps each degree at 128, 192 and 256 bits of
+// classical security. The figures are those of the Homomorphic Encryption Standard for a
+// ternary secret key, which were estimated with the lattice estimator.
+const SECURITY_TABLE: [(usize, [usize; 3]); 6] = [
+    (1024, [27, 19, 14]),
+    (2048, [54, 37, 29]),
+    (4096, [109, 75, 58]),
+    (8192, [218, 152, 118]),
+    (16384, [438, 305, 237]),
+    (32768, [881, 611, 476]),
+];
+const SECURITY_LEVELS: [u32; 3] = [128, 192, 256];
+
+// The size of the product of the moduli, in bits, rounded up.
+pub fn modulus_bits(moduli: &[u64]) -> usize {
+    moduli
+        .iter()
+        .map(|modulus| (u64::BITS - modulus.leading_zeros()) as usize)
+        .sum()
+}
+
+// The approximate security of a degree and a ciphertext modulus of `modulus_bits`, in bits: the
+// highest level of the table the modulus fits under, or `None` below 128 bits. A degree between
+// two rows of the table counts as the smaller one, and one past the end as the largest.
+pub fn security_level(degree: usize, modulus_bits: usize) -> Option<u32> {
+    let (_, bounds) = SECURITY_TABLE
+        .iter()
+        .rev()
+        .find(|(table_degree, _)| *table_degree <= degree)?;
+    SECURITY_LEVELS
+        .iter()
+        .zip(bounds)
+        .filter(|(_, &bound)| modulus_bits <= bound)
+        .map(|(&level, _)| level)
+        .last()
+}
+
+// The parameters give less than 128 bits of security, see `security_level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsecureError {
+    pub degree: usize,
+    pub modulus_bits: usize,
+}
+
+impl fmt::Display for InsecureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a degree of {} with a {} bit ciphertext modulus gives less than 128 bits of security",
+            self.degree, self.modulus_bits
+        )?;
+        match SECURITY_TABLE
+            .iter()
+            .find(|(_, bounds)| self.modulus_bits <= bounds[0])
+        {
+            Some((degree, _)) => write!(f, ": use a degree of at least {degree}"),
+            None => write!(f, ": use fewer bits of ciphertext modulus"),
+        }
+    }
+}
+
+impl Error for InsecureError {}
+
+// Checks that the parameters give at least 128 bits of security, and returns how many they give.
+pub fn check_security(degree: usize, modulus_bits: usize) -> Result<u32, InsecureError> {
+    security_level(degree, modulus_bits).ok_or(InsecureError {
+        degree,
+        modulus_bits,
+    })
+}
+
+// The heap taken by one ciphertext: two polynomials of `degree` coefficients modulo each of the
+// moduli, one u64 per coefficient. Keys and decryption shares are of the same order.
+pub fn ciphertext_memory(degree: usize, num_moduli: usize) -> u64 {
+    (2 * degree * num_moduli * 8) as u64
+}
+
+// The chain of ciphertext moduli to build the parameters with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ModuliChain {
+    // `MODULI`, all an election that only adds ballots needs.
+    #[default]
+    Workshop,
+    // Primes of these bit sizes, picked by fhe.rs.
+    Sizes(Vec<usize>),
+    // Enough bits for this many multiplications of ciphertexts, see `moduli_sizes`.
+    Depth(usize),
+}
+
+impl ModuliChain {
+    pub fn build(
+        &self,
+        degree: usize,
+        plaintext_modulus: u64,
+    ) -> Result<Arc<BfvParameters>, ModuliError> {
+        match self {
+            ModuliChain::Workshop => Ok(build(degree, plaintext_modulus, &MODULI)?),
+            ModuliChain::Sizes(sizes) => build_with_sizes(degree, plaintext_modulus, sizes),
+            ModuliChain::Depth(depth) => build_with_sizes(
+                degree,
+                plaintext_modulus,
+                &moduli_sizes(degree, plaintext_modulus, *depth),
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ModuliError {
+    Empty,
+    // A size fhe.rs can't generate a prime of.
+    Size(usize),
+    Fhe(fhe::Error),
+}
+
+impl fmt::Display for ModuliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuliError::Empty => write!(
+                f,
+                "the moduli chain is empty: give the bit size of at least one modulus, or a \
+                 multiplicative depth to pick the chain for"
+            ),
+            ModuliError::Size(bits) => write!(
+                f,
+                "a modulus of {bits} bits is out of range: each modulus takes between \
+                 {MIN_MODULUS_BITS} and {MAX_MODULUS_BITS} bits, and a larger ciphertext \
+                 modulus takes more of them"
+            ),
+            ModuliError::Fhe(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ModuliError {}
+
+impl From<fhe::Error> for ModuliError {
+    fn from(e: fhe::Error) -> Self {
+        ModuliError::Fhe(e)
+    }
+}
+
+// The bit sizes of a chain of moduli with room for `depth` multiplications of ciphertexts,
+// a rule of thumb rather than a bound: a fresh ciphertext needs the bits of the plaintext
+// modulus plus `FRESH_NOISE_BITS`, and each multiplication those of `t·n` plus
+// `MULTIPLICATION_NOISE_BITS` more. The total is split evenly into moduli of at most
+// `CHAIN_MODULUS_BITS`. The larger the total, the larger the degree has to be for the
+// parameters to stay secure.
+pub fn moduli_sizes(degree: usize, plaintext_modulus: u64, depth: usize) -> Vec<usize> {
+    let plaintext_bits: usize = (u64::BITS - plaintext_modulus.leading_zeros()) as usize;
+    let degree_bits: usize = degree.max(1).ilog2() as usize;
+    let total: usize = plaintext_bits
+        + FRESH_NOISE_BITS
+        + depth * (plaintext_bits + degree_bits + MULTIPLICATION_NOISE_BITS);
+    let count: usize = total.div_ceil(CHAIN_MODULUS_BITS);
+    let size: usize = total.div_ceil(count).max(MIN_MODULUS_BITS);
+    vec![size; count]
+}
+
+// Builds the parameters with primes of the given bit sizes, one for each level of the chain.
+pub fn build_with_sizes(
+    degree: usize,
+    plaintext_modulus: u64,
+    sizes: &[usize],
+) -> Result<Arc<BfvParameters>, ModuliError> {
+    if sizes.is_empty() {
+        return Err(ModuliError::Empty);
+    }
+    if let Some(&bits) = sizes
+        .iter()
+        .find(|&&bits| !(MIN_MODULUS_BITS..=MAX_MODULUS_BITS).contains(&bits))
+    {
+        return Err(ModuliError::Size(bits));
+    }
+    Ok(BfvParametersBuilder::new()
+        .set_degree(degree)
+        .set_plaintext_modulus(plaintext_modulus)
+        .set_moduli_sizes(sizes)
+        .build_arc()?)
+}
+
+pub fn build(
+    degree: usize,
+    plaintext_modulus: u64,
+    moduli: &[u64],
+) -> Result<Arc<BfvParameters>, fhe::Error> {
+    BfvParametersBuilder::new()
+        .set_degree(degree)
+        .set_plaintext_modulus(plaintext_modulus)
+        .set_moduli(moduli)
+        .build_arc()
+}
@@ -0,0 +1,48 @@
+// A party (trustee) in the threshold key ceremony and the threshold decryption.
+//
+// Each party holds a share of the secret key. Together, their public key shares add up to the
+// public key voters encrypt to, and their decryption shares add up to the decrypted tally. No
+// single party ever holds the full secret key.
+//
+// A party keeps its secret key share only. Its public key share is handed over as it is made, to
+// be sent in the key ceremony, see `KeyShareMessage::new`, and summed into the public key, so
+// that the party, the message and the ceremony don't each hold a copy of it.
+
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, PublicKey, SecretKey},
+    mbfv::{AggregateIter, CommonRandomPoly, DecryptionShare, PublicKeyShare},
+};
+use rand::{CryptoRng, RngCore};
+use std::sync::Arc;
+
+pub struct Party {
+    pub sk_share: SecretKey,
+}
+
+impl Party {
+    // Samples a fresh secret key share and derives the matching public key share from the CRP.
+    pub fn new<R: RngCore + CryptoRng>(
+        params: &Arc<BfvParameters>,
+        crp: &CommonRandomPoly,
+        rng: &mut R,
+    ) -> Result<(Self, PublicKeyShare), fhe::Error> {
+        let sk_share: SecretKey = SecretKey::random(params, rng);
+        let pk_share: PublicKeyShare = PublicKeyShare::new(&sk_share, crp.clone(), rng)?;
+        Ok((Party { sk_share }, pk_share))
+    }
+
+    pub fn decryption_share<R: RngCore + CryptoRng>(
+        &self,
+        tally: &Arc<Ciphertext>,
+        rng: &mut R,
+    ) -> Result<DecryptionShare, fhe::Error> {
+        DecryptionShare::new(&self.sk_share, tally, rng)
+    }
+}
+
+// Sums the public key shares of all parties into the shared public key.
+pub fn aggregate_public_key(
+    shares: impl IntoIterator<Item = PublicKeyShare>,
+) -> Result<PublicKey, fhe::Error> {
+    shares.into_iter().aggregate()
+}
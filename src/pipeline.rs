@@ -0,0 +1,131 @@
+// An async front end to the election, for running it behind a server.
+//
+// The command line demo runs each phase to completion with rayon, which blocks the whole
+// process until the phase is done. A coordinator serving voters and trustees over the network
+// wants to keep accepting messages and writing them to disk while the FHE work runs. Here the
+// CPU-bound work (encryption, aggregation, decryption) is moved onto tokio's blocking thread
+// pool, and the key and decryption shares are taken from streams, as they arrive.
+
+use crate::{
+    ballot::Ballot,
+    decryption_ceremony::{DecryptionCeremony, DecryptionShareMessage, Progress},
+    key_ceremony::{CeremonyError, KeyCeremony, KeyShareCommitment, KeyShareMessage},
+    precinct::PrecinctId,
+    validity::{BallotProof, TrustedProof},
+    voter::{self, Choice},
+    zero_pool::ZeroPool,
+};
+use ed25519_dalek::SigningKey;
+use fhe::bfv::{BfvParameters, Ciphertext, PublicKey};
+use futures::{Stream, StreamExt};
+use rand::{CryptoRng, RngCore};
+use std::{panic, sync::Arc};
+
+// Runs CPU-bound work on the blocking thread pool, passing on any panic.
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(e) => panic::resume_unwind(e.into_panic()),
+    }
+}
+
+// Encrypts and signs a vote, with a proof of its validity, without blocking the caller's task.
+// The generator moves onto the blocking thread pool with the rest of the work.
+pub async fn cast_async<R: RngCore + CryptoRng + Send + 'static>(
+    vote: Choice,
+    precinct: PrecinctId,
+    signing_key: SigningKey,
+    pk: Arc<PublicKey>,
+    params: Arc<BfvParameters>,
+    mut rng: R,
+) -> Result<Ballot, fhe::Error> {
+    blocking(move || {
+        let ct: Ciphertext = voter::encrypt_vote(vote, &pk, &params, &mut rng)?;
+        let proof: Vec<u8> = TrustedProof.prove(&vote.slots(), &ct)?;
+        Ok(Ballot::with_proof(ct, precinct, proof, &signing_key))
+    })
+    .await
+}
+
+// The same from a pool of precomputed encryptions of zero, see `zero_pool`. Adding the vote to
+// a zero takes milliseconds, so it runs on the caller's task, unless the pool has run dry.
+pub async fn cast_pooled_async<R: RngCore + CryptoRng + Send + 'static>(
+    vote: Choice,
+    precinct: PrecinctId,
+    signing_key: SigningKey,
+    pool: Arc<ZeroPool>,
+    mut rng: R,
+) -> Result<Ballot, fhe::Error> {
+    let dry: bool = pool.is_empty();
+    let cast = move |rng: &mut R| {
+        let ct: Ciphertext = pool.encrypt_vote(vote, rng)?;
+        let proof: Vec<u8> = TrustedProof.prove(&vote.slots(), &ct)?;
+        Ok(Ballot::with_proof(ct, precinct, proof, &signing_key))
+    };
+    if dry {
+        blocking(move || cast(&mut rng)).await
+    } else {
+        cast(&mut rng)
+    }
+}
+
+// Tops the pool up to `target` zeros in the background, returning how many were added. Call it
+// whenever the server is idle, or after every few ballots.
+pub async fn fill_pool_async<R: RngCore + CryptoRng + Send + 'static>(
+    pool: Arc<ZeroPool>,
+    target: usize,
+    mut rng: R,
+) -> Result<usize, fhe::Error> {
+    blocking(move || pool.refill(target, &mut rng)).await
+}
+
+// Feeds the commitments to the ceremony as they arrive, then the key shares once every party has
+// committed, and aggregates the public key once the shares stream ends. The parties should only
+// reveal their shares once the commitments stream has ended.
+pub async fn collect_key_shares(
+    mut ceremony: KeyCeremony,
+    commitments: impl Stream<Item = KeyShareCommitment>,
+    shares: impl Stream<Item = KeyShareMessage>,
+) -> Result<(KeyCeremony, PublicKey), CeremonyError> {
+    let mut commitments = std::pin::pin!(commitments);
+    while let Some(message) = commitments.next().await {
+        ceremony.commit(message)?;
+    }
+    let mut shares = std::pin::pin!(shares);
+    while let Some(message) = shares.next().await {
+        ceremony.receive(message)?;
+    }
+    blocking(move || {
+        let pk: PublicKey = ceremony.aggregate()?.clone();
+        Ok((ceremony, pk))
+    })
+    .await
+}
+
+// Feeds the decryption shares to the ceremony as they arrive, returning the progress once the
+// stream ends.
+//
+// Every share on the stream is taken, even past the quorum, as the spare shares are what lets
+// the ceremony leave out parties sending bad ones.
+pub async fn collect_decryption_shares(
+    ceremony: &mut DecryptionCeremony,
+    shares: impl Stream<Item = DecryptionShareMessage>,
+) -> Result<Progress, CeremonyError> {
+    let mut shares = std::pin::pin!(shares);
+    while let Some(message) = shares.next().await {
+        ceremony.receive(message)?;
+    }
+    Ok(ceremony.progress())
+}
+
+// Decrypts the first `len` slots without blocking the caller's task.
+pub async fn decrypt_async(
+    mut ceremony: DecryptionCeremony,
+    len: usize,
+) -> Result<(DecryptionCeremony, Vec<u64>), CeremonyError> {
+    blocking(move || {
+        let result: Vec<u64> = ceremony.decrypt(len)?.to_vec();
+        Ok((ceremony, result))
+    })
+    .await
+}
@@ -0,0 +1,268 @@
+// Precincts: tallying each region on its own and adding the regional tallies up.
+//
+// Every ballot is tagged with the precinct it was cast in. The ballots of each precinct are
+// summed into an encrypted precinct tally and, since a sum of sums is still the sum, the
+// precinct tallies add up to the overall tally without going back to the ballots. Only the
+// overall tally has to be decrypted. Precinct totals can be decrypted too, but the total of a
+// precinct with a handful of voters gives away how they voted, so only precincts with at least
+// a minimum number of ballots should be.
+//
+// The precinct tallies can be summed once voting is over, from the ballots or from a store, or
+// kept as a `RunningTally` that the ballot box adds each ballot to as it accepts it, so the tally
+// is ready the moment voting closes and no second copy of the ciphertexts is ever held. With
+// `PrecinctStores`, the ballot box appends each ciphertext to its precinct's store as it accepts
+// it instead, so the ciphertexts go to disk as they come in and are streamed back out to be
+// summed once voting is over.
+
+use crate::{
+    ballot::Ballot,
+    stats::IoSplit,
+    store::{CiphertextStore, StoreError, StoreReader},
+    tally,
+};
+use fhe::bfv::{BfvParameters, Ciphertext};
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    sync::Arc,
+    time::Instant,
+};
+
+pub type PrecinctId = u32;
+
+pub struct PrecinctTally {
+    pub ciphertext: Ciphertext,
+    pub ballots: usize,
+}
+
+// Sums the ballots of each precinct separately.
+pub fn tally_by_precinct<'a>(
+    ballots: impl IntoIterator<Item = &'a Ballot>,
+    params: &Arc<BfvParameters>,
+) -> BTreeMap<PrecinctId, PrecinctTally> {
+    let mut tallies: BTreeMap<PrecinctId, PrecinctTally> = BTreeMap::new();
+    for ballot in ballots {
+        let precinct: &mut PrecinctTally =
+            tallies
+                .entry(ballot.precinct)
+                .or_insert_with(|| PrecinctTally {
+                    ciphertext: Ciphertext::zero(params),
+                    ballots: 0,
+                });
+        precinct.ciphertext += &ballot.ciphertext;
+        precinct.ballots += 1;
+    }
+    tallies
+}
+
+// The same, with the ballots of each precinct already split out into their own store. The
+// ciphertexts are streamed out of the stores, so they don't all have to fit in memory.
+pub fn tally_stores(
+    stores: &PrecinctStores,
+    params: &Arc<BfvParameters>,
+) -> Result<(BTreeMap<PrecinctId, PrecinctTally>, IoSplit), StoreError> {
+    let mut tallies: BTreeMap<PrecinctId, PrecinctTally> = BTreeMap::new();
+    let mut split: IoSplit = IoSplit::default();
+    for (&id, store) in &stores.stores {
+        let (ciphertext, store_split) = tally::sum_store(store.as_ref(), params)?;
+        split += store_split;
+        tallies.insert(
+            id,
+            PrecinctTally {
+                ciphertext,
+                ballots: stores.ballots(id),
+            },
+        );
+    }
+    Ok((tallies, split))
+}
+
+// The same, summing each store `chunk_size` ciphertexts at a time, see `tally::sum_store_chunked`.
+// `done` holds the chunk sums of each precinct persisted by an earlier run, and `persist` is
+// handed each new one along with its precinct.
+pub fn tally_stores_chunked<E: From<StoreError>>(
+    stores: &PrecinctStores,
+    params: &Arc<BfvParameters>,
+    chunk_size: usize,
+    mut done: BTreeMap<PrecinctId, Vec<Ciphertext>>,
+    mut persist: impl FnMut(PrecinctId, usize, &Ciphertext) -> Result<(), E>,
+) -> Result<(BTreeMap<PrecinctId, PrecinctTally>, IoSplit), E> {
+    let mut tallies: BTreeMap<PrecinctId, PrecinctTally> = BTreeMap::new();
+    let mut split: IoSplit = IoSplit::default();
+    for (&id, store) in &stores.stores {
+        let (ciphertext, store_split) = tally::sum_store_chunked(
+            store.as_ref(),
+            params,
+            chunk_size,
+            done.remove(&id).unwrap_or_default(),
+            |chunk, sum| persist(id, chunk, sum),
+        )?;
+        split += store_split;
+        tallies.insert(
+            id,
+            PrecinctTally {
+                ciphertext,
+                ballots: stores.ballots(id),
+            },
+        );
+    }
+    Ok((tallies, split))
+}
+
+// Makes the store of a precinct, see `PrecinctStores::new`.
+pub type OpenStore = Box<dyn FnMut(PrecinctId) -> Result<Box<dyn CiphertextStore>, StoreError>>;
+
+// The ciphertexts of each precinct in a store of their own, appended to one ballot at a time,
+// see `BallotBox::with_store`.
+//
+// Stores are append-only, so a replaced ballot is taken back out by appending the negation of
+// its ciphertext. Arithmetic on ciphertexts is exact, so the two cancel out and the store sums
+// to the same ciphertext as the surviving ballots alone. A store can then hold more records than
+// its precinct has ballots, so the ballots are counted apart.
+pub struct PrecinctStores {
+    params: Arc<BfvParameters>,
+    open: OpenStore,
+    stores: BTreeMap<PrecinctId, Box<dyn CiphertextStore>>,
+    ballots: BTreeMap<PrecinctId, usize>,
+}
+
+impl PrecinctStores {
+    // `open` makes the store of a precinct when its first ballot comes in.
+    pub fn new(params: &Arc<BfvParameters>, open: OpenStore) -> Self {
+        PrecinctStores {
+            params: params.clone(),
+            open,
+            stores: BTreeMap::new(),
+            ballots: BTreeMap::new(),
+        }
+    }
+
+    // Appends the ciphertext of a ballot to its precinct's store, returning where it went.
+    pub fn add(
+        &mut self,
+        precinct: PrecinctId,
+        ciphertext: &Ciphertext,
+    ) -> Result<usize, StoreError> {
+        let store: &mut Box<dyn CiphertextStore> = match self.stores.entry(precinct) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert((self.open)(precinct)?),
+        };
+        let position: usize = store.append(ciphertext)?;
+        *self.ballots.entry(precinct).or_default() += 1;
+        Ok(position)
+    }
+
+    // Takes the ciphertext of a replaced ballot back out of its precinct's sum.
+    pub fn remove(&mut self, precinct: PrecinctId, position: usize) -> Result<(), StoreError> {
+        let mut negated: Ciphertext = Ciphertext::zero(&self.params);
+        negated -= &self.get(precinct, position)?;
+        self.stores
+            .get_mut(&precinct)
+            .expect("the precinct has a store once a ballot was added")
+            .append(&negated)?;
+        *self
+            .ballots
+            .get_mut(&precinct)
+            .expect("the precinct is counted once a ballot was added") -= 1;
+        Ok(())
+    }
+
+    // Reads back the ciphertext appended at `position`.
+    pub fn get(&self, precinct: PrecinctId, position: usize) -> Result<Ciphertext, StoreError> {
+        StoreReader::new(self.store(precinct)).read(position)
+    }
+
+    // The store of a precinct that has had a ballot.
+    pub fn store(&self, precinct: PrecinctId) -> &dyn CiphertextStore {
+        self.stores
+            .get(&precinct)
+            .expect("the precinct has a store once a ballot was added")
+            .as_ref()
+    }
+
+    // The precincts that have had a ballot.
+    pub fn precincts(&self) -> impl Iterator<Item = PrecinctId> + '_ {
+        self.stores.keys().copied()
+    }
+
+    // How many surviving ballots the precinct has.
+    pub fn ballots(&self, precinct: PrecinctId) -> usize {
+        self.ballots.get(&precinct).copied().unwrap_or(0)
+    }
+}
+
+// The tally of each precinct so far, updated one ballot at a time, see `BallotBox::with_tally`.
+//
+// A ballot replaced by a later one from the same voter is subtracted back out. Arithmetic on
+// ciphertexts is exact, so the tallies end up the same ciphertexts as summing the surviving
+// ballots once voting is over.
+pub struct RunningTally {
+    params: Arc<BfvParameters>,
+    tallies: BTreeMap<PrecinctId, PrecinctTally>,
+    // Only ever time spent adding, as the ciphertexts are already in memory.
+    split: IoSplit,
+}
+
+impl RunningTally {
+    pub fn new(params: &Arc<BfvParameters>) -> Self {
+        RunningTally {
+            params: params.clone(),
+            tallies: BTreeMap::new(),
+            split: IoSplit::default(),
+        }
+    }
+
+    fn precinct(&mut self, id: PrecinctId) -> &mut PrecinctTally {
+        self.tallies.entry(id).or_insert_with(|| PrecinctTally {
+            ciphertext: Ciphertext::zero(&self.params),
+            ballots: 0,
+        })
+    }
+
+    pub fn add(&mut self, ballot: &Ballot) {
+        let start: Instant = Instant::now();
+        let precinct: &mut PrecinctTally = self.precinct(ballot.precinct);
+        precinct.ciphertext += &ballot.ciphertext;
+        precinct.ballots += 1;
+        self.split.add_compute(start.elapsed());
+    }
+
+    // Takes a replaced ballot back out of its precinct's tally.
+    pub fn remove(&mut self, ballot: &Ballot) {
+        let start: Instant = Instant::now();
+        let precinct: &mut PrecinctTally = self.precinct(ballot.precinct);
+        precinct.ciphertext -= &ballot.ciphertext;
+        precinct.ballots -= 1;
+        self.split.add_compute(start.elapsed());
+    }
+
+    pub fn tallies(&self) -> &BTreeMap<PrecinctId, PrecinctTally> {
+        &self.tallies
+    }
+
+    // The precinct tallies, and how long went on adding them up.
+    pub fn finish(self) -> (BTreeMap<PrecinctId, PrecinctTally>, IoSplit) {
+        (self.tallies, self.split)
+    }
+}
+
+// Adds the precinct tallies up into the overall tally.
+pub fn aggregate<'a>(
+    tallies: impl IntoIterator<Item = &'a PrecinctTally>,
+    params: &Arc<BfvParameters>,
+) -> Ciphertext {
+    tally::sum(
+        tallies.into_iter().map(|precinct| &precinct.ciphertext),
+        params,
+    )
+}
+
+// The precincts with enough ballots for their total to be decrypted.
+pub fn decryptable(
+    tallies: &BTreeMap<PrecinctId, PrecinctTally>,
+    min_ballots: usize,
+) -> impl Iterator<Item = (PrecinctId, &PrecinctTally)> {
+    tallies
+        .iter()
+        .filter(move |(_, precinct)| precinct.ballots >= min_ballots)
+        .map(|(&id, precinct)| (id, precinct))
+}
@@ -0,0 +1,150 @@
+// Differentially private results: noise added to the tally under encryption, before it is
+// decrypted.
+//
+// An exact count over a small group can give a member away. If a survey of five people is
+// published, and then again once a sixth has answered, the difference is the sixth's answer.
+// Adding random noise to every slot of the result hides what any one input changed, at the cost
+// of the result being approximate: with discrete Laplace noise of scale `Δ/ε`, where `Δ` is the
+// most a single input changes the slots by in total, the result is `ε`-differentially private.
+//
+// The noise is drawn by the trustees, not the coordinator: each encrypts noise of its own and
+// adds it to the tally, see `encrypt_noise`, so the noise is there as long as a single trustee
+// drew theirs honestly, and no one knows the total. The price is noise that is as many times
+// larger as there are trustees.
+//
+// The noise is truncated to `bound` either way, so the result can't wrap around the plaintext
+// modulus: a draw past it is drawn again. The truncation is where the `δ` of `(ε, δ)`-privacy
+// comes in, it is the chance of a draw past the bound. The decrypted slots are noisy counts
+// modulo `t` that may be negative, see `centered`, so the plaintext modulus must hold the largest
+// count plus the noise of every trustee on either side of zero, see `headroom`.
+//
+// Note: the noise is drawn with floating point arithmetic, which leaks a little through the
+// rounding of the logarithms. A production system would sample it exactly.
+
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, PublicKey};
+use fhe_traits::{FheEncoder, FheEncrypter};
+use rand::{CryptoRng, Rng, RngCore};
+use std::{error::Error, fmt, sync::Arc};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrivacyError {
+    // Epsilon must be positive.
+    Epsilon(f64),
+    // Delta must be strictly between 0 and 1.
+    Delta(f64),
+    Sensitivity,
+}
+
+impl fmt::Display for PrivacyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrivacyError::Epsilon(epsilon) => write!(f, "epsilon is {epsilon}, not positive"),
+            PrivacyError::Delta(delta) => write!(f, "delta is {delta}, not between 0 and 1"),
+            PrivacyError::Sensitivity => write!(f, "the sensitivity must be at least 1"),
+        }
+    }
+}
+
+impl Error for PrivacyError {}
+
+// Discrete Laplace noise, truncated to `bound`, giving `(ε, δ)`-differential privacy for a
+// result that a single input changes by at most `sensitivity` in total over its slots.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiscreteLaplace {
+    epsilon: f64,
+    delta: f64,
+    sensitivity: u64,
+}
+
+impl DiscreteLaplace {
+    pub fn new(epsilon: f64, delta: f64, sensitivity: u64) -> Result<Self, PrivacyError> {
+        if !epsilon.is_finite() || epsilon <= 0.0 {
+            return Err(PrivacyError::Epsilon(epsilon));
+        }
+        if delta.is_nan() || delta <= 0.0 || delta >= 1.0 {
+            return Err(PrivacyError::Delta(delta));
+        }
+        if sensitivity == 0 {
+            return Err(PrivacyError::Sensitivity);
+        }
+        Ok(DiscreteLaplace {
+            epsilon,
+            delta,
+            sensitivity,
+        })
+    }
+
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    // The ratio of the probabilities of two neighbouring values, `e^(−ε/Δ)`.
+    fn ratio(&self) -> f64 {
+        (-self.epsilon / self.sensitivity as f64).exp()
+    }
+
+    // The largest noise drawn. Past it, the discrete Laplace distribution has a mass of
+    // `2·q^(k+1)/(1+q)`, which is kept below delta.
+    pub fn bound(&self) -> u64 {
+        let q: f64 = self.ratio();
+        let k: f64 = ((self.delta * (1.0 + q) / 2.0).ln() / q.ln()).ceil() - 1.0;
+        k.max(0.0) as u64
+    }
+
+    // How far the noise of `num_trustees` trustees can move a slot either way.
+    pub fn headroom(&self, num_trustees: usize) -> u64 {
+        self.bound() * num_trustees as u64
+    }
+
+    // One draw, as the difference of two geometric draws.
+    pub fn sample<R: RngCore + CryptoRng>(&self, rng: &mut R) -> i64 {
+        let q: f64 = self.ratio();
+        let bound: i64 = self.bound() as i64;
+        let mut geometric = || {
+            let u: f64 = 1.0 - rng.gen::<f64>();
+            (u.ln() / q.ln()).floor() as i64
+        };
+        loop {
+            let noise: i64 = geometric() - geometric();
+            if noise.abs() <= bound {
+                return noise;
+            }
+        }
+    }
+
+    // A draw for each of `len` slots.
+    pub fn sample_slots<R: RngCore + CryptoRng>(&self, len: usize, rng: &mut R) -> Vec<i64> {
+        (0..len).map(|_| self.sample(rng)).collect()
+    }
+}
+
+// Encrypts one trustee's noise, to be added to the tally before it is decrypted. It is encoded
+// as polynomials like the ballots, each slot taken modulo the plaintext modulus.
+pub fn encrypt_noise<R: RngCore + CryptoRng>(
+    noise: &[i64],
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let t: i64 = params.plaintext() as i64;
+    let slots: Vec<u64> = noise
+        .iter()
+        .map(|&noise| noise.rem_euclid(t) as u64)
+        .collect();
+    let pt: Plaintext = Plaintext::try_encode(&slots, Encoding::poly(), params)?;
+    pk.try_encrypt(&pt, rng)
+}
+
+// A decrypted slot as a signed number: the values past half the plaintext modulus are the
+// negative ones.
+pub fn centered(slot: u64, plaintext_modulus: u64) -> i64 {
+    if slot > plaintext_modulus / 2 {
+        slot as i64 - plaintext_modulus as i64
+    } else {
+        slot as i64
+    }
+}
@@ -0,0 +1,251 @@
+// Projecting the cost of an election far larger than can be run on a laptop, from the reports of
+// a few small calibration runs, see `report`.
+//
+// Every phase of the election is modelled as a fixed cost plus a cost per ballot plus a cost per
+// party, `fixed + per_ballot·votes + per_party·parties`, fitted by least squares over the
+// calibration runs. Most phases are dominated by one term: encryption, submission, the
+// commitment and the tally by the ballots, the key ceremony and decryption by the parties. The
+// memory held at the end of the run and its peak memory use are modelled the same way.
+//
+// The models are linear, so they are only as good as that assumption. They leave out what
+// changes with the size of the election besides the number of ballots and parties, e.g. a larger
+// plaintext modulus for a larger tally, the machine running out of memory, or the per-party work
+// growing faster than linearly with a threshold. Negative terms, which noise in short runs can
+// produce, are clamped to zero.
+//
+// The ballots and key shares carry stand-in proofs for now, see `validity` and `share_proof`, so
+// their cost is part of the encryption, submission and key generation phases and says nothing
+// about a real proof system. What is projected as proof cost is the size of a voter's inclusion
+// proof against the ballots root, and the commitment phase that hashes every ballot into it.
+
+use crate::{merkle::Hash, report::RunReport};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// A measurement of one run, of the given size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub votes: usize,
+    pub parties: usize,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostModel {
+    pub fixed: f64,
+    pub per_ballot: f64,
+    pub per_party: f64,
+}
+
+impl CostModel {
+    // Fits the model to the samples by least squares. A size that doesn't vary across the
+    // samples can't be told apart from the fixed cost, so its term is left at zero.
+    pub fn fit(samples: &[Sample]) -> Self {
+        if samples.is_empty() {
+            return CostModel::default();
+        }
+        let n: f64 = samples.len() as f64;
+        let mean = |f: fn(&Sample) -> f64| samples.iter().map(f).sum::<f64>() / n;
+        let (mv, mp, my): (f64, f64, f64) = (
+            mean(|s| s.votes as f64),
+            mean(|s| s.parties as f64),
+            mean(|s| s.value),
+        );
+        // The sums of the centered products of each pair of variables.
+        let sum = |f: &dyn Fn(f64, f64, f64) -> f64| -> f64 {
+            samples
+                .iter()
+                .map(|s| f(s.votes as f64 - mv, s.parties as f64 - mp, s.value - my))
+                .sum()
+        };
+        let svv: f64 = sum(&|v, _, _| v * v);
+        let spp: f64 = sum(&|_, p, _| p * p);
+        let svp: f64 = sum(&|v, p, _| v * p);
+        let svy: f64 = sum(&|v, _, y| v * y);
+        let spy: f64 = sum(&|_, p, y| p * y);
+
+        let det: f64 = svv * spp - svp * svp;
+        let (per_ballot, per_party): (f64, f64) = if svv > 0.0 && spp > 0.0 && det.abs() > 0.0 {
+            ((svy * spp - spy * svp) / det, (spy * svv - svy * svp) / det)
+        } else if svv > 0.0 {
+            (svy / svv, 0.0)
+        } else if spp > 0.0 {
+            (0.0, spy / spp)
+        } else {
+            (0.0, 0.0)
+        };
+        let per_ballot: f64 = per_ballot.max(0.0);
+        let per_party: f64 = per_party.max(0.0);
+        CostModel {
+            fixed: (my - per_ballot * mv - per_party * mp).max(0.0),
+            per_ballot,
+            per_party,
+        }
+    }
+
+    pub fn predict(&self, votes: usize, parties: usize) -> f64 {
+        self.fixed + self.per_ballot * votes as f64 + self.per_party * parties as f64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhaseProjection {
+    pub phase: String,
+    // In milliseconds.
+    pub model: CostModel,
+    pub projected_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Projection {
+    pub votes: usize,
+    pub parties: usize,
+    pub calibration_runs: usize,
+    pub phases: Vec<PhaseProjection>,
+    pub total_ms: f64,
+    // The serialized ciphertexts, keys and shares held at the end of the run.
+    pub held_bytes: f64,
+    // Only known on Linux.
+    pub peak_rss_bytes: Option<f64>,
+    pub bytes_transferred: u64,
+    // The size of one voter's inclusion proof against the ballots root, at most.
+    pub inclusion_proof_bytes: usize,
+}
+
+// The name of the phase covering the whole run.
+const TOTAL: &str = "total";
+
+// Fits a model to the calibration runs for each phase, and for memory, and projects them to an
+// election of `votes` ballots and `parties` parties. The sizes of the artifacts don't depend on
+// the size of the election, so the bytes transferred are computed from those of the last run.
+pub fn project(reports: &[RunReport], votes: usize, parties: usize) -> Projection {
+    let samples = |value: &dyn Fn(&RunReport) -> Option<f64>| -> Vec<Sample> {
+        reports
+            .iter()
+            .filter_map(|report| {
+                Some(Sample {
+                    votes: report.params.num_votes,
+                    parties: report.params.num_parties,
+                    value: value(report)?,
+                })
+            })
+            .collect()
+    };
+    let phase = |report: &RunReport, name: &str| {
+        report
+            .phases
+            .iter()
+            .find(|phase| phase.name == name)
+            .cloned()
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    for report in reports {
+        for timing in &report.phases {
+            if timing.name != TOTAL && !names.contains(&timing.name) {
+                names.push(timing.name.clone());
+            }
+        }
+    }
+    let phases: Vec<PhaseProjection> = names
+        .into_iter()
+        .map(|name| {
+            let model: CostModel =
+                CostModel::fit(&samples(&|report| Some(phase(report, &name)?.elapsed_ms)));
+            PhaseProjection {
+                projected_ms: model.predict(votes, parties),
+                phase: name,
+                model,
+            }
+        })
+        .collect();
+    let total: CostModel =
+        CostModel::fit(&samples(&|report| Some(phase(report, TOTAL)?.elapsed_ms)));
+    let held: CostModel = CostModel::fit(&samples(&|report| {
+        Some(phase(report, TOTAL)?.held_bytes? as f64)
+    }));
+    let peak_rss: Vec<Sample> =
+        samples(&|report| Some(phase(report, TOTAL)?.peak_rss_bytes? as f64));
+    let bytes_transferred: u64 = reports
+        .last()
+        .map(|report| report.sizes.transferred(votes, parties))
+        .unwrap_or(0);
+
+    Projection {
+        votes,
+        parties,
+        calibration_runs: reports.len(),
+        phases,
+        total_ms: total.predict(votes, parties),
+        held_bytes: held.predict(votes, parties),
+        peak_rss_bytes: (!peak_rss.is_empty())
+            .then(|| CostModel::fit(&peak_rss).predict(votes, parties)),
+        bytes_transferred,
+        inclusion_proof_bytes: votes.next_power_of_two().trailing_zeros() as usize
+            * std::mem::size_of::<Hash>(),
+    }
+}
+
+fn duration(ms: f64) -> String {
+    let secs: f64 = ms / 1000.0;
+    match secs {
+        s if s < 1.0 => format!("{ms:.1} ms"),
+        s if s < 60.0 => format!("{s:.1} s"),
+        s if s < 3600.0 => format!("{:.1} min", s / 60.0),
+        s if s < 86400.0 => format!("{:.1} h", s / 3600.0),
+        s => format!("{:.1} days", s / 86400.0),
+    }
+}
+
+fn bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value: f64 = bytes;
+    let mut unit: usize = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+// A line per phase with its model and projected time, then the totals.
+impl fmt::Display for Projection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "projection for {} ballots and {} parties, from {} calibration runs",
+            self.votes, self.parties, self.calibration_runs
+        )?;
+        writeln!(
+            f,
+            "{:<12} {:>12} {:>14} {:>14} {:>12}",
+            "phase", "fixed", "per ballot", "per party", "projected"
+        )?;
+        for phase in &self.phases {
+            writeln!(
+                f,
+                "{:<12} {:>12} {:>14} {:>14} {:>12}",
+                phase.phase,
+                duration(phase.model.fixed),
+                format!("{:.3} ms", phase.model.per_ballot),
+                format!("{:.3} ms", phase.model.per_party),
+                duration(phase.projected_ms)
+            )?;
+        }
+        writeln!(f, "total time          {}", duration(self.total_ms))?;
+        writeln!(f, "held in memory      {}", bytes(self.held_bytes))?;
+        if let Some(peak_rss) = self.peak_rss_bytes {
+            writeln!(f, "peak memory         {}", bytes(peak_rss))?;
+        }
+        writeln!(
+            f,
+            "bytes transferred   {}",
+            bytes(self.bytes_transferred as f64)
+        )?;
+        write!(
+            f,
+            "inclusion proof     {} per voter",
+            bytes(self.inclusion_proof_bytes as f64)
+        )
+    }
+}
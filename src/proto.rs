@@ -0,0 +1,177 @@
+// The protobuf messages of the coordination protocol, for clients not written in Rust.
+//
+// The messages are defined in `proto/workshop.proto` and their Rust types generated by prost
+// when building with the `proto` feature, see `build.rs`. This module converts them to and from
+// the protocol types. The fhe.rs objects travel as their own serialization, like in the wire
+// format of `wire`, so turning a message back into a protocol type takes the parameters, and
+// for shares the CRP or the tally they were computed from. Fixed size fields are checked for
+// their size, and a field of the wrong size is reported by name.
+
+use crate::{
+    ballot::Ballot,
+    decryption_ceremony::DecryptionShareMessage,
+    key_ceremony::{KeyShareCommitment, KeyShareMessage},
+};
+use ed25519_dalek::{Signature, VerifyingKey};
+use fhe::{
+    bfv::{BfvParameters, Ciphertext},
+    mbfv::{CommonRandomPoly, DecryptionShare, PublicKeyShare},
+};
+use fhe_traits::{DeserializeParametrized, Serialize};
+use std::{error::Error, fmt, sync::Arc};
+
+pub mod v1 {
+    include!(concat!(env!("OUT_DIR"), "/fhe_workshop.v1.rs"));
+}
+
+#[derive(Debug)]
+pub enum ProtoError {
+    // A field that doesn't hold what it should, by name.
+    Field(&'static str),
+    Fhe(fhe::Error),
+}
+
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtoError::Field(field) => write!(f, "invalid `{field}` field"),
+            ProtoError::Fhe(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ProtoError {}
+
+impl From<fhe::Error> for ProtoError {
+    fn from(e: fhe::Error) -> Self {
+        ProtoError::Fhe(e)
+    }
+}
+
+fn array<const N: usize>(bytes: &[u8], field: &'static str) -> Result<[u8; N], ProtoError> {
+    bytes.try_into().map_err(|_| ProtoError::Field(field))
+}
+
+impl From<&KeyShareCommitment> for v1::KeyShareCommitment {
+    fn from(message: &KeyShareCommitment) -> Self {
+        v1::KeyShareCommitment {
+            party: message.party as u64,
+            crp_hash: message.crp_hash.to_vec(),
+            commitment: message.commitment.to_vec(),
+        }
+    }
+}
+
+impl v1::KeyShareCommitment {
+    pub fn to_message(&self) -> Result<KeyShareCommitment, ProtoError> {
+        Ok(KeyShareCommitment {
+            party: self.party as usize,
+            crp_hash: array(&self.crp_hash, "crp_hash")?,
+            commitment: array(&self.commitment, "commitment")?,
+        })
+    }
+}
+
+impl From<&KeyShareMessage> for v1::KeyShare {
+    fn from(message: &KeyShareMessage) -> Self {
+        v1::KeyShare {
+            party: message.party as u64,
+            crp_hash: message.crp_hash.to_vec(),
+            share: message.share.to_bytes(),
+            proof: message.proof.clone(),
+        }
+    }
+}
+
+impl v1::KeyShare {
+    // The share is read against the CRP it was generated from.
+    pub fn to_message(
+        &self,
+        params: &Arc<BfvParameters>,
+        crp: CommonRandomPoly,
+    ) -> Result<KeyShareMessage, ProtoError> {
+        Ok(KeyShareMessage {
+            party: self.party as usize,
+            crp_hash: array(&self.crp_hash, "crp_hash")?,
+            share: PublicKeyShare::deserialize(&self.share, params, crp)?,
+            proof: self.proof.clone(),
+        })
+    }
+}
+
+impl From<&Ballot> for v1::Ballot {
+    fn from(ballot: &Ballot) -> Self {
+        v1::Ballot {
+            voter: ballot.voter.to_bytes().to_vec(),
+            nullifier: ballot.nullifier.to_vec(),
+            precinct: ballot.precinct,
+            proof: ballot.proof.clone(),
+            signature: ballot.signature.to_bytes().to_vec(),
+            ciphertext: ballot.ciphertext.to_bytes(),
+            turnout: ballot
+                .turnout
+                .as_ref()
+                .map(Ciphertext::to_bytes)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl v1::Ballot {
+    // The signature is not checked here, that's up to the ballot box.
+    pub fn to_ballot(&self, params: &Arc<BfvParameters>) -> Result<Ballot, ProtoError> {
+        Ok(Ballot {
+            voter: VerifyingKey::from_bytes(&array(&self.voter, "voter")?)
+                .map_err(|_| ProtoError::Field("voter"))?,
+            nullifier: array(&self.nullifier, "nullifier")?,
+            precinct: self.precinct,
+            ciphertext: Ciphertext::from_bytes(&self.ciphertext, params)?,
+            proof: self.proof.clone(),
+            turnout: (!self.turnout.is_empty())
+                .then(|| Ciphertext::from_bytes(&self.turnout, params))
+                .transpose()?,
+            signature: Signature::from_bytes(&array(&self.signature, "signature")?),
+        })
+    }
+}
+
+impl From<&Ciphertext> for v1::Tally {
+    fn from(tally: &Ciphertext) -> Self {
+        v1::Tally {
+            ciphertext: tally.to_bytes(),
+        }
+    }
+}
+
+impl v1::Tally {
+    pub fn to_ciphertext(&self, params: &Arc<BfvParameters>) -> Result<Ciphertext, ProtoError> {
+        Ok(Ciphertext::from_bytes(&self.ciphertext, params)?)
+    }
+}
+
+impl From<&DecryptionShareMessage> for v1::DecryptionShare {
+    fn from(message: &DecryptionShareMessage) -> Self {
+        v1::DecryptionShare {
+            slot: message.slot as u64,
+            trustee: message.trustee as u64,
+            tally_hash: message.tally_hash.to_vec(),
+            share: message.share.to_bytes(),
+        }
+    }
+}
+
+impl v1::DecryptionShare {
+    // The share is read against the tally it decrypts.
+    pub fn to_message(
+        &self,
+        params: &Arc<BfvParameters>,
+        tally: Arc<Ciphertext>,
+    ) -> Result<DecryptionShareMessage, ProtoError> {
+        Ok(DecryptionShareMessage {
+            slot: self.slot as usize,
+            trustee: self.trustee as usize,
+            tally_hash: array(&self.tally_hash, "tally_hash")?,
+            share: DecryptionShare::deserialize(&self.share, params, tally)?,
+        })
+    }
+}
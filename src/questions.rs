@@ -0,0 +1,92 @@
+// Several questions on one ballot.
+//
+// The key ceremony is by far the most expensive part of setting up an election, so rather than
+// running one election per question, every question goes on the same ballot. Each question
+// takes up its own range of the plaintext slots, one slot per option, and a voter's answer puts
+// a 1 in the slot of the option they picked. Summing the ballots then counts every option of
+// every question at once, and the decrypted tally is split back up into one tally per question.
+//
+// A yes or no question is simply a question with two options.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Questions {
+    // The number of options of each question.
+    options: Vec<usize>,
+}
+
+impl Questions {
+    // Lays out the questions one after the other, each with the given number of options.
+    pub fn new(options: Vec<usize>) -> Result<Self, fhe::Error> {
+        if options.is_empty() || options.contains(&0) {
+            return Err(fhe::Error::DefaultError(
+                "every question needs at least one option".to_string(),
+            ));
+        }
+        Ok(Questions { options })
+    }
+
+    pub fn num_questions(&self) -> usize {
+        self.options.len()
+    }
+
+    pub fn options(&self, question: usize) -> usize {
+        self.options[question]
+    }
+
+    // The number of plaintext slots taken up by all the questions.
+    pub fn num_slots(&self) -> usize {
+        self.options.iter().sum()
+    }
+
+    // The slots of the given question.
+    pub fn range(&self, question: usize) -> Range<usize> {
+        let start: usize = self.options[..question].iter().sum();
+        start..start + self.options[question]
+    }
+
+    // The slots of a ballot answering each question with the option at the same position.
+    pub fn slots(&self, answers: &[usize]) -> Result<Vec<u64>, fhe::Error> {
+        if answers.len() != self.num_questions() {
+            return Err(fhe::Error::DefaultError(format!(
+                "expected {} answers, got {}",
+                self.num_questions(),
+                answers.len()
+            )));
+        }
+        let mut slots: Vec<u64> = vec![0; self.num_slots()];
+        for (question, &answer) in answers.iter().enumerate() {
+            if answer >= self.options[question] {
+                return Err(fhe::Error::DefaultError(format!(
+                    "question {question} has {} options, got answer {answer}",
+                    self.options[question]
+                )));
+            }
+            slots[self.range(question).start + answer] = 1;
+        }
+        Ok(slots)
+    }
+
+    // Reads the answers back from the slots of a single ballot, if it holds exactly one answer
+    // per question.
+    pub fn answers(&self, slots: &[u64]) -> Option<Vec<usize>> {
+        if slots.len() != self.num_slots() {
+            return None;
+        }
+        (0..self.num_questions())
+            .map(|question| {
+                let slots: &[u64] = &slots[self.range(question)];
+                let answer: usize = slots.iter().position(|&slot| slot == 1)?;
+                (slots.iter().sum::<u64>() == 1).then_some(answer)
+            })
+            .collect()
+    }
+
+    // Splits a decrypted tally into the tally of each question.
+    pub fn split<'a>(&self, tally: &'a [u64]) -> Vec<&'a [u64]> {
+        (0..self.num_questions())
+            .map(|question| &tally[self.range(question)])
+            .collect()
+    }
+}
@@ -0,0 +1,115 @@
+// A record of one run of the election, for archiving runs and comparing them.
+//
+// Everything in the report is public: the parameters, how long each phase took, how large the
+// artifacts were, the decrypted tally and the seats it hands out. It never holds a key share or
+// an individual vote.
+
+use crate::{
+    seats::Allocation,
+    stats::{IoSplit, ItemStats},
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunReport {
+    pub params: RunParams,
+    pub phases: Vec<PhaseTiming>,
+    pub sizes: ArtifactSizes,
+    // The same artifacts framed and compressed with zstd, see `codec`.
+    pub compressed_sizes: ArtifactSizes,
+    pub bytes_transferred: u64,
+    // The ballots alone, the number cast times the size of one.
+    #[serde(default)]
+    pub ballot_bytes: u64,
+    // The memory the run was expected to take before it started, see `--max-memory`.
+    #[serde(default)]
+    pub estimated_memory_bytes: u64,
+    pub tally: Vec<u64>,
+    // The seats won by each candidate, in the order of the tally, with `--seats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seats: Option<Allocation>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunParams {
+    pub num_votes: usize,
+    pub num_parties: usize,
+    pub degree: usize,
+    pub plaintext_modulus: u64,
+    pub moduli: Vec<u64>,
+    // The threads the phases ran on, see `--threads`. Missing from reports written before it.
+    #[serde(default)]
+    pub threads: usize,
+}
+
+// How long a phase took, and the peak memory use of the process once it was done, if known.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub elapsed_ms: f64,
+    pub peak_rss_bytes: Option<u64>,
+    // For phases working through a list of items, e.g. one per ballot, see `stats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<ItemStats>,
+    // The serialized size of the ciphertexts, keys and shares held once the phase was done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub held_bytes: Option<u64>,
+    // For phases streaming their items out of a store, see `store`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_split: Option<IoSplit>,
+}
+
+impl PhaseTiming {
+    pub fn new(name: &str, elapsed: Duration, peak_rss_bytes: Option<u64>) -> Self {
+        PhaseTiming {
+            name: name.to_string(),
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            peak_rss_bytes,
+            items: None,
+            held_bytes: None,
+            io_split: None,
+        }
+    }
+
+    pub fn with_held_bytes(mut self, held_bytes: u64) -> Self {
+        self.held_bytes = Some(held_bytes);
+        self
+    }
+
+    pub fn with_io_split(mut self, io_split: IoSplit) -> Self {
+        self.io_split = Some(io_split);
+        self
+    }
+
+    pub fn with_items(mut self, items: ItemStats) -> Self {
+        self.items = Some(items);
+        self
+    }
+}
+
+// The serialized size in bytes of one of each of the artifacts exchanged during a run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArtifactSizes {
+    pub pk_share: usize,
+    pub public_key: usize,
+    pub ballot: usize,
+    pub tally: usize,
+    pub decryption_share: usize,
+}
+
+impl ArtifactSizes {
+    // The bytes exchanged with the coordinator over a run: every party uploads a public key
+    // share, every voter downloads the public key and uploads a ballot, and every party
+    // downloads the tally and uploads a decryption share.
+    pub fn transferred(&self, num_votes: usize, num_parties: usize) -> u64 {
+        let per_voter: usize = self.public_key + self.ballot;
+        let per_party: usize = self.pk_share + self.tally + self.decryption_share;
+        (num_votes * per_voter + num_parties * per_party) as u64
+    }
+
+    // The bytes taken by `num_ballots` ballots, what the ballot box receives and stores.
+    pub fn ballot_bandwidth(&self, num_ballots: usize) -> u64 {
+        (num_ballots * self.ballot) as u64
+    }
+}
@@ -0,0 +1,273 @@
+// Handing the secret key over to a new set of trustees, without anyone ever holding it.
+//
+// The trustees of one election aren't always the trustees of the next: someone leaves, someone
+// joins, the threshold changes. Running a new key ceremony would change the public key, and
+// every ciphertext encrypted to the old one would have to be decrypted and encrypted again.
+// Resharing keeps the key: the secret key is the sum of the old slots' shares (see `threshold`),
+// and each old slot's share is split again, into random parts adding up to it, one part for
+// each slot of the new trustee set. A new slot's share is the sum of the parts it was sent, so
+// the new shares add up to the same secret key, and nobody ever sees more than random parts of
+// it. A quorum of the old trustees is enough, as between them they hold every old slot, see
+// `TrusteeSet::assign`.
+//
+// The parts are taken modulo the ciphertext modulus, so each one on its own is uniformly
+// random. That is only simple with a single modulus, as the election's parameters have, so
+// parameters with a chain of moduli are refused.
+//
+// The new shares come with new public key shares, which add up to a different public key, as
+// each has noise of its own. It is a key to the same secret, and the old one stays the one
+// voters encrypt to. `check_same_key` checks that the new shares decrypt what was encrypted to
+// it, which is all that matters.
+//
+// Refreshing the shares is resharing to the same trustee set, done the proactive way: each slot
+// draws a sharing of zero, random parts adding up to nothing, and sends a part to every slot,
+// which adds them to its share, see `zero_sharing` and `Reshare::refreshed_share`. The secret key
+// doesn't change, but every share does, so shares stolen before a refresh are useless together
+// with shares stolen after it. An attacker has to get a quorum of them between two refreshes.
+//
+// Note: resharing only helps if the old trustees delete their shares afterwards, which nothing
+// here can enforce. Until they do, a quorum of them can still decrypt.
+
+use crate::party::Party;
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, PublicKey, SecretKey},
+    mbfv::{AggregateIter, CommonRandomPoly, DecryptionShare, PublicKeyShare},
+};
+use fhe_traits::{FheDecoder, FheEncoder, FheEncrypter};
+use rand::{CryptoRng, Rng, RngCore};
+use std::{collections::BTreeMap, error::Error, fmt, sync::Arc};
+
+#[derive(Debug)]
+pub enum ReshareError {
+    // The parameters have this many ciphertext moduli rather than one.
+    Moduli(usize),
+    UnknownSlot(usize),
+    DuplicatePart { from: usize, to: usize },
+    // A part of the wrong length for the parameters.
+    MalformedPart { from: usize, to: usize },
+    MissingPart { from: usize, to: usize },
+    Fhe(fhe::Error),
+}
+
+impl fmt::Display for ReshareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReshareError::Moduli(count) => {
+                write!(
+                    f,
+                    "resharing needs a single ciphertext modulus, not {count}"
+                )
+            }
+            ReshareError::UnknownSlot(slot) => write!(f, "unknown slot {slot}"),
+            ReshareError::DuplicatePart { from, to } => {
+                write!(f, "slot {from} already sent its part for slot {to}")
+            }
+            ReshareError::MalformedPart { from, to } => {
+                write!(f, "the part of slot {from} for slot {to} is malformed")
+            }
+            ReshareError::MissingPart { from, to } => {
+                write!(f, "slot {from} hasn't sent its part for slot {to}")
+            }
+            ReshareError::Fhe(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ReshareError {}
+
+impl From<fhe::Error> for ReshareError {
+    fn from(e: fhe::Error) -> Self {
+        ReshareError::Fhe(e)
+    }
+}
+
+// A part of an old slot's share, for one new slot. It is as secret as a share: it must only be
+// sent to the new slot's holders, over a channel nobody else can read.
+#[derive(Clone)]
+pub struct ReshareMessage {
+    pub from: usize,
+    pub to: usize,
+    pub part: Vec<i64>,
+}
+
+fn modulus(params: &BfvParameters) -> Result<u64, ReshareError> {
+    match params.moduli() {
+        [q] => Ok(*q),
+        moduli => Err(ReshareError::Moduli(moduli.len())),
+    }
+}
+
+// Splits the share of old slot `from` into a random part for each of `num_new_slots` new
+// slots. The parts add up to the share, modulo the ciphertext modulus.
+pub fn split_share<R: RngCore + CryptoRng>(
+    from: usize,
+    key: &Party,
+    num_new_slots: usize,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Vec<ReshareMessage>, ReshareError> {
+    let q: i64 = modulus(params)? as i64;
+    Ok(split(from, &key.sk_share.coeffs, num_new_slots, q, rng))
+}
+
+// Random parts for each of `num_slots` slots adding up to zero, modulo the ciphertext modulus.
+pub fn zero_sharing<R: RngCore + CryptoRng>(
+    from: usize,
+    num_slots: usize,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Vec<ReshareMessage>, ReshareError> {
+    let q: i64 = modulus(params)? as i64;
+    Ok(split(from, &vec![0; params.degree()], num_slots, q, rng))
+}
+
+// Random parts adding up to the coefficients modulo `q`, the first one making up the rest.
+fn split<R: RngCore + CryptoRng>(
+    from: usize,
+    coeffs: &[i64],
+    num_slots: usize,
+    q: i64,
+    rng: &mut R,
+) -> Vec<ReshareMessage> {
+    let mut rest: Vec<i64> = coeffs.iter().map(|coeff| coeff.rem_euclid(q)).collect();
+    let parts: Vec<Vec<i64>> = (1..num_slots)
+        .map(|_| {
+            let part: Vec<i64> = (0..rest.len()).map(|_| rng.gen_range(0..q)).collect();
+            for (rest, part) in rest.iter_mut().zip(&part) {
+                *rest = (*rest - part).rem_euclid(q);
+            }
+            part
+        })
+        .collect();
+    std::iter::once(rest)
+        .chain(parts)
+        .enumerate()
+        .map(|(to, part)| ReshareMessage { from, to, part })
+        .collect()
+}
+
+// Collects the parts sent by the old slots, and adds them up into the new slots' shares.
+pub struct Reshare {
+    params: Arc<BfvParameters>,
+    crp: CommonRandomPoly,
+    num_old_slots: usize,
+    num_new_slots: usize,
+    parts: BTreeMap<(usize, usize), Vec<i64>>,
+}
+
+impl Reshare {
+    // `crp` is the CRP the new public key shares are derived from, e.g. the election's.
+    pub fn new(
+        params: &Arc<BfvParameters>,
+        crp: &CommonRandomPoly,
+        num_old_slots: usize,
+        num_new_slots: usize,
+    ) -> Result<Self, ReshareError> {
+        modulus(params)?;
+        Ok(Reshare {
+            params: params.clone(),
+            crp: crp.clone(),
+            num_old_slots,
+            num_new_slots,
+            parts: BTreeMap::new(),
+        })
+    }
+
+    pub fn receive(&mut self, message: ReshareMessage) -> Result<(), ReshareError> {
+        let ReshareMessage { from, to, part } = message;
+        if from >= self.num_old_slots {
+            return Err(ReshareError::UnknownSlot(from));
+        }
+        if to >= self.num_new_slots {
+            return Err(ReshareError::UnknownSlot(to));
+        }
+        if part.len() != self.params.degree() {
+            return Err(ReshareError::MalformedPart { from, to });
+        }
+        if self.parts.contains_key(&(from, to)) {
+            return Err(ReshareError::DuplicatePart { from, to });
+        }
+        self.parts.insert((from, to), part);
+        Ok(())
+    }
+
+    // A refresh of the shares of `num_slots` slots, among themselves.
+    pub fn refresh(
+        params: &Arc<BfvParameters>,
+        crp: &CommonRandomPoly,
+        num_slots: usize,
+    ) -> Result<Self, ReshareError> {
+        Self::new(params, crp, num_slots, num_slots)
+    }
+
+    // Whether every old slot has sent its part for the new slot.
+    pub fn is_complete(&self, to: usize) -> bool {
+        (0..self.num_old_slots).all(|from| self.parts.contains_key(&(from, to)))
+    }
+
+    // The key share of new slot `to`, the sum of the parts every old slot sent it, with a fresh
+    // public key share.
+    pub fn new_share<R: RngCore + CryptoRng>(
+        &self,
+        to: usize,
+        rng: &mut R,
+    ) -> Result<(Party, PublicKeyShare), ReshareError> {
+        self.add_parts(to, vec![0; self.params.degree()], rng)
+    }
+
+    // The share of slot `to` after a refresh: its share before, `key`, plus the parts of zero
+    // every slot sent it, with a fresh public key share.
+    pub fn refreshed_share<R: RngCore + CryptoRng>(
+        &self,
+        to: usize,
+        key: &Party,
+        rng: &mut R,
+    ) -> Result<(Party, PublicKeyShare), ReshareError> {
+        self.add_parts(to, key.sk_share.coeffs.to_vec(), rng)
+    }
+
+    fn add_parts<R: RngCore + CryptoRng>(
+        &self,
+        to: usize,
+        mut coeffs: Vec<i64>,
+        rng: &mut R,
+    ) -> Result<(Party, PublicKeyShare), ReshareError> {
+        if to >= self.num_new_slots {
+            return Err(ReshareError::UnknownSlot(to));
+        }
+        let q: i64 = modulus(&self.params)? as i64;
+        for from in 0..self.num_old_slots {
+            let part: &Vec<i64> = self
+                .parts
+                .get(&(from, to))
+                .ok_or(ReshareError::MissingPart { from, to })?;
+            for (coeff, part) in coeffs.iter_mut().zip(part) {
+                *coeff = (*coeff + part).rem_euclid(q);
+            }
+        }
+        let sk_share: SecretKey = SecretKey::new(coeffs, &self.params);
+        let pk_share: PublicKeyShare = PublicKeyShare::new(&sk_share, self.crp.clone(), rng)?;
+        Ok((Party { sk_share }, pk_share))
+    }
+}
+
+// Whether the keys, one per slot, add up to the secret key of `pk`: encrypts random slots to it
+// and decrypts them with the keys.
+pub fn check_same_key<R: RngCore + CryptoRng>(
+    pk: &PublicKey,
+    keys: &[Party],
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<bool, fhe::Error> {
+    let slots: Vec<u64> = (0..params.degree())
+        .map(|_| rng.gen_range(0..params.plaintext()))
+        .collect();
+    let pt: Plaintext = Plaintext::try_encode(&slots, Encoding::poly(), params)?;
+    let ct: Arc<Ciphertext> = Arc::new(pk.try_encrypt(&pt, rng)?);
+    let shares: Vec<DecryptionShare> = keys
+        .iter()
+        .map(|key| key.decryption_share(&ct, rng))
+        .collect::<Result<_, _>>()?;
+    let decrypted: Plaintext = shares.into_iter().aggregate()?;
+    Ok(Vec::<u64>::try_decode(&decrypted, Encoding::poly())? == slots)
+}
@@ -0,0 +1,150 @@
+// Several rounds of computation under the keys of one key ceremony.
+//
+// A key ceremony is the slow part of an election: every trustee has to be there, and the
+// relinearization key takes two more rounds on top. Nothing about the key ties it to a single
+// question, so the same public key can take the ballots of any number of rounds, one after the
+// other or at the same time, as long as what is decrypted is only ever the result of a round.
+//
+// `Rounds` keeps track of which ciphertexts belong to which round. A round is opened, takes
+// ciphertexts until it is closed, and its tally is then the sum of them, see `tally::sum`. The
+// decryption ceremony of a round is bound to it, see `DecryptionCeremony::with_round`, so the
+// trustees' shares for one round can't be replayed into another, even when two rounds happen to
+// have the same tally.
+//
+// Note: every round decrypted with the same key is one more result the trustees have agreed to
+// reveal. Reusing the key is only as safe as the trustees are careful to decrypt the tallies of
+// closed rounds and nothing else.
+
+use crate::{decryption_ceremony::DecryptionCeremony, tally, threshold::TrusteeSet};
+use fhe::bfv::{BfvParameters, Ciphertext};
+use std::{collections::BTreeMap, error::Error, fmt, sync::Arc};
+
+pub type RoundId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundError {
+    UnknownRound(RoundId),
+    // The round no longer takes ciphertexts.
+    Closed(RoundId),
+    // The round has no tally until it is closed.
+    Open(RoundId),
+}
+
+impl fmt::Display for RoundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundError::UnknownRound(round) => write!(f, "unknown round {round}"),
+            RoundError::Closed(round) => write!(f, "round {round} is closed"),
+            RoundError::Open(round) => write!(f, "round {round} is still open"),
+        }
+    }
+}
+
+impl Error for RoundError {}
+
+struct Round {
+    label: String,
+    ciphertexts: Vec<Ciphertext>,
+    // The sum of the ciphertexts, once the round is closed.
+    tally: Option<Arc<Ciphertext>>,
+}
+
+// The rounds run under one key, numbered in the order they were opened.
+pub struct Rounds {
+    params: Arc<BfvParameters>,
+    rounds: BTreeMap<RoundId, Round>,
+    next: RoundId,
+}
+
+impl Rounds {
+    pub fn new(params: &Arc<BfvParameters>) -> Self {
+        Rounds {
+            params: params.clone(),
+            rounds: BTreeMap::new(),
+            next: 0,
+        }
+    }
+
+    // Opens a round, e.g. for one question, and returns its number.
+    pub fn open(&mut self, label: &str) -> RoundId {
+        let round: RoundId = self.next;
+        self.next += 1;
+        self.rounds.insert(
+            round,
+            Round {
+                label: label.to_string(),
+                ciphertexts: Vec::new(),
+                tally: None,
+            },
+        );
+        round
+    }
+
+    fn round(&self, round: RoundId) -> Result<&Round, RoundError> {
+        self.rounds
+            .get(&round)
+            .ok_or(RoundError::UnknownRound(round))
+    }
+
+    pub fn label(&self, round: RoundId) -> Result<&str, RoundError> {
+        Ok(&self.round(round)?.label)
+    }
+
+    // The rounds still taking ciphertexts.
+    pub fn open_rounds(&self) -> impl Iterator<Item = RoundId> + '_ {
+        self.rounds
+            .iter()
+            .filter(|(_, round)| round.tally.is_none())
+            .map(|(&id, _)| id)
+    }
+
+    // Adds a ciphertext to an open round.
+    pub fn submit(&mut self, round: RoundId, ciphertext: Ciphertext) -> Result<(), RoundError> {
+        let entry: &mut Round = self
+            .rounds
+            .get_mut(&round)
+            .ok_or(RoundError::UnknownRound(round))?;
+        if entry.tally.is_some() {
+            return Err(RoundError::Closed(round));
+        }
+        entry.ciphertexts.push(ciphertext);
+        Ok(())
+    }
+
+    // How many ciphertexts the round took.
+    pub fn num_ciphertexts(&self, round: RoundId) -> Result<usize, RoundError> {
+        Ok(self.round(round)?.ciphertexts.len())
+    }
+
+    // Closes the round and sums its ciphertexts. Closing it again returns the same tally.
+    pub fn close(&mut self, round: RoundId) -> Result<Arc<Ciphertext>, RoundError> {
+        let params: Arc<BfvParameters> = self.params.clone();
+        let entry: &mut Round = self
+            .rounds
+            .get_mut(&round)
+            .ok_or(RoundError::UnknownRound(round))?;
+        let tally: &Arc<Ciphertext> = entry
+            .tally
+            .get_or_insert_with(|| Arc::new(tally::sum(&entry.ciphertexts, &params)));
+        Ok(tally.clone())
+    }
+
+    // The tally of a closed round.
+    pub fn tally(&self, round: RoundId) -> Result<Arc<Ciphertext>, RoundError> {
+        self.round(round)?
+            .tally
+            .clone()
+            .ok_or(RoundError::Open(round))
+    }
+
+    // The decryption ceremony of a closed round, only taking shares bound to it, see
+    // `DecryptionShareMessage::with_round`.
+    pub fn decryption(
+        &self,
+        round: RoundId,
+        trustees: &TrusteeSet,
+    ) -> Result<DecryptionCeremony, RoundError> {
+        let tally: Arc<Ciphertext> = self.tally(round)?;
+        Ok(DecryptionCeremony::new(&tally, trustees).with_round(round))
+    }
+}
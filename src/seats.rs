@@ -0,0 +1,214 @@
+// Seats in a parliament, handed out in proportion to the votes of each party.
+//
+// Pure post-processing of the decrypted tally: the vote totals of the parties go in, and the
+// number of seats each one wins comes out, see `allocate`. Both methods are highest averages
+// methods. Seats are handed out one at a time, each to the party with the highest quotient, its
+// votes divided by a divisor that grows with the seats it has already won:
+//
+// - D'Hondt divides by `s + 1`, which favours larger parties a little,
+// - Sainte-Laguë divides by `2s + 1`, which is closer to exact proportionality.
+//
+// Quotients are compared exactly, as fractions. Two parties with the same quotient are told
+// apart by their total votes and then by their position, but a tie for the last seat can only
+// be settled by the rules of the election, e.g. by drawing lots, so it is reported, see
+// `Allocation::tie`.
+
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, error::Error, fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeatError {
+    // No party has any votes to hand seats out by.
+    NoVotes,
+    UnknownMethod(String),
+}
+
+impl fmt::Display for SeatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeatError::NoVotes => write!(f, "no party has any votes to allocate seats by"),
+            SeatError::UnknownMethod(method) => write!(
+                f,
+                "unknown seat allocation method {method:?}, expected dhondt or sainte-lague"
+            ),
+        }
+    }
+}
+
+impl Error for SeatError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeatMethod {
+    Dhondt,
+    SainteLague,
+}
+
+impl SeatMethod {
+    // The divisor of a party that has already won `seats`.
+    fn divisor(self, seats: u64) -> u64 {
+        match self {
+            SeatMethod::Dhondt => seats + 1,
+            SeatMethod::SainteLague => 2 * seats + 1,
+        }
+    }
+}
+
+impl fmt::Display for SeatMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeatMethod::Dhondt => write!(f, "dhondt"),
+            SeatMethod::SainteLague => write!(f, "sainte-lague"),
+        }
+    }
+}
+
+impl FromStr for SeatMethod {
+    type Err = SeatError;
+
+    fn from_str(method: &str) -> Result<Self, Self::Err> {
+        match method.to_lowercase().as_str() {
+            "dhondt" | "d'hondt" | "d-hondt" | "jefferson" => Ok(SeatMethod::Dhondt),
+            "sainte-lague" | "sainte-laguë" | "webster" => Ok(SeatMethod::SainteLague),
+            _ => Err(SeatError::UnknownMethod(method.to_string())),
+        }
+    }
+}
+
+// A party's quotient for its next seat, as a fraction.
+#[derive(Debug, Clone, Copy)]
+struct Quotient {
+    votes: u64,
+    divisor: u64,
+}
+
+impl Quotient {
+    fn cmp(&self, other: &Quotient) -> Ordering {
+        (self.votes as u128 * other.divisor as u128)
+            .cmp(&(other.votes as u128 * self.divisor as u128))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Allocation {
+    pub method: SeatMethod,
+    pub seats: u64,
+    // The seats each party won, in the order of the vote totals.
+    pub allocation: Vec<u64>,
+    // Whether another party had the same quotient as the party that won the last seat, so that
+    // the last seat could just as well have gone to it.
+    pub tie: bool,
+}
+
+// Hands out `seats` to the parties in proportion to their `votes`. A party without votes never
+// wins a seat.
+pub fn allocate(votes: &[u64], seats: u64, method: SeatMethod) -> Result<Allocation, SeatError> {
+    if seats > 0 && votes.iter().all(|&votes| votes == 0) {
+        return Err(SeatError::NoVotes);
+    }
+    let quotient = |allocation: &[u64], party: usize| Quotient {
+        votes: votes[party],
+        divisor: method.divisor(allocation[party]),
+    };
+    // The party with the highest quotient, the one with the most votes among those with the
+    // same quotient, and the first of those.
+    let best = |allocation: &[u64]| -> usize {
+        (0..votes.len())
+            .max_by(|&a, &b| {
+                quotient(allocation, a)
+                    .cmp(&quotient(allocation, b))
+                    .then(votes[a].cmp(&votes[b]))
+                    .then(b.cmp(&a))
+            })
+            .expect("there are votes, so there are parties")
+    };
+    let mut allocation: Vec<u64> = vec![0; votes.len()];
+    let mut last: Option<Quotient> = None;
+    for _ in 0..seats {
+        let party: usize = best(&allocation);
+        last = Some(quotient(&allocation, party));
+        allocation[party] += 1;
+    }
+    // The next seat would go to a party whose quotient is as high as the last seat's.
+    let tie: bool = match last {
+        Some(last) => {
+            let next: usize = best(&allocation);
+            votes[next] > 0 && quotient(&allocation, next).cmp(&last) == Ordering::Equal
+        }
+        None => false,
+    };
+    Ok(Allocation {
+        method,
+        seats,
+        allocation,
+        tie,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dhondt_favours_larger_parties() {
+        let allocation: Allocation =
+            allocate(&[100_000, 80_000, 30_000, 20_000], 8, SeatMethod::Dhondt).unwrap();
+        assert_eq!(allocation.allocation, vec![4, 3, 1, 0]);
+        assert!(!allocation.tie);
+        let allocation: Allocation =
+            allocate(&[53_000, 24_000, 23_000], 7, SeatMethod::Dhondt).unwrap();
+        assert_eq!(allocation.allocation, vec![4, 2, 1]);
+    }
+
+    #[test]
+    fn sainte_lague_is_closer_to_proportional() {
+        let allocation: Allocation = allocate(
+            &[100_000, 80_000, 30_000, 20_000],
+            8,
+            SeatMethod::SainteLague,
+        )
+        .unwrap();
+        assert_eq!(allocation.allocation, vec![3, 3, 1, 1]);
+        let allocation: Allocation =
+            allocate(&[53_000, 24_000, 23_000], 7, SeatMethod::SainteLague).unwrap();
+        assert_eq!(allocation.allocation, vec![3, 2, 2]);
+        assert!(!allocation.tie);
+    }
+
+    #[test]
+    fn tie_for_the_last_seat_is_reported() {
+        let allocation: Allocation = allocate(&[1, 1], 1, SeatMethod::Dhondt).unwrap();
+        assert_eq!(allocation.allocation, vec![1, 0]);
+        assert!(allocation.tie);
+        // Both parties with the same quotient win a seat, so nothing is left to settle.
+        let allocation: Allocation = allocate(&[2, 2], 2, SeatMethod::SainteLague).unwrap();
+        assert_eq!(allocation.allocation, vec![1, 1]);
+        assert!(!allocation.tie);
+    }
+
+    #[test]
+    fn party_without_votes_wins_no_seat() {
+        let allocation: Allocation = allocate(&[10, 0, 5], 3, SeatMethod::Dhondt).unwrap();
+        assert_eq!(allocation.allocation, vec![2, 0, 1]);
+        assert_eq!(
+            allocate(&[0, 0], 1, SeatMethod::Dhondt),
+            Err(SeatError::NoVotes)
+        );
+    }
+
+    #[test]
+    fn no_seats_to_hand_out() {
+        let allocation: Allocation = allocate(&[0, 0], 0, SeatMethod::Dhondt).unwrap();
+        assert_eq!(allocation.allocation, vec![0, 0]);
+        assert!(!allocation.tie);
+        let allocation: Allocation = allocate(&[3, 7], 0, SeatMethod::SainteLague).unwrap();
+        assert_eq!(allocation.allocation, vec![0, 0]);
+    }
+
+    #[test]
+    fn method_parses_from_its_name() {
+        assert_eq!("dhondt".parse(), Ok(SeatMethod::Dhondt));
+        assert_eq!("Sainte-Lague".parse(), Ok(SeatMethod::SainteLague));
+        assert!("hare".parse::<SeatMethod>().is_err());
+    }
+}
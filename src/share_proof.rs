@@ -0,0 +1,88 @@
+// Proofs that a public key share was generated from the CRP by a party knowing its secret.
+//
+// A public key share is `-a·s + e` for the CRP `a`, the party's secret key share `s` and a small
+// error `e`, and the public key is the sum of the shares. A party that holds back its own share
+// until it has seen everyone else's (a rushing adversary) can send one that cancels theirs out,
+// leaving a public key whose secret key it knows on its own. So each share should come with a
+// proof that its sender knows a small `s` and `e` it is made of, and the key ceremony checks
+// every proof before it adds up any share, see `KeyCeremony::aggregate`.
+//
+// Proof systems plug in through `ShareProof`, as ballot proofs do through `BallotProof`. A real
+// one is a zero knowledge proof of a short solution to the RLWE relation above, which fhe.rs has
+// no support for. The only one so far, `BindingProof`, is a stand-in: it is a hash binding the
+// share to the party and the CRP, which anyone can compute. It catches a share sent under the
+// wrong party or for another CRP, but it proves nothing about the secret. It is there so that the
+// proof travels with every share and the verification step runs in every ceremony until a real
+// proof takes its place.
+//
+// What does stand against a rushing adversary in the meantime is the commitment round of the key
+// ceremony: every party commits to the hash of its share before any share is revealed, and a
+// revealed share must match its commitment, see `KeyCeremony::commit`. A party can't pick its
+// share after seeing the others', but a share it committed to still needn't be honestly made.
+
+use crate::{
+    key_ceremony::{self, PartyId},
+    party::Party,
+};
+use fhe::mbfv::{CommonRandomPoly, PublicKeyShare};
+use fhe_traits::Serialize;
+use sha2::{Digest, Sha256};
+
+const BINDING_DOMAIN: &[u8] = b"fhe-workshop/binding-proof";
+
+pub trait ShareProof: Send + Sync {
+    // Proves that `share` was generated from the CRP and the secret key share of `key`, by party
+    // `party`.
+    fn prove(
+        &self,
+        party: PartyId,
+        key: &Party,
+        share: &PublicKeyShare,
+        crp: &CommonRandomPoly,
+    ) -> Result<Vec<u8>, fhe::Error>;
+
+    fn verify(
+        &self,
+        party: PartyId,
+        share: &PublicKeyShare,
+        crp: &CommonRandomPoly,
+        proof: &[u8],
+    ) -> bool;
+}
+
+// The stand-in proof system: a hash of the party, the CRP and the share.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BindingProof;
+
+impl BindingProof {
+    fn binding(party: PartyId, share: &PublicKeyShare, crp: &CommonRandomPoly) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(BINDING_DOMAIN);
+        hasher.update((party as u64).to_le_bytes());
+        hasher.update(key_ceremony::crp_hash(crp));
+        hasher.update(share.to_bytes());
+        hasher.finalize().into()
+    }
+}
+
+impl ShareProof for BindingProof {
+    fn prove(
+        &self,
+        party: PartyId,
+        _key: &Party,
+        share: &PublicKeyShare,
+        crp: &CommonRandomPoly,
+    ) -> Result<Vec<u8>, fhe::Error> {
+        Ok(BindingProof::binding(party, share, crp).to_vec())
+    }
+
+    fn verify(
+        &self,
+        party: PartyId,
+        share: &PublicKeyShare,
+        crp: &CommonRandomPoly,
+        proof: &[u8],
+    ) -> bool {
+        proof == BindingProof::binding(party, share, crp)
+    }
+}
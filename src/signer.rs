@@ -0,0 +1,470 @@
+// Where a trustee's secret key share lives, and who computes with it.
+//
+// A trustee only ever needs its secret key share for two things: its public key share (with the
+// proof that goes with it, see `share_proof.rs`) and its decryption shares. `TrusteeSigner` is
+// those two operations, so the code running the protocol never has to touch the secret itself.
+// `LocalSigner` keeps the share in memory, in the same process. `RemoteSigner` asks another
+// process for the shares over a socket, so the share can stay on a machine, or in a hardware
+// security module, that nothing else runs on.
+//
+// The other end of `RemoteSigner` is `serve`, a mock HSM: it answers the requests of one
+// connection with a `LocalSigner`. A real HSM would keep the share in hardware, but it speaks
+// the same protocol. Every message is a frame: its length as four bytes, little-endian, then
+// that many bytes. A request starts with its kind, a response with whether it succeeded:
+//
+// - key share: the party number as eight bytes, answered with the key share message in the
+//   wire format, see `wire`,
+// - decryption share: the ciphertext in the wire format, answered with the decryption share if
+//   the trustee approved the tally, see `serve`,
+// - a failed request is answered with the error, as text.
+//
+// The frames go over a Noise channel, `Noise_KK_25519_ChaChaPoly_BLAKE2s`: each end has a static
+// X25519 key, see `generate_channel_key`, and is given the other end's public key beforehand, so
+// the handshake authenticates both of them and nobody else can connect or read the traffic. The
+// session name and trustee number go into the prologue, so a channel set up for one trustee's key
+// share can't be used to reach another's. A request or response larger than a Noise message is
+// sent as its length, then as many messages as it takes.
+//
+// Even over the channel, the HSM doesn't decrypt whatever it is sent: a ciphertext whose hash the
+// trustee hasn't approved is refused, so a compromised process on the trustee's side can't have
+// it decrypt a single ballot, only the tallies the trustee checked.
+//
+// Note: the trustees have no identity keys of their own besides their key shares, which can't
+// sign. The channel keys are the identities here, and are as worth guarding as the share.
+
+use crate::{
+    decryption_ceremony::{self, TallyHash},
+    key_ceremony::{self, KeyShareMessage, PartyId},
+    party::Party,
+    share_proof::{BindingProof, ShareProof},
+    wire::{self, WireError},
+};
+use fhe::{
+    bfv::{BfvParameters, Ciphertext},
+    mbfv::{CommonRandomPoly, DecryptionShare, PublicKeyShare},
+};
+use fhe_traits::{DeserializeParametrized, Serialize};
+use rand::{rngs::StdRng, CryptoRng, Rng, RngCore, SeedableRng};
+use snow::{Builder, HandshakeState, TransportState};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+};
+
+// The largest request or response, sent over as many Noise messages as it takes. A ciphertext at
+// the largest parameters is a few MiB, this leaves plenty of room.
+const MAX_FRAME: usize = 64 << 20;
+
+const NOISE_PARAMS: &str = "Noise_KK_25519_ChaChaPoly_BLAKE2s";
+const NOISE_MAX_MESSAGE: usize = 65535;
+const NOISE_TAG: usize = 16;
+const PROLOGUE_DOMAIN: &[u8] = b"fhe-workshop/signer-channel";
+
+// An X25519 key of one end of the channel, secret or public.
+pub type ChannelKey = [u8; 32];
+
+const KEY_SHARE: u8 = 0;
+const DECRYPTION_SHARE: u8 = 1;
+
+const OK: u8 = 0;
+const FAILED: u8 = 1;
+
+#[derive(Debug)]
+pub enum SignerError {
+    Io(io::Error),
+    Fhe(fhe::Error),
+    // The other end refused the request.
+    Refused(String),
+    // A frame that doesn't follow the protocol.
+    Malformed,
+    Wire(WireError),
+    // The Noise handshake failed, e.g. the other end holds another key, or a message didn't
+    // decrypt.
+    Noise(snow::Error),
+    // A decryption share of a tally the trustee didn't approve.
+    Unapproved(TallyHash),
+}
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignerError::Io(e) => write!(f, "{e}"),
+            SignerError::Fhe(e) => write!(f, "{e}"),
+            SignerError::Refused(reason) => write!(f, "the signer refused: {reason}"),
+            SignerError::Malformed => write!(f, "malformed signer message"),
+            SignerError::Wire(e) => write!(f, "{e}"),
+            SignerError::Noise(e) => write!(f, "signer channel: {e}"),
+            SignerError::Unapproved(tally) => {
+                write!(
+                    f,
+                    "tally {} wasn't approved for decryption",
+                    hex::encode(tally)
+                )
+            }
+        }
+    }
+}
+
+impl Error for SignerError {}
+
+impl From<io::Error> for SignerError {
+    fn from(e: io::Error) -> Self {
+        SignerError::Io(e)
+    }
+}
+
+impl From<fhe::Error> for SignerError {
+    fn from(e: fhe::Error) -> Self {
+        SignerError::Fhe(e)
+    }
+}
+
+impl From<WireError> for SignerError {
+    fn from(e: WireError) -> Self {
+        SignerError::Wire(e)
+    }
+}
+
+impl From<snow::Error> for SignerError {
+    fn from(e: snow::Error) -> Self {
+        SignerError::Noise(e)
+    }
+}
+
+// A fresh key pair for one end of the channel, as its secret and public key.
+pub fn generate_channel_key() -> Result<(ChannelKey, ChannelKey), SignerError> {
+    let keypair: snow::Keypair = Builder::new(NOISE_PARAMS.parse()?).generate_keypair()?;
+    let key = |bytes: Vec<u8>| ChannelKey::try_from(bytes).map_err(|_| SignerError::Malformed);
+    Ok((key(keypair.private)?, key(keypair.public)?))
+}
+
+// Reads a channel key from its hex encoding.
+pub fn parse_channel_key(hex: &str) -> Result<ChannelKey, String> {
+    let bytes: Vec<u8> = hex::decode(hex.trim()).map_err(|e| format!("{e}"))?;
+    ChannelKey::try_from(bytes).map_err(|_| "a channel key is 32 bytes, 64 hex digits".to_string())
+}
+
+// What both ends of a channel have to agree on besides the keys: the session and the trustee
+// whose key share the HSM holds.
+pub fn channel_prologue(session: &str, trustee: PartyId) -> Vec<u8> {
+    [
+        PROLOGUE_DOMAIN,
+        &(session.len() as u64).to_le_bytes(),
+        session.as_bytes(),
+        &(trustee as u64).to_le_bytes(),
+    ]
+    .concat()
+}
+
+// Both ends of a channel: this end's secret key, the other end's public key, and the prologue.
+#[derive(Clone)]
+pub struct ChannelKeys {
+    pub secret: ChannelKey,
+    pub peer: ChannelKey,
+    pub prologue: Vec<u8>,
+}
+
+// An authenticated and encrypted connection, once the handshake is over.
+struct Channel {
+    stream: TcpStream,
+    noise: TransportState,
+}
+
+impl Channel {
+    fn builder(keys: &ChannelKeys) -> Result<Builder<'_>, SignerError> {
+        Ok(Builder::new(NOISE_PARAMS.parse()?)
+            .local_private_key(&keys.secret)
+            .remote_public_key(&keys.peer)
+            .prologue(&keys.prologue))
+    }
+
+    // The connecting end: sends the first handshake message and reads the answer.
+    fn initiate(mut stream: TcpStream, keys: &ChannelKeys) -> Result<Self, SignerError> {
+        let mut handshake: HandshakeState = Self::builder(keys)?.build_initiator()?;
+        let mut buffer: Vec<u8> = vec![0; NOISE_MAX_MESSAGE];
+        let len: usize = handshake.write_message(&[], &mut buffer)?;
+        write_frame(&mut stream, &buffer[..len])?;
+        let answer: Vec<u8> = read_frame(&mut stream)?.ok_or(SignerError::Malformed)?;
+        handshake.read_message(&answer, &mut buffer)?;
+        Ok(Channel {
+            stream,
+            noise: handshake.into_transport_mode()?,
+        })
+    }
+
+    // The listening end: reads the first handshake message and answers it.
+    fn respond(mut stream: TcpStream, keys: &ChannelKeys) -> Result<Self, SignerError> {
+        let mut handshake: HandshakeState = Self::builder(keys)?.build_responder()?;
+        let mut buffer: Vec<u8> = vec![0; NOISE_MAX_MESSAGE];
+        let first: Vec<u8> = read_frame(&mut stream)?.ok_or(SignerError::Malformed)?;
+        handshake.read_message(&first, &mut buffer)?;
+        let len: usize = handshake.write_message(&[], &mut buffer)?;
+        write_frame(&mut stream, &buffer[..len])?;
+        Ok(Channel {
+            stream,
+            noise: handshake.into_transport_mode()?,
+        })
+    }
+
+    // Sends the length of the bytes, then the bytes, in as many Noise messages as they take.
+    fn send(&mut self, bytes: &[u8]) -> Result<(), SignerError> {
+        let mut buffer: Vec<u8> = vec![0; NOISE_MAX_MESSAGE];
+        let len: usize = self
+            .noise
+            .write_message(&(bytes.len() as u32).to_le_bytes(), &mut buffer)?;
+        write_frame(&mut self.stream, &buffer[..len])?;
+        for chunk in bytes.chunks(NOISE_MAX_MESSAGE - NOISE_TAG) {
+            let len: usize = self.noise.write_message(chunk, &mut buffer)?;
+            write_frame(&mut self.stream, &buffer[..len])?;
+        }
+        Ok(())
+    }
+
+    // The next bytes sent, or `None` if the other end hung up between them.
+    fn receive(&mut self) -> Result<Option<Vec<u8>>, SignerError> {
+        let mut buffer: Vec<u8> = vec![0; NOISE_MAX_MESSAGE];
+        let Some(header) = read_frame(&mut self.stream)? else {
+            return Ok(None);
+        };
+        let len: usize = self.noise.read_message(&header, &mut buffer)?;
+        let total: [u8; 4] = buffer[..len]
+            .try_into()
+            .map_err(|_| SignerError::Malformed)?;
+        let total: usize = u32::from_le_bytes(total) as usize;
+        if total > MAX_FRAME {
+            return Err(SignerError::Malformed);
+        }
+        let mut bytes: Vec<u8> = Vec::with_capacity(total);
+        while bytes.len() < total {
+            let message: Vec<u8> = read_frame(&mut self.stream)?.ok_or(SignerError::Malformed)?;
+            let len: usize = self.noise.read_message(&message, &mut buffer)?;
+            bytes.extend_from_slice(&buffer[..len]);
+        }
+        if bytes.len() != total {
+            return Err(SignerError::Malformed);
+        }
+        Ok(Some(bytes))
+    }
+}
+
+pub trait TrusteeSigner: Send + Sync {
+    // The public key share, with its proof, sent as party `party`.
+    fn key_share(&self, party: PartyId) -> Result<KeyShareMessage, SignerError>;
+
+    // A decryption share of the ciphertext. The smudging noise is the signer's to draw.
+    fn decryption_share(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+    ) -> Result<DecryptionShare, SignerError>;
+}
+
+// A signer holding the key share in memory.
+pub struct LocalSigner {
+    key: Party,
+    share: PublicKeyShare,
+    crp: CommonRandomPoly,
+    prover: Box<dyn ShareProof>,
+    rng: Mutex<StdRng>,
+}
+
+impl LocalSigner {
+    // Signs with `key`, generated from `crp` along with the public key share `share`, drawing the
+    // smudging noise from a generator seeded from `rng`.
+    pub fn new<R: RngCore + CryptoRng>(
+        key: Party,
+        share: PublicKeyShare,
+        crp: CommonRandomPoly,
+        rng: &mut R,
+    ) -> Self {
+        LocalSigner {
+            key,
+            share,
+            crp,
+            prover: Box::new(BindingProof),
+            rng: Mutex::new(StdRng::from_seed(rng.gen())),
+        }
+    }
+
+    // Proves the public key share with `prover` instead of `BindingProof`.
+    pub fn with_share_proof(mut self, prover: impl ShareProof + 'static) -> Self {
+        self.prover = Box::new(prover);
+        self
+    }
+
+    pub fn public_key_share(&self) -> &PublicKeyShare {
+        &self.share
+    }
+}
+
+impl TrusteeSigner for LocalSigner {
+    // The signer keeps its share to answer every request for it, so each message gets a copy.
+    fn key_share(&self, party: PartyId) -> Result<KeyShareMessage, SignerError> {
+        Ok(KeyShareMessage::new(
+            party,
+            &self.key,
+            self.share.clone(),
+            &self.crp,
+            self.prover.as_ref(),
+        )?)
+    }
+
+    fn decryption_share(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+    ) -> Result<DecryptionShare, SignerError> {
+        let mut rng = self
+            .rng
+            .lock()
+            .expect("the signer's generator is never poisoned");
+        Ok(self.key.decryption_share(ciphertext, &mut *rng)?)
+    }
+}
+
+// A signer in another process, reached over a Noise channel, see `serve`.
+pub struct RemoteSigner {
+    channel: Mutex<Channel>,
+    params: Arc<BfvParameters>,
+    crp: CommonRandomPoly,
+}
+
+impl RemoteSigner {
+    // Connects to the signer at `address`, holding a key share for these parameters and CRP,
+    // and sets up the channel with the keys.
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        keys: &ChannelKeys,
+        params: &Arc<BfvParameters>,
+        crp: &CommonRandomPoly,
+    ) -> Result<Self, SignerError> {
+        Ok(RemoteSigner {
+            channel: Mutex::new(Channel::initiate(TcpStream::connect(address)?, keys)?),
+            params: params.clone(),
+            crp: crp.clone(),
+        })
+    }
+
+    // Sends a request and waits for the response to it.
+    fn request(&self, kind: u8, body: &[u8]) -> Result<Vec<u8>, SignerError> {
+        let mut channel = self
+            .channel
+            .lock()
+            .expect("the signer's connection is never poisoned");
+        let mut request: Vec<u8> = Vec::with_capacity(1 + body.len());
+        request.push(kind);
+        request.extend_from_slice(body);
+        channel.send(&request)?;
+        let response: Vec<u8> = channel.receive()?.ok_or(SignerError::Malformed)?;
+        match response.split_first() {
+            Some((&OK, body)) => Ok(body.to_vec()),
+            Some((&FAILED, reason)) => Err(SignerError::Refused(
+                String::from_utf8_lossy(reason).into_owned(),
+            )),
+            _ => Err(SignerError::Malformed),
+        }
+    }
+}
+
+impl TrusteeSigner for RemoteSigner {
+    fn key_share(&self, party: PartyId) -> Result<KeyShareMessage, SignerError> {
+        let body: Vec<u8> = self.request(KEY_SHARE, &(party as u64).to_le_bytes())?;
+        let message: KeyShareMessage =
+            wire::decode_key_share(&body, &self.params, self.crp.clone())?;
+        if message.party != party || message.crp_hash != key_ceremony::crp_hash(&self.crp) {
+            return Err(SignerError::Malformed);
+        }
+        Ok(message)
+    }
+
+    fn decryption_share(
+        &self,
+        ciphertext: &Arc<Ciphertext>,
+    ) -> Result<DecryptionShare, SignerError> {
+        let body: Vec<u8> = self.request(DECRYPTION_SHARE, &wire::encode_tally(ciphertext))?;
+        Ok(DecryptionShare::deserialize(
+            &body,
+            &self.params,
+            ciphertext.clone(),
+        )?)
+    }
+}
+
+// Answers the requests of one connection with `signer` until the other end hangs up. A request
+// the signer fails on is answered with the error, and the connection is kept. A connection that
+// doesn't complete the handshake with the keys is dropped before any request is read. A
+// decryption share is only computed for a ciphertext whose tally hash `approve` accepts.
+pub fn serve(
+    stream: TcpStream,
+    keys: &ChannelKeys,
+    params: &Arc<BfvParameters>,
+    signer: &dyn TrusteeSigner,
+    approve: &dyn Fn(&TallyHash) -> bool,
+) -> Result<(), SignerError> {
+    let mut channel: Channel = Channel::respond(stream, keys)?;
+    while let Some(request) = channel.receive()? {
+        let mut response: Vec<u8> = Vec::new();
+        match answer(&request, params, signer, approve) {
+            Ok(body) => {
+                response.push(OK);
+                response.extend_from_slice(&body);
+            }
+            Err(e) => {
+                response.push(FAILED);
+                response.extend_from_slice(e.to_string().as_bytes());
+            }
+        }
+        channel.send(&response)?;
+    }
+    Ok(())
+}
+
+fn answer(
+    request: &[u8],
+    params: &Arc<BfvParameters>,
+    signer: &dyn TrusteeSigner,
+    approve: &dyn Fn(&TallyHash) -> bool,
+) -> Result<Vec<u8>, SignerError> {
+    match request.split_first() {
+        Some((&KEY_SHARE, party)) => {
+            let party: [u8; 8] = party.try_into().map_err(|_| SignerError::Malformed)?;
+            let message: KeyShareMessage = signer.key_share(u64::from_le_bytes(party) as usize)?;
+            Ok(wire::encode_key_share(&message))
+        }
+        Some((&DECRYPTION_SHARE, ciphertext)) => {
+            let ciphertext: Arc<Ciphertext> = Arc::new(wire::decode_tally(ciphertext, params)?);
+            let tally_hash: TallyHash = decryption_ceremony::tally_hash(&ciphertext);
+            if !approve(&tally_hash) {
+                return Err(SignerError::Unapproved(tally_hash));
+            }
+            Ok(signer.decryption_share(&ciphertext)?.to_bytes())
+        }
+        _ => Err(SignerError::Malformed),
+    }
+}
+
+fn write_frame(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)?;
+    writer.flush()
+}
+
+// The next frame, or `None` if the other end hung up between frames. Every frame is a single
+// Noise message, of the handshake or after it, so anything larger is refused before a byte of it
+// is read, and an unauthenticated peer can't have the HSM allocate more than that.
+fn read_frame(reader: &mut impl Read) -> Result<Option<Vec<u8>>, SignerError> {
+    let mut len: [u8; 4] = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len: usize = u32::from_le_bytes(len) as usize;
+    if len > NOISE_MAX_MESSAGE {
+        return Err(SignerError::Malformed);
+    }
+    let mut frame: Vec<u8> = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
@@ -0,0 +1,308 @@
+// Snapshots of an election in a single archive, to hand an election in progress over to another
+// machine or to file away a finished one.
+//
+// A snapshot is a tar archive. Its first entry is `manifest.json`, holding the version of the
+// snapshot format and, for every other entry, its path, size and SHA-256 hash. The other entries
+// are the files of the election as they were on disk, each under the part it belongs to:
+//
+// - `artifacts/`, the published artifacts: parameters, public key, ballots, tally, decryption
+//   shares, result and tally journal, see `artifacts.rs`,
+// - `checkpoint/`, the coordinator's checkpoint of an election in progress, see `checkpoint.rs`,
+// - `election.json`, the state file of the `election` subcommand, see `lifecycle.rs`,
+// - `events.jsonl`, the transparency log, see `transparency.rs`.
+//
+// Any part can be left out. Importing checks every entry against the manifest as it is
+// unpacked, into a directory next to the destination that is only renamed into place once all
+// of them matched, so an archive that was cut short or tampered with leaves nothing behind.
+
+use crate::merkle::Hash;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use tar::{Archive, Builder, EntryType, Header};
+
+pub const VERSION: u32 = 1;
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const ARTIFACTS_DIR: &str = "artifacts";
+pub const CHECKPOINT_DIR: &str = "checkpoint";
+pub const STATE_FILE: &str = "election.json";
+pub const LOG_FILE: &str = "events.jsonl";
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    // The archive was written by another version of the workshop.
+    Version(u32),
+    // The archive doesn't start with a manifest.
+    MissingManifest,
+    // An entry is not in the manifest, or its path is not one a snapshot holds.
+    Unlisted(String),
+    // An entry doesn't match its size or hash in the manifest.
+    Mismatch(String),
+    // An entry of the manifest is not in the archive.
+    Missing(String),
+    // The destination of an import already exists.
+    Exists(PathBuf),
+    // Nothing to export.
+    Empty,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{e}"),
+            SnapshotError::Json(e) => write!(f, "{e}"),
+            SnapshotError::Version(version) => {
+                write!(f, "snapshot version {version} is not supported")
+            }
+            SnapshotError::MissingManifest => write!(f, "the archive has no manifest"),
+            SnapshotError::Unlisted(path) => write!(f, "{path} is not in the manifest"),
+            SnapshotError::Mismatch(path) => write!(f, "{path} doesn't match the manifest"),
+            SnapshotError::Missing(path) => write!(f, "{path} is missing from the archive"),
+            SnapshotError::Exists(path) => write!(f, "{} already exists", path.display()),
+            SnapshotError::Empty => write!(f, "there is nothing to put in the snapshot"),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Json(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    #[serde(with = "hex::serde")]
+    pub sha256: Hash,
+}
+
+// Where the parts of an election are on disk, for `export`.
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    pub artifacts: Option<PathBuf>,
+    pub checkpoint: Option<PathBuf>,
+    pub state: Option<PathBuf>,
+    pub log: Option<PathBuf>,
+}
+
+impl Sources {
+    // Every file to archive, with its path in the archive, in the order they are archived.
+    fn files(&self) -> Result<Vec<(String, PathBuf)>, SnapshotError> {
+        let mut files: Vec<(String, PathBuf)> = Vec::new();
+        for (name, dir) in [
+            (ARTIFACTS_DIR, &self.artifacts),
+            (CHECKPOINT_DIR, &self.checkpoint),
+        ] {
+            let Some(dir) = dir else { continue };
+            let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<_, io::Error>>()?;
+            paths.sort();
+            for path in paths.into_iter().filter(|path| path.is_file()) {
+                let file_name: String = path
+                    .file_name()
+                    .expect("a directory entry has a name")
+                    .to_string_lossy()
+                    .into_owned();
+                files.push((format!("{name}/{file_name}"), path));
+            }
+        }
+        for (name, file) in [(STATE_FILE, &self.state), (LOG_FILE, &self.log)] {
+            let Some(file) = file else { continue };
+            files.push((name.to_string(), file.clone()));
+        }
+        Ok(files)
+    }
+}
+
+// Writes the parts of the election to a snapshot archive and returns its manifest.
+pub fn export(sources: &Sources, output: &Path) -> Result<Manifest, SnapshotError> {
+    let files: Vec<(String, PathBuf)> = sources.files()?;
+    if files.is_empty() {
+        return Err(SnapshotError::Empty);
+    }
+    let manifest: Manifest = Manifest {
+        version: VERSION,
+        entries: files
+            .iter()
+            .map(|(name, path)| {
+                let mut hasher: HashWriter<io::Sink> = HashWriter::new(io::sink());
+                io::copy(&mut File::open(path)?, &mut hasher)?;
+                let (size, sha256) = hasher.finish();
+                Ok(ManifestEntry {
+                    path: name.clone(),
+                    size,
+                    sha256,
+                })
+            })
+            .collect::<Result<_, SnapshotError>>()?,
+    };
+
+    let mut builder: Builder<BufWriter<File>> = Builder::new(BufWriter::new(File::create(output)?));
+    let manifest_bytes: Vec<u8> = serde_json::to_vec_pretty(&manifest)?;
+    builder.append_data(
+        &mut header(manifest_bytes.len() as u64),
+        MANIFEST_FILE,
+        manifest_bytes.as_slice(),
+    )?;
+    for ((name, path), entry) in files.iter().zip(&manifest.entries) {
+        // The size is the one that was hashed. A file appended to since, e.g. the checkpoint of
+        // a coordinator still running, is cut back to it.
+        let file: File = File::open(path)?;
+        builder.append_data(&mut header(entry.size), name, file.take(entry.size))?;
+    }
+    builder.into_inner()?.flush()?;
+    Ok(manifest)
+}
+
+// Unpacks a snapshot archive into a new directory, checking every entry against the manifest,
+// and returns the manifest.
+pub fn import(archive: &Path, into: &Path) -> Result<Manifest, SnapshotError> {
+    if into.exists() {
+        return Err(SnapshotError::Exists(into.to_path_buf()));
+    }
+    let partial: PathBuf = into.with_extension("partial");
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    let unpacked: Result<Manifest, SnapshotError> = unpack(archive, &partial);
+    match unpacked {
+        Ok(manifest) => {
+            fs::rename(&partial, into)?;
+            Ok(manifest)
+        }
+        Err(e) => {
+            let _ = fs::remove_dir_all(&partial);
+            Err(e)
+        }
+    }
+}
+
+fn unpack(archive: &Path, into: &Path) -> Result<Manifest, SnapshotError> {
+    fs::create_dir_all(into.join(ARTIFACTS_DIR))?;
+    fs::create_dir_all(into.join(CHECKPOINT_DIR))?;
+    let mut archive: Archive<File> = Archive::new(File::open(archive)?);
+    let mut entries = archive.entries()?;
+
+    let mut first = entries.next().ok_or(SnapshotError::MissingManifest)??;
+    if first.path()?.as_ref() != Path::new(MANIFEST_FILE) {
+        return Err(SnapshotError::MissingManifest);
+    }
+    let mut manifest_bytes: Vec<u8> = Vec::new();
+    first.read_to_end(&mut manifest_bytes)?;
+    let manifest: Manifest = serde_json::from_slice(&manifest_bytes)?;
+    if manifest.version != VERSION {
+        return Err(SnapshotError::Version(manifest.version));
+    }
+
+    let mut unpacked: Vec<bool> = vec![false; manifest.entries.len()];
+    for entry in entries {
+        let mut entry = entry?;
+        let name: String = entry.path()?.to_string_lossy().into_owned();
+        let position: usize = manifest
+            .entries
+            .iter()
+            .position(|listed| listed.path == name)
+            .filter(|&position| !unpacked[position])
+            .filter(|_| entry.header().entry_type() == EntryType::Regular && is_part(&name))
+            .ok_or_else(|| SnapshotError::Unlisted(name.clone()))?;
+        let mut writer: HashWriter<BufWriter<File>> =
+            HashWriter::new(BufWriter::new(File::create(into.join(&name))?));
+        io::copy(&mut entry, &mut writer)?;
+        writer.inner.flush()?;
+        let listed: &ManifestEntry = &manifest.entries[position];
+        if writer.finish() != (listed.size, listed.sha256) {
+            return Err(SnapshotError::Mismatch(name));
+        }
+        unpacked[position] = true;
+    }
+    if let Some(position) = unpacked.iter().position(|&unpacked| !unpacked) {
+        return Err(SnapshotError::Missing(
+            manifest.entries[position].path.clone(),
+        ));
+    }
+    Ok(manifest)
+}
+
+// Whether the path is one a snapshot holds: a file directly in one of the two directories, or
+// one of the two files at the top.
+fn is_part(name: &str) -> bool {
+    let components: Vec<Component<'_>> = Path::new(name).components().collect();
+    match components.as_slice() {
+        [Component::Normal(file)] => *file == STATE_FILE || *file == LOG_FILE,
+        [Component::Normal(dir), Component::Normal(_)] => {
+            *dir == ARTIFACTS_DIR || *dir == CHECKPOINT_DIR
+        }
+        _ => false,
+    }
+}
+
+// A header for a plain file of the given size. The time and owner are left out, so the same
+// election always makes the same archive.
+fn header(size: u64) -> Header {
+    let mut header: Header = Header::new_gnu();
+    header.set_entry_type(EntryType::Regular);
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header.set_cksum();
+    header
+}
+
+// Counts and hashes what goes through it.
+struct HashWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W> HashWriter<W> {
+    fn new(inner: W) -> Self {
+        HashWriter {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    fn finish(self) -> (u64, Hash) {
+        (self.size, self.hasher.finalize().into())
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let written: usize = self.inner.write(bytes)?;
+        self.hasher.update(&bytes[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,127 @@
+// Per-item statistics of a phase: latency percentiles, throughput and bytes processed.
+//
+// The time a whole phase took hides how much its items vary, which is what matters when sizing
+// a deployment: the p99 latency of encrypting a ballot says more about how many voters can be
+// served at once than the average does. Items can be timed from any thread.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::AddAssign,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// The latencies of the items of a phase, recorded as they are processed.
+#[derive(Debug, Default)]
+pub struct Latencies(Mutex<Vec<Duration>>);
+
+impl Latencies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Runs `f` as one item, recording how long it took.
+    pub fn time<T>(&self, f: impl FnOnce() -> T) -> T {
+        let start: Instant = Instant::now();
+        let result: T = f();
+        self.record(start.elapsed());
+        result
+    }
+
+    pub fn record(&self, latency: Duration) {
+        self.0.lock().unwrap().push(latency);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The statistics of the items recorded, processed in `elapsed` and `bytes` long between them.
+    pub fn stats(&self, elapsed: Duration, bytes: u64) -> ItemStats {
+        let mut latencies: Vec<Duration> = self.0.lock().unwrap().clone();
+        latencies.sort_unstable();
+        ItemStats {
+            latency: LatencySummary::new(&latencies),
+            ..ItemStats::new(latencies.len(), elapsed, bytes)
+        }
+    }
+}
+
+// The nearest-rank percentile `p` (0 to 100) of latencies sorted in increasing order.
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank: usize = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub p50_us: f64,
+    pub p95_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+impl LatencySummary {
+    // Summarizes latencies sorted in increasing order, if there are any.
+    pub fn new(sorted: &[Duration]) -> Option<Self> {
+        let max: Duration = *sorted.last()?;
+        let micros = |latency: Duration| latency.as_secs_f64() * 1e6;
+        Some(LatencySummary {
+            p50_us: micros(percentile(sorted, 50.0)),
+            p95_us: micros(percentile(sorted, 95.0)),
+            p99_us: micros(percentile(sorted, 99.0)),
+            max_us: micros(max),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemStats {
+    pub count: usize,
+    pub per_sec: f64,
+    pub bytes: u64,
+    // Missing for phases whose items aren't timed one at a time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencySummary>,
+}
+
+impl ItemStats {
+    // The throughput of `count` items processed in `elapsed`, without their latencies.
+    pub fn new(count: usize, elapsed: Duration, bytes: u64) -> Self {
+        ItemStats {
+            count,
+            per_sec: count as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+            bytes,
+            latency: None,
+        }
+    }
+}
+
+// How the time of a phase streaming its items out of storage splits between loading them
+// (reading and deserializing) and computing on them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct IoSplit {
+    pub io_ms: f64,
+    pub compute_ms: f64,
+}
+
+impl IoSplit {
+    pub fn add_io(&mut self, elapsed: Duration) {
+        self.io_ms += elapsed.as_secs_f64() * 1000.0;
+    }
+
+    pub fn add_compute(&mut self, elapsed: Duration) {
+        self.compute_ms += elapsed.as_secs_f64() * 1000.0;
+    }
+}
+
+impl AddAssign for IoSplit {
+    fn add_assign(&mut self, other: IoSplit) {
+        self.io_ms += other.io_ms;
+        self.compute_ms += other.compute_ms;
+    }
+}
@@ -0,0 +1,445 @@
+// Where the ciphertexts to be tallied are kept.
+//
+// The tally only ever walks the ciphertexts once, front to back, so they don't have to be in
+// memory. A `MemoryStore` keeps them in a `Vec`, a `FileStore` appends them to segment files
+// in a directory and reads them back one at a time, so the tally takes the memory of a single
+// ciphertext however many there are. A `FileStore` can be reopened, so one process can fill it
+// and another tally it.
+//
+// A segment file is a run of records, each the length of the serialized ciphertext as a
+// little-endian u64 followed by the ciphertext. A segment holds `SEGMENT_RECORDS` records, after
+// which the next one is started. Records are only ever appended, a store has no way to change
+// or remove one. A `StoreReader` reads them back by position, e.g. the ballots a ballot box
+// keeps in stores, see `BallotBox::with_store`.
+//
+// With the `mmap` feature a `FileStore` can map its segments into memory instead of reading
+// them, see `FileStore::with_mmap`. Each ciphertext is then deserialized straight out of the
+// mapping when the tally reaches it, without a copy of its bytes on the heap, and the pages
+// the kernel brought in can be dropped again under memory pressure.
+
+use fhe::bfv::{BfvParameters, Ciphertext};
+use fhe_traits::{DeserializeParametrized, Serialize};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub const SEGMENT_RECORDS: usize = 1024;
+
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    Fhe(fhe::Error),
+    // The directory already holds segments, and `FileStore::create` won't add to them.
+    NotEmpty(PathBuf),
+    // A segment ends in the middle of a record, e.g. the process died while appending it.
+    Truncated(PathBuf),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "{e}"),
+            StoreError::Fhe(e) => write!(f, "{e}"),
+            StoreError::NotEmpty(dir) => {
+                write!(f, "{} already holds a ciphertext store", dir.display())
+            }
+            StoreError::Truncated(segment) => {
+                write!(f, "{} ends in the middle of a record", segment.display())
+            }
+        }
+    }
+}
+
+impl Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<fhe::Error> for StoreError {
+    fn from(e: fhe::Error) -> Self {
+        StoreError::Fhe(e)
+    }
+}
+
+pub trait CiphertextStore {
+    // Adds the ciphertext at the end of the store and returns its position.
+    fn append(&mut self, ciphertext: &Ciphertext) -> Result<usize, StoreError>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The ciphertexts in the order they were appended. A `MemoryStore` lends them, a
+    // `FileStore` reads each one from disk as it's reached.
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Ciphertext>, StoreError>> + '_>;
+
+    // The same from the ciphertext at position `start` on, e.g. to resume a tally.
+    fn iter_from(
+        &self,
+        start: usize,
+    ) -> Box<dyn Iterator<Item = Result<Cow<'_, Ciphertext>, StoreError>> + '_> {
+        Box::new(self.iter().skip(start))
+    }
+}
+
+// Reads the ciphertexts of a store by position. Reading the one after the last read goes on
+// where that left off, so a store read mostly in order is only walked once, anything else starts
+// over at the position, see `CiphertextStore::iter_from`.
+pub struct StoreReader<'a> {
+    store: &'a dyn CiphertextStore,
+    position: usize,
+    ciphertexts: Option<Box<dyn Iterator<Item = Result<Cow<'a, Ciphertext>, StoreError>> + 'a>>,
+}
+
+impl<'a> StoreReader<'a> {
+    pub fn new(store: &'a dyn CiphertextStore) -> Self {
+        StoreReader {
+            store,
+            position: 0,
+            ciphertexts: None,
+        }
+    }
+
+    // The ciphertext appended at `position`, which must be in the store.
+    pub fn read(&mut self, position: usize) -> Result<Ciphertext, StoreError> {
+        if self.ciphertexts.is_none() || self.position != position {
+            self.ciphertexts = Some(self.store.iter_from(position));
+        }
+        self.position = position + 1;
+        let ciphertext: Cow<'a, Ciphertext> = self
+            .ciphertexts
+            .as_mut()
+            .and_then(Iterator::next)
+            .expect("the position is in the store")?;
+        Ok(ciphertext.into_owned())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    ciphertexts: Vec<Ciphertext>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+}
+
+impl CiphertextStore for MemoryStore {
+    fn append(&mut self, ciphertext: &Ciphertext) -> Result<usize, StoreError> {
+        self.ciphertexts.push(ciphertext.clone());
+        Ok(self.ciphertexts.len() - 1)
+    }
+
+    fn len(&self) -> usize {
+        self.ciphertexts.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Ciphertext>, StoreError>> + '_> {
+        Box::new(self.ciphertexts.iter().map(|ct| Ok(Cow::Borrowed(ct))))
+    }
+}
+
+pub struct FileStore {
+    dir: PathBuf,
+    params: Arc<BfvParameters>,
+    len: usize,
+    // The segment being appended to, opened on the first append.
+    segment: Option<File>,
+    mmap: bool,
+}
+
+impl FileStore {
+    // Starts an empty store in the directory, creating it if needed.
+    pub fn create(
+        dir: impl Into<PathBuf>,
+        params: &Arc<BfvParameters>,
+    ) -> Result<Self, StoreError> {
+        let dir: PathBuf = dir.into();
+        fs::create_dir_all(&dir)?;
+        if segment_path(&dir, 0).exists() {
+            return Err(StoreError::NotEmpty(dir));
+        }
+        Ok(FileStore {
+            dir,
+            params: params.clone(),
+            len: 0,
+            segment: None,
+            mmap: false,
+        })
+    }
+
+    // Reopens a store, counting its records. Appends go on at the end.
+    pub fn open(dir: impl Into<PathBuf>, params: &Arc<BfvParameters>) -> Result<Self, StoreError> {
+        let dir: PathBuf = dir.into();
+        fs::metadata(&dir)?;
+        let mut len: usize = 0;
+        let mut segment: Option<File> = None;
+        for index in 0.. {
+            let path: PathBuf = segment_path(&dir, index);
+            if !path.exists() {
+                break;
+            }
+            let records: usize = count_records(&path)?;
+            len += records;
+            segment = if records < SEGMENT_RECORDS {
+                Some(OpenOptions::new().append(true).open(&path)?)
+            } else {
+                None
+            };
+        }
+        Ok(FileStore {
+            dir,
+            params: params.clone(),
+            len,
+            segment,
+            mmap: false,
+        })
+    }
+
+    // Reads the segments through a memory mapping rather than a buffered reader.
+    #[cfg(feature = "mmap")]
+    pub fn with_mmap(mut self) -> Self {
+        self.mmap = true;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl CiphertextStore for FileStore {
+    fn append(&mut self, ciphertext: &Ciphertext) -> Result<usize, StoreError> {
+        if self.len % SEGMENT_RECORDS == 0 {
+            let path: PathBuf = segment_path(&self.dir, self.len / SEGMENT_RECORDS);
+            self.segment = Some(
+                OpenOptions::new()
+                    .append(true)
+                    .create_new(true)
+                    .open(path)?,
+            );
+        }
+        let bytes: Vec<u8> = ciphertext.to_bytes();
+        // The length and the ciphertext go out in a single write.
+        let mut record: Vec<u8> = Vec::with_capacity(8 + bytes.len());
+        record.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        record.extend_from_slice(&bytes);
+        self.segment
+            .as_mut()
+            .expect("a segment is open after the first append")
+            .write_all(&record)?;
+        self.len += 1;
+        Ok(self.len - 1)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Ciphertext>, StoreError>> + '_> {
+        self.iter_from(0)
+    }
+
+    // Skips the segments before `start` without opening them, and the records before it in its
+    // segment without deserializing them.
+    fn iter_from(
+        &self,
+        start: usize,
+    ) -> Box<dyn Iterator<Item = Result<Cow<'_, Ciphertext>, StoreError>> + '_> {
+        Box::new(FileStoreIter {
+            store: self,
+            position: start,
+            reader: None,
+        })
+    }
+}
+
+struct FileStoreIter<'a> {
+    store: &'a FileStore,
+    position: usize,
+    reader: Option<(PathBuf, Segment)>,
+}
+
+// The segment being read, and how far into it.
+enum Segment {
+    Buffered(BufReader<File>),
+    #[cfg(feature = "mmap")]
+    Mapped {
+        map: Mmap,
+        offset: usize,
+    },
+}
+
+impl Segment {
+    fn open(path: &Path, mmap: bool) -> Result<Self, StoreError> {
+        let file: File = File::open(path)?;
+        match mmap {
+            #[cfg(feature = "mmap")]
+            true => {
+                // SAFETY: the mapping is only read, and records are never changed once appended.
+                // Appending to the segment while it is mapped only adds bytes past the end of
+                // the mapping.
+                let map: Mmap = unsafe { Mmap::map(&file)? };
+                Ok(Segment::Mapped { map, offset: 0 })
+            }
+            _ => Ok(Segment::Buffered(BufReader::new(file))),
+        }
+    }
+
+    // Moves past the next record without reading it into a ciphertext.
+    fn skip_record(&mut self, path: &Path) -> Result<(), StoreError> {
+        match self {
+            Segment::Buffered(reader) => {
+                read_record(reader, path)?
+                    .ok_or_else(|| StoreError::Truncated(path.to_path_buf()))?;
+            }
+            #[cfg(feature = "mmap")]
+            Segment::Mapped { map, offset } => {
+                let truncated = || StoreError::Truncated(path.to_path_buf());
+                let len: [u8; 8] = map
+                    .get(*offset..*offset + 8)
+                    .ok_or_else(truncated)?
+                    .try_into()
+                    .expect("the slice is 8 bytes long");
+                *offset = usize::try_from(u64::from_le_bytes(len))
+                    .ok()
+                    .and_then(|len| (*offset + 8).checked_add(len))
+                    .filter(|&end| end <= map.len())
+                    .ok_or_else(truncated)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_ciphertext(
+        &mut self,
+        path: &Path,
+        params: &Arc<BfvParameters>,
+    ) -> Result<Ciphertext, StoreError> {
+        match self {
+            Segment::Buffered(reader) => {
+                let bytes: Vec<u8> = read_record(reader, path)?
+                    .ok_or_else(|| StoreError::Truncated(path.to_path_buf()))?;
+                Ok(Ciphertext::from_bytes(&bytes, params)?)
+            }
+            #[cfg(feature = "mmap")]
+            Segment::Mapped { map, offset } => {
+                let truncated = || StoreError::Truncated(path.to_path_buf());
+                let len: [u8; 8] = map
+                    .get(*offset..*offset + 8)
+                    .ok_or_else(truncated)?
+                    .try_into()
+                    .expect("the slice is 8 bytes long");
+                let start: usize = *offset + 8;
+                let end: usize = usize::try_from(u64::from_le_bytes(len))
+                    .ok()
+                    .and_then(|len| start.checked_add(len))
+                    .ok_or_else(truncated)?;
+                let bytes: &[u8] = map.get(start..end).ok_or_else(truncated)?;
+                *offset = end;
+                Ok(Ciphertext::from_bytes(bytes, params)?)
+            }
+        }
+    }
+}
+
+impl FileStoreIter<'_> {
+    fn read_next(&mut self) -> Result<Ciphertext, StoreError> {
+        // A segment is opened at its first record, or wherever the iteration starts in it.
+        if self.position % SEGMENT_RECORDS == 0 || self.reader.is_none() {
+            let path: PathBuf = segment_path(&self.store.dir, self.position / SEGMENT_RECORDS);
+            let mut segment: Segment = Segment::open(&path, self.store.mmap)?;
+            for _ in 0..self.position % SEGMENT_RECORDS {
+                segment.skip_record(&path)?;
+            }
+            self.reader = Some((path, segment));
+        }
+        let (path, segment) = self
+            .reader
+            .as_mut()
+            .expect("a segment is open once the first record is read");
+        segment.read_ciphertext(path, &self.store.params)
+    }
+}
+
+impl<'a> Iterator for FileStoreIter<'a> {
+    type Item = Result<Cow<'a, Ciphertext>, StoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.store.len {
+            return None;
+        }
+        let ciphertext: Result<Ciphertext, StoreError> = self.read_next();
+        // A broken record ends the iteration, nothing after it can be trusted to line up.
+        self.position = if ciphertext.is_ok() {
+            self.position + 1
+        } else {
+            self.store.len
+        };
+        Some(ciphertext.map(Cow::Owned))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining: usize = self.store.len.saturating_sub(self.position);
+        (remaining, Some(remaining))
+    }
+}
+
+fn segment_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("segment-{index:06}.bin"))
+}
+
+// Reads the next record, or `None` at the end of the segment.
+fn read_record(reader: &mut impl Read, path: &Path) -> Result<Option<Vec<u8>>, StoreError> {
+    let mut len: [u8; 8] = [0; 8];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len: u64 = u64::from_le_bytes(len);
+    let mut bytes: Vec<u8> = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(StoreError::Truncated(path.to_path_buf()));
+    }
+    Ok(Some(bytes))
+}
+
+// The number of whole records in a segment, skipping over the ciphertexts.
+fn count_records(path: &Path) -> Result<usize, StoreError> {
+    let segment_len: u64 = fs::metadata(path)?.len();
+    let mut reader: BufReader<File> = BufReader::new(File::open(path)?);
+    let mut offset: u64 = 0;
+    let mut records: usize = 0;
+    while offset < segment_len {
+        let mut len: [u8; 8] = [0; 8];
+        reader
+            .read_exact(&mut len)
+            .map_err(|_| StoreError::Truncated(path.to_path_buf()))?;
+        let len: u64 = u64::from_le_bytes(len);
+        offset = offset
+            .checked_add(8)
+            .and_then(|offset| offset.checked_add(len))
+            .filter(|&end| end <= segment_len)
+            .ok_or_else(|| StoreError::Truncated(path.to_path_buf()))?;
+        reader.seek_relative(len as i64)?;
+        records += 1;
+    }
+    Ok(records)
+}
@@ -0,0 +1,131 @@
+// Summing encrypted ballots and decrypting the result.
+
+use crate::{
+    stats::IoSplit,
+    store::{CiphertextStore, StoreError},
+};
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, PublicKey},
+    mbfv::{AggregateIter, DecryptionShare},
+};
+use fhe_traits::{FheDecoder, FheEncrypter};
+use rand::{CryptoRng, RngCore};
+use std::{borrow::Cow, sync::Arc, time::Instant};
+
+// Adds up the ciphertexts. The sum decrypts to the sum of the plaintexts.
+pub fn sum<'a>(
+    ciphertexts: impl IntoIterator<Item = &'a Ciphertext>,
+    params: &Arc<BfvParameters>,
+) -> Ciphertext {
+    let mut sum: Ciphertext = Ciphertext::zero(params);
+    for ct in ciphertexts {
+        sum += ct;
+    }
+    sum
+}
+
+// The same, reading the ciphertexts from a store one at a time instead of holding them all,
+// along with how long went on loading them and on adding them up.
+pub fn sum_store(
+    store: &dyn CiphertextStore,
+    params: &Arc<BfvParameters>,
+) -> Result<(Ciphertext, IoSplit), StoreError> {
+    let mut sum: Ciphertext = Ciphertext::zero(params);
+    let mut split: IoSplit = IoSplit::default();
+    let mut ciphertexts = store.iter();
+    loop {
+        let start: Instant = Instant::now();
+        let Some(ct) = ciphertexts.next() else {
+            break;
+        };
+        let ct: Cow<'_, Ciphertext> = ct?;
+        split.add_io(start.elapsed());
+        let start: Instant = Instant::now();
+        sum += &*ct;
+        split.add_compute(start.elapsed());
+    }
+    Ok((sum, split))
+}
+
+// The same, `chunk_size` ciphertexts at a time, so a tally interrupted half way doesn't have to
+// start over. The sum of each chunk is handed to `persist` with its index as soon as it is done,
+// and once every chunk is summed, the chunk sums are added up in a final reduction.
+//
+// `done` are the sums of the first chunks, persisted by an earlier run over the same store. The
+// ciphertexts of those chunks are skipped, and only the rest are read and added.
+pub fn sum_store_chunked<E: From<StoreError>>(
+    store: &dyn CiphertextStore,
+    params: &Arc<BfvParameters>,
+    chunk_size: usize,
+    done: Vec<Ciphertext>,
+    mut persist: impl FnMut(usize, &Ciphertext) -> Result<(), E>,
+) -> Result<(Ciphertext, IoSplit), E> {
+    let chunk_size: usize = chunk_size.max(1);
+    let num_chunks: usize = store.len().div_ceil(chunk_size);
+    let mut chunks: Vec<Ciphertext> = done;
+    chunks.truncate(num_chunks);
+    let mut split: IoSplit = IoSplit::default();
+    let mut ciphertexts = store.iter_from(chunks.len() * chunk_size);
+    while chunks.len() < num_chunks {
+        let mut sum: Ciphertext = Ciphertext::zero(params);
+        for _ in 0..chunk_size {
+            let start: Instant = Instant::now();
+            let Some(ct) = ciphertexts.next() else {
+                break;
+            };
+            let ct: Cow<'_, Ciphertext> = ct?;
+            split.add_io(start.elapsed());
+            let start: Instant = Instant::now();
+            sum += &*ct;
+            split.add_compute(start.elapsed());
+        }
+        persist(chunks.len(), &sum)?;
+        chunks.push(sum);
+    }
+    let start: Instant = Instant::now();
+    let total: Ciphertext = sum(&chunks, params);
+    split.add_compute(start.elapsed());
+    Ok((total, split))
+}
+
+// Adds a fresh encryption of zero to the tally. It decrypts to the same result, but is no
+// longer the exact sum of the ballots, so the published tally can't be linked back to the
+// arithmetic that produced it.
+pub fn rerandomize<R: RngCore + CryptoRng>(
+    tally: &Ciphertext,
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let zero: Plaintext = Plaintext::zero(Encoding::poly(), params)?;
+    let mut tally: Ciphertext = tally.clone();
+    tally += &pk.try_encrypt(&zero, rng)?;
+    Ok(tally)
+}
+
+// Aggregates the decryption shares of every party and decodes the first `len` slots.
+pub fn decrypt(
+    shares: impl IntoIterator<Item = DecryptionShare>,
+    len: usize,
+) -> Result<Vec<u64>, fhe::Error> {
+    let mut tally: Vec<u64> = decrypt_slots(shares)?;
+    tally.truncate(len);
+    Ok(tally)
+}
+
+// Aggregates the decryption shares of every party and decodes every slot.
+pub fn decrypt_slots(
+    shares: impl IntoIterator<Item = DecryptionShare>,
+) -> Result<Vec<u64>, fhe::Error> {
+    decrypt_encoded(shares, Encoding::poly())
+}
+
+// The same, for a plaintext encoded with `encoding`. Ballots are encoded as polynomials, but a
+// ciphertext multiplied slot by slot, e.g. in the auction example, needs `Encoding::simd()`.
+pub fn decrypt_encoded(
+    shares: impl IntoIterator<Item = DecryptionShare>,
+    encoding: Encoding,
+) -> Result<Vec<u64>, fhe::Error> {
+    let pt: Plaintext = shares.into_iter().aggregate()?;
+    Vec::<u64>::try_decode(&pt, encoding)
+}
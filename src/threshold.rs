@@ -0,0 +1,169 @@
+// Tolerating missing trustees: t-of-n decryption on top of the additive (n-of-n) key shares.
+//
+// The shares of `party` add up to the secret key, so every one of them is needed to decrypt.
+// To let any `t` of `n` trustees decrypt, we use replicated secret sharing: the secret key is
+// split into one additive share per group of `t - 1` trustees, and that share (a "slot") is
+// handed to every trustee *outside* the group. Any `t - 1` trustees are missing the slot of
+// their own group, so together they learn nothing, while any `t` trustees hold every slot
+// between them and can produce a decryption share for each.
+//
+// Each slot is a regular party of the key and decryption ceremonies, so nothing else changes.
+// The catch is the number of slots, `C(n, t - 1)`, which explodes quickly: this is for the
+// handful of trustees of a workshop, not for thousands. With `t = n` there is exactly one slot
+// per trustee, which is the plain additive scheme.
+
+use crate::key_ceremony::PartyId;
+use std::{collections::BTreeSet, error::Error, fmt};
+
+// More slots than this and the key ceremony alone would take forever.
+pub const MAX_SLOTS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThresholdError {
+    InvalidThreshold {
+        threshold: usize,
+        trustees: usize,
+    },
+    TooManySlots {
+        trustees: usize,
+        threshold: usize,
+    },
+    Quorum {
+        present: usize,
+        threshold: usize,
+        trustees: usize,
+    },
+}
+
+impl fmt::Display for ThresholdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThresholdError::InvalidThreshold {
+                threshold,
+                trustees,
+            } => {
+                write!(
+                    f,
+                    "threshold must be between 1 and {trustees}, got {threshold}"
+                )
+            }
+            ThresholdError::TooManySlots {
+                trustees,
+                threshold,
+            } => write!(
+                f,
+                "a {threshold}-of-{trustees} threshold needs more than {MAX_SLOTS} key slots, \
+                 use fewer trustees or a threshold closer to the number of trustees"
+            ),
+            ThresholdError::Quorum {
+                present,
+                threshold,
+                trustees,
+            } => write!(
+                f,
+                "quorum not reached: only {present} of {trustees} trustees submitted decryption \
+                 shares and {threshold} are needed"
+            ),
+        }
+    }
+}
+
+impl Error for ThresholdError {}
+
+pub struct TrusteeSet {
+    num_trustees: usize,
+    threshold: usize,
+    // The trustees holding each slot.
+    holders: Vec<Vec<PartyId>>,
+}
+
+impl TrusteeSet {
+    pub fn new(num_trustees: usize, threshold: usize) -> Result<Self, ThresholdError> {
+        if threshold == 0 || threshold > num_trustees {
+            return Err(ThresholdError::InvalidThreshold {
+                threshold,
+                trustees: num_trustees,
+            });
+        }
+        if binomial(num_trustees, threshold - 1) > MAX_SLOTS {
+            return Err(ThresholdError::TooManySlots {
+                trustees: num_trustees,
+                threshold,
+            });
+        }
+        let holders: Vec<Vec<PartyId>> = combinations(num_trustees, threshold - 1)
+            .into_iter()
+            .map(|group| complement(&group, num_trustees))
+            .collect();
+        Ok(TrusteeSet {
+            num_trustees,
+            threshold,
+            holders,
+        })
+    }
+
+    pub fn num_trustees(&self) -> usize {
+        self.num_trustees
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn num_slots(&self) -> usize {
+        self.holders.len()
+    }
+
+    pub fn holders(&self, slot: usize) -> &[PartyId] {
+        &self.holders[slot]
+    }
+
+    // The slots held by a trustee.
+    pub fn slots_of(&self, trustee: PartyId) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_slots()).filter(move |&slot| self.holders[slot].contains(&trustee))
+    }
+
+    // Picks, for every slot, one of the present trustees holding it to produce its decryption
+    // share. Fails with a quorum error if fewer than `threshold` trustees are present.
+    pub fn assign(&self, present: &BTreeSet<PartyId>) -> Result<Vec<PartyId>, ThresholdError> {
+        self.holders
+            .iter()
+            .map(|holders| holders.iter().copied().find(|h| present.contains(h)))
+            .collect::<Option<Vec<PartyId>>>()
+            .ok_or(ThresholdError::Quorum {
+                present: present.len(),
+                threshold: self.threshold,
+                trustees: self.num_trustees,
+            })
+    }
+}
+
+// `C(n, k)`, saturating rather than overflowing.
+fn binomial(n: usize, k: usize) -> usize {
+    let k: usize = k.min(n - k);
+    (0..k).fold(1usize, |acc, i| acc.saturating_mul(n - i) / (i + 1))
+}
+
+// Every subset of `k` elements of `0..n`, in lexicographic order.
+pub(crate) fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut result: Vec<Vec<usize>> = Vec::new();
+    let mut current: Vec<usize> = (0..k).collect();
+    loop {
+        result.push(current.clone());
+        let Some(i) = (0..k).rev().find(|&i| current[i] != i + n - k) else {
+            return result;
+        };
+        let start: usize = current[i] + 1;
+        for (offset, element) in current[i..].iter_mut().enumerate() {
+            *element = start + offset;
+        }
+    }
+}
+
+// The elements of `0..n` missing from the sorted `subset`.
+fn complement(subset: &[usize], n: usize) -> Vec<usize> {
+    let mut rest = subset.iter().peekable();
+    (0..n)
+        .filter(|element| rest.next_if_eq(&element).is_none())
+        .collect()
+}
@@ -0,0 +1,149 @@
+// Tracking codes: a short receipt for a ballot, to check it was recorded as cast.
+//
+// The ballot box hands a voter a tracking code for every ballot it accepts, see
+// `BallotBox::submit`. The code is the start of the ballot's leaf in the ballots Merkle tree,
+// see `merkle`, written in Crockford's base32 so it can be read out or copied by hand:
+// `7K2M-Q9XD-4RTB-0WZE`. It says nothing about the vote, as the leaf is the hash of the
+// ciphertext.
+//
+// Once voting closes, the codes of the counted ballots are published with the artifacts, and a
+// voter looks theirs up with the `track` subcommand, which finds the ballot and checks its
+// inclusion proof against the ballots root. A code that isn't there means the ballot wasn't
+// counted, e.g. because the voter cast another one after it.
+//
+// Ten bytes of the hash are kept, 80 bits, so two ballots are only likely to share a code among
+// around 2^40 of them.
+
+use crate::{ballot::Ballot, merkle};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{error::Error, fmt, str::FromStr};
+
+const CODE_BYTES: usize = 10;
+// Crockford's base32: no I, L, O or U, which are easily mistaken for 1, 0 or V.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const GROUP: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackingError {
+    // A character outside the alphabet.
+    InvalidCharacter(char),
+    // The code has this many characters rather than 16, leaving out dashes.
+    Length(usize),
+}
+
+impl fmt::Display for TrackingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackingError::InvalidCharacter(c) => {
+                write!(f, "{c:?} is not a tracking code character")
+            }
+            TrackingError::Length(len) => {
+                write!(
+                    f,
+                    "a tracking code has {} characters, not {len}",
+                    CODE_BYTES * 8 / 5
+                )
+            }
+        }
+    }
+}
+
+impl Error for TrackingError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TrackingCode([u8; CODE_BYTES]);
+
+impl TrackingCode {
+    pub fn of(ballot: &Ballot) -> Self {
+        let leaf: merkle::Hash = ballot.ciphertext_hash();
+        let mut code: [u8; CODE_BYTES] = [0; CODE_BYTES];
+        code.copy_from_slice(&leaf[..CODE_BYTES]);
+        TrackingCode(code)
+    }
+}
+
+// The codes of the counted ballots, as published, sorted so their order says nothing about
+// when each ballot was cast.
+pub fn published_codes(ballots: &[Ballot]) -> Vec<TrackingCode> {
+    let mut codes: Vec<TrackingCode> = ballots.iter().map(TrackingCode::of).collect();
+    codes.sort();
+    codes
+}
+
+impl fmt::Display for TrackingCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Five bits at a time, from the most significant.
+        let mut chars: Vec<char> = Vec::new();
+        let mut buffer: u16 = 0;
+        let mut bits: u32 = 0;
+        for byte in self.0 {
+            buffer = (buffer << 8) | byte as u16;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                chars.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+            }
+        }
+        let groups: Vec<String> = chars
+            .chunks(GROUP)
+            .map(|group| group.iter().collect())
+            .collect();
+        write!(f, "{}", groups.join("-"))
+    }
+}
+
+// Reads a code back, ignoring dashes and case, and taking O for 0 and I or L for 1 the way
+// Crockford's base32 does.
+impl FromStr for TrackingCode {
+    type Err = TrackingError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let values: Vec<u8> = code
+            .chars()
+            .filter(|&c| c != '-')
+            .map(|c| {
+                let c: char = match c.to_ascii_uppercase() {
+                    'O' => '0',
+                    'I' | 'L' => '1',
+                    c => c,
+                };
+                ALPHABET
+                    .iter()
+                    .position(|&letter| letter as char == c)
+                    .map(|value| value as u8)
+                    .ok_or(TrackingError::InvalidCharacter(c))
+            })
+            .collect::<Result<_, _>>()?;
+        if values.len() != CODE_BYTES * 8 / 5 {
+            return Err(TrackingError::Length(values.len()));
+        }
+        let mut code: [u8; CODE_BYTES] = [0; CODE_BYTES];
+        let mut buffer: u16 = 0;
+        let mut bits: u32 = 0;
+        let mut byte: usize = 0;
+        for value in values {
+            buffer = (buffer << 5) | value as u16;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                code[byte] = (buffer >> bits) as u8;
+                byte += 1;
+            }
+        }
+        Ok(TrackingCode(code))
+    }
+}
+
+impl Serialize for TrackingCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TrackingCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,493 @@
+// A transcript of every message of the election, for an auditor to replay.
+//
+// The transparency log, see `transparency`, refers to the messages by hash and is small enough
+// to publish as it grows. The transcript holds the messages themselves, in the order the
+// coordinator took them in: the parameters and trustee set, the CRP, every commitment to a
+// public key share and then every share, every accepted ballot with its voter's signature, the
+// tally, every decryption share and the result. Each message is in its wire encoding, see
+// `wire`, and each entry is hash-chained to the one before it like the entries of the
+// transparency log, so nothing can be edited, dropped or reordered without breaking the chain.
+//
+// `verify` replays the transcript from nothing else: it checks the chain, runs the key
+// ceremony again on the recorded commitments and shares to re-derive the public key, checks
+// every ballot's signature, sums the ballots into the tally and checks it against the recorded
+// one, and runs the decryption ceremony again on the recorded shares to re-derive the result. A
+// rerandomized tally can't be recomputed from the ballots, see `tally::rerandomize`, and the
+// transcript says so rather than failing.
+//
+// Only the ballots are signed. The trustees' messages are bound to the CRP and tally they were
+// made for, and the ceremonies refuse them otherwise, but nothing here says which trustee sent
+// them.
+
+use crate::{
+    ballot::{Ballot, BallotError, Nullifier},
+    decryption_ceremony::{self, DecryptionCeremony, DecryptionShareMessage, TallyHash},
+    key_ceremony::{CeremonyError, CrpMessage, KeyCeremony, KeyShareCommitment, KeyShareMessage},
+    merkle::Hash,
+    tally,
+    threshold::{ThresholdError, TrusteeSet},
+    transparency,
+    wire::{self, WireError},
+};
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, PublicKey},
+    mbfv::CommonRandomPoly,
+};
+use fhe_traits::{Deserialize as FheDeserialize, Serialize as FheSerialize};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+const ENTRY_DOMAIN: &[u8] = b"fhe-workshop/transcript";
+
+// A message of the election. The fhe.rs objects are in their wire encoding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "message", rename_all = "snake_case")]
+pub enum TranscriptMessage {
+    Parameters {
+        #[serde(with = "hex::serde")]
+        params: Vec<u8>,
+        num_trustees: usize,
+        threshold: usize,
+    },
+    Crp {
+        #[serde(with = "hex::serde")]
+        crp: Vec<u8>,
+    },
+    KeyShareCommitment {
+        #[serde(with = "hex::serde")]
+        commitment: Vec<u8>,
+    },
+    KeyShare {
+        #[serde(with = "hex::serde")]
+        share: Vec<u8>,
+    },
+    Ballot {
+        #[serde(with = "hex::serde")]
+        ballot: Vec<u8>,
+    },
+    Tally {
+        #[serde(with = "hex::serde")]
+        tally: Vec<u8>,
+        rerandomized: bool,
+    },
+    DecryptionShare {
+        #[serde(with = "hex::serde")]
+        share: Vec<u8>,
+    },
+    Result {
+        result: Vec<u64>,
+    },
+}
+
+impl TranscriptMessage {
+    pub fn parameters(params: &BfvParameters, trustees: &TrusteeSet) -> Self {
+        TranscriptMessage::Parameters {
+            params: params.to_bytes(),
+            num_trustees: trustees.num_trustees(),
+            threshold: trustees.threshold(),
+        }
+    }
+
+    pub fn crp(message: &CrpMessage) -> Self {
+        TranscriptMessage::Crp {
+            crp: wire::encode_crp(message),
+        }
+    }
+
+    pub fn key_share_commitment(message: &KeyShareCommitment) -> Self {
+        TranscriptMessage::KeyShareCommitment {
+            commitment: wire::encode_key_share_commitment(message),
+        }
+    }
+
+    pub fn key_share(message: &KeyShareMessage) -> Self {
+        TranscriptMessage::KeyShare {
+            share: wire::encode_key_share(message),
+        }
+    }
+
+    pub fn ballot(ballot: &Ballot) -> Self {
+        TranscriptMessage::Ballot {
+            ballot: wire::encode_ballot(ballot),
+        }
+    }
+
+    pub fn tally(tally: &Ciphertext, rerandomized: bool) -> Self {
+        TranscriptMessage::Tally {
+            tally: wire::encode_tally(tally),
+            rerandomized,
+        }
+    }
+
+    pub fn decryption_share(message: &DecryptionShareMessage) -> Self {
+        TranscriptMessage::DecryptionShare {
+            share: wire::encode_decryption_share(message),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            TranscriptMessage::Parameters { .. } => "parameters",
+            TranscriptMessage::Crp { .. } => "crp",
+            TranscriptMessage::KeyShareCommitment { .. } => "key_share_commitment",
+            TranscriptMessage::KeyShare { .. } => "key_share",
+            TranscriptMessage::Ballot { .. } => "ballot",
+            TranscriptMessage::Tally { .. } => "tally",
+            TranscriptMessage::DecryptionShare { .. } => "decryption_share",
+            TranscriptMessage::Result { .. } => "result",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub index: u64,
+    #[serde(with = "hex::serde")]
+    pub prev: Hash,
+    #[serde(flatten)]
+    pub message: TranscriptMessage,
+    #[serde(with = "hex::serde")]
+    pub hash: Hash,
+}
+
+impl TranscriptEntry {
+    fn new(index: u64, prev: Hash, message: TranscriptMessage) -> Self {
+        let hash: Hash = entry_hash(index, &prev, &message);
+        TranscriptEntry {
+            index,
+            prev,
+            message,
+            hash,
+        }
+    }
+}
+
+fn entry_hash(index: u64, prev: &Hash, message: &TranscriptMessage) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(ENTRY_DOMAIN);
+    hasher.update(prev);
+    hasher.update(index.to_le_bytes());
+    hasher.update(serde_json::to_vec(message).unwrap());
+    hasher.finalize().into()
+}
+
+#[derive(Debug)]
+pub enum TranscriptError {
+    Io(io::Error),
+    Json {
+        line: usize,
+        error: serde_json::Error,
+    },
+    BrokenChain {
+        index: u64,
+    },
+    // A message that can't come where it does, e.g. a ballot before the public key.
+    OutOfOrder {
+        index: u64,
+        kind: &'static str,
+    },
+    // The transcript ends before this message.
+    Missing(&'static str),
+    Wire {
+        index: u64,
+        error: WireError,
+    },
+    Ceremony(CeremonyError),
+    Threshold(ThresholdError),
+    Ballot {
+        index: u64,
+        error: BallotError,
+    },
+    TallyMismatch,
+    ResultMismatch {
+        recorded: Vec<u64>,
+        replayed: Vec<u64>,
+    },
+    Fhe(fhe::Error),
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptError::Io(e) => write!(f, "{e}"),
+            TranscriptError::Json { line, error } => write!(f, "line {line}: {error}"),
+            TranscriptError::BrokenChain { index } => {
+                write!(f, "hash chain broken at entry {index}")
+            }
+            TranscriptError::OutOfOrder { index, kind } => {
+                write!(f, "entry {index}: a {kind} message is out of order")
+            }
+            TranscriptError::Missing(kind) => write!(f, "the transcript has no {kind} message"),
+            TranscriptError::Wire { index, error } => write!(f, "entry {index}: {error}"),
+            TranscriptError::Ceremony(e) => write!(f, "{e}"),
+            TranscriptError::Threshold(e) => write!(f, "{e}"),
+            TranscriptError::Ballot { index, error } => write!(f, "entry {index}: {error}"),
+            TranscriptError::TallyMismatch => {
+                write!(
+                    f,
+                    "the recorded tally isn't the sum of the recorded ballots"
+                )
+            }
+            TranscriptError::ResultMismatch { recorded, replayed } => write!(
+                f,
+                "the recorded result {recorded:?} isn't the decryption {replayed:?}"
+            ),
+            TranscriptError::Fhe(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for TranscriptError {}
+
+impl From<io::Error> for TranscriptError {
+    fn from(e: io::Error) -> Self {
+        TranscriptError::Io(e)
+    }
+}
+
+impl From<CeremonyError> for TranscriptError {
+    fn from(e: CeremonyError) -> Self {
+        TranscriptError::Ceremony(e)
+    }
+}
+
+impl From<ThresholdError> for TranscriptError {
+    fn from(e: ThresholdError) -> Self {
+        TranscriptError::Threshold(e)
+    }
+}
+
+impl From<fhe::Error> for TranscriptError {
+    fn from(e: fhe::Error) -> Self {
+        TranscriptError::Fhe(e)
+    }
+}
+
+// Appends messages to a transcript file, one JSON line per entry, written out as soon as it's
+// added.
+pub struct Transcript {
+    file: LineWriter<File>,
+    len: u64,
+    head: Hash,
+}
+
+impl Transcript {
+    // Starts a new transcript, replacing any file already at `path`.
+    pub fn create(path: &Path) -> Result<Self, TranscriptError> {
+        Ok(Transcript {
+            file: LineWriter::new(File::create(path)?),
+            len: 0,
+            head: [0; 32],
+        })
+    }
+
+    // Appends a message, returning the hash of its entry.
+    pub fn append(&mut self, message: TranscriptMessage) -> Result<Hash, TranscriptError> {
+        let entry: TranscriptEntry = TranscriptEntry::new(self.len, self.head, message);
+        serde_json::to_writer(&mut self.file, &entry).map_err(io::Error::from)?;
+        self.file.write_all(b"\n")?;
+        self.len += 1;
+        self.head = entry.hash;
+        Ok(entry.hash)
+    }
+
+    // The hash of the last entry, which commits to the whole transcript.
+    pub fn head(&self) -> Hash {
+        self.head
+    }
+}
+
+// Reads a transcript file back.
+pub fn read(path: &Path) -> Result<Vec<TranscriptEntry>, TranscriptError> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .enumerate()
+        .map(|(line, text)| {
+            serde_json::from_str(&text?).map_err(|error| TranscriptError::Json {
+                line: line + 1,
+                error,
+            })
+        })
+        .collect()
+}
+
+// What the replay re-derived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub head: Hash,
+    pub public_key_hash: Hash,
+    pub ballots: usize,
+    pub tally_hash: TallyHash,
+    // Whether the tally was rerandomized, and so not checked against the ballots.
+    pub rerandomized: bool,
+    pub result: Vec<u64>,
+    // The trustees left out of the decryption for sending bad shares.
+    pub excluded: Vec<usize>,
+    // Decryption shares of other ciphertexts than the tally, e.g. of precinct tallies.
+    pub other_shares: usize,
+}
+
+// Replays the transcript, re-deriving the public key, the tally and the result, and checking
+// them against the recorded ones.
+pub fn verify(entries: &[TranscriptEntry]) -> Result<Replay, TranscriptError> {
+    let mut head: Hash = [0; 32];
+    for (index, entry) in entries.iter().enumerate() {
+        let index: u64 = index as u64;
+        if entry.index != index
+            || entry.prev != head
+            || entry.hash != entry_hash(index, &head, &entry.message)
+        {
+            return Err(TranscriptError::BrokenChain { index });
+        }
+        head = entry.hash;
+    }
+
+    let mut setup: Option<(Arc<BfvParameters>, TrusteeSet)> = None;
+    let mut key_ceremony: Option<KeyCeremony> = None;
+    let mut public_key: Option<PublicKey> = None;
+    // The latest ballot of each voter, as the ballot box keeps them.
+    let mut ballots: BTreeMap<Nullifier, Ballot> = BTreeMap::new();
+    let mut recorded_tally: Option<(Arc<Ciphertext>, bool)> = None;
+    let mut shares: Vec<(u64, &[u8])> = Vec::new();
+    let mut recorded_result: Option<&[u64]> = None;
+    for entry in entries {
+        let index: u64 = entry.index;
+        let out_of_order = || TranscriptError::OutOfOrder {
+            index,
+            kind: entry.message.kind(),
+        };
+        let wire_error = |error: WireError| TranscriptError::Wire { index, error };
+        match &entry.message {
+            TranscriptMessage::Parameters {
+                params,
+                num_trustees,
+                threshold,
+            } => {
+                if setup.is_some() {
+                    return Err(out_of_order());
+                }
+                let params: Arc<BfvParameters> = Arc::new(BfvParameters::try_deserialize(params)?);
+                setup = Some((params, TrusteeSet::new(*num_trustees, *threshold)?));
+            }
+            TranscriptMessage::Crp { crp } => {
+                let Some((params, trustees)) = &setup else {
+                    return Err(out_of_order());
+                };
+                if key_ceremony.is_some() {
+                    return Err(out_of_order());
+                }
+                let CrpMessage { crp } = wire::decode_crp(crp, params).map_err(wire_error)?;
+                let mut ceremony: KeyCeremony = KeyCeremony::new(params, trustees.num_slots());
+                ceremony.open_with_crp(crp)?;
+                key_ceremony = Some(ceremony);
+            }
+            TranscriptMessage::KeyShareCommitment { commitment } => {
+                let (Some(ceremony), None) = (&mut key_ceremony, &public_key) else {
+                    return Err(out_of_order());
+                };
+                ceremony
+                    .commit(wire::decode_key_share_commitment(commitment).map_err(wire_error)?)?;
+            }
+            TranscriptMessage::KeyShare { share } => {
+                let (Some((params, _)), Some(ceremony), None) =
+                    (&setup, &mut key_ceremony, &public_key)
+                else {
+                    return Err(out_of_order());
+                };
+                let crp: CommonRandomPoly = ceremony
+                    .crp()
+                    .expect("the ceremony opens with the CRP")
+                    .clone();
+                ceremony
+                    .receive(wire::decode_key_share(share, params, crp).map_err(wire_error)?)?;
+                if ceremony.received() == ceremony.required() {
+                    public_key = Some(ceremony.aggregate()?.clone());
+                }
+            }
+            TranscriptMessage::Ballot { ballot } => {
+                let (Some((params, _)), Some(_), None) = (&setup, &public_key, &recorded_tally)
+                else {
+                    return Err(out_of_order());
+                };
+                let ballot: Ballot = wire::decode_ballot(ballot, params).map_err(wire_error)?;
+                ballot
+                    .verify()
+                    .map_err(|error| TranscriptError::Ballot { index, error })?;
+                ballots.insert(ballot.nullifier, ballot);
+            }
+            TranscriptMessage::Tally {
+                tally,
+                rerandomized,
+            } => {
+                let (Some((params, _)), Some(_), None) = (&setup, &public_key, &recorded_tally)
+                else {
+                    return Err(out_of_order());
+                };
+                let tally: Ciphertext = wire::decode_tally(tally, params).map_err(wire_error)?;
+                recorded_tally = Some((Arc::new(tally), *rerandomized));
+            }
+            TranscriptMessage::DecryptionShare { share } => {
+                if public_key.is_none() {
+                    return Err(out_of_order());
+                }
+                // Read once the tally is known, as a share is read against its ciphertext.
+                shares.push((index, share));
+            }
+            TranscriptMessage::Result { result } => {
+                if recorded_tally.is_none() || recorded_result.is_some() {
+                    return Err(out_of_order());
+                }
+                recorded_result = Some(result);
+            }
+        }
+    }
+
+    let (params, trustees) = setup.ok_or(TranscriptError::Missing("parameters"))?;
+    let public_key: PublicKey = public_key.ok_or(TranscriptError::Missing("key share"))?;
+    let (tally, rerandomized) = recorded_tally.ok_or(TranscriptError::Missing("tally"))?;
+    let recorded_result: &[u64] = recorded_result.ok_or(TranscriptError::Missing("result"))?;
+    let tally_hash: TallyHash = decryption_ceremony::tally_hash(&tally);
+    let sum: Ciphertext = tally::sum(ballots.values().map(|ballot| &ballot.ciphertext), &params);
+    if !rerandomized && decryption_ceremony::tally_hash(&sum) != tally_hash {
+        return Err(TranscriptError::TallyMismatch);
+    }
+
+    let mut decryption: DecryptionCeremony = DecryptionCeremony::new(&tally, &trustees);
+    let mut other_shares: usize = 0;
+    for (index, share) in shares {
+        let message: DecryptionShareMessage =
+            wire::decode_decryption_share(share, &params, tally.clone())
+                .map_err(|error| TranscriptError::Wire { index, error })?;
+        if message.tally_hash != tally_hash {
+            other_shares += 1;
+            continue;
+        }
+        decryption.receive(message)?;
+    }
+    let result: Vec<u64> = decryption.decrypt(recorded_result.len())?.to_vec();
+    if result != recorded_result {
+        return Err(TranscriptError::ResultMismatch {
+            recorded: recorded_result.to_vec(),
+            replayed: result,
+        });
+    }
+    Ok(Replay {
+        head,
+        public_key_hash: transparency::artifact_hash(&public_key.to_bytes()),
+        ballots: ballots.len(),
+        tally_hash,
+        rerandomized,
+        result,
+        excluded: decryption.excluded().iter().copied().collect(),
+        other_shares,
+    })
+}
@@ -0,0 +1,217 @@
+// A tamper-evident log of everything that happened during the election.
+//
+// Every protocol event is appended to a log file as a line of JSON. Each entry carries the hash
+// of the entry before it, and its own hash covers that link, its position and the event, so
+// editing, dropping or reordering any entry breaks every hash after it. An auditor replays the
+// chain with `verify` and can then compare the events with the published artifacts: the
+// ballots accepted against the published ballots, the tally against the published tally.
+//
+// Events refer to the artifacts by hash rather than repeating them, so the log stays small.
+
+use crate::{
+    ballot::Ballot,
+    merkle::{self, Hash},
+};
+use fhe_traits::Serialize as FheSerialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::Path,
+};
+
+const ENTRY_DOMAIN: &[u8] = b"fhe-workshop/transparency-log";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    CrpPublished {
+        #[serde(with = "hex::serde")]
+        crp_hash: Hash,
+    },
+    KeyShareCommitted {
+        party: usize,
+        #[serde(with = "hex::serde")]
+        commitment: Hash,
+    },
+    KeyShareReceived {
+        party: usize,
+        #[serde(with = "hex::serde")]
+        share_hash: Hash,
+    },
+    // The ciphertext hash is the ballot's leaf in the ballots Merkle tree.
+    BallotAccepted {
+        #[serde(with = "hex::serde")]
+        nullifier: Hash,
+        #[serde(with = "hex::serde")]
+        ciphertext_hash: Hash,
+    },
+    TallyPublished {
+        #[serde(with = "hex::serde")]
+        tally_hash: Hash,
+        #[serde(with = "hex::serde")]
+        ballots_root: Hash,
+    },
+    // The tally hash says which ciphertext the share decrypts, the tally or another one.
+    DecryptionShareReceived {
+        slot: usize,
+        trustee: usize,
+        #[serde(with = "hex::serde")]
+        tally_hash: Hash,
+        #[serde(with = "hex::serde")]
+        share_hash: Hash,
+    },
+    ResultPublished {
+        tally: Vec<u64>,
+    },
+}
+
+impl Event {
+    pub fn ballot_accepted(ballot: &Ballot) -> Self {
+        Event::BallotAccepted {
+            nullifier: ballot.nullifier,
+            ciphertext_hash: merkle::hash_leaf(&ballot.ciphertext.to_bytes()),
+        }
+    }
+}
+
+// Hashes a serialized artifact for an event to refer to.
+pub fn artifact_hash(bytes: &[u8]) -> Hash {
+    Sha256::digest(bytes).into()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub index: u64,
+    #[serde(with = "hex::serde")]
+    pub prev: Hash,
+    #[serde(flatten)]
+    pub event: Event,
+    #[serde(with = "hex::serde")]
+    pub hash: Hash,
+}
+
+impl LogEntry {
+    fn new(index: u64, prev: Hash, event: Event) -> Self {
+        let hash: Hash = entry_hash(index, &prev, &event);
+        LogEntry {
+            index,
+            prev,
+            event,
+            hash,
+        }
+    }
+}
+
+fn entry_hash(index: u64, prev: &Hash, event: &Event) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(ENTRY_DOMAIN);
+    hasher.update(prev);
+    hasher.update(index.to_le_bytes());
+    hasher.update(serde_json::to_vec(event).unwrap());
+    hasher.finalize().into()
+}
+
+#[derive(Debug)]
+pub enum LogError {
+    Io(io::Error),
+    Json {
+        line: usize,
+        error: serde_json::Error,
+    },
+    BrokenChain {
+        index: u64,
+    },
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::Io(e) => write!(f, "{e}"),
+            LogError::Json { line, error } => write!(f, "line {line}: {error}"),
+            LogError::BrokenChain { index } => write!(f, "hash chain broken at entry {index}"),
+        }
+    }
+}
+
+impl Error for LogError {}
+
+impl From<io::Error> for LogError {
+    fn from(e: io::Error) -> Self {
+        LogError::Io(e)
+    }
+}
+
+// Appends events to a log file, one JSON line per entry, written out as soon as it's added.
+pub struct TransparencyLog {
+    file: LineWriter<File>,
+    len: u64,
+    head: Hash,
+}
+
+impl TransparencyLog {
+    // Starts a new log, replacing any file already at `path`.
+    pub fn create(path: &Path) -> Result<Self, LogError> {
+        Ok(TransparencyLog {
+            file: LineWriter::new(File::create(path)?),
+            len: 0,
+            head: [0; 32],
+        })
+    }
+
+    // Appends an event, returning the hash of its entry.
+    pub fn append(&mut self, event: Event) -> Result<Hash, LogError> {
+        let entry: LogEntry = LogEntry::new(self.len, self.head, event);
+        serde_json::to_writer(&mut self.file, &entry).map_err(io::Error::from)?;
+        self.file.write_all(b"\n")?;
+        self.len += 1;
+        self.head = entry.hash;
+        Ok(entry.hash)
+    }
+
+    // The hash of the last entry, which commits to the whole log.
+    pub fn head(&self) -> Hash {
+        self.head
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+// Reads a log file back.
+pub fn read(path: &Path) -> Result<Vec<LogEntry>, LogError> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .enumerate()
+        .map(|(line, text)| {
+            serde_json::from_str(&text?).map_err(|error| LogError::Json {
+                line: line + 1,
+                error,
+            })
+        })
+        .collect()
+}
+
+// Replays the hash chain, returning the head of the log if every entry links up.
+pub fn verify(entries: &[LogEntry]) -> Result<Hash, LogError> {
+    let mut head: Hash = [0; 32];
+    for (index, entry) in entries.iter().enumerate() {
+        let index: u64 = index as u64;
+        if entry.index != index
+            || entry.prev != head
+            || entry.hash != entry_hash(index, &head, &entry.event)
+        {
+            return Err(LogError::BrokenChain { index });
+        }
+        head = entry.hash;
+    }
+    Ok(head)
+}
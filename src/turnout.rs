@@ -0,0 +1,54 @@
+// Counting turnout under encryption, separately from the votes.
+//
+// Every voter encrypts `[1]` under the election key alongside their vote, see `encrypt_one`, and
+// signs it into their ballot, see `Ballot::with_turnout`. The ballot box sums the turnout
+// ciphertext of the latest ballot of every voter, so a re-vote replaces the voter's 1 rather
+// than adding another. The sum is a ciphertext of its own and is decrypted through a decryption
+// ceremony of its own. Its decryption shares say nothing about the tally, so turnout can be
+// announced while voting is still open, without waiting for the tally or revealing any vote.
+//
+// Note: nothing proves a turnout ciphertext holds `[1]`. A voter who encrypts `[5]` counts five
+// times, which a proof of validity covering the turnout ciphertext would have to rule out, see
+// `validity`.
+
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, PublicKey};
+use fhe_traits::{FheEncoder, FheEncrypter};
+use rand::{CryptoRng, RngCore};
+use std::sync::Arc;
+
+// What a voter sends along with their vote to be counted.
+pub fn encrypt_one<R: RngCore + CryptoRng>(
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let pt: Plaintext = Plaintext::try_encode(&[1u64], Encoding::poly(), params)?;
+    pk.try_encrypt(&pt, rng)
+}
+
+pub struct TurnoutCounter {
+    count: Ciphertext,
+}
+
+impl TurnoutCounter {
+    pub fn new(params: &Arc<BfvParameters>) -> Self {
+        TurnoutCounter {
+            count: Ciphertext::zero(params),
+        }
+    }
+
+    // Counts the voter whose ballot carries this turnout ciphertext.
+    pub fn add(&mut self, turnout: &Ciphertext) {
+        self.count += turnout;
+    }
+
+    // Takes back the turnout ciphertext of a ballot that was replaced.
+    pub fn remove(&mut self, turnout: &Ciphertext) {
+        self.count -= turnout;
+    }
+
+    // The encrypted number of voters counted so far, decrypting to `[turnout]`.
+    pub fn ciphertext(&self) -> &Ciphertext {
+        &self.count
+    }
+}
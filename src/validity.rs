@@ -0,0 +1,135 @@
+// Checking that a ballot holds a valid vote before it is counted.
+//
+// The signature on a ballot says who cast it, not what is inside. A voter running a modified
+// client could encrypt a vote of 1000 instead of 1, or spend more than their quadratic voting
+// budget, and the ballot box couldn't tell by looking at the ciphertext. The ballot box runs
+// every ballot through a `ValidityCheck` after checking its signature.
+//
+// By default the check is `TrustClient`, which accepts everything and relies on the voter's
+// client having checked the vote before encrypting it (as `voter::encode_vote` and
+// `voter::encode_quadratic` do).
+//
+// The alternative is for the voter to attach a proof that their ciphertext encrypts a valid
+// vote, e.g. a one-hot vector of bits, which the ballot box checks with `ProofCheck`. Proof
+// systems plug in through `BallotProof`. The only one so far, `TrustedProof`, is a stub that
+// proves nothing, so the proof travels through the whole pipeline (attached by the voter,
+// signed, serialized and checked by the ballot box) until a zero knowledge one takes its place.
+//
+// A third way needs no proof from the voter, at the cost of a round of decryption: the
+// coordinator checks the ballots under encryption. For a vote of bits encoded slot by slot with
+// `Encoding::simd()`, `b·(1−b)` is 0 in every slot exactly when every slot holds 0 or 1, and the
+// product of a ciphertext with itself computes it in every slot at once, see `bit_indicators`.
+// The trustees then decrypt the indicators rather than the ballots. To reveal no more than
+// whether a ballot is valid, each indicator is multiplied by random non-zero masks first, see
+// `mask_indicators`: the plaintext modulus is prime, so a masked slot is zero exactly when the
+// indicator is. Summing the masked indicators of many ballots checks them all with a single
+// decryption, and only if that comes out non-zero do the ballots have to be decrypted one by
+// one to find the invalid ones. The product needs parameters with room for a multiplication and
+// a relinearization key, see `params::ModuliChain::Depth`.
+//
+// Note: the election encodes its ballots as polynomials, where multiplying two ciphertexts
+// multiplies the polynomials rather than the slots, so this check only applies to ballots
+// encoded with `Encoding::simd()`, e.g. in `examples/ballot-validity.rs`.
+
+use crate::{
+    ballot::{Ballot, BallotError},
+    tally,
+};
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, RelinearizationKey};
+use fhe_traits::FheEncoder;
+use rand::{CryptoRng, Rng, RngCore};
+use std::sync::Arc;
+
+pub trait ValidityCheck: Send + Sync {
+    fn check(&self, ballot: &Ballot) -> Result<(), BallotError>;
+}
+
+// Accepts every ballot, trusting the voter's client to have only encrypted valid votes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrustClient;
+
+impl ValidityCheck for TrustClient {
+    fn check(&self, _ballot: &Ballot) -> Result<(), BallotError> {
+        Ok(())
+    }
+}
+
+// A proof system for the validity of a vote.
+pub trait BallotProof: Send + Sync {
+    // Proves that the ciphertext encrypts the vote, given as the slots of its plaintext, and
+    // that the vote is valid.
+    fn prove(&self, vote: &[u64], ciphertext: &Ciphertext) -> Result<Vec<u8>, fhe::Error>;
+
+    fn verify(&self, ciphertext: &Ciphertext, proof: &[u8]) -> bool;
+}
+
+const TRUSTED_PROOF: &[u8] = b"fhe-workshop/trusted-proof";
+
+// A stand-in proof system. The proof is a fixed marker and verifying it only checks the marker
+// is there, so it is no stronger than `TrustClient`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrustedProof;
+
+impl BallotProof for TrustedProof {
+    fn prove(&self, _vote: &[u64], _ciphertext: &Ciphertext) -> Result<Vec<u8>, fhe::Error> {
+        Ok(TRUSTED_PROOF.to_vec())
+    }
+
+    fn verify(&self, _ciphertext: &Ciphertext, proof: &[u8]) -> bool {
+        proof == TRUSTED_PROOF
+    }
+}
+
+// Accepts a ballot only if it carries a proof of validity that verifies.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProofCheck<P>(pub P);
+
+impl<P: BallotProof> ValidityCheck for ProofCheck<P> {
+    fn check(&self, ballot: &Ballot) -> Result<(), BallotError> {
+        if !self.0.verify(&ballot.ciphertext, &ballot.proof) {
+            return Err(BallotError::InvalidVote);
+        }
+        Ok(())
+    }
+}
+
+// Encrypts `b·(1−b)` in every slot of a ballot encrypting `b`, which is 0 in the slots that
+// hold a bit and something else in the others.
+pub fn bit_indicators(
+    ciphertext: &Ciphertext,
+    rlk: &RelinearizationKey,
+) -> Result<Ciphertext, fhe::Error> {
+    let mut square: Ciphertext = ciphertext * ciphertext;
+    rlk.relinearizes(&mut square)?;
+    Ok(ciphertext - &square)
+}
+
+// Multiplies every slot of the indicators by a random non-zero mask, so that decrypting them
+// tells which slots are zero and nothing else. Whoever draws the masks must not see what they
+// decrypt to.
+pub fn mask_indicators<R: RngCore + CryptoRng>(
+    indicators: &Ciphertext,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let masks: Vec<u64> = (0..params.degree())
+        .map(|_| rng.gen_range(1..params.plaintext()))
+        .collect();
+    let masks: Plaintext = Plaintext::try_encode(&masks, Encoding::simd(), params)?;
+    Ok(indicators * &masks)
+}
+
+// A random linear combination of the indicators of many ballots, each masked on its own. Every
+// slot decrypts to zero if every ballot is valid, and otherwise to zero with probability `1/t`
+// at most, for a plaintext modulus `t`.
+pub fn combine_indicators<'a, R: RngCore + CryptoRng>(
+    indicators: impl IntoIterator<Item = &'a Ciphertext>,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let masked: Vec<Ciphertext> = indicators
+        .into_iter()
+        .map(|indicators| mask_indicators(indicators, params, rng))
+        .collect::<Result<_, _>>()?;
+    Ok(tally::sum(&masked, params))
+}
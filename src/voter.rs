@@ -0,0 +1,206 @@
+// The voter's side of the election: turning a vote into an encrypted ballot.
+//
+// This is everything a voter needs and nothing more: the election parameters, the shared
+// public key and their own signing key. It deliberately doesn't depend on rayon or indicatif so
+// that it can be compiled to WebAssembly and run in a browser: batches are only spread over
+// threads through `parallel`, which runs them on one thread without the `parallel` feature.
+
+use crate::{
+    candidates::Candidates,
+    parallel::{self, prelude::*},
+    questions::Questions,
+};
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, PublicKey};
+use fhe_traits::{FheEncoder, FheEncrypter};
+use rand::{rngs::StdRng, CryptoRng, RngCore, SeedableRng};
+use std::{collections::HashMap, sync::Arc};
+
+// How many distinct votes `encrypt_votes` keeps encoded per thread.
+const MAX_ENCODED: usize = 256;
+
+// A vote on a single question.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Against,
+    For,
+    Abstain,
+}
+
+impl Choice {
+    pub const ALL: [Choice; 3] = [Choice::Against, Choice::For, Choice::Abstain];
+
+    // The vote as `[against, for, abstain]`, with exactly one slot set.
+    pub fn slots(self) -> [u64; 3] {
+        let mut slots: [u64; 3] = [0; 3];
+        slots[self as usize] = 1;
+        slots
+    }
+}
+
+// Reads a vote from its position in `[against, for, abstain]`.
+impl TryFrom<u64> for Choice {
+    type Error = fhe::Error;
+
+    fn try_from(vote: u64) -> Result<Self, Self::Error> {
+        match vote {
+            0 => Ok(Choice::Against),
+            1 => Ok(Choice::For),
+            2 => Ok(Choice::Abstain),
+            _ => Err(fhe::Error::DefaultError(format!(
+                "vote must be 0 (against), 1 (for) or 2 (abstain), got {vote}"
+            ))),
+        }
+    }
+}
+
+// Encodes a vote as `[against, for, abstain]`, so summing ballots counts all three columns at
+// once. Abstaining still counts towards turnout, unlike not voting at all.
+pub fn encode_vote(vote: Choice, params: &Arc<BfvParameters>) -> Result<Plaintext, fhe::Error> {
+    Plaintext::try_encode(vote.slots().as_slice(), Encoding::poly(), params)
+}
+
+pub fn encrypt_vote<R: RngCore + CryptoRng>(
+    vote: Choice,
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let pt: Plaintext = encode_vote(vote, params)?;
+    pk.try_encrypt(&pt, rng)
+}
+
+// Encrypts many votes at once, in order, each given as the slots of its plaintext as in
+// `Choice::slots`, `encode_quadratic`, `Questions::slots` or `Candidates::slots`.
+//
+// The votes are split into a chunk per thread. Each chunk encodes each distinct vote once: a
+// yes/no election only has three plaintexts, so a million ballots take three encodings rather
+// than a million. The slots are encoded as given, so a quadratic budget or one answer per
+// question has to be checked beforehand.
+//
+// Each vote is encrypted with a generator of its own, seeded from `rng` up front, so the
+// ciphertexts only depend on `rng` and not on how the votes were split between threads.
+pub fn encrypt_votes<V: AsRef<[u64]> + Sync, R: RngCore + CryptoRng>(
+    votes: &[V],
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Vec<Ciphertext>, fhe::Error> {
+    let seeds: Vec<[u8; 32]> = votes
+        .iter()
+        .map(|_| {
+            let mut seed: [u8; 32] = [0; 32];
+            rng.fill_bytes(&mut seed);
+            seed
+        })
+        .collect();
+    let chunk_size: usize = votes.len().div_ceil(parallel::current_num_threads()).max(1);
+    let chunks: Vec<Vec<Ciphertext>> = votes
+        .par_chunks(chunk_size)
+        .zip(seeds.par_chunks(chunk_size))
+        .map(|(chunk, seeds)| {
+            let mut encoded: HashMap<&[u64], Plaintext> = HashMap::new();
+            chunk
+                .iter()
+                .zip(seeds)
+                .map(|(vote, seed)| {
+                    let mut rng: StdRng = StdRng::from_seed(*seed);
+                    let slots: &[u64] = vote.as_ref();
+                    if !encoded.contains_key(slots) && encoded.len() < MAX_ENCODED {
+                        encoded.insert(
+                            slots,
+                            Plaintext::try_encode(slots, Encoding::poly(), params)?,
+                        );
+                    }
+                    match encoded.get(slots) {
+                        Some(pt) => pk.try_encrypt(pt, &mut rng),
+                        None => pk.try_encrypt(
+                            &Plaintext::try_encode(slots, Encoding::poly(), params)?,
+                            &mut rng,
+                        ),
+                    }
+                })
+                .collect::<Result<Vec<Ciphertext>, fhe::Error>>()
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+// What an allocation of votes over the options costs under quadratic voting: casting `v` votes
+// for an option costs `v²` credits.
+pub fn quadratic_cost(allocation: &[u64]) -> u64 {
+    allocation.iter().map(|votes| votes * votes).sum()
+}
+
+// Encodes a quadratic vote as the number of votes given to each option, so summing ballots
+// counts the votes for every option at once.
+//
+// The budget is checked here, on the voter's client. The ballot box can't see inside the
+// ciphertext to check it again, see `validity`.
+pub fn encode_quadratic(
+    allocation: &[u64],
+    credits: u64,
+    params: &Arc<BfvParameters>,
+) -> Result<Plaintext, fhe::Error> {
+    let cost: u64 = quadratic_cost(allocation);
+    if cost > credits {
+        return Err(fhe::Error::DefaultError(format!(
+            "allocation costs {cost} credits, the budget is {credits}"
+        )));
+    }
+    Plaintext::try_encode(allocation, Encoding::poly(), params)
+}
+
+pub fn encrypt_quadratic<R: RngCore + CryptoRng>(
+    allocation: &[u64],
+    credits: u64,
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let pt: Plaintext = encode_quadratic(allocation, credits, params)?;
+    pk.try_encrypt(&pt, rng)
+}
+
+// Encodes the answers to several questions, each in its own range of slots, see `questions`.
+pub fn encode_answers(
+    questions: &Questions,
+    answers: &[usize],
+    params: &Arc<BfvParameters>,
+) -> Result<Plaintext, fhe::Error> {
+    Plaintext::try_encode(&questions.slots(answers)?, Encoding::poly(), params)
+}
+
+pub fn encrypt_answers<R: RngCore + CryptoRng>(
+    questions: &Questions,
+    answers: &[usize],
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let pt: Plaintext = encode_answers(questions, answers, params)?;
+    pk.try_encrypt(&pt, rng)
+}
+
+// Encodes a vote for the candidate with the id, one-hot over the candidates' slots, see
+// `candidates`.
+pub fn encode_candidate(
+    candidates: &Candidates,
+    id: &str,
+    params: &Arc<BfvParameters>,
+) -> Result<Plaintext, fhe::Error> {
+    let slots: Vec<u64> = candidates
+        .slots(id)
+        .map_err(|e| fhe::Error::DefaultError(e.to_string()))?;
+    Plaintext::try_encode(&slots, Encoding::poly(), params)
+}
+
+pub fn encrypt_candidate<R: RngCore + CryptoRng>(
+    candidates: &Candidates,
+    id: &str,
+    pk: &PublicKey,
+    params: &Arc<BfvParameters>,
+    rng: &mut R,
+) -> Result<Ciphertext, fhe::Error> {
+    let pt: Plaintext = encode_candidate(candidates, id, params)?;
+    pk.try_encrypt(&pt, rng)
+}
@@ -0,0 +1,86 @@
+// WebAssembly bindings for the voter, so a ballot can be cast from a browser.
+//
+// The browser loads the election parameters and shared public key published by the
+// coordinator, encrypts the vote locally and hands back the serialized, signed ballot to be
+// submitted. The vote itself never leaves the browser in the clear.
+//
+// Everything crossing the boundary is framed by `codec`: the parameters and public key may come
+// compressed, the ballot is handed back uncompressed since the `zstd` feature isn't built for
+// the browser.
+//
+// Build with `wasm-pack build --target web --no-default-features --features wasm`.
+
+use crate::{
+    ballot::Ballot,
+    codec::{self, Compression},
+    validity::{BallotProof, TrustedProof},
+    voter::Choice,
+    zero_pool::ZeroPool,
+};
+use ed25519_dalek::SigningKey;
+use fhe::bfv::{BfvParameters, Ciphertext, PublicKey};
+use fhe_traits::{Deserialize, DeserializeParametrized};
+use rand::thread_rng;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct ElectionKey {
+    pool: ZeroPool,
+}
+
+#[wasm_bindgen]
+pub struct EncryptedVote {
+    ciphertext: Ciphertext,
+    proof: Vec<u8>,
+}
+
+// Loads the serialized parameters and public key of the election.
+#[wasm_bindgen]
+pub fn load_public_key(params: &[u8], public_key: &[u8]) -> Result<ElectionKey, JsError> {
+    let params: Arc<BfvParameters> =
+        Arc::new(BfvParameters::try_deserialize(&codec::decode(params)?)?);
+    let pk: PublicKey = PublicKey::from_bytes(&codec::decode(public_key)?, &params)?;
+    Ok(ElectionKey {
+        pool: ZeroPool::new(&pk, &params),
+    })
+}
+
+// Precomputes `count` encryptions of zero, e.g. while the voter is reading the ballot, so that
+// `encrypt_vote` only has to add the vote to one, see `zero_pool`.
+#[wasm_bindgen]
+pub fn precompute(key: &ElectionKey, count: usize) -> Result<(), JsError> {
+    Ok(key.pool.fill(count, &mut thread_rng())?)
+}
+
+// Encrypts a vote: 0 against, 1 for or 2 to abstain, along with a proof of its validity. A zero
+// from `precompute` is used if there is one left.
+#[wasm_bindgen]
+pub fn encrypt_vote(key: &ElectionKey, vote: u64) -> Result<EncryptedVote, JsError> {
+    let choice: Choice = Choice::try_from(vote)?;
+    let ciphertext: Ciphertext = key.pool.encrypt_vote(choice, &mut thread_rng())?;
+    let proof: Vec<u8> = TrustedProof.prove(&choice.slots(), &ciphertext)?;
+    Ok(EncryptedVote { ciphertext, proof })
+}
+
+// Signs the encrypted vote and the voter's precinct with the voter's 32 byte Ed25519 secret key
+// and serializes the resulting ballot, ready to be submitted to the ballot box.
+#[wasm_bindgen]
+pub fn serialize_ballot(
+    vote: &EncryptedVote,
+    precinct: u32,
+    signing_key: &[u8],
+) -> Result<Vec<u8>, JsError> {
+    let signing_key: SigningKey = SigningKey::from_bytes(
+        signing_key
+            .try_into()
+            .map_err(|_| JsError::new("signing key must be 32 bytes"))?,
+    );
+    let ballot: Ballot = Ballot::with_proof(
+        vote.ciphertext.clone(),
+        precinct,
+        vote.proof.clone(),
+        &signing_key,
+    );
+    Ok(codec::encode(&ballot.to_bytes(), Compression::None)?)
+}
@@ -0,0 +1,352 @@
+// The CBOR encoding of the protocol messages, the same on disk and on the network.
+//
+// Every message is a CBOR map of the format version and the message itself, tagged with its
+// kind:
+//
+//     {"version": 2, "message": {"kind": "ballot", "voter": h'..', ...}}
+//
+// The fhe.rs objects inside (the CRP, key shares, ciphertexts and decryption shares) are byte
+// strings holding fhe.rs's own serialization, which takes the parameters to read back. Fields
+// are always written in the same order with definite lengths, so a message has exactly one
+// encoding and hashes the same wherever it was encoded. A reader turns a version it doesn't know
+// away instead of misreading it, and a message of the wrong kind rather than guessing.
+//
+// The protocol messages have an `encode_*` and `decode_*` function each. Messages of other
+// modules, like those gossiped between trustees, go through `encode` and `decode` to get the
+// same versioned envelope.
+
+use crate::{
+    ballot::{Ballot, Nullifier, MAX_PROOF_LEN},
+    decryption_ceremony::{DecryptionShareMessage, TallyHash},
+    key_ceremony::{
+        CrpHash, CrpMessage, KeyShareCommitment, KeyShareMessage, PartyId, ShareCommitment,
+    },
+    precinct::PrecinctId,
+};
+use ciborium::Value;
+use ed25519_dalek::{Signature, VerifyingKey};
+use fhe::{
+    bfv::{BfvParameters, Ciphertext},
+    mbfv::{CommonRandomPoly, DecryptionShare, PublicKeyShare},
+};
+use fhe_traits::{DeserializeParametrized, Serialize as FheSerialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{error::Error, fmt, sync::Arc};
+
+pub const VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum WireError {
+    // ciborium has a different error type for each direction and for values, so only the
+    // message is kept.
+    Cbor(String),
+    UnsupportedVersion(u32),
+    UnexpectedKind {
+        expected: &'static str,
+        found: &'static str,
+    },
+    Malformed,
+    Fhe(fhe::Error),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::Cbor(e) => write!(f, "invalid CBOR: {e}"),
+            WireError::UnsupportedVersion(v) => write!(f, "unsupported wire format version {v}"),
+            WireError::UnexpectedKind { expected, found } => {
+                write!(f, "expected a {expected} message, got a {found}")
+            }
+            WireError::Malformed => write!(f, "malformed message"),
+            WireError::Fhe(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for WireError {}
+
+impl From<fhe::Error> for WireError {
+    fn from(e: fhe::Error) -> Self {
+        WireError::Fhe(e)
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    message: &'a T,
+}
+
+// Read with the message left as a CBOR value, so the version is checked before the message is.
+#[derive(Deserialize)]
+struct RawEnvelope {
+    version: u32,
+    message: Value,
+}
+
+// Wraps the message in the versioned envelope.
+pub fn encode<T: Serialize>(message: &T) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    ciborium::into_writer(
+        &Envelope {
+            version: VERSION,
+            message,
+        },
+        &mut bytes,
+    )
+    .expect("a message always serializes");
+    bytes
+}
+
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, WireError> {
+    let envelope: RawEnvelope =
+        ciborium::from_reader(bytes).map_err(|e| WireError::Cbor(e.to_string()))?;
+    if envelope.version != VERSION {
+        return Err(WireError::UnsupportedVersion(envelope.version));
+    }
+    envelope
+        .message
+        .deserialized()
+        .map_err(|e| WireError::Cbor(e.to_string()))
+}
+
+// The protocol messages as they are encoded, with the fhe.rs objects still serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum WireMessage {
+    Crp {
+        #[serde(with = "serde_bytes")]
+        crp: Vec<u8>,
+    },
+    KeyShareCommitment {
+        party: PartyId,
+        #[serde(with = "serde_bytes")]
+        crp_hash: CrpHash,
+        #[serde(with = "serde_bytes")]
+        commitment: ShareCommitment,
+    },
+    KeyShare {
+        party: PartyId,
+        #[serde(with = "serde_bytes")]
+        crp_hash: CrpHash,
+        #[serde(with = "serde_bytes")]
+        share: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        proof: Vec<u8>,
+    },
+    Ballot {
+        #[serde(with = "serde_bytes")]
+        voter: [u8; 32],
+        #[serde(with = "serde_bytes")]
+        nullifier: Nullifier,
+        precinct: PrecinctId,
+        #[serde(with = "serde_bytes")]
+        proof: Vec<u8>,
+        // Empty if the ballot carries no turnout ciphertext.
+        #[serde(with = "serde_bytes")]
+        turnout: Vec<u8>,
+        #[serde(with = "serde_bytes")]
+        signature: [u8; 64],
+        #[serde(with = "serde_bytes")]
+        ciphertext: Vec<u8>,
+    },
+    Tally {
+        #[serde(with = "serde_bytes")]
+        ciphertext: Vec<u8>,
+    },
+    DecryptionShare {
+        slot: usize,
+        trustee: PartyId,
+        #[serde(with = "serde_bytes")]
+        tally_hash: TallyHash,
+        #[serde(with = "serde_bytes")]
+        share: Vec<u8>,
+    },
+}
+
+impl WireMessage {
+    fn kind(&self) -> &'static str {
+        match self {
+            WireMessage::Crp { .. } => "crp",
+            WireMessage::KeyShareCommitment { .. } => "key_share_commitment",
+            WireMessage::KeyShare { .. } => "key_share",
+            WireMessage::Ballot { .. } => "ballot",
+            WireMessage::Tally { .. } => "tally",
+            WireMessage::DecryptionShare { .. } => "decryption_share",
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> WireError {
+        WireError::UnexpectedKind {
+            expected,
+            found: self.kind(),
+        }
+    }
+}
+
+pub fn encode_crp(message: &CrpMessage) -> Vec<u8> {
+    encode(&WireMessage::Crp {
+        crp: message.crp.to_bytes(),
+    })
+}
+
+pub fn decode_crp(bytes: &[u8], params: &Arc<BfvParameters>) -> Result<CrpMessage, WireError> {
+    match decode(bytes)? {
+        WireMessage::Crp { crp } => Ok(CrpMessage {
+            crp: CommonRandomPoly::deserialize(&crp, params)?,
+        }),
+        other => Err(other.unexpected("crp")),
+    }
+}
+
+pub fn encode_key_share_commitment(message: &KeyShareCommitment) -> Vec<u8> {
+    encode(&WireMessage::KeyShareCommitment {
+        party: message.party,
+        crp_hash: message.crp_hash,
+        commitment: message.commitment,
+    })
+}
+
+pub fn decode_key_share_commitment(bytes: &[u8]) -> Result<KeyShareCommitment, WireError> {
+    match decode(bytes)? {
+        WireMessage::KeyShareCommitment {
+            party,
+            crp_hash,
+            commitment,
+        } => Ok(KeyShareCommitment {
+            party,
+            crp_hash,
+            commitment,
+        }),
+        other => Err(other.unexpected("key_share_commitment")),
+    }
+}
+
+pub fn encode_key_share(message: &KeyShareMessage) -> Vec<u8> {
+    encode(&WireMessage::KeyShare {
+        party: message.party,
+        crp_hash: message.crp_hash,
+        share: message.share.to_bytes(),
+        proof: message.proof.clone(),
+    })
+}
+
+// The share is read against the CRP it was generated from.
+pub fn decode_key_share(
+    bytes: &[u8],
+    params: &Arc<BfvParameters>,
+    crp: CommonRandomPoly,
+) -> Result<KeyShareMessage, WireError> {
+    match decode(bytes)? {
+        WireMessage::KeyShare {
+            party,
+            crp_hash,
+            share,
+            proof,
+        } => Ok(KeyShareMessage {
+            party,
+            crp_hash,
+            share: PublicKeyShare::deserialize(&share, params, crp)?,
+            proof,
+        }),
+        other => Err(other.unexpected("key_share")),
+    }
+}
+
+pub fn encode_ballot(ballot: &Ballot) -> Vec<u8> {
+    encode(&WireMessage::Ballot {
+        voter: ballot.voter.to_bytes(),
+        nullifier: ballot.nullifier,
+        precinct: ballot.precinct,
+        proof: ballot.proof.clone(),
+        turnout: ballot
+            .turnout
+            .as_ref()
+            .map(Ciphertext::to_bytes)
+            .unwrap_or_default(),
+        signature: ballot.signature.to_bytes(),
+        ciphertext: ballot.ciphertext.to_bytes(),
+    })
+}
+
+// The signature is not checked here, that's up to the ballot box. A proof or ciphertext longer
+// than any under the parameters is turned away before the ciphertext is deserialized.
+pub fn decode_ballot(bytes: &[u8], params: &Arc<BfvParameters>) -> Result<Ballot, WireError> {
+    match decode(bytes)? {
+        WireMessage::Ballot { proof, .. } if proof.len() > MAX_PROOF_LEN => {
+            Err(WireError::Malformed)
+        }
+        WireMessage::Ballot {
+            ciphertext,
+            turnout,
+            ..
+        } if ciphertext.len().max(turnout.len()) > Ballot::max_ciphertext_len(params) => {
+            Err(WireError::Malformed)
+        }
+        WireMessage::Ballot {
+            voter,
+            nullifier,
+            precinct,
+            proof,
+            turnout,
+            signature,
+            ciphertext,
+        } => Ok(Ballot {
+            voter: VerifyingKey::from_bytes(&voter).map_err(|_| WireError::Malformed)?,
+            nullifier,
+            precinct,
+            ciphertext: Ciphertext::from_bytes(&ciphertext, params)?,
+            proof,
+            turnout: (!turnout.is_empty())
+                .then(|| Ciphertext::from_bytes(&turnout, params))
+                .transpose()?,
+            signature: Signature::from_bytes(&signature),
+        }),
+        other => Err(other.unexpected("ballot")),
+    }
+}
+
+pub fn encode_tally(tally: &Ciphertext) -> Vec<u8> {
+    encode(&WireMessage::Tally {
+        ciphertext: tally.to_bytes(),
+    })
+}
+
+pub fn decode_tally(bytes: &[u8], params: &Arc<BfvParameters>) -> Result<Ciphertext, WireError> {
+    match decode(bytes)? {
+        WireMessage::Tally { ciphertext } => Ok(Ciphertext::from_bytes(&ciphertext, params)?),
+        other => Err(other.unexpected("tally")),
+    }
+}
+
+pub fn encode_decryption_share(message: &DecryptionShareMessage) -> Vec<u8> {
+    encode(&WireMessage::DecryptionShare {
+        slot: message.slot,
+        trustee: message.trustee,
+        tally_hash: message.tally_hash,
+        share: message.share.to_bytes(),
+    })
+}
+
+// The share is read against the tally it decrypts. Whether it is a share of that tally is up
+// to the decryption ceremony, which checks the tally hash.
+pub fn decode_decryption_share(
+    bytes: &[u8],
+    params: &Arc<BfvParameters>,
+    tally: Arc<Ciphertext>,
+) -> Result<DecryptionShareMessage, WireError> {
+    match decode(bytes)? {
+        WireMessage::DecryptionShare {
+            slot,
+            trustee,
+            tally_hash,
+            share,
+        } => Ok(DecryptionShareMessage {
+            slot,
+            trustee,
+            tally_hash,
+            share: DecryptionShare::deserialize(&share, params, tally)?,
+        }),
+        other => Err(other.unexpected("decryption_share")),
+    }
+}
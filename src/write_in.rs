@@ -0,0 +1,180 @@
+// Write-ins: names voters write in for the write-in candidate, kept off the ballot.
+//
+// A vote for the write-in bucket is a vote like any other, one-hot in the bucket's slot, see
+// `candidates`, so the tally counts the bucket without anyone learning who voted for it. The
+// name itself can't go in the ciphertext: it would be decrypted with the tally for everyone to
+// read, even when only a handful of voters wrote anything in, and those names would point at
+// the voters who wrote them.
+//
+// Instead the voter hands the name to a collector, off the path of the ballot and without the
+// voter's key, with a random salt, see `WriteIn`. The collector publishes a salted hash of each
+// name, its commitment, once voting closes, in sorted order so it says nothing about when each
+// name arrived. Only if the bucket gets at least `reveal_at` percent of the votes are the names
+// revealed, counted together whatever their case or spacing, with the salts so anyone can check
+// them against the commitments. Otherwise the names are withheld for good.
+//
+// The collector must hold one name for every vote in the bucket, which is checked against the
+// decrypted count before anything is revealed.
+//
+// Note: the collector sees every name as it arrives, and is trusted not to publish them early.
+
+use crate::candidates::WriteInRule;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, error::Error, fmt};
+
+const COMMITMENT_DOMAIN: &[u8] = b"fhe-workshop/write-in";
+
+pub type Commitment = [u8; 32];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteInError {
+    Empty,
+    // The bucket was tallied with this many votes, but the collector holds another number of
+    // names.
+    CountMismatch { tallied: u64, collected: usize },
+}
+
+impl fmt::Display for WriteInError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteInError::Empty => write!(f, "a write-in needs a name"),
+            WriteInError::CountMismatch { tallied, collected } => write!(
+                f,
+                "the write-in bucket has {tallied} votes but {collected} names were collected"
+            ),
+        }
+    }
+}
+
+impl Error for WriteInError {}
+
+// A name written in, and the salt that hides it in its commitment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteIn {
+    pub name: String,
+    #[serde(with = "hex::serde")]
+    pub salt: [u8; 32],
+}
+
+impl WriteIn {
+    pub fn new<R: RngCore + CryptoRng>(name: &str, rng: &mut R) -> Result<Self, WriteInError> {
+        if normalize(name).is_empty() {
+            return Err(WriteInError::Empty);
+        }
+        let mut salt: [u8; 32] = [0; 32];
+        rng.fill_bytes(&mut salt);
+        Ok(WriteIn {
+            name: name.trim().to_string(),
+            salt,
+        })
+    }
+
+    pub fn commitment(&self) -> Commitment {
+        let mut hasher = Sha256::new();
+        hasher.update(COMMITMENT_DOMAIN);
+        hasher.update(self.salt);
+        hasher.update(self.name.as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+// The form names are counted under: lowercase, with single spaces.
+pub fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Whether a bucket with `votes` of the `total` votes is revealed.
+pub fn reaches(votes: u64, total: u64, rule: WriteInRule) -> bool {
+    votes > 0 && votes * 100 >= rule.reveal_at as u64 * total
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reveal {
+    // The bucket fell short, and only the commitments are published.
+    Withheld {
+        commitments: Vec<Commitment>,
+    },
+    Revealed {
+        // How often each name was written in, most first, under its first spelling in sorted
+        // order.
+        counts: Vec<(String, u64)>,
+        // Every name with its salt, in the order of their commitments.
+        openings: Vec<WriteIn>,
+    },
+}
+
+// The collector of the names written in.
+#[derive(Debug, Clone, Default)]
+pub struct WriteIns {
+    write_ins: Vec<WriteIn>,
+}
+
+impl WriteIns {
+    pub fn new() -> Self {
+        WriteIns::default()
+    }
+
+    // Takes a name, returning its commitment as the voter's receipt.
+    pub fn submit(&mut self, write_in: WriteIn) -> Commitment {
+        let commitment: Commitment = write_in.commitment();
+        self.write_ins.push(write_in);
+        commitment
+    }
+
+    pub fn len(&self) -> usize {
+        self.write_ins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.write_ins.is_empty()
+    }
+
+    // The commitments of the names, sorted.
+    pub fn commitments(&self) -> Vec<Commitment> {
+        let mut commitments: Vec<Commitment> =
+            self.write_ins.iter().map(WriteIn::commitment).collect();
+        commitments.sort();
+        commitments
+    }
+
+    // Reveals the names if the bucket, with `votes` of the `total` votes, reaches the rule's
+    // threshold.
+    pub fn reveal(
+        &self,
+        votes: u64,
+        total: u64,
+        rule: WriteInRule,
+    ) -> Result<Reveal, WriteInError> {
+        if votes != self.write_ins.len() as u64 {
+            return Err(WriteInError::CountMismatch {
+                tallied: votes,
+                collected: self.write_ins.len(),
+            });
+        }
+        if !reaches(votes, total, rule) {
+            return Ok(Reveal::Withheld {
+                commitments: self.commitments(),
+            });
+        }
+        let mut openings: Vec<WriteIn> = self.write_ins.clone();
+        openings.sort_by_key(WriteIn::commitment);
+        let mut spellings: BTreeMap<String, (String, u64)> = BTreeMap::new();
+        for write_in in &openings {
+            let (spelling, count): &mut (String, u64) = spellings
+                .entry(normalize(&write_in.name))
+                .or_insert_with(|| (write_in.name.clone(), 0));
+            if write_in.name < *spelling {
+                *spelling = write_in.name.clone();
+            }
+            *count += 1;
+        }
+        let mut counts: Vec<(String, u64)> = spellings.into_values().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        Ok(Reveal::Revealed { counts, openings })
+    }
+}
@@ -0,0 +1,124 @@
+// Encryptions of zero computed ahead of time, so that casting a ballot takes milliseconds.
+//
+// Almost all the work of encrypting a vote under the public key goes into its randomness:
+// sampling the masking and error polynomials and multiplying the public key by them. None of it
+// depends on the vote, so it can be done in advance, while the server is idle or the voter is
+// still making up their mind, as an encryption of zero. The encoded vote is added to it once it
+// is cast. The sum is distributed exactly as a fresh encryption of the vote would be, as long as
+// no zero is ever used twice, so the pool hands each one out once and forgets it.
+//
+// Once the pool has run dry, votes are encrypted from scratch, so a burst of ballots larger than
+// the pool is slower rather than refused. Keeping it topped up is up to the caller, see
+// `pipeline::fill_pool_async`.
+
+use crate::{
+    parallel::prelude::*,
+    voter::{self, Choice},
+};
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, PublicKey};
+use fhe_traits::{FheEncoder, FheEncrypter};
+use rand::{CryptoRng, RngCore};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub struct ZeroPool {
+    pk: PublicKey,
+    params: Arc<BfvParameters>,
+    zeros: Mutex<Vec<Ciphertext>>,
+}
+
+impl ZeroPool {
+    pub fn new(pk: &PublicKey, params: &Arc<BfvParameters>) -> Self {
+        ZeroPool {
+            pk: pk.clone(),
+            params: params.clone(),
+            zeros: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn zeros(&self) -> MutexGuard<'_, Vec<Ciphertext>> {
+        self.zeros.lock().unwrap()
+    }
+
+    // Precomputes `count` more encryptions of zero, spread over every core, see
+    // `voter::encrypt_votes`.
+    pub fn fill<R: RngCore + CryptoRng>(
+        &self,
+        count: usize,
+        rng: &mut R,
+    ) -> Result<(), fhe::Error> {
+        let zeros: Vec<Ciphertext> =
+            voter::encrypt_votes(&vec![[0u64]; count], &self.pk, &self.params, rng)?;
+        self.zeros().extend(zeros);
+        Ok(())
+    }
+
+    // Tops the pool up to `target` zeros, returning how many were added.
+    pub fn refill<R: RngCore + CryptoRng>(
+        &self,
+        target: usize,
+        rng: &mut R,
+    ) -> Result<usize, fhe::Error> {
+        let missing: usize = target.saturating_sub(self.len());
+        self.fill(missing, rng)?;
+        Ok(missing)
+    }
+
+    pub fn len(&self) -> usize {
+        self.zeros().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.zeros().is_empty()
+    }
+
+    // Encrypts the slots of a vote, as in `voter::encrypt_votes`, taking a zero from the pool if
+    // there is one left.
+    pub fn encrypt<R: RngCore + CryptoRng>(
+        &self,
+        slots: &[u64],
+        rng: &mut R,
+    ) -> Result<Ciphertext, fhe::Error> {
+        let pt: Plaintext = Plaintext::try_encode(slots, Encoding::poly(), &self.params)?;
+        let zero: Option<Ciphertext> = self.zeros().pop();
+        match zero {
+            Some(mut ct) => {
+                ct += &pt;
+                Ok(ct)
+            }
+            None => self.pk.try_encrypt(&pt, rng),
+        }
+    }
+
+    pub fn encrypt_vote<R: RngCore + CryptoRng>(
+        &self,
+        vote: Choice,
+        rng: &mut R,
+    ) -> Result<Ciphertext, fhe::Error> {
+        self.encrypt(&vote.slots(), rng)
+    }
+
+    // Encrypts many votes at once, in order, taking a zero from the pool for as many of them as
+    // it holds and encrypting the rest from scratch, see `voter::encrypt_votes`.
+    pub fn encrypt_votes<V: AsRef<[u64]> + Sync, R: RngCore + CryptoRng>(
+        &self,
+        votes: &[V],
+        rng: &mut R,
+    ) -> Result<Vec<Ciphertext>, fhe::Error> {
+        let zeros: Vec<Ciphertext> = {
+            let mut pool: MutexGuard<'_, Vec<Ciphertext>> = self.zeros();
+            let keep: usize = pool.len().saturating_sub(votes.len());
+            pool.split_off(keep)
+        };
+        let (pooled, rest): (&[V], &[V]) = votes.split_at(zeros.len());
+        let mut ciphertexts: Vec<Ciphertext> = zeros
+            .into_par_iter()
+            .zip(pooled.par_iter())
+            .map(|(mut ct, vote)| {
+                ct += &Plaintext::try_encode(vote.as_ref(), Encoding::poly(), &self.params)?;
+                Ok(ct)
+            })
+            .collect::<Result<_, fhe::Error>>()?;
+        ciphertexts.extend(voter::encrypt_votes(rest, &self.pk, &self.params, rng)?);
+        Ok(ciphertexts)
+    }
+}
@@ -0,0 +1,192 @@
+// Tests of the ballot box's handling of spoiled ballots, see `BallotBox::spoil`, of the
+// turnout it counts, see `turnout`, and of the stores it can keep its ballots in, see
+// `BallotBox::with_store`.
+//
+// A spoiled ballot is published with the randomness it was encrypted with, so its ciphertext
+// must never be counted, however it is signed. The turnout counts every voter once, however many
+// times they vote, and so do the stores.
+
+use ed25519_dalek::SigningKey;
+use fhe::bfv::{BfvParameters, Ciphertext, Encoding, Plaintext, PublicKey, SecretKey};
+use fhe_traits::{FheDecoder, FheDecrypter};
+use fhe_workshop::{
+    ballot::{Ballot, BallotBox, BallotError, DuplicatePolicy},
+    params,
+    precinct::{self, PrecinctId, PrecinctStores, PrecinctTally},
+    store::{CiphertextStore, MemoryStore, StoreError},
+    turnout::{self, TurnoutCounter},
+    voter::{self, Choice},
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::{collections::BTreeMap, sync::Arc};
+
+// A ballot box with a single voter on the roll, their key and an encrypted vote.
+fn setup() -> (BallotBox, SigningKey, Ciphertext) {
+    let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(0);
+    let params: Arc<BfvParameters> = params::build(
+        params::DEGREE,
+        params::plaintext_modulus(1),
+        &params::MODULI,
+    )
+    .unwrap();
+    let sk: SecretKey = SecretKey::random(&params, &mut rng);
+    let pk: PublicKey = PublicKey::new(&sk, &mut rng);
+    let voter: SigningKey = SigningKey::generate(&mut rng);
+    let ciphertext: Ciphertext = voter::encrypt_vote(Choice::For, &pk, &params, &mut rng).unwrap();
+    let ballot_box: BallotBox = BallotBox::new([voter.verifying_key()], DuplicatePolicy::Reject);
+    (ballot_box, voter, ciphertext)
+}
+
+#[test]
+fn spoiled_ballot_is_never_cast() {
+    let (mut ballot_box, voter, ciphertext) = setup();
+    let ballot: Ballot = Ballot::new(ciphertext, 0, &voter);
+    ballot_box.spoil(ballot.clone()).unwrap();
+    assert_eq!(ballot_box.submit(ballot), Err(BallotError::Spoiled));
+    assert!(ballot_box.ballots().is_empty());
+}
+
+#[test]
+fn spoiled_ciphertext_signed_again_is_never_cast() {
+    let (mut ballot_box, voter, ciphertext) = setup();
+    ballot_box
+        .spoil(Ballot::new(ciphertext.clone(), 0, &voter))
+        .unwrap();
+    // Another precinct and a proof give the same ciphertext a different signature.
+    let resigned: Ballot = Ballot::with_proof(ciphertext, 1, vec![1], &voter);
+    assert_eq!(ballot_box.submit(resigned), Err(BallotError::Spoiled));
+    assert!(ballot_box.ballots().is_empty());
+}
+
+#[test]
+fn cast_ciphertext_signed_again_is_never_spoiled() {
+    let (mut ballot_box, voter, ciphertext) = setup();
+    ballot_box
+        .submit(Ballot::new(ciphertext.clone(), 0, &voter))
+        .unwrap();
+    let resigned: Ballot = Ballot::new(ciphertext, 1, &voter);
+    assert_eq!(ballot_box.spoil(resigned), Err(BallotError::AlreadyCast));
+    assert!(ballot_box.spoiled().is_empty());
+}
+
+#[test]
+fn turnout_counts_each_voter_once() {
+    let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(1);
+    let params: Arc<BfvParameters> = params::build(
+        params::DEGREE,
+        params::plaintext_modulus(1),
+        &params::MODULI,
+    )
+    .unwrap();
+    let sk: SecretKey = SecretKey::random(&params, &mut rng);
+    let pk: PublicKey = PublicKey::new(&sk, &mut rng);
+    let voters: Vec<SigningKey> = (0..2).map(|_| SigningKey::generate(&mut rng)).collect();
+    let mut ballot_box: BallotBox = BallotBox::new(
+        voters.iter().map(SigningKey::verifying_key),
+        DuplicatePolicy::KeepLatest,
+    )
+    .with_turnout(TurnoutCounter::new(&params));
+    let mut ballot = |voter: &SigningKey, choice: Choice| -> Ballot {
+        Ballot::with_turnout(
+            voter::encrypt_vote(choice, &pk, &params, &mut rng).unwrap(),
+            turnout::encrypt_one(&pk, &params, &mut rng).unwrap(),
+            0,
+            Vec::new(),
+            voter,
+        )
+    };
+
+    // The first voter votes twice, the second once, and a ballot without a turnout ciphertext
+    // is turned away.
+    let first: Ballot = ballot(&voters[0], Choice::For);
+    let revote: Ballot = ballot(&voters[0], Choice::Against);
+    let second: Ballot = ballot(&voters[1], Choice::Abstain);
+    ballot_box.submit(first).unwrap();
+    ballot_box.submit(revote).unwrap();
+    let uncounted: Ballot = Ballot::new(second.ciphertext.clone(), 0, &voters[1]);
+    assert_eq!(ballot_box.submit(uncounted), Err(BallotError::NoTurnout));
+    ballot_box.submit(second).unwrap();
+
+    // Decrypted while the box is still open.
+    assert!(ballot_box.is_open());
+    let pt: Plaintext = sk.try_decrypt(ballot_box.turnout().unwrap()).unwrap();
+    let turnout: Vec<u64> = Vec::<u64>::try_decode(&pt, Encoding::poly()).unwrap();
+    assert_eq!(turnout[0], 2);
+}
+
+#[test]
+fn stored_ballots_sum_to_the_surviving_votes() {
+    let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(2);
+    let params: Arc<BfvParameters> = params::build(
+        params::DEGREE,
+        params::plaintext_modulus(1),
+        &params::MODULI,
+    )
+    .unwrap();
+    let sk: SecretKey = SecretKey::random(&params, &mut rng);
+    let pk: PublicKey = PublicKey::new(&sk, &mut rng);
+    let voters: Vec<SigningKey> = (0..2).map(|_| SigningKey::generate(&mut rng)).collect();
+    let votes: PrecinctStores = PrecinctStores::new(
+        &params,
+        Box::new(
+            |_: PrecinctId| -> Result<Box<dyn CiphertextStore>, StoreError> {
+                Ok(Box::new(MemoryStore::new()))
+            },
+        ),
+    );
+    let mut ballot_box: BallotBox = BallotBox::new(
+        voters.iter().map(SigningKey::verifying_key),
+        DuplicatePolicy::KeepLatest,
+    )
+    .with_turnout(TurnoutCounter::new(&params))
+    .with_store(votes, Box::new(MemoryStore::new()));
+    let mut ballot = |voter: &SigningKey, choice: Choice| -> Ballot {
+        Ballot::with_turnout(
+            voter::encrypt_vote(choice, &pk, &params, &mut rng).unwrap(),
+            turnout::encrypt_one(&pk, &params, &mut rng).unwrap(),
+            0,
+            Vec::new(),
+            voter,
+        )
+    };
+    let decrypt = |ct: &Ciphertext| -> Vec<u64> {
+        let pt: Plaintext = sk.try_decrypt(ct).unwrap();
+        Vec::<u64>::try_decode(&pt, Encoding::poly()).unwrap()
+    };
+
+    // The first voter votes twice, the second once.
+    let first: Ballot = ballot(&voters[0], Choice::For);
+    let revote: Ballot = ballot(&voters[0], Choice::Against);
+    let second: Ballot = ballot(&voters[1], Choice::Abstain);
+    ballot_box.submit(first).unwrap();
+    ballot_box.submit(revote.clone()).unwrap();
+    ballot_box.submit(second.clone()).unwrap();
+
+    // Only the stores hold the ciphertexts, and they read back as the surviving ballots.
+    assert!(ballot_box.ballots().is_empty());
+    assert_eq!(ballot_box.len(), 2);
+    let read: Vec<Ballot> = ballot_box
+        .iter()
+        .map(|ballot| ballot.map(|ballot| ballot.into_owned()))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    for (read, submitted) in read.iter().zip([&revote, &second]) {
+        assert_eq!(read.key(), submitted.key());
+        read.verify().unwrap();
+    }
+    assert_eq!(
+        ballot_box.ballot(&revote.nullifier).unwrap().unwrap().key(),
+        revote.key()
+    );
+
+    // The replaced vote is taken back out of the precinct's sum.
+    let (tallies, _): (BTreeMap<PrecinctId, PrecinctTally>, _) =
+        precinct::tally_stores(ballot_box.precinct_stores().unwrap(), &params).unwrap();
+    assert_eq!(tallies[&0].ballots, 2);
+    assert_eq!(
+        decrypt(&tallies[&0].ciphertext),
+        decrypt(&(&revote.ciphertext + &second.ciphertext))
+    );
+    assert_eq!(decrypt(ballot_box.turnout().unwrap())[0], 2);
+}
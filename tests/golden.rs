@@ -0,0 +1,138 @@
+// Golden vectors: parameters, public key, ballots and tally generated from a pinned seed and
+// kept in `testdata/golden`.
+//
+// fhe.rs is pinned by git revision and its serialization has changed between betas. These tests
+// regenerate every vector from the seed and compare it byte for byte with the file, then read
+// the files back, re-check the tally and decrypt it. A bump that changes the serialization or
+// the parameter defaults then fails here instead of in front of the workshop.
+//
+// After an intended change, regenerate the files with
+// `UPDATE_GOLDEN=1 cargo test --test golden` and commit them.
+
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, PublicKey},
+    mbfv::{CommonRandomPoly, DecryptionShare, PublicKeyShare},
+};
+use fhe_traits::{Deserialize, DeserializeParametrized, Serialize};
+use fhe_workshop::{
+    params,
+    party::{self, Party},
+    tally,
+    voter::{self, Choice},
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::{env, fs, path::PathBuf, sync::Arc};
+
+const SEED: u64 = 0x5ec2e7_ba11075;
+const NUM_PARTIES: usize = 3;
+const VOTES: [Choice; 5] = [
+    Choice::For,
+    Choice::Against,
+    Choice::For,
+    Choice::Abstain,
+    Choice::For,
+];
+const EXPECTED_TALLY: [u64; 3] = [1, 3, 1];
+
+struct Vectors {
+    params: Arc<BfvParameters>,
+    parties: Vec<Party>,
+    public_key: PublicKey,
+    ballots: Vec<Ciphertext>,
+    tally: Ciphertext,
+}
+
+// Runs a small election from the seed, always drawing from the RNG in the same order.
+fn generate() -> Vectors {
+    let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(SEED);
+    let params: Arc<BfvParameters> = params::build(
+        params::DEGREE,
+        params::plaintext_modulus(VOTES.len()),
+        &params::MODULI,
+    )
+    .unwrap();
+    let crp: CommonRandomPoly = CommonRandomPoly::new(&params, &mut rng).unwrap();
+    let (parties, shares): (Vec<Party>, Vec<PublicKeyShare>) = (0..NUM_PARTIES)
+        .map(|_| Party::new(&params, &crp, &mut rng).unwrap())
+        .unzip();
+    let public_key: PublicKey = party::aggregate_public_key(shares).unwrap();
+    let ballots: Vec<Ciphertext> = VOTES
+        .iter()
+        .map(|&vote| voter::encrypt_vote(vote, &public_key, &params, &mut rng).unwrap())
+        .collect();
+    let tally: Ciphertext = tally::sum(&ballots, &params);
+    Vectors {
+        params,
+        parties,
+        public_key,
+        ballots,
+        tally,
+    }
+}
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata/golden")
+        .join(name)
+}
+
+fn read(name: &str) -> Vec<u8> {
+    fs::read(path(name)).unwrap_or_else(|e| {
+        panic!("{name}: {e}, generate it with `UPDATE_GOLDEN=1 cargo test --test golden`")
+    })
+}
+
+// Compares the bytes with the file, or overwrites the file with `UPDATE_GOLDEN` set.
+fn check(name: &str, bytes: &[u8]) {
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(path(name), bytes).unwrap();
+        return;
+    }
+    assert!(
+        read(name) == bytes,
+        "{name} no longer matches the golden vector, did fhe.rs change its serialization?"
+    );
+}
+
+fn ballot_name(index: usize) -> String {
+    format!("ballot-{index}.bin")
+}
+
+#[test]
+fn vectors_are_unchanged() {
+    let vectors: Vectors = generate();
+    check("params.bin", &vectors.params.to_bytes());
+    check("public_key.bin", &vectors.public_key.to_bytes());
+    for (index, ballot) in vectors.ballots.iter().enumerate() {
+        check(&ballot_name(index), &ballot.to_bytes());
+    }
+    check("tally.bin", &vectors.tally.to_bytes());
+}
+
+#[test]
+fn vectors_deserialize_and_decrypt() {
+    let params: Arc<BfvParameters> =
+        Arc::new(BfvParameters::try_deserialize(&read("params.bin")).unwrap());
+    let public_key: PublicKey = PublicKey::from_bytes(&read("public_key.bin"), &params).unwrap();
+    assert_eq!(public_key.to_bytes(), read("public_key.bin"));
+    let ballots: Vec<Ciphertext> = (0..VOTES.len())
+        .map(|index| Ciphertext::from_bytes(&read(&ballot_name(index)), &params).unwrap())
+        .collect();
+
+    // The ballots read back still add up to the published tally.
+    let tally: Arc<Ciphertext> = Arc::new(tally::sum(&ballots, &params));
+    assert_eq!(tally.to_bytes(), read("tally.bin"));
+
+    // The parties' secret keys are never written out, they come from the seed.
+    let mut rng: ChaCha8Rng = ChaCha8Rng::seed_from_u64(SEED);
+    let shares: Vec<DecryptionShare> = generate()
+        .parties
+        .iter()
+        .map(|party| party.decryption_share(&tally, &mut rng).unwrap())
+        .collect();
+    assert_eq!(
+        tally::decrypt(shares, Choice::ALL.len()).unwrap(),
+        EXPECTED_TALLY
+    );
+}
@@ -0,0 +1,86 @@
+// Property tests of the tally: for any votes, candidates and parties, decrypting the sum of the
+// encrypted ballots gives the sum of the votes.
+//
+// Each case runs a key generation and encrypts every ballot, so the number of cases is kept
+// small.
+
+use fhe::{
+    bfv::{BfvParameters, Ciphertext, PublicKey},
+    mbfv::{CommonRandomPoly, DecryptionShare, PublicKeyShare},
+};
+use fhe_workshop::{
+    params,
+    party::{self, Party},
+    tally, voter,
+};
+use proptest::prelude::*;
+use rand::thread_rng;
+use std::sync::Arc;
+
+const MAX_CANDIDATES: usize = 8;
+const MAX_PARTIES: usize = 5;
+const MAX_VOTES: usize = 32;
+
+// Every ballot gives each candidate 0 or 1 votes, including the edge cases of ballots that are
+// all zeros or all ones.
+fn votes() -> impl Strategy<Value = Vec<Vec<u64>>> {
+    (1..=MAX_CANDIDATES, 1..=MAX_VOTES).prop_flat_map(|(candidates, num_votes)| {
+        prop_oneof![
+            prop::collection::vec(prop::collection::vec(0..=1u64, candidates), num_votes),
+            Just(vec![vec![0; candidates]; num_votes]),
+            Just(vec![vec![1; candidates]; num_votes]),
+        ]
+    })
+}
+
+// Runs the election over the votes with the given number of parties and decrypts the tally.
+fn decrypted_tally(votes: &[Vec<u64>], num_parties: usize) -> Vec<u64> {
+    let candidates: usize = votes[0].len();
+    let params: Arc<BfvParameters> = params::build(
+        params::DEGREE,
+        params::plaintext_modulus(votes.len()),
+        &params::MODULI,
+    )
+    .unwrap();
+    let crp: CommonRandomPoly = CommonRandomPoly::new(&params, &mut thread_rng()).unwrap();
+    let (parties, shares): (Vec<Party>, Vec<PublicKeyShare>) = (0..num_parties)
+        .map(|_| Party::new(&params, &crp, &mut thread_rng()).unwrap())
+        .unzip();
+    let pk: PublicKey = party::aggregate_public_key(shares).unwrap();
+
+    let ballots: Vec<Ciphertext> = votes
+        .iter()
+        .map(|vote| {
+            voter::encrypt_quadratic(vote, candidates as u64, &pk, &params, &mut thread_rng())
+                .unwrap()
+        })
+        .collect();
+    let sum: Arc<Ciphertext> = Arc::new(tally::sum(&ballots, &params));
+    let shares: Vec<DecryptionShare> = parties
+        .iter()
+        .map(|party| party.decryption_share(&sum, &mut thread_rng()).unwrap())
+        .collect();
+    tally::decrypt(shares, candidates).unwrap()
+}
+
+fn plaintext_sum(votes: &[Vec<u64>]) -> Vec<u64> {
+    let mut sum: Vec<u64> = vec![0; votes[0].len()];
+    for vote in votes {
+        for (total, v) in sum.iter_mut().zip(vote) {
+            *total += v;
+        }
+    }
+    sum
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn tally_decrypts_to_the_sum_of_the_votes(
+        votes in votes(),
+        num_parties in 1..=MAX_PARTIES,
+    ) {
+        prop_assert_eq!(decrypted_tally(&votes, num_parties), plaintext_sum(&votes));
+    }
+}